@@ -0,0 +1,63 @@
+//! Event-to-item pipeline: raw input events through `ComboState` into
+//! overlay items. Run with `cargo bench` and compare against the previous
+//! run to catch allocation or formatting regressions on the hot path.
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use evdev::Key;
+use keyway_visualizer::combo::ComboState;
+use keyway_visualizer::hotkey::Hotkey;
+use keyway_visualizer::input::InputEvent;
+use keyway_visualizer::ring::RingBuffer;
+use std::time::Duration;
+
+fn state() -> ComboState {
+    ComboState::new(
+        5,
+        Duration::from_millis(900),
+        Duration::from_millis(200),
+        Duration::from_millis(120),
+        Hotkey::parse("Ctrl+Shift+P").unwrap(),
+    )
+}
+
+fn press(state: &mut ComboState, key: Key) {
+    black_box(state.handle_event(InputEvent::KeyPressed(key)));
+    black_box(state.handle_event(InputEvent::KeyReleased(key)));
+}
+
+fn pipeline(c: &mut Criterion) {
+    let mut typing = state();
+    c.bench_function("plain key", |b| b.iter(|| press(&mut typing, Key::KEY_A)));
+
+    let mut shortcut = state();
+    shortcut.handle_event(InputEvent::KeyPressed(Key::KEY_LEFTCTRL));
+    shortcut.handle_event(InputEvent::KeyPressed(Key::KEY_LEFTSHIFT));
+    c.bench_function("shortcut", |b| b.iter(|| press(&mut shortcut, Key::KEY_T)));
+
+    let mut special = state();
+    c.bench_function("special key", |b| b.iter(|| press(&mut special, Key::KEY_ENTER)));
+
+    let mut repeat = state();
+    repeat.handle_event(InputEvent::KeyPressed(Key::KEY_BACKSPACE));
+    c.bench_function("key repeat", |b| {
+        b.iter(|| black_box(repeat.handle_event(InputEvent::KeyRepeat(Key::KEY_BACKSPACE))))
+    });
+}
+
+/// Pushing into a full history, the steady state of a long session.
+fn history(c: &mut Criterion) {
+    let mut ring = RingBuffer::new(1000);
+    for i in 0..1000u64 {
+        ring.push_back(i);
+    }
+    let mut next = 1000u64;
+    c.bench_function("history push", |b| {
+        b.iter(|| {
+            next += 1;
+            black_box(ring.push_back(next))
+        })
+    });
+}
+
+criterion_group!(benches, pipeline, history);
+criterion_main!(benches);
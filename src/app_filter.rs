@@ -0,0 +1,173 @@
+use anyhow::{Context, Result};
+use regex::{Regex, RegexBuilder};
+
+/// Which window property a filter entry is matched against.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MatchTarget {
+    Class,
+    Title,
+    Either,
+}
+
+#[derive(Debug, Clone)]
+enum Pattern {
+    Substring(String),
+    Regex(Regex),
+}
+
+/// A single `disabled_apps` entry.
+///
+/// Syntax: `[class:|title:][re:|glob:]pattern`. Without a kind prefix a pattern
+/// starting with `^` or ending with `$` is a regex, one containing `*` or `?`
+/// is a glob, and anything else is a plain substring. All matching is
+/// case-insensitive.
+#[derive(Debug, Clone)]
+pub struct FilterRule {
+    target: MatchTarget,
+    pattern: Pattern,
+}
+
+impl FilterRule {
+    pub fn parse(entry: &str) -> Result<Self> {
+        let entry = entry.trim();
+        let (target, rest) = split_target(entry);
+
+        let pattern = if let Some(re) = rest.strip_prefix("re:") {
+            Pattern::Regex(build_regex(re)?)
+        } else if let Some(glob) = rest.strip_prefix("glob:") {
+            Pattern::Regex(build_regex(&glob_to_regex(glob))?)
+        } else if rest.starts_with('^') || rest.ends_with('$') {
+            Pattern::Regex(build_regex(rest)?)
+        } else if rest.contains(['*', '?']) {
+            Pattern::Regex(build_regex(&glob_to_regex(rest))?)
+        } else {
+            Pattern::Substring(rest.to_lowercase())
+        };
+
+        Ok(Self { target, pattern })
+    }
+
+    pub fn matches(&self, class: &str, title: &str) -> bool {
+        match self.target {
+            MatchTarget::Class => self.matches_value(class),
+            MatchTarget::Title => self.matches_value(title),
+            MatchTarget::Either => self.matches_value(class) || self.matches_value(title),
+        }
+    }
+
+    fn matches_value(&self, value: &str) -> bool {
+        match &self.pattern {
+            Pattern::Substring(needle) => value.to_lowercase().contains(needle.as_str()),
+            Pattern::Regex(re) => re.is_match(value),
+        }
+    }
+}
+
+/// Parses every entry, skipping invalid ones. Errors are returned alongside so
+/// callers can log or display them.
+pub fn compile_rules(entries: &[String]) -> (Vec<FilterRule>, Vec<String>) {
+    let mut rules = Vec::new();
+    let mut errors = Vec::new();
+
+    for entry in entries {
+        match FilterRule::parse(entry) {
+            Ok(rule) => rules.push(rule),
+            Err(e) => errors.push(format!("{}: {:#}", entry, e)),
+        }
+    }
+
+    (rules, errors)
+}
+
+/// The entry "Pick window" adds: exactly this class and no other.
+pub fn exact_class_entry(class: &str) -> String {
+    format!("class:^{}$", regex::escape(class))
+}
+
+pub fn any_matches(rules: &[FilterRule], class: &str, title: &str) -> bool {
+    rules.iter().any(|rule| rule.matches(class, title))
+}
+
+fn split_target(entry: &str) -> (MatchTarget, &str) {
+    if let Some(rest) = strip_prefix_ignore_case(entry, "class:") {
+        (MatchTarget::Class, rest)
+    } else if let Some(rest) = strip_prefix_ignore_case(entry, "title:") {
+        (MatchTarget::Title, rest)
+    } else {
+        (MatchTarget::Either, entry)
+    }
+}
+
+fn strip_prefix_ignore_case<'a>(value: &'a str, prefix: &str) -> Option<&'a str> {
+    let head = value.get(..prefix.len())?;
+    if head.eq_ignore_ascii_case(prefix) {
+        Some(&value[prefix.len()..])
+    } else {
+        None
+    }
+}
+
+pub fn build_regex(pattern: &str) -> Result<Regex> {
+    RegexBuilder::new(pattern)
+        .case_insensitive(true)
+        .build()
+        .with_context(|| format!("Invalid pattern {:?}", pattern))
+}
+
+pub fn glob_to_regex(glob: &str) -> String {
+    let mut out = String::with_capacity(glob.len() + 8);
+    out.push('^');
+    for ch in glob.chars() {
+        match ch {
+            '*' => out.push_str(".*"),
+            '?' => out.push('.'),
+            _ => out.push_str(&regex::escape(ch.encode_utf8(&mut [0; 4]))),
+        }
+    }
+    out.push('$');
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn plain_entry_is_case_insensitive_substring() {
+        let rule = FilterRule::parse("KeePass").unwrap();
+        assert!(rule.matches("org.keepassxc.KeePassXC", ""));
+        assert!(rule.matches("other", "keepass - vault"));
+        assert!(!rule.matches("firefox", "Mozilla Firefox"));
+    }
+
+    #[test]
+    fn class_regex_only_checks_class() {
+        let rule = FilterRule::parse(r"class:^org\.mozilla\..*").unwrap();
+        assert!(rule.matches("org.mozilla.firefox", ""));
+        assert!(!rule.matches("firefox", "org.mozilla.firefox"));
+    }
+
+    #[test]
+    fn title_glob_matches_whole_title() {
+        let rule = FilterRule::parse("title:*Private*").unwrap();
+        assert!(rule.matches("firefox", "Mozilla Firefox Private Browsing"));
+        assert!(!rule.matches("private", "Mozilla Firefox"));
+    }
+
+    #[test]
+    fn picked_class_matches_only_itself() {
+        let rule = FilterRule::parse(&exact_class_entry("org.foo.Bar")).unwrap();
+        assert!(rule.matches("org.foo.bar", ""));
+        assert!(!rule.matches("org.foo.Bar2", ""));
+        assert!(!rule.matches("orgxfooxBar", ""));
+        let rule = FilterRule::parse(&exact_class_entry("app (beta)+")).unwrap();
+        assert!(rule.matches("app (beta)+", ""));
+    }
+
+    #[test]
+    fn invalid_regex_is_reported() {
+        let (rules, errors) = compile_rules(&["re:(".to_string(), "firefox".to_string()]);
+        assert_eq!(rules.len(), 1);
+        assert_eq!(errors.len(), 1);
+    }
+}
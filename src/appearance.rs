@@ -0,0 +1,113 @@
+use crate::portal::{CALL_TIMEOUT, PORTAL_DEST, PORTAL_PATH, PROCESS_STEP};
+use crate::settings::Theme;
+use anyhow::{Context, Result};
+use async_channel::{Receiver, Sender};
+use dbus::arg::{RefArg, Variant};
+use dbus::blocking::Connection;
+use dbus::message::MatchRule;
+use std::thread;
+use tracing::{debug, info};
+
+const SETTINGS_IFACE: &str = "org.freedesktop.portal.Settings";
+const NAMESPACE: &str = "org.freedesktop.appearance";
+const KEY: &str = "color-scheme";
+
+/// The desktop's `color-scheme` preference.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum ColorScheme {
+    #[default]
+    NoPreference,
+    Dark,
+    Light,
+}
+
+impl ColorScheme {
+    /// From the portal's value; unknown ones count as no preference.
+    fn from_portal(value: u64) -> Self {
+        match value {
+            1 => ColorScheme::Dark,
+            2 => ColorScheme::Light,
+            _ => ColorScheme::NoPreference,
+        }
+    }
+
+    /// The overlay theme `theme = "auto"` stands for. Without a
+    /// preference the overlay keeps its default dark theme.
+    pub fn theme(self) -> Theme {
+        match self {
+            ColorScheme::Light => Theme::Light,
+            ColorScheme::Dark | ColorScheme::NoPreference => Theme::Dark,
+        }
+    }
+}
+
+/// Follows the color scheme through the Settings portal: the current value
+/// first, then each change. Nothing is sent when there is no portal, and
+/// the overlay stays dark. Dropping the receiver stops the watcher.
+pub fn start_color_scheme_monitor() -> Receiver<ColorScheme> {
+    let (tx, rx) = async_channel::bounded(4);
+    thread::spawn(move || {
+        if let Err(e) = watch_color_scheme(&tx) {
+            info!("Not following the desktop color scheme: {:#}", e);
+        }
+    });
+    rx
+}
+
+fn watch_color_scheme(tx: &Sender<ColorScheme>) -> Result<()> {
+    let conn = Connection::new_session().context("Failed to connect to the session bus")?;
+
+    let change_tx = tx.clone();
+    conn.add_match(
+        MatchRule::new_signal(SETTINGS_IFACE, "SettingChanged").with_path(PORTAL_PATH),
+        move |(namespace, key, value): (String, String, Variant<Box<dyn RefArg>>), _, _| {
+            if namespace == NAMESPACE && key == KEY {
+                let scheme = ColorScheme::from_portal(value.0.as_u64().unwrap_or(0));
+                debug!("Color scheme changed to {:?}", scheme);
+                return change_tx.send_blocking(scheme).is_ok();
+            }
+            true
+        },
+    )
+    .context("Failed to subscribe to SettingChanged")?;
+
+    let scheme = read_color_scheme(&conn)?;
+    info!("Desktop color scheme: {:?}", scheme);
+    let _ = tx.send_blocking(scheme);
+
+    while !tx.is_closed() {
+        conn.process(PROCESS_STEP).context("Lost connection to the session bus")?;
+    }
+    Ok(())
+}
+
+fn read_color_scheme(conn: &Connection) -> Result<ColorScheme> {
+    let proxy = conn.with_proxy(PORTAL_DEST, PORTAL_PATH, CALL_TIMEOUT);
+    let read_one: Result<(Variant<Box<dyn RefArg>>,), dbus::Error> =
+        proxy.method_call(SETTINGS_IFACE, "ReadOne", (NAMESPACE, KEY));
+    let value = match read_one {
+        Ok((value,)) => value.0.as_u64(),
+        // Portals before version 2 only have `Read`, which wraps the value
+        // in a second variant.
+        Err(_) => {
+            let (value,): (Variant<Box<dyn RefArg>>,) = proxy
+                .method_call(SETTINGS_IFACE, "Read", (NAMESPACE, KEY))
+                .context("No Settings portal")?;
+            value.0.as_iter().and_then(|mut inner| inner.next()?.as_u64())
+        }
+    };
+    Ok(ColorScheme::from_portal(value.unwrap_or(0)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn portal_values_pick_the_theme() {
+        assert_eq!(ColorScheme::from_portal(1).theme(), Theme::Dark);
+        assert_eq!(ColorScheme::from_portal(2).theme(), Theme::Light);
+        assert_eq!(ColorScheme::from_portal(0), ColorScheme::NoPreference);
+        assert_eq!(ColorScheme::from_portal(7).theme(), Theme::Dark);
+    }
+}
@@ -0,0 +1,140 @@
+use crate::flatpak;
+use crate::portal::{portal_request, variant, CALL_TIMEOUT, PORTAL_DEST, PORTAL_PATH};
+use anyhow::{anyhow, Context, Result};
+use dbus::arg::{PropMap, Variant};
+use dbus::blocking::LocalConnection;
+use dbus::Path as DbusPath;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::AtomicBool;
+use std::thread;
+use tracing::{info, warn};
+
+const BACKGROUND_IFACE: &str = "org.freedesktop.portal.Background";
+
+const ENTRY_NAME: &str = "keyway-visualizer.desktop";
+/// Marks entries written here, so one the user wrote by hand is left alone.
+const MARKER: &str = "X-Keyway-Autostart=true";
+
+/// `autostart`: starts the overlay with the desktop session. Inside Flatpak
+/// the Background portal keeps the entry; elsewhere it is a file in
+/// `~/.config/autostart`.
+///
+/// At `startup` a disabled Flatpak entry is left as it is: asking the portal
+/// to drop it could prompt on every start.
+pub fn sync(enabled: bool, startup: bool) {
+    if flatpak::in_flatpak() {
+        if enabled || !startup {
+            request_background(enabled);
+        }
+        return;
+    }
+    let Some(dir) = dirs::config_dir().map(|dir| dir.join("autostart")) else {
+        warn!("No config directory for the autostart entry");
+        return;
+    };
+    if let Err(e) = write_entry(&dir, enabled) {
+        warn!("Failed to update autostart: {:#}", e);
+    }
+}
+
+/// Writes or removes the entry in `dir`. An entry without the marker is
+/// the user's own and is neither replaced nor removed.
+fn write_entry(dir: &Path, enabled: bool) -> Result<()> {
+    let path = dir.join(ENTRY_NAME);
+    let existing = fs::read_to_string(&path).ok();
+    if existing.as_ref().is_some_and(|entry| !entry.contains(MARKER)) {
+        return Ok(());
+    }
+    if !enabled {
+        if existing.is_some() {
+            fs::remove_file(&path).with_context(|| format!("Failed to remove {:?}", path))?;
+            info!("Removed autostart entry {:?}", path);
+        }
+        return Ok(());
+    }
+    let program = std::env::current_exe().unwrap_or_else(|_| PathBuf::from("keyway-visualizer"));
+    let entry = format!(
+        "[Desktop Entry]\n\
+         Type=Application\n\
+         Name=Keyway Visualizer\n\
+         Comment=Wayland keystroke overlay\n\
+         Exec=\"{}\"\n\
+         Icon=input-keyboard\n\
+         Terminal=false\n\
+         {}\n",
+        program.display(),
+        MARKER
+    );
+    if existing.is_some_and(|existing| existing == entry) {
+        return Ok(());
+    }
+    fs::create_dir_all(dir).with_context(|| format!("Failed to create {:?}", dir))?;
+    fs::write(&path, entry).with_context(|| format!("Failed to write {:?}", path))?;
+    info!("Wrote autostart entry {:?}", path);
+    Ok(())
+}
+
+/// Asks the Background portal to (not) start us with the session. The
+/// portal may ask the user first, so this runs on its own thread.
+fn request_background(autostart: bool) {
+    thread::spawn(move || {
+        let running = AtomicBool::new(true);
+        match call_background(&running, autostart) {
+            Ok(true) => info!("Background portal: autostart {}", autostart),
+            Ok(false) => warn!("Background portal refused to change autostart"),
+            Err(e) => warn!("Background portal unavailable, autostart unchanged: {:#}", e),
+        }
+    });
+}
+
+fn call_background(running: &AtomicBool, autostart: bool) -> Result<bool> {
+    let conn = LocalConnection::new_session().context("Failed to connect to the session bus")?;
+    let proxy = conn.with_proxy(PORTAL_DEST, PORTAL_PATH, CALL_TIMEOUT);
+    let results = portal_request(&conn, running, "keyway_background", |token| {
+        let mut options = PropMap::new();
+        options.insert("handle_token".to_string(), variant(token.to_string()));
+        options.insert(
+            "reason".to_string(),
+            variant("Show keystrokes from the start of the session".to_string()),
+        );
+        options.insert("autostart".to_string(), Variant(Box::new(autostart)));
+        options.insert(
+            "commandline".to_string(),
+            Variant(Box::new(vec!["keyway-visualizer".to_string()])),
+        );
+        let (_,): (DbusPath,) =
+            proxy.method_call(BACKGROUND_IFACE, "RequestBackground", ("", options))?;
+        Ok(())
+    })?;
+    results
+        .get("autostart")
+        .and_then(|value| value.0.as_i64())
+        .map(|granted| granted != 0 || !autostart)
+        .ok_or_else(|| anyhow!("Portal did not say whether autostart is set"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn writes_and_removes_only_its_own_entry() {
+        let dir = std::env::temp_dir().join(format!("keyway-autostart-{}", std::process::id()));
+        let path = dir.join(ENTRY_NAME);
+
+        write_entry(&dir, true).unwrap();
+        let entry = fs::read_to_string(&path).unwrap();
+        assert!(entry.starts_with("[Desktop Entry]\n"));
+        assert!(entry.contains(MARKER));
+        write_entry(&dir, false).unwrap();
+        assert!(!path.exists());
+
+        let own = "[Desktop Entry]\nExec=keyway-visualizer --demo\n";
+        fs::write(&path, own).unwrap();
+        write_entry(&dir, true).unwrap();
+        write_entry(&dir, false).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), own);
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
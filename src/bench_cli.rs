@@ -0,0 +1,124 @@
+use crate::combo::ComboState;
+use crate::hotkey::Hotkey;
+use crate::input::{send_event, InputEvent, EVENT_CHANNEL_CAPACITY};
+use crate::metrics;
+use crate::settings::Settings;
+use anyhow::{bail, Result};
+use clap::Parser;
+use evdev::Key;
+use std::sync::atomic::Ordering;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Pushes synthetic input through the listener's channel and `ComboState`,
+/// the way the overlay receives it, without opening any device.
+#[derive(Parser, Debug, Clone)]
+pub struct BenchArgs {
+    /// Events to send
+    #[arg(long, default_value_t = 200_000)]
+    pub events: u64,
+    /// Events sent back to back, like a burst of fast typing
+    #[arg(long, default_value_t = 64)]
+    pub burst: u64,
+    /// Pause between bursts, in microseconds
+    #[arg(long, default_value_t = 1000)]
+    pub gap_us: u64,
+    /// Channel capacity to test instead of the overlay's
+    #[arg(long, default_value_t = EVENT_CHANNEL_CAPACITY)]
+    pub capacity: usize,
+}
+
+pub fn run(args: &BenchArgs) -> Result<()> {
+    if args.events == 0 || args.burst == 0 || args.capacity == 0 {
+        bail!("--events, --burst and --capacity must be above zero");
+    }
+    let settings = Settings::default();
+    let mut combo = ComboState::new(
+        settings.max_items,
+        Duration::from_millis(settings.ttl_ms),
+        Duration::from_millis(settings.repeat_coalesce_ms),
+        Duration::from_millis(settings.modifier_grace_ms),
+        Hotkey::parse(&settings.pause_hotkey)?,
+    );
+
+    let (tx, rx) = async_channel::bounded(args.capacity);
+    let (events, burst, gap) = (args.events, args.burst, Duration::from_micros(args.gap_us));
+    let started = Instant::now();
+    let producer = thread::spawn(move || {
+        for (sent, event) in synthetic_events().take(events as usize).enumerate() {
+            if send_event(&tx, event).is_err() {
+                return;
+            }
+            if (sent as u64 + 1).is_multiple_of(burst) && !gap.is_zero() {
+                thread::sleep(gap);
+            }
+        }
+    });
+
+    let mut handled = 0u64;
+    let mut combos = 0u64;
+    let mut in_combo_state = Duration::ZERO;
+    while let Ok(event) = rx.recv_blocking() {
+        let before = Instant::now();
+        let action = combo.handle_event(event);
+        in_combo_state += before.elapsed();
+        handled += 1;
+        if action.combo.is_some() {
+            combos += 1;
+        }
+    }
+    let elapsed = started.elapsed();
+    let _ = producer.join();
+
+    let delivered = metrics::EVENTS_RECEIVED.load(Ordering::Relaxed);
+    let dropped = metrics::EVENTS_DROPPED.load(Ordering::Relaxed);
+    let percent = |count: u64| count as f64 * 100.0 / events as f64;
+    println!(
+        "Sent        {} events in bursts of {}, {} µs apart, channel of {}",
+        events, burst, args.gap_us, args.capacity
+    );
+    println!("Delivered   {} ({:.2}%)", delivered, percent(delivered));
+    println!("Dropped     {} ({:.2}%)", dropped, percent(dropped));
+    println!("Combos      {}", combos);
+    println!("Elapsed     {:.3} s", elapsed.as_secs_f64());
+    println!(
+        "Throughput  {:.0} events/s end to end",
+        handled as f64 / elapsed.as_secs_f64()
+    );
+    println!(
+        "ComboState  {} ns per event",
+        in_combo_state.as_nanos() / u128::from(handled.max(1))
+    );
+    Ok(())
+}
+
+/// Typing with a shortcut and some held-key repeats mixed in, forever.
+fn synthetic_events() -> impl Iterator<Item = InputEvent> {
+    let tap = |key| [InputEvent::KeyPressed(key), InputEvent::KeyReleased(key)];
+    let mut pattern = Vec::new();
+    for key in [Key::KEY_H, Key::KEY_E, Key::KEY_L, Key::KEY_L, Key::KEY_O, Key::KEY_SPACE] {
+        pattern.extend(tap(key));
+    }
+    pattern.push(InputEvent::KeyPressed(Key::KEY_LEFTCTRL));
+    pattern.extend(tap(Key::KEY_S));
+    pattern.push(InputEvent::KeyReleased(Key::KEY_LEFTCTRL));
+    pattern.push(InputEvent::KeyPressed(Key::KEY_BACKSPACE));
+    pattern.extend((0..4).map(|_| InputEvent::KeyRepeat(Key::KEY_BACKSPACE)));
+    pattern.push(InputEvent::KeyReleased(Key::KEY_BACKSPACE));
+    pattern.into_iter().cycle()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn synthetic_typing_releases_every_key() {
+        let events: Vec<InputEvent> = synthetic_events().take(44).collect();
+        let pattern = &events[..22];
+        let presses = pattern.iter().filter(|e| matches!(e, InputEvent::KeyPressed(_))).count();
+        let releases = pattern.iter().filter(|e| matches!(e, InputEvent::KeyReleased(_))).count();
+        assert_eq!(presses, releases);
+        assert!(matches!(events[22], InputEvent::KeyPressed(Key::KEY_H)));
+    }
+}
@@ -0,0 +1,69 @@
+use std::time::{Duration, Instant};
+
+/// `break_reminder_minutes`: counts how long typing has gone on without a
+/// pause of at least `idle`, and says when a break is due. Each reminder
+/// starts the count again, so a long stretch gets one every `every`.
+#[derive(Debug, Clone)]
+pub struct BreakTimer {
+    every: Duration,
+    idle: Duration,
+    /// Start of the current stretch of typing
+    started: Option<Instant>,
+    last_press: Option<Instant>,
+}
+
+impl BreakTimer {
+    pub fn new(every: Duration, idle: Duration) -> Self {
+        Self {
+            every,
+            idle,
+            started: None,
+            last_press: None,
+        }
+    }
+
+    /// Records a key press; true when it is time for a break.
+    pub fn press(&mut self, now: Instant) -> bool {
+        let rested = self
+            .last_press
+            .is_none_or(|last| now.saturating_duration_since(last) >= self.idle);
+        self.last_press = Some(now);
+        let started = match self.started {
+            Some(started) if !rested => started,
+            _ => *self.started.insert(now),
+        };
+        if now.saturating_duration_since(started) < self.every {
+            return false;
+        }
+        self.started = Some(now);
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reminds_after_a_stretch_and_idle_resets_it() {
+        let minutes = |n: u64| Duration::from_secs(n * 60);
+        let mut timer = BreakTimer::new(minutes(30), minutes(5));
+        let start = Instant::now();
+
+        // Typing every minute for half an hour.
+        assert!(!timer.press(start));
+        for minute in 1..30 {
+            assert!(!timer.press(start + minutes(minute)));
+        }
+        assert!(timer.press(start + minutes(30)));
+        assert!(!timer.press(start + minutes(31)));
+
+        // A five minute pause starts the count again.
+        assert!(!timer.press(start + minutes(36)));
+        assert!(!timer.press(start + minutes(40)));
+        for minute in 41..66 {
+            assert!(!timer.press(start + minutes(minute)));
+        }
+        assert!(timer.press(start + minutes(66)));
+    }
+}
@@ -0,0 +1,274 @@
+use crate::control::CaptureFormat;
+use crate::portal::{portal_request, variant, CALL_TIMEOUT, PORTAL_DEST, PORTAL_PATH};
+use crate::session_log::create_private;
+use anyhow::{anyhow, bail, Context, Result};
+use async_channel::Sender;
+use dbus::arg::{PropMap, RefArg, Variant};
+use dbus::blocking::LocalConnection;
+use dbus::Path;
+use nix::sys::signal::{kill, Signal};
+use nix::unistd::Pid;
+use std::fs;
+use std::os::fd::{FromRawFd, IntoRawFd, OwnedFd};
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+use std::sync::atomic::AtomicBool;
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tracing::{debug, info, warn};
+
+const SCREENCAST_IFACE: &str = "org.freedesktop.portal.ScreenCast";
+const SESSION_IFACE: &str = "org.freedesktop.portal.Session";
+/// `types` bit for whole monitors in `SelectSources`
+const SOURCE_MONITOR: u32 = 1;
+const GIF_FPS: u32 = 15;
+/// Longest recording; the raw stream is written uncropped, so long ones get big.
+const MAX_SECONDS: u32 = 120;
+
+/// How a recording ended.
+#[derive(Debug, Clone)]
+pub enum CaptureEvent {
+    Finished(PathBuf),
+    /// The reason is logged where it happens
+    Failed,
+}
+
+/// The overlay's rectangle as fractions of its monitor, so the crop holds
+/// whatever size the portal streams the monitor at.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CropRegion {
+    x: f64,
+    y: f64,
+    width: f64,
+    height: f64,
+}
+
+impl CropRegion {
+    /// `rect` is x, y, width and height in monitor pixels, grown by
+    /// `padding` on each side and clipped to the monitor.
+    pub fn new(rect: (i32, i32, i32, i32), monitor: (i32, i32), padding: i32) -> Option<Self> {
+        let (x, y, width, height) = rect;
+        let (monitor_w, monitor_h) = monitor;
+        if width <= 0 || height <= 0 || monitor_w <= 0 || monitor_h <= 0 {
+            return None;
+        }
+        let left = (x - padding).clamp(0, monitor_w);
+        let top = (y - padding).clamp(0, monitor_h);
+        let right = (x + width + padding).clamp(0, monitor_w);
+        let bottom = (y + height + padding).clamp(0, monitor_h);
+        if right <= left || bottom <= top {
+            return None;
+        }
+        Some(Self {
+            x: f64::from(left) / f64::from(monitor_w),
+            y: f64::from(top) / f64::from(monitor_h),
+            width: f64::from(right - left) / f64::from(monitor_w),
+            height: f64::from(bottom - top) / f64::from(monitor_h),
+        })
+    }
+
+    /// ffmpeg crop filter; the size is rounded down to even pixels for the
+    /// encoders' chroma subsampling.
+    fn filter(&self) -> String {
+        format!(
+            "crop=trunc(iw*{:.4}/2)*2:trunc(ih*{:.4}/2)*2:iw*{:.4}:ih*{:.4}",
+            self.width, self.height, self.x, self.y
+        )
+    }
+}
+
+pub struct CaptureRequest {
+    pub seconds: u32,
+    pub format: CaptureFormat,
+    pub region: CropRegion,
+    pub dir: PathBuf,
+}
+
+/// Records the overlay's region for `request.seconds` (1 to `MAX_SECONDS`)
+/// on a worker thread. The portal asks which monitor to share, so this
+/// cannot be silent.
+pub fn start_capture(request: CaptureRequest, tx: Sender<CaptureEvent>) {
+    thread::spawn(move || {
+        let event = match record(&request) {
+            Ok(path) => {
+                info!("Saved overlay recording to {:?}", path);
+                CaptureEvent::Finished(path)
+            }
+            Err(e) => {
+                warn!("Overlay recording failed: {:#}", e);
+                CaptureEvent::Failed
+            }
+        };
+        let _ = tx.send_blocking(event);
+    });
+}
+
+fn record(request: &CaptureRequest) -> Result<PathBuf> {
+    fs::create_dir_all(&request.dir)
+        .with_context(|| format!("Failed to create {:?}", request.dir))?;
+    let stamp = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+    let output = request
+        .dir
+        .join(format!("keyway-{}.{}", stamp, request.format.extension()));
+    // The uncropped stream shows the whole monitor, so it goes where only
+    // the user can read it.
+    let runtime = dirs::runtime_dir().context("XDG_RUNTIME_DIR is not set")?;
+    let (_, raw) = create_private(&runtime, &format!("keyway-capture-{}", stamp), "webm")?;
+
+    let result = record_raw(request, &raw)
+        .and_then(|()| encode(&raw, &output, request.format, request.region));
+    let _ = fs::remove_file(&raw);
+    result.map(|()| output)
+}
+
+fn record_raw(request: &CaptureRequest, raw: &std::path::Path) -> Result<()> {
+    let conn = LocalConnection::new_session().context("Failed to connect to the session bus")?;
+    let (session, node, fd) = open_stream(&conn)?;
+    let seconds = request.seconds.clamp(1, MAX_SECONDS);
+    let recorded = record_stream(fd, node, seconds, raw);
+    close_session(&conn, session);
+    recorded
+}
+
+/// Starts a ScreenCast session for one monitor and returns it with the
+/// PipeWire node of its stream and a remote to read it from.
+fn open_stream(conn: &LocalConnection) -> Result<(Path<'static>, u32, OwnedFd)> {
+    let running = AtomicBool::new(true);
+    let proxy = conn.with_proxy(PORTAL_DEST, PORTAL_PATH, CALL_TIMEOUT);
+
+    let results = portal_request(conn, &running, "keyway_capture_session", |token| {
+        let mut options = PropMap::new();
+        options.insert("handle_token".to_string(), variant(token.to_string()));
+        options.insert(
+            "session_handle_token".to_string(),
+            variant("keyway_capture".to_string()),
+        );
+        let (_,): (Path,) = proxy.method_call(SCREENCAST_IFACE, "CreateSession", (options,))?;
+        Ok(())
+    })?;
+    let session = results
+        .get("session_handle")
+        .and_then(|v| v.0.as_str())
+        .ok_or_else(|| anyhow!("Portal returned no session handle"))?;
+    let session = Path::new(session.to_string()).map_err(|e| anyhow!(e))?;
+
+    portal_request(conn, &running, "keyway_capture_sources", |token| {
+        let mut options = PropMap::new();
+        options.insert("handle_token".to_string(), variant(token.to_string()));
+        options.insert("types".to_string(), Variant(Box::new(SOURCE_MONITOR)));
+        options.insert("multiple".to_string(), Variant(Box::new(false)));
+        let (_,): (Path,) =
+            proxy.method_call(SCREENCAST_IFACE, "SelectSources", (session.clone(), options))?;
+        Ok(())
+    })?;
+
+    // The user picks the monitor here, so the reply can take a while.
+    let results = portal_request(conn, &running, "keyway_capture_start", |token| {
+        let mut options = PropMap::new();
+        options.insert("handle_token".to_string(), variant(token.to_string()));
+        let (_,): (Path,) =
+            proxy.method_call(SCREENCAST_IFACE, "Start", (session.clone(), "", options))?;
+        Ok(())
+    })?;
+    // `streams` is a(ua{sv}); the node id leads each entry.
+    let node = results
+        .get("streams")
+        .and_then(|v| v.0.as_iter()?.next()?.as_iter()?.next()?.as_u64())
+        .ok_or_else(|| anyhow!("Portal returned no stream"))?;
+    debug!("Recording PipeWire node {}", node);
+
+    let (fd,): (dbus::arg::OwnedFd,) = proxy.method_call(
+        SCREENCAST_IFACE,
+        "OpenPipeWireRemote",
+        (session.clone(), PropMap::new()),
+    )?;
+    // SAFETY: the descriptor was just received and nothing else owns it.
+    let fd = unsafe { OwnedFd::from_raw_fd(fd.into_raw_fd()) };
+    Ok((session, node as u32, fd))
+}
+
+fn close_session(conn: &LocalConnection, session: Path<'static>) {
+    let proxy = conn.with_proxy(PORTAL_DEST, session, CALL_TIMEOUT);
+    let result: Result<(), dbus::Error> = proxy.method_call(SESSION_IFACE, "Close", ());
+    if let Err(e) = result {
+        debug!("Failed to close the ScreenCast session: {}", e);
+    }
+}
+
+/// Writes the whole monitor to `raw` with GStreamer, stopping it with an
+/// interrupt so the file is finalized. The process is reaped either way.
+fn record_stream(fd: OwnedFd, node: u32, seconds: u32, raw: &std::path::Path) -> Result<()> {
+    let mut child = Command::new("gst-launch-1.0")
+        .args(["-e", "-q", "pipewiresrc", "fd=0"])
+        .arg(format!("path={}", node))
+        .args(["do-timestamp=true", "!", "videoconvert", "!", "vp8enc", "deadline=1"])
+        .args(["!", "webmmux", "!", "filesink"])
+        .arg(format!("location={}", raw.display()))
+        .stdin(Stdio::from(fd))
+        .stdout(Stdio::null())
+        .spawn()
+        .context("Failed to run gst-launch-1.0")?;
+
+    thread::sleep(Duration::from_secs(u64::from(seconds)));
+    let pid = Pid::from_raw(child.id() as i32);
+    let stopped = kill(pid, Signal::SIGINT);
+    if stopped.is_err() {
+        let _ = child.kill();
+    }
+    let status = child.wait();
+    stopped.context("Failed to stop gst-launch-1.0")?;
+    let status = status?;
+    if fs::metadata(raw).map_or(true, |meta| meta.len() == 0) {
+        bail!("gst-launch-1.0 wrote nothing ({})", status);
+    }
+    Ok(())
+}
+
+fn encode(
+    raw: &std::path::Path,
+    output: &std::path::Path,
+    format: CaptureFormat,
+    region: CropRegion,
+) -> Result<()> {
+    let mut command = Command::new("ffmpeg");
+    command.args(["-y", "-loglevel", "error", "-i"]).arg(raw);
+    match format {
+        CaptureFormat::Gif => {
+            let graph = format!(
+                "{},fps={},split[a][b];[a]palettegen[p];[b][p]paletteuse",
+                region.filter(),
+                GIF_FPS
+            );
+            command.args(["-filter_complex", &graph]);
+        }
+        CaptureFormat::Webm => {
+            command
+                .args(["-vf", &region.filter()])
+                .args(["-c:v", "libvpx-vp9", "-b:v", "0", "-crf", "32", "-an"]);
+        }
+    }
+    let status = command
+        .arg(output)
+        .stdin(Stdio::null())
+        .status()
+        .context("Failed to run ffmpeg")?;
+    if !status.success() {
+        bail!("ffmpeg failed ({})", status);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn crop_is_padded_and_clipped_to_the_monitor() {
+        let region = CropRegion::new((1592, 1000, 200, 60), (1920, 1080), 8).unwrap();
+        assert_eq!(
+            region.filter(),
+            "crop=trunc(iw*0.1125/2)*2:trunc(ih*0.0704/2)*2:iw*0.8250:ih*0.9185"
+        );
+        assert_eq!(CropRegion::new((0, 0, 0, 40), (1920, 1080), 8), None);
+    }
+}
@@ -1,26 +1,186 @@
 use crate::hotkey::Hotkey;
+use crate::i18n::tr;
+use crate::ime::ImeEvent;
 use crate::input::InputEvent;
-use crate::xkb::{is_modifier, key_label, XkbState};
+use crate::ring::RingBuffer;
+use crate::settings::{DisplayMode, DragLabel};
+use crate::speed::RateMeter;
+use crate::xkb::{is_modifier, key_label, layout_abbreviation, Compose, LockState, XkbState};
 use evdev::Key;
-use std::collections::{HashMap, HashSet, VecDeque};
-use std::time::{Duration, Instant};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// Shown instead of plain keys while input is masked.
+pub const MASK_LABEL: &str = "•";
+
+/// Typing speed is averaged over this window.
+const TYPING_WINDOW: Duration = Duration::from_secs(20);
+/// Actions per minute react faster, as in game overlays.
+const ACTIONS_WINDOW: Duration = Duration::from_secs(10);
+/// Status messages such as the pause confirmation stay at least this long,
+/// so a short `ttl_ms` cannot hide them before they are read.
+const STATUS_TTL: Duration = Duration::from_secs(2);
+/// Status messages kept on screen at once, outside `max_items`.
+const STATUS_SLOTS: usize = 2;
+/// The "Ctrl+…" ghost stays until a key comes or the modifiers go up.
+const GHOST_TTL: Duration = Duration::from_secs(60);
+/// How long a ghost takes to fade once its modifiers are released.
+const GHOST_FADE: Duration = Duration::from_millis(300);
+/// A plain key shown this recently may have been the one that started an
+/// input method composition, reported only after the key.
+const IME_GRACE: Duration = Duration::from_millis(300);
 
 #[derive(Debug, Clone)]
 pub struct ComboItem {
+    /// Stays the same while the bubble is on screen, even when a repeat or
+    /// merge changes its text
+    pub id: u64,
     pub text: String,
     pub at: Instant,
+    /// Extra CSS class for the bubble, set by plugins and practice mode
+    pub class: Option<String>,
+    /// Overrides the global TTL for this item
+    pub ttl: Option<Duration>,
+    /// The keys, shown under `text` when teaching mode put an action name there
+    pub caption: Option<String>,
+    /// Presses coalesced into this bubble, for `{count}` in `combo_format`
+    pub count: u32,
+    /// How often the combo was pressed this session, 0 to 4, for
+    /// `frequency_emphasis`
+    pub heat: u8,
+}
+
+impl ComboItem {
+    pub fn expires_at(&self, default_ttl: Duration) -> Instant {
+        self.at + self.ttl.unwrap_or(default_ttl)
+    }
+}
+
+/// A combo as it was added to the overlay, for external consumers.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct ComboEvent {
+    pub text: String,
+    pub kind: ComboKind,
+}
+
+impl ComboEvent {
+    /// `{"type":"combo","text":..,"kind":..,"timestamp":..}`, stamped now.
+    pub fn to_json(&self) -> Value {
+        json!({
+            "type": "combo",
+            "text": self.text,
+            "kind": self.kind,
+            "timestamp": unix_millis(),
+        })
+    }
+}
+
+/// A combo label's parts, modifiers first. A "+" not followed by a key is
+/// the plus key itself, so "Ctrl++" is Ctrl and "+".
+pub fn combo_parts(text: &str) -> Vec<&str> {
+    let (mods, key) = match text.strip_suffix('+') {
+        Some(mods) if mods.is_empty() || mods.ends_with('+') => (mods, "+"),
+        _ => text.rsplit_once('+').unwrap_or(("", text)),
+    };
+    mods.split('+')
+        .filter(|part| !part.is_empty())
+        .chain(std::iter::once(key))
+        .collect()
+}
+
+/// Wall-clock time in Unix milliseconds, for events leaving the process.
+pub fn unix_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ComboKind {
+    Key,
+    Shortcut,
+    Mouse,
+}
+
+impl ComboKind {
+    pub fn name(self) -> &'static str {
+        match self {
+            ComboKind::Key => "key",
+            ComboKind::Shortcut => "shortcut",
+            ComboKind::Mouse => "mouse",
+        }
+    }
+}
+
+/// What a `ComboTransform` decided for a new combo.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Transformed {
+    Keep,
+    Drop,
+    Relabel {
+        text: String,
+        class: Option<String>,
+        /// Replace the newest bubble instead of adding one
+        merge: bool,
+    },
+}
+
+/// Gets every new combo before it is added to the overlay.
+pub trait ComboTransform {
+    fn transform(&mut self, combo: &ComboEvent) -> Transformed;
 }
 
 pub struct ComboState {
     held_mods: HashSet<Key>,
     mod_release_at: HashMap<Key, Instant>,
-    items: VecDeque<ComboItem>,
-    max_items: usize,
+    /// Sized to `max_items`; a new item pushes out the oldest
+    items: RingBuffer<ComboItem>,
+    /// Pause, resume and layout messages, kept apart so a burst of keys
+    /// cannot push them off screen
+    status: RingBuffer<ComboItem>,
+    /// Name of the layout keys were last read with
+    layout: String,
+    next_id: u64,
     ttl: Duration,
     repeat_coalesce: Duration,
     modifier_grace: Duration,
     paused: bool,
     pause_hotkey: Hotkey,
+    hotkey_enabled: bool,
+    presenter_hotkey: Option<Hotkey>,
+    screenshot_hotkey: Option<Hotkey>,
+    marker_hotkey: Option<Hotkey>,
+    bell_keys: Vec<Hotkey>,
+    display_mode: DisplayMode,
+    masked: bool,
+    /// Show what dead-key and Compose sequences produce instead of their keys
+    show_composed: bool,
+    /// An input method has preedit text, so plain keys go to it
+    ime_composing: bool,
+    /// The newest plain key bubble and when it was shown
+    last_plain_key: Option<(u64, Instant)>,
+    transform: Option<Box<dyn ComboTransform>>,
+    /// Teaching mode action names by lowercased combo; empty when it is off
+    aliases: HashMap<String, String>,
+    /// Lowercased device name fragments and the tag their combos get
+    device_tags: Vec<(String, String)>,
+    /// Tag of the device the current presses come from
+    device_tag: Option<String>,
+    /// The dimmed bubble of the modifiers held before a key comes, shown in
+    /// modifier-gated mode and with `ghost_preview`
+    ghost: Option<u64>,
+    ghost_preview: bool,
+    /// Show "Caps Lock ON" and the like when a lock toggles
+    lock_announcements: bool,
+    mouse_drag: DragLabel,
+    /// The bubble of the last click, which a drag replaces
+    click: Option<u64>,
+    typing: RateMeter,
+    actions: RateMeter,
     xkb: XkbState,
 }
 
@@ -32,17 +192,41 @@ impl ComboState {
         modifier_grace: Duration,
         pause_hotkey: Hotkey,
     ) -> Self {
+        let xkb = XkbState::new();
         Self {
             held_mods: HashSet::new(),
             mod_release_at: HashMap::new(),
-            items: VecDeque::new(),
-            max_items,
+            items: RingBuffer::new(max_items),
+            status: RingBuffer::new(STATUS_SLOTS),
+            next_id: 0,
             ttl,
             repeat_coalesce,
             modifier_grace,
             paused: false,
             pause_hotkey,
-            xkb: XkbState::new(),
+            hotkey_enabled: true,
+            presenter_hotkey: None,
+            screenshot_hotkey: None,
+            marker_hotkey: None,
+            bell_keys: Vec::new(),
+            display_mode: DisplayMode::All,
+            masked: false,
+            show_composed: true,
+            ime_composing: false,
+            last_plain_key: None,
+            transform: None,
+            aliases: HashMap::new(),
+            device_tags: Vec::new(),
+            device_tag: None,
+            ghost: None,
+            ghost_preview: false,
+            lock_announcements: false,
+            mouse_drag: DragLabel::Direction,
+            click: None,
+            typing: RateMeter::new(TYPING_WINDOW),
+            actions: RateMeter::new(ACTIONS_WINDOW),
+            layout: xkb.layout_name().to_string(),
+            xkb,
         }
     }
 
@@ -54,14 +238,53 @@ impl ComboState {
 
         match event {
             InputEvent::KeyPressed(key) => {
-                self.xkb.update_key(key, true);
+                let locks = self.xkb.locks();
+                if self.xkb.update_key(key, true) {
+                    let name = self.xkb.layout_name().to_string();
+                    action.render |= self.note_layout(name, true);
+                }
+                if self.lock_announcements && !self.paused {
+                    action.render |= self.announce_locks(locks, now);
+                }
+                if !self.paused {
+                    self.actions.record(now);
+                }
                 if is_modifier(key) {
                     self.held_mods.insert(key);
                     self.mod_release_at.remove(&key);
+                    if !self.paused {
+                        action.render |= self.update_ghost(now, true);
+                    }
                 } else {
                     let label = key_label(key, &self.xkb);
 
-                    if self.pause_hotkey.matches(&self.held_mods, &label) {
+                    if self
+                        .presenter_hotkey
+                        .as_ref()
+                        .is_some_and(|hotkey| hotkey.matches(&self.held_mods, &label))
+                    {
+                        action.presenter_toggled = true;
+                        return action;
+                    }
+                    if self
+                        .screenshot_hotkey
+                        .as_ref()
+                        .is_some_and(|hotkey| hotkey.matches(&self.held_mods, &label))
+                    {
+                        action.screenshot = true;
+                        return action;
+                    }
+                    if self
+                        .marker_hotkey
+                        .as_ref()
+                        .is_some_and(|hotkey| hotkey.matches(&self.held_mods, &label))
+                    {
+                        action.marker = true;
+                        return action;
+                    }
+
+                    if self.hotkey_enabled && self.pause_hotkey.matches(&self.held_mods, &label)
+                    {
                         self.toggle_pause();
                         action.paused_changed = Some(self.paused());
                         action.render = true;
@@ -71,9 +294,41 @@ impl ComboState {
                     if self.paused {
                         return action;
                     }
+                    action.bell = self
+                        .bell_keys
+                        .iter()
+                        .any(|hotkey| hotkey.matches(&self.held_mods, &label));
+                    self.typing.record(now);
+                    let composed = match self.compose(key) {
+                        Compose::None => None,
+                        Compose::Pending => return action,
+                        Compose::Done(text) => Some(text),
+                    };
+                    if !self.should_display(&self.held_mods) {
+                        return action;
+                    }
 
-                    let combo = format_combo(&self.held_mods, &label);
-                    action.render |= self.push_combo(combo, now);
+                    let kind = if is_shortcut(&self.held_mods) {
+                        ComboKind::Shortcut
+                    } else {
+                        ComboKind::Key
+                    };
+                    if self.ime_composing && kind == ComboKind::Key {
+                        return action;
+                    }
+                    let text = match composed {
+                        Some(_) if self.masked => MASK_LABEL.to_string(),
+                        Some(text) => text,
+                        None => self.display_text(&label),
+                    };
+                    let combo = ComboEvent { text, kind };
+                    let ghost = self.ghost.take();
+                    self.add_combo(combo, now, &mut action);
+                    self.last_plain_key = match (kind, self.items.back_mut()) {
+                        (ComboKind::Key, Some(item)) => Some((item.id, now)),
+                        _ => None,
+                    };
+                    action.render |= self.solidify_ghost(ghost);
                 }
             }
             InputEvent::KeyRepeat(key) => {
@@ -81,56 +336,153 @@ impl ComboState {
                 if self.paused {
                     return action;
                 }
-                if !is_modifier(key) {
+                let composing = self.ime_composing && !is_shortcut(&self.held_mods);
+                if !is_modifier(key) && !composing && self.should_display(&self.held_mods) {
                     let label = key_label(key, &self.xkb);
-                    let combo = format_combo(&self.held_mods, &label);
-                    action.render |= self.push_combo(combo, now);
+                    let combo = self.display_text(&label);
+                    action.render |= self.push_combo(combo, None, None, now);
                 }
             }
             InputEvent::KeyReleased(key) => {
-                self.xkb.update_key(key, false);
+                // Caps Lock goes off on the release of its second press.
+                let locks = self.xkb.locks();
+                if self.xkb.update_key(key, false) {
+                    let name = self.xkb.layout_name().to_string();
+                    action.render |= self.note_layout(name, true);
+                }
+                if self.lock_announcements && !self.paused {
+                    action.render |= self.announce_locks(locks, now);
+                }
                 if is_modifier(key) {
                     self.mod_release_at.insert(key, now);
+                    action.render |= self.update_ghost(now, false);
                 }
             }
             InputEvent::MouseButtonPressed(key) => {
                 if self.paused {
                     return action;
                 }
+                self.actions.record(now);
                 if let Some(label) = mouse_label(key) {
-                    action.render |= self.push_combo(label.to_string(), now);
+                    let combo = ComboEvent {
+                        text: label.to_string(),
+                        kind: ComboKind::Mouse,
+                    };
+                    self.add_combo(combo, now, &mut action);
+                    self.click = match action.combo {
+                        Some(_) => self.items.back_mut().map(|item| item.id),
+                        None => None,
+                    };
                 }
             }
             InputEvent::MouseButtonReleased => {}
+            InputEvent::MouseDrag { button, dx, dy } => {
+                let click = self.click.take();
+                if self.paused || self.mouse_drag == DragLabel::Off {
+                    return action;
+                }
+                let Some(label) = mouse_label(button) else {
+                    return action;
+                };
+                action.render |= self.take_back_click(click);
+                let combo = ComboEvent {
+                    text: drag_label(label, dx, dy, self.mouse_drag),
+                    kind: ComboKind::Mouse,
+                };
+                self.add_combo(combo, now, &mut action);
+            }
+            InputEvent::Device(name) => self.note_device(&name),
         }
 
         action
     }
 
+    /// Undoes the click that started a drag: its bubble goes, or counts one
+    /// press less when repeated clicks were coalesced into it.
+    fn take_back_click(&mut self, click: Option<u64>) -> bool {
+        let Some(id) = click else {
+            return false;
+        };
+        if let Some(item) = self.items.iter_mut().find(|item| item.id == id && item.count > 1) {
+            item.count -= 1;
+            return true;
+        }
+        self.items.retain(|item| item.id != id)
+    }
+
     pub fn prune_expired(&mut self) -> bool {
         let now = Instant::now();
         let mut changed = false;
 
         self.prune_mods(now);
 
-        while let Some(front) = self.items.front() {
-            if now.duration_since(front.at) > self.ttl {
-                self.items.pop_front();
-                changed = true;
-            } else {
-                break;
-            }
-        }
+        let ttl = self.ttl;
+        // Items with their own TTL can outlive newer ones, so expired items
+        // are not always at the front.
+        changed |= self.items.retain(|item| now <= item.expires_at(ttl));
+        changed |= self.status.retain(|item| now <= item.expires_at(ttl));
 
         changed
     }
 
-    pub fn items(&self) -> &VecDeque<ComboItem> {
+    /// When the next item is due to be pruned.
+    pub fn next_expiry(&self) -> Option<Instant> {
+        self.items
+            .iter()
+            .chain(self.status.iter())
+            .map(|item| item.expires_at(self.ttl))
+            .min()
+    }
+
+    pub fn items(&self) -> &RingBuffer<ComboItem> {
         &self.items
     }
 
+    /// Adds `note` to the caption of item `id`, after any teaching caption.
+    /// False when the item is gone.
+    pub fn annotate(&mut self, id: u64, note: &str) -> bool {
+        let Some(item) = self.items.iter_mut().find(|item| item.id == id) else {
+            return false;
+        };
+        item.caption = Some(match item.caption.take() {
+            Some(caption) => format!("{} · {}", caption, note),
+            None => note.to_string(),
+        });
+        true
+    }
+
+    /// Sets the heat of item `id`; false when the item is gone or already
+    /// that warm.
+    pub fn set_heat(&mut self, id: u64, heat: u8) -> bool {
+        match self.items.iter_mut().find(|item| item.id == id) {
+            Some(item) if item.heat != heat => {
+                item.heat = heat;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Replaces the extra CSS class of item `id`; false when the item is gone.
+    pub fn set_class(&mut self, id: u64, class: &str) -> bool {
+        match self.items.iter_mut().find(|item| item.id == id) {
+            Some(item) => {
+                item.class = Some(class.to_string());
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Status messages on screen, oldest first. Their ids come from the same
+    /// counter as the items', so the two can be merged in order.
+    pub fn status(&self) -> &RingBuffer<ComboItem> {
+        &self.status
+    }
+
     pub fn clear_items(&mut self) {
         self.items.clear();
+        self.status.clear();
     }
 
     pub fn handle_event_suppressed(&mut self, event: InputEvent) {
@@ -151,7 +503,10 @@ impl ComboState {
             InputEvent::KeyRepeat(key) => {
                 self.xkb.update_key(key, true);
             }
-            InputEvent::MouseButtonPressed(_) | InputEvent::MouseButtonReleased => {}
+            InputEvent::MouseButtonPressed(_)
+            | InputEvent::MouseButtonReleased
+            | InputEvent::MouseDrag { .. } => {}
+            InputEvent::Device(name) => self.note_device(&name),
         }
     }
 
@@ -163,6 +518,20 @@ impl ComboState {
         self.paused
     }
 
+    /// Non-modifier key presses per minute over the last few seconds.
+    pub fn typing_rate(&mut self, now: Instant) -> f64 {
+        self.typing.per_minute(now)
+    }
+
+    /// Key presses (modifiers included) and clicks per minute.
+    pub fn action_rate(&mut self, now: Instant) -> f64 {
+        self.actions.per_minute(now)
+    }
+
+    pub fn rates_settled(&self, now: Instant) -> bool {
+        self.typing.is_settled(now) && self.actions.is_settled(now)
+    }
+
     pub fn update_settings(
         &mut self,
         max_items: usize,
@@ -171,43 +540,433 @@ impl ComboState {
         modifier_grace: Duration,
         pause_hotkey: Hotkey,
     ) {
-        self.max_items = max_items;
+        self.items.set_capacity(max_items);
         self.ttl = ttl;
         self.repeat_coalesce = repeat_coalesce;
         self.modifier_grace = modifier_grace;
         self.pause_hotkey = pause_hotkey;
+    }
+
+    /// Turns off evdev hotkey matching while the portal handles it instead.
+    pub fn set_hotkey_enabled(&mut self, enabled: bool) {
+        self.hotkey_enabled = enabled;
+    }
+
+    pub fn set_ttl(&mut self, ttl: Duration) {
+        self.ttl = ttl;
+    }
+
+    pub fn set_presenter_hotkey(&mut self, hotkey: Option<Hotkey>) {
+        self.presenter_hotkey = hotkey;
+    }
+
+    pub fn set_screenshot_hotkey(&mut self, hotkey: Option<Hotkey>) {
+        self.screenshot_hotkey = hotkey;
+    }
+
+    pub fn set_marker_hotkey(&mut self, hotkey: Option<Hotkey>) {
+        self.marker_hotkey = hotkey;
+    }
+
+    /// Keys that ring the visual bell, whether or not they are displayed.
+    pub fn set_bell_keys(&mut self, keys: Vec<Hotkey>) {
+        self.bell_keys = keys;
+    }
+
+    /// Teaching mode: combos with an alias show its action name instead,
+    /// with the keys as a caption beneath.
+    pub fn set_teaching(&mut self, enabled: bool, aliases: &BTreeMap<String, String>) {
+        self.aliases = if enabled {
+            aliases
+                .iter()
+                .map(|(combo, action)| (combo.to_lowercase(), action.clone()))
+                .collect()
+        } else {
+            HashMap::new()
+        };
+    }
+
+    /// Tags for combos from particular devices, such as `pad` for a macro
+    /// pad, by a fragment of the device name.
+    pub fn set_device_tags(&mut self, tags: &BTreeMap<String, String>) {
+        self.device_tags = tags
+            .iter()
+            .map(|(name, tag)| (name.to_lowercase(), tag.clone()))
+            .collect();
+        self.device_tag = None;
+    }
+
+    fn note_device(&mut self, name: &str) {
+        let name = name.to_lowercase();
+        self.device_tag = self
+            .device_tags
+            .iter()
+            .find(|(fragment, _)| name.contains(fragment.as_str()))
+            .map(|(_, tag)| tag.clone());
+    }
+
+    pub fn set_display_mode(&mut self, display_mode: DisplayMode) {
+        self.display_mode = display_mode;
+        self.drop_ghost();
+    }
+
+    pub fn set_ghost_preview(&mut self, ghost_preview: bool) {
+        self.ghost_preview = ghost_preview;
+        self.drop_ghost();
+    }
+
+    pub fn set_masked(&mut self, masked: bool) {
+        self.masked = masked;
+    }
+
+    pub fn masked(&self) -> bool {
+        self.masked
+    }
+
+    pub fn set_mouse_drag(&mut self, mouse_drag: DragLabel) {
+        self.mouse_drag = mouse_drag;
+    }
+
+    pub fn set_show_composed(&mut self, show_composed: bool) {
+        self.show_composed = show_composed;
+    }
+
+    /// While an input method composes, plain keys go to it and are not
+    /// shown; `show_ime_text` shows what it commits instead. The key that
+    /// started the composition was shown before the IME said so, and is
+    /// taken back. True when that changed the overlay.
+    pub fn set_ime_composing(&mut self, composing: bool) -> bool {
+        let was = std::mem::replace(&mut self.ime_composing, composing);
+        if was || !composing {
+            return false;
+        }
+        match self.last_plain_key.take() {
+            Some((id, at)) if at.elapsed() <= IME_GRACE => self.items.retain(|item| item.id != id),
+            _ => false,
+        }
+    }
+
+    /// Shows text an input method committed, like a key would be.
+    pub fn show_ime_text(&mut self, text: String) -> ComboAction {
+        let mut action = ComboAction::default();
+        if self.paused || text.trim().is_empty() {
+            return action;
+        }
+        let text = if self.masked { MASK_LABEL.to_string() } else { text };
+        self.add_combo(ComboEvent { text, kind: ComboKind::Key }, Instant::now(), &mut action);
+        action
+    }
+
+    /// An input method's report. While `suppressed` (the overlay hidden or
+    /// the session locked) committed text is dropped and composing ends, so
+    /// none of it turns up once capture is back.
+    pub fn handle_ime(&mut self, event: ImeEvent, suppressed: bool) -> ComboAction {
+        match event {
+            ImeEvent::Composing(composing) => ComboAction {
+                render: self.set_ime_composing(composing && !suppressed),
+                ..ComboAction::default()
+            },
+            ImeEvent::Commit(_) if suppressed => ComboAction::default(),
+            ImeEvent::Commit(text) => self.show_ime_text(text),
+        }
+    }
+
+    /// Shortcuts bypass composing, as they do in applications.
+    fn compose(&mut self, key: Key) -> Compose {
+        if !self.show_composed || is_shortcut(&self.held_mods) {
+            return Compose::None;
+        }
+        self.xkb.compose(key)
+    }
+
+    /// Ctrl, Shift, Alt and Super, true while physically held. Unlike
+    /// combos, this ignores the release grace period.
+    pub fn modifiers_down(&self) -> [bool; 4] {
+        let down: HashSet<Key> = self
+            .held_mods
+            .iter()
+            .filter(|key| !self.mod_release_at.contains_key(key))
+            .copied()
+            .collect();
+        [has_ctrl(&down), has_shift(&down), has_alt(&down), has_super(&down)]
+    }
+
+    /// Abbreviation of the layout keys are read with, such as "DE".
+    pub fn layout_abbreviation(&self) -> Option<String> {
+        (!self.layout.is_empty()).then(|| layout_abbreviation(&self.layout))
+    }
+
+    pub fn locks(&self) -> LockState {
+        self.xkb.locks()
+    }
+
+    pub fn set_lock_announcements(&mut self, enabled: bool) {
+        self.lock_announcements = enabled;
+    }
+
+    /// A status such as "Caps Lock ON" for each lock that changed since
+    /// `before`, replacing the one from its last toggle.
+    fn announce_locks(&mut self, before: LockState, now: Instant) -> bool {
+        let after = self.xkb.locks();
+        let locks = [
+            ("Caps Lock", before.caps, after.caps),
+            ("Num Lock", before.num, after.num),
+            ("Scroll Lock", before.scroll, after.scroll),
+        ];
+        let mut changed = false;
+        for (name, was, is) in locks.into_iter().filter(|(_, was, is)| was != is) {
+            let state = |on: bool| format!("{} {}", tr(name), tr(if on { "ON" } else { "OFF" }));
+            self.dismiss_status(&state(was));
+            self.push_status(state(is), now);
+            changed = true;
+        }
+        changed
+    }
+
+    /// Follows a layout switch reported by the compositor. Labels change
+    /// with it when the keymap has a layout of that name. With `announce`,
+    /// a switch shows a "Layout: DE" status; true when it did.
+    pub fn set_layout(&mut self, name: &str, announce: bool) -> bool {
+        self.xkb.select_layout(name);
+        self.note_layout(name.to_string(), announce)
+    }
+
+    fn note_layout(&mut self, name: String, announce: bool) -> bool {
+        if self.layout == name {
+            return false;
+        }
+        let text = format!("{} {}", tr("Layout:"), layout_abbreviation(&name));
+        self.layout = name;
+        // Nothing typed while paused is shown, so neither is the layout.
+        if !announce || self.paused {
+            return false;
+        }
+        self.push_status(text, Instant::now());
+        true
+    }
+
+    /// Shows a combo received from another instance (remote display).
+    pub fn show_remote(&mut self, combo: ComboEvent) -> ComboAction {
+        let mut action = ComboAction::default();
+        if !self.paused {
+            self.add_combo(combo, Instant::now(), &mut action);
+        }
+        action
+    }
+
+    /// Shows a message pushed from outside (control socket, D-Bus).
+    pub fn announce(&mut self, text: String) -> bool {
+        self.push_combo(text, Some("announce".to_string()), None, Instant::now())
+    }
+
+    pub fn set_transform(&mut self, transform: Option<Box<dyn ComboTransform>>) {
+        self.transform = transform;
+    }
+
+    fn display_text(&self, label: &str) -> String {
+        if self.masked && !is_shortcut(&self.held_mods) {
+            return MASK_LABEL.to_string();
+        }
+        format_combo(&self.held_mods, label)
+    }
 
-        while self.items.len() > self.max_items {
-            self.items.pop_front();
+    fn should_display(&self, held_mods: &HashSet<Key>) -> bool {
+        match self.display_mode {
+            DisplayMode::All => true,
+            DisplayMode::ShortcutsOnly => is_shortcut(held_mods),
+            DisplayMode::ModifierGated => !held_mods.is_empty(),
+        }
+    }
+
+    /// Shows the modifiers physically down as a dimmed "Ctrl+Shift+…":
+    /// any of them in modifier-gated mode, shortcut ones with
+    /// `ghost_preview`. Without them the ghost fades out. Outside
+    /// modifier-gated mode a release (`start` false) only changes a ghost
+    /// already up, so letting go of Shift after Ctrl+Shift+T brings none
+    /// back. True when the items changed.
+    fn update_ghost(&mut self, now: Instant, start: bool) -> bool {
+        let down: HashSet<Key> = self
+            .held_mods
+            .iter()
+            .filter(|key| !self.mod_release_at.contains_key(key))
+            .copied()
+            .collect();
+        let shown = match self.display_mode {
+            DisplayMode::ModifierGated => !down.is_empty(),
+            DisplayMode::All | DisplayMode::ShortcutsOnly => {
+                self.ghost_preview && is_shortcut(&down)
+            }
+        };
+        if !shown {
+            return self.fade_ghost(now);
         }
+        let text = format_combo(&down, "…");
+        let ghost = self.ghost;
+        if let Some(item) = self.items.iter_mut().find(|item| Some(item.id) == ghost) {
+            if item.text == text {
+                return false;
+            }
+            item.text = text;
+            item.at = now;
+            return true;
+        }
+        if !start && self.display_mode != DisplayMode::ModifierGated {
+            return false;
+        }
+        let item = self.new_item(text, Some("ghost".to_string()), Some(GHOST_TTL), now);
+        self.ghost = Some(item.id);
+        self.items.push_back(item);
+        true
     }
 
-    fn set_paused(&mut self, paused: bool) -> bool {
+    /// The modifiers went up without a key: the ghost fades and is pruned.
+    fn fade_ghost(&mut self, now: Instant) -> bool {
+        let ghost = self.ghost.take();
+        let Some(item) = self.items.iter_mut().find(|item| Some(item.id) == ghost) else {
+            return false;
+        };
+        item.class = Some("ghost-fading".to_string());
+        item.ttl = Some(now.duration_since(item.at) + GHOST_FADE);
+        true
+    }
+
+    /// The key arrived: the combo just added takes over the ghost's id, so
+    /// the bubble stays where it was and only turns solid.
+    fn solidify_ghost(&mut self, ghost: Option<u64>) -> bool {
+        let Some(id) = ghost else {
+            return false;
+        };
+        let removed = self.items.retain(|item| item.id != id);
+        let newest = self.next_id - 1;
+        if let Some(back) = self.items.back_mut().filter(|back| back.id == newest && newest > id) {
+            back.id = id;
+        }
+        removed
+    }
+
+    fn drop_ghost(&mut self) -> bool {
+        match self.ghost.take() {
+            Some(id) => self.items.retain(|item| item.id != id),
+            None => false,
+        }
+    }
+
+    pub fn set_paused(&mut self, paused: bool) -> bool {
         if self.paused == paused {
             return false;
         }
 
         self.paused = paused;
-        let label = if paused { "Paused" } else { "Resumed" };
-        let _ = self.push_combo(label.to_string(), Instant::now());
+        let label = tr(if paused { "Paused" } else { "Resumed" });
+        self.push_status(label.to_string(), Instant::now());
         true
     }
 
-    fn push_combo(&mut self, text: String, now: Instant) -> bool {
+    /// Shows `text` as a status message for `ttl`, or keeps the one already
+    /// showing it up for another `ttl`. True when it was newly added.
+    pub fn show_status(&mut self, text: &str, ttl: Duration) -> bool {
+        let now = Instant::now();
+        if let Some(item) = self.status.iter_mut().find(|item| item.text == text) {
+            item.at = now;
+            item.ttl = Some(ttl);
+            return false;
+        }
+        let item = self.new_item(text.to_string(), None, Some(ttl), now);
+        self.status.push_back(item);
+        true
+    }
+
+    /// Takes a status message down before its TTL runs out.
+    pub fn dismiss_status(&mut self, text: &str) -> bool {
+        self.status.retain(|item| item.text != text)
+    }
+
+    fn push_status(&mut self, text: String, now: Instant) {
+        let ttl = self.ttl.max(STATUS_TTL);
+        let item = self.new_item(text, None, Some(ttl), now);
+        self.status.push_back(item);
+    }
+
+    /// Runs a new combo through the transform and adds what is left of it.
+    fn add_combo(&mut self, mut combo: ComboEvent, now: Instant, action: &mut ComboAction) {
+        let mut class = None;
+        let mut merge = false;
+        if let Some(transform) = &mut self.transform {
+            match transform.transform(&combo) {
+                Transformed::Keep => {}
+                Transformed::Drop => return,
+                Transformed::Relabel {
+                    text,
+                    class: new_class,
+                    merge: new_merge,
+                } => {
+                    combo.text = text;
+                    class = new_class;
+                    merge = new_merge;
+                }
+            }
+        }
+
+        match self.items.back_mut() {
+            Some(back) if merge => {
+                back.text = tagged(self.device_tag.as_deref(), combo.text.clone());
+                back.class = class;
+                back.caption = None;
+                back.at = now;
+                action.render = true;
+            }
+            _ => match self.aliases.get(&combo.text.to_lowercase()).cloned() {
+                Some(name) => {
+                    action.render |= self.push_combo(name, Some("teaching".to_string()), None, now);
+                    if let Some(back) = self.items.back_mut() {
+                        back.caption = Some(combo.text.clone());
+                    }
+                }
+                None => action.render |= self.push_combo(combo.text.clone(), class, None, now),
+            },
+        }
+        action.combo = Some(combo);
+    }
+
+    fn push_combo(
+        &mut self,
+        text: String,
+        class: Option<String>,
+        ttl: Option<Duration>,
+        now: Instant,
+    ) -> bool {
+        let text = tagged(self.device_tag.as_deref(), text);
         if let Some(back) = self.items.back_mut() {
             if back.text == text && now.duration_since(back.at) <= self.repeat_coalesce {
                 back.at = now;
+                back.count += 1;
                 return true;
             }
         }
 
-        self.items.push_back(ComboItem { text, at: now });
+        let item = self.new_item(text, class, ttl, now);
+        self.items.push_back(item);
+        true
+    }
 
-        while self.items.len() > self.max_items {
-            self.items.pop_front();
+    fn new_item(
+        &mut self,
+        text: String,
+        class: Option<String>,
+        ttl: Option<Duration>,
+        now: Instant,
+    ) -> ComboItem {
+        self.next_id += 1;
+        ComboItem {
+            id: self.next_id - 1,
+            text,
+            at: now,
+            class,
+            ttl,
+            caption: None,
+            count: 1,
+            heat: 0,
         }
-
-        true
     }
 
     fn prune_mods(&mut self, now: Instant) {
@@ -230,26 +989,49 @@ impl ComboState {
 pub struct ComboAction {
     pub render: bool,
     pub paused_changed: Option<bool>,
+    /// The presenter mode hotkey was pressed.
+    pub presenter_toggled: bool,
+    /// The screenshot hotkey was pressed.
+    pub screenshot: bool,
+    /// The timeline marker hotkey was pressed.
+    pub marker: bool,
+    /// A visual bell key was pressed.
+    pub bell: bool,
+    /// Set when a key press or click added a new combo.
+    pub combo: Option<ComboEvent>,
 }
 
+/// `[pad] F13` for a combo from a tagged device.
+fn tagged(tag: Option<&str>, text: String) -> String {
+    match tag {
+        Some(tag) => format!("[{}] {}", tag, text),
+        None => text,
+    }
+}
+
+/// Builds the label in one allocation; this runs on every key press.
 fn format_combo(held_mods: &HashSet<Key>, key_label: &str) -> String {
-    let mut parts: Vec<&str> = Vec::new();
+    let mods = [
+        (has_ctrl(held_mods), "Ctrl+"),
+        (has_shift(held_mods), "Shift+"),
+        (has_alt(held_mods), "Alt+"),
+        (has_super(held_mods), "Super+"),
+    ];
+    let len = mods.iter().filter(|(held, _)| *held).map(|(_, prefix)| prefix.len()).sum::<usize>();
 
-    if has_ctrl(held_mods) {
-        parts.push("Ctrl");
-    }
-    if has_shift(held_mods) {
-        parts.push("Shift");
-    }
-    if has_alt(held_mods) {
-        parts.push("Alt");
-    }
-    if has_super(held_mods) {
-        parts.push("Super");
+    let mut text = String::with_capacity(len + key_label.len());
+    for (held, prefix) in mods {
+        if held {
+            text.push_str(prefix);
+        }
     }
+    text.push_str(key_label);
+    text
+}
 
-    parts.push(key_label);
-    parts.join("+")
+/// Shift alone only changes the typed character, so it doesn't count.
+fn is_shortcut(mods: &HashSet<Key>) -> bool {
+    has_ctrl(mods) || has_alt(mods) || has_super(mods)
 }
 
 fn has_ctrl(mods: &HashSet<Key>) -> bool {
@@ -277,6 +1059,20 @@ fn mouse_label(key: Key) -> Option<&'static str> {
     }
 }
 
+/// "LMB drag", with the direction as an arrow and the distance when asked.
+fn drag_label(button: &str, dx: i32, dy: i32, style: DragLabel) -> String {
+    const ARROWS: [&str; 8] = ["→", "↗", "↑", "↖", "←", "↙", "↓", "↘"];
+    let (dx, dy) = (f64::from(dx), f64::from(dy));
+    // Screen y grows downwards, so up is negative dy.
+    let octant = ((-dy).atan2(dx) / std::f64::consts::FRAC_PI_4).round() as i32;
+    let arrow = ARROWS[octant.rem_euclid(8) as usize];
+    match style {
+        DragLabel::Off | DragLabel::Plain => format!("{} drag", button),
+        DragLabel::Direction => format!("{} drag {}", button, arrow),
+        DragLabel::Length => format!("{} drag {} {:.0}", button, arrow, dx.hypot(dy)),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -291,4 +1087,327 @@ mod tests {
         let combo = format_combo(&mods, "A");
         assert_eq!(combo, "Ctrl+Shift+Alt+A");
     }
+
+    fn test_state() -> ComboState {
+        ComboState::new(
+            5,
+            Duration::from_millis(900),
+            Duration::from_millis(200),
+            Duration::from_millis(120),
+            Hotkey::parse("Ctrl+Shift+P").unwrap(),
+        )
+    }
+
+    #[test]
+    fn masked_state_hides_plain_keys_only() {
+        let mut state = test_state();
+        state.set_masked(true);
+
+        state.handle_event(InputEvent::KeyPressed(Key::KEY_A));
+        state.handle_event(InputEvent::KeyReleased(Key::KEY_A));
+        state.handle_event(InputEvent::KeyPressed(Key::KEY_LEFTALT));
+        state.handle_event(InputEvent::KeyPressed(Key::KEY_C));
+
+        let texts: Vec<&str> = state.items().iter().map(|i| i.text.as_str()).collect();
+        assert_eq!(texts, vec![MASK_LABEL, "Alt+C"]);
+    }
+
+    #[test]
+    fn ime_composition_shows_committed_text() {
+        let mut state = test_state();
+        state.handle_event(InputEvent::KeyPressed(Key::KEY_N));
+        assert!(state.set_ime_composing(true));
+        state.handle_event(InputEvent::KeyPressed(Key::KEY_I));
+        state.handle_event(InputEvent::KeyPressed(Key::KEY_LEFTCTRL));
+        state.handle_event(InputEvent::KeyPressed(Key::KEY_C));
+        state.handle_event(InputEvent::KeyReleased(Key::KEY_LEFTCTRL));
+        assert!(!state.set_ime_composing(false));
+        let action = state.show_ime_text("你".to_string());
+        assert_eq!(action.combo.map(|combo| combo.text).as_deref(), Some("你"));
+
+        let texts: Vec<&str> = state.items().iter().map(|i| i.text.as_str()).collect();
+        assert_eq!(texts, vec!["Ctrl+C", "你"]);
+    }
+
+    #[test]
+    fn suppressed_ime_text_is_dropped() {
+        let mut state = test_state();
+        state.handle_ime(ImeEvent::Composing(true), false);
+        state.handle_ime(ImeEvent::Composing(true), true);
+        let action = state.handle_ime(ImeEvent::Commit("密码".to_string()), true);
+        assert!(action.combo.is_none());
+        assert_eq!(state.items().iter().count(), 0);
+
+        // Composing ended, so plain keys show again.
+        state.handle_event(InputEvent::KeyPressed(Key::KEY_A));
+        assert_eq!(state.items().iter().count(), 1);
+    }
+
+    #[test]
+    fn ctrl_combos_use_the_key_symbol() {
+        let mut state = test_state();
+        state.handle_event(InputEvent::KeyPressed(Key::KEY_LEFTCTRL));
+        state.handle_event(InputEvent::KeyPressed(Key::KEY_C));
+        state.handle_event(InputEvent::KeyPressed(Key::KEY_1));
+
+        let texts: Vec<&str> = state.items().iter().map(|i| i.text.as_str()).collect();
+        assert_eq!(texts, vec!["Ctrl+C", "Ctrl+1"]);
+    }
+
+    #[test]
+    fn presenter_hotkey_toggles_without_showing() {
+        let mut state = test_state();
+        state.set_presenter_hotkey(Some(Hotkey::parse("Ctrl+Shift+F10").unwrap()));
+        state.handle_event(InputEvent::KeyPressed(Key::KEY_LEFTCTRL));
+        state.handle_event(InputEvent::KeyPressed(Key::KEY_LEFTSHIFT));
+
+        let action = state.handle_event(InputEvent::KeyPressed(Key::KEY_F10));
+        assert!(action.presenter_toggled);
+        assert!(action.combo.is_none());
+        assert!(state.items().iter().next().is_none());
+    }
+
+    #[test]
+    fn tagged_devices_prefix_their_combos() {
+        let mut state = test_state();
+        let tags = BTreeMap::from([("macro pad".to_string(), "pad".to_string())]);
+        state.set_device_tags(&tags);
+
+        state.handle_event(InputEvent::Device("USB Macro Pad Keyboard".into()));
+        let action = state.handle_event(InputEvent::KeyPressed(Key::KEY_F13));
+        assert_eq!(action.combo.unwrap().text, "F13");
+        state.handle_event(InputEvent::Device("AT Translated Set 2 keyboard".into()));
+        state.handle_event(InputEvent::KeyPressed(Key::KEY_A));
+
+        let texts: Vec<&str> = state.items().iter().map(|i| i.text.as_str()).collect();
+        assert_eq!(texts, vec!["[pad] F13", "A"]);
+    }
+
+    #[test]
+    fn modifier_gated_mode_builds_the_combo_live() {
+        let mut state = test_state();
+        state.set_display_mode(DisplayMode::ModifierGated);
+        let texts = |state: &ComboState| -> Vec<String> {
+            state.items().iter().map(|i| i.text.clone()).collect()
+        };
+
+        state.handle_event(InputEvent::KeyPressed(Key::KEY_A));
+        assert!(texts(&state).is_empty());
+        state.handle_event(InputEvent::KeyPressed(Key::KEY_LEFTCTRL));
+        assert_eq!(texts(&state), vec!["Ctrl+…"]);
+        state.handle_event(InputEvent::KeyPressed(Key::KEY_LEFTSHIFT));
+        assert_eq!(texts(&state), vec!["Ctrl+Shift+…"]);
+        state.handle_event(InputEvent::KeyPressed(Key::KEY_T));
+        assert_eq!(texts(&state), vec!["Ctrl+Shift+T"]);
+
+        state.handle_event(InputEvent::KeyReleased(Key::KEY_T));
+        state.handle_event(InputEvent::KeyReleased(Key::KEY_LEFTSHIFT));
+        assert_eq!(texts(&state), vec!["Ctrl+Shift+T", "Ctrl+…"]);
+        state.handle_event(InputEvent::KeyReleased(Key::KEY_LEFTCTRL));
+        let ghost = state.items().iter().last().unwrap();
+        assert_eq!(ghost.class.as_deref(), Some("ghost-fading"));
+        assert!(ghost.expires_at(Duration::ZERO) <= Instant::now() + GHOST_FADE);
+    }
+
+    #[test]
+    fn ghost_preview_solidifies_into_the_shortcut() {
+        let mut state = test_state();
+        state.set_ghost_preview(true);
+        let bubbles = |state: &ComboState| -> Vec<(u64, String, Option<String>)> {
+            state.items().iter().map(|i| (i.id, i.text.clone(), i.class.clone())).collect()
+        };
+
+        state.handle_event(InputEvent::KeyPressed(Key::KEY_A));
+        state.handle_event(InputEvent::KeyPressed(Key::KEY_LEFTCTRL));
+        let (ghost, text, class) = bubbles(&state).pop().unwrap();
+        assert_eq!((text.as_str(), class.as_deref()), ("Ctrl+…", Some("ghost")));
+        state.handle_event(InputEvent::KeyPressed(Key::KEY_S));
+        assert_eq!(bubbles(&state).pop().unwrap(), (ghost, "Ctrl+S".to_string(), None));
+        assert_eq!(bubbles(&state).len(), 2);
+
+        // Released without a key, the ghost fades instead.
+        state.handle_event(InputEvent::KeyReleased(Key::KEY_S));
+        state.handle_event(InputEvent::KeyReleased(Key::KEY_LEFTCTRL));
+        assert_eq!(bubbles(&state).len(), 2);
+        state.handle_event(InputEvent::KeyPressed(Key::KEY_LEFTALT));
+        state.handle_event(InputEvent::KeyReleased(Key::KEY_LEFTALT));
+        let (_, text, class) = bubbles(&state).pop().unwrap();
+        assert_eq!((text.as_str(), class.as_deref()), ("Alt+…", Some("ghost-fading")));
+
+        // Shift alone only changes what is typed.
+        state.handle_event(InputEvent::KeyPressed(Key::KEY_LEFTSHIFT));
+        assert_eq!(bubbles(&state).len(), 3);
+    }
+
+    #[test]
+    fn lock_toggles_are_announced() {
+        let mut state = test_state();
+        state.set_lock_announcements(true);
+        let statuses = |state: &ComboState| -> Vec<String> {
+            state.status.iter().map(|i| i.text.clone()).collect()
+        };
+
+        state.handle_event(InputEvent::KeyPressed(Key::KEY_CAPSLOCK));
+        state.handle_event(InputEvent::KeyReleased(Key::KEY_CAPSLOCK));
+        assert_eq!(statuses(&state), vec!["Caps Lock ON"]);
+        state.handle_event(InputEvent::KeyPressed(Key::KEY_CAPSLOCK));
+        state.handle_event(InputEvent::KeyReleased(Key::KEY_CAPSLOCK));
+        assert_eq!(statuses(&state), vec!["Caps Lock OFF"]);
+
+        state.handle_event(InputEvent::KeyPressed(Key::KEY_SCROLLLOCK));
+        state.handle_event(InputEvent::KeyRepeat(Key::KEY_SCROLLLOCK));
+        assert!(state.locks().scroll);
+        assert_eq!(statuses(&state), vec!["Caps Lock OFF", "Scroll Lock ON"]);
+    }
+
+    #[test]
+    fn a_drag_replaces_its_click() {
+        let mut state = test_state();
+        let texts = |state: &ComboState| -> Vec<String> {
+            state.items.iter().map(|i| i.text.clone()).collect()
+        };
+        let drag = |dx, dy| InputEvent::MouseDrag {
+            button: Key::BTN_LEFT,
+            dx,
+            dy,
+        };
+
+        state.handle_event(InputEvent::MouseButtonPressed(Key::BTN_LEFT));
+        let action = state.handle_event(drag(100, -100));
+        assert_eq!(action.combo.unwrap().text, "LMB drag ↗");
+        assert_eq!(texts(&state), vec!["LMB drag ↗"]);
+
+        // The second click of a double click was the start of the drag.
+        state.handle_event(InputEvent::MouseButtonPressed(Key::BTN_RIGHT));
+        state.handle_event(InputEvent::MouseButtonPressed(Key::BTN_RIGHT));
+        state.set_mouse_drag(DragLabel::Length);
+        state.handle_event(InputEvent::MouseDrag {
+            button: Key::BTN_RIGHT,
+            dx: 0,
+            dy: 40,
+        });
+        assert_eq!(texts(&state), vec!["LMB drag ↗", "RMB", "RMB drag ↓ 40"]);
+
+        state.set_mouse_drag(DragLabel::Off);
+        state.handle_event(InputEvent::MouseButtonPressed(Key::BTN_LEFT));
+        state.handle_event(drag(-50, 0));
+        assert_eq!(texts(&state).last().unwrap(), "LMB");
+    }
+
+    #[test]
+    fn bell_keys_ring_even_when_hidden() {
+        let mut state = test_state();
+        state.set_bell_keys(vec![Hotkey::parse("F13").unwrap()]);
+        state.set_display_mode(DisplayMode::ShortcutsOnly);
+
+        let action = state.handle_event(InputEvent::KeyPressed(Key::KEY_F13));
+        assert!(action.bell);
+        assert!(action.combo.is_none());
+        assert!(!state.handle_event(InputEvent::KeyPressed(Key::KEY_A)).bell);
+    }
+
+    #[test]
+    fn released_modifiers_go_dark_at_once() {
+        let mut state = test_state();
+        state.handle_event(InputEvent::KeyPressed(Key::KEY_LEFTCTRL));
+        state.handle_event(InputEvent::KeyPressed(Key::KEY_RIGHTALT));
+        assert_eq!(state.modifiers_down(), [true, false, true, false]);
+
+        // Still inside the grace period, so Ctrl+A would show as a combo.
+        state.handle_event(InputEvent::KeyReleased(Key::KEY_LEFTCTRL));
+        assert_eq!(state.modifiers_down(), [false, false, true, false]);
+    }
+
+    #[test]
+    fn teaching_mode_shows_the_action_name() {
+        let mut state = test_state();
+        let aliases =
+            BTreeMap::from([("ctrl+shift+t".to_string(), "Reopen closed tab".to_string())]);
+        state.set_teaching(true, &aliases);
+        state.handle_event(InputEvent::KeyPressed(Key::KEY_LEFTCTRL));
+        state.handle_event(InputEvent::KeyPressed(Key::KEY_LEFTSHIFT));
+
+        let action = state.handle_event(InputEvent::KeyPressed(Key::KEY_T));
+        assert_eq!(action.combo.unwrap().text, "Ctrl+Shift+T");
+        let item = &state.items()[0];
+        assert_eq!(item.text, "Reopen closed tab");
+        assert_eq!(item.caption.as_deref(), Some("Ctrl+Shift+T"));
+    }
+
+    #[test]
+    fn oldest_item_sets_the_next_expiry() {
+        let mut state = test_state();
+        assert_eq!(state.next_expiry(), None);
+
+        state.handle_event(InputEvent::KeyPressed(Key::KEY_A));
+        state.handle_event(InputEvent::KeyPressed(Key::KEY_B));
+        let first = &state.items()[0];
+        assert_eq!(state.next_expiry(), Some(first.at + Duration::from_millis(900)));
+        assert_ne!(first.id, state.items()[1].id);
+    }
+
+    #[test]
+    fn status_items_outlive_the_global_ttl() {
+        let mut state = test_state();
+        state.handle_event(InputEvent::KeyPressed(Key::KEY_A));
+        state.set_paused(true);
+        assert_eq!(state.status()[0].text, "Paused");
+        assert_eq!(state.next_expiry(), Some(state.items()[0].at + Duration::from_millis(900)));
+
+        // Age both past the global TTL but not the status TTL.
+        let earlier = Instant::now() - Duration::from_millis(1000);
+        state.items.get_mut(0).unwrap().at = earlier;
+        state.status.get_mut(0).unwrap().at = earlier;
+        assert!(state.prune_expired());
+        assert!(state.items().iter().next().is_none());
+        assert_eq!(state.status()[0].text, "Paused");
+        assert_eq!(state.next_expiry(), Some(earlier + STATUS_TTL));
+    }
+
+    #[test]
+    fn key_bursts_do_not_evict_status_messages() {
+        let mut state = test_state();
+        state.set_paused(true);
+        state.set_paused(false);
+        for key in [Key::KEY_A, Key::KEY_B, Key::KEY_C, Key::KEY_D, Key::KEY_E, Key::KEY_F] {
+            state.handle_event(InputEvent::KeyPressed(key));
+        }
+
+        let texts: Vec<&str> = state.items().iter().map(|i| i.text.as_str()).collect();
+        assert_eq!(texts, vec!["B", "C", "D", "E", "F"]);
+        let status: Vec<&str> = state.status().iter().map(|i| i.text.as_str()).collect();
+        assert_eq!(status, vec!["Paused", "Resumed"]);
+    }
+
+    #[test]
+    fn layout_switches_show_a_status() {
+        let mut state = test_state();
+        assert!(!state.set_layout("German", false));
+        assert!(!state.set_layout("German", true));
+        assert!(state.set_layout("English (US)", true));
+        assert_eq!(state.status()[0].text, "Layout: US");
+    }
+
+    #[test]
+    fn repeated_notices_refresh_one_status() {
+        let mut state = test_state();
+        let ttl = Duration::from_secs(10);
+        assert!(state.show_status("Waiting", ttl));
+        let id = state.status()[0].id;
+        assert!(!state.show_status("Waiting", ttl));
+        assert_eq!(state.status().iter().map(|item| item.id).collect::<Vec<_>>(), vec![id]);
+
+        assert!(state.dismiss_status("Waiting"));
+        assert!(!state.dismiss_status("Waiting"));
+    }
+
+    #[test]
+    fn shift_alone_is_not_a_shortcut() {
+        let mut mods = HashSet::new();
+        mods.insert(Key::KEY_LEFTSHIFT);
+        assert!(!is_shortcut(&mods));
+
+        mods.insert(Key::KEY_RIGHTALT);
+        assert!(is_shortcut(&mods));
+    }
 }
@@ -0,0 +1,320 @@
+use crate::combo::combo_parts;
+use crate::i18n;
+use crate::settings::{Notation, Settings};
+use anyhow::{anyhow, bail, Result};
+
+/// `combo_format`: how a combo is spelled on screen, such as
+/// `{mods:-}{key|lower}` for "Ctrl-Shift-t" or `{mods} {key}[ ×{count}]`
+/// for "Ctrl+Shift T ×3".
+///
+/// - `{mods}` joins the held modifiers with `combo_separator`; `{mods:SEP}`
+///   puts `SEP` after each one instead, so `{mods:+}{key}` is the default
+///   spelling
+/// - `{key}` is the key or button
+/// - `{count}` is how many times a repeated combo was coalesced, empty for
+///   one press
+/// - `|upper` or `|lower` after a name changes its case
+/// - text in `[...]` is left out when a placeholder in it is empty
+/// - `{{`, `}}`, `[[` and `]]` are literal braces and brackets
+#[derive(Debug, Clone, PartialEq)]
+pub struct ComboFormat {
+    parts: Vec<Part>,
+    /// Between modifiers for a bare `{mods}`
+    separator: String,
+    /// Emacs notation: `C-`, `M-`, `S-` and `s-`, and lowercase letters
+    emacs: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Part {
+    Text(String),
+    Field(Field),
+    Optional(Vec<Part>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Field {
+    name: FieldName,
+    /// After each modifier, for `{mods:SEP}`
+    separator: Option<String>,
+    case: Case,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum FieldName {
+    Mods,
+    Key,
+    Count,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Case {
+    Keep,
+    Upper,
+    Lower,
+}
+
+impl ComboFormat {
+    /// `combo_format` with `combo_separator`, `separator_spaces` and
+    /// `notation`, or `None` while all keep the built-in "Ctrl+Shift+T".
+    pub fn from_settings(settings: &Settings) -> Result<Option<Self>> {
+        let emacs = settings.notation == Notation::Emacs;
+        let separator = match emacs {
+            true => "-".to_string(),
+            false => separator(&settings.combo_separator, settings.separator_spaces),
+        };
+        let template = settings.combo_format.trim();
+        if template.is_empty() && separator == "+" {
+            return Ok(None);
+        }
+        let mut format = if template.is_empty() {
+            Self {
+                parts: vec![
+                    Part::Field(Field::new(FieldName::Mods, Some(separator.clone()))),
+                    Part::Field(Field::new(FieldName::Key, None)),
+                ],
+                separator: String::new(),
+                emacs: false,
+            }
+        } else {
+            Self::parse(&settings.combo_format)
+                .map_err(|e| anyhow!("Invalid combo_format {:?}: {}", settings.combo_format, e))?
+        };
+        format.separator = separator;
+        format.emacs = emacs;
+        Ok(Some(format))
+    }
+
+    pub fn parse(text: &str) -> Result<Self> {
+        let mut chars = text.chars().peekable();
+        let mut stack: Vec<Vec<Part>> = vec![Vec::new()];
+        let mut literal = String::new();
+        while let Some(c) = chars.next() {
+            match c {
+                '{' | '}' | '[' | ']' if chars.peek() == Some(&c) => {
+                    chars.next();
+                    literal.push(c);
+                }
+                '{' => {
+                    let mut inner = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('}') => break,
+                            Some(c) => inner.push(c),
+                            None => bail!("unclosed {{"),
+                        }
+                    }
+                    let parts = stack.last_mut().expect("stack is never empty");
+                    flush(&mut literal, parts);
+                    parts.push(Part::Field(parse_field(&inner)?));
+                }
+                '[' => {
+                    flush(&mut literal, stack.last_mut().expect("stack is never empty"));
+                    stack.push(Vec::new());
+                }
+                ']' => {
+                    if stack.len() < 2 {
+                        bail!("] without [");
+                    }
+                    let mut group = stack.pop().expect("checked above");
+                    flush(&mut literal, &mut group);
+                    let parts = stack.last_mut().expect("stack is never empty");
+                    parts.push(Part::Optional(group));
+                }
+                '}' => bail!("}} without {{"),
+                c => literal.push(c),
+            }
+        }
+        if stack.len() > 1 {
+            bail!("unclosed [");
+        }
+        let mut parts = stack.pop().expect("stack is never empty");
+        flush(&mut literal, &mut parts);
+        Ok(Self {
+            parts,
+            separator: "+".to_string(),
+            emacs: false,
+        })
+    }
+
+    /// `mods` and `key` are already in the overlay's language.
+    pub fn render(&self, mods: &[&str], key: &str, count: u32) -> String {
+        let mut out = String::new();
+        render_parts(&self.parts, &self.separator, (mods, key, count), &mut out);
+        out.trim().to_string()
+    }
+}
+
+impl Field {
+    fn new(name: FieldName, separator: Option<String>) -> Self {
+        Self {
+            name,
+            separator,
+            case: Case::Keep,
+        }
+    }
+}
+
+/// "+" as is, or " + " with spaces; a blank separator is one space.
+fn separator(separator: &str, spaces: bool) -> String {
+    match separator.trim() {
+        "" => " ".to_string(),
+        trimmed if spaces => format!(" {} ", trimmed),
+        _ => separator.to_string(),
+    }
+}
+
+/// A combo label as the overlay shows it: key names in the current
+/// language, spelled by `format` when one is set.
+pub fn spell(text: &str, format: Option<&ComboFormat>, count: u32) -> String {
+    let Some(format) = format else {
+        return i18n::localize_combo(text);
+    };
+    let parts = combo_parts(text);
+    let (key, mods) = parts.split_last().unwrap_or((&"", &[]));
+    if format.emacs {
+        let mods: Vec<&str> = mods.iter().map(|name| emacs_modifier(name)).collect();
+        let key = match key.chars().count() {
+            1 => key.to_lowercase(),
+            _ => i18n::localize_key(key).to_string(),
+        };
+        return format.render(&mods, &key, count);
+    }
+    let mods: Vec<&str> = mods.iter().map(|name| i18n::localize_key(name)).collect();
+    format.render(&mods, i18n::localize_key(key), count)
+}
+
+fn emacs_modifier(name: &str) -> &str {
+    match name {
+        "Ctrl" => "C",
+        "Alt" => "M",
+        "Shift" => "S",
+        "Super" => "s",
+        other => other,
+    }
+}
+
+fn flush(literal: &mut String, parts: &mut Vec<Part>) {
+    if !literal.is_empty() {
+        parts.push(Part::Text(std::mem::take(literal)));
+    }
+}
+
+fn parse_field(text: &str) -> Result<Field> {
+    let (text, case) = match text.split_once('|') {
+        Some((text, "upper")) => (text, Case::Upper),
+        Some((text, "lower")) => (text, Case::Lower),
+        Some((_, filter)) => bail!("unknown filter {:?}, expected upper or lower", filter),
+        None => (text, Case::Keep),
+    };
+    let (name, separator) = match text.split_once(':') {
+        Some((name, separator)) => (name, Some(separator.to_string())),
+        None => (text, None),
+    };
+    let name = match name.trim() {
+        "mods" => FieldName::Mods,
+        "key" => FieldName::Key,
+        "count" => FieldName::Count,
+        other => bail!("unknown placeholder {{{}}}, expected mods, key or count", other),
+    };
+    if separator.is_some() && name != FieldName::Mods {
+        bail!("only {{mods}} takes a separator");
+    }
+    Ok(Field {
+        case,
+        ..Field::new(name, separator)
+    })
+}
+
+/// Writes `parts` to `out`; false when a placeholder came out empty.
+fn render_parts(
+    parts: &[Part],
+    joiner: &str,
+    (mods, key, count): (&[&str], &str, u32),
+    out: &mut String,
+) -> bool {
+    let mut complete = true;
+    for part in parts {
+        match part {
+            Part::Text(text) => out.push_str(text),
+            Part::Field(field) => {
+                let value = match field.name {
+                    FieldName::Mods => match &field.separator {
+                        Some(separator) => {
+                            mods.iter().map(|m| format!("{}{}", m, separator)).collect()
+                        }
+                        None => mods.join(joiner),
+                    },
+                    FieldName::Key => key.to_string(),
+                    FieldName::Count if count > 1 => count.to_string(),
+                    FieldName::Count => String::new(),
+                };
+                complete &= !value.is_empty();
+                match field.case {
+                    Case::Keep => out.push_str(&value),
+                    Case::Upper => out.push_str(&value.to_uppercase()),
+                    Case::Lower => out.push_str(&value.to_lowercase()),
+                }
+            }
+            Part::Optional(group) => {
+                let mut text = String::new();
+                if render_parts(group, joiner, (mods, key, count), &mut text) {
+                    out.push_str(&text);
+                }
+            }
+        }
+    }
+    complete
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn render(format: &str, text: &str, count: u32) -> String {
+        spell(text, Some(&ComboFormat::parse(format).unwrap()), count)
+    }
+
+    #[test]
+    fn formats_spell_mods_key_and_count() {
+        assert_eq!(render("{mods:+}{key}", "Ctrl+Shift+T", 1), "Ctrl+Shift+T");
+        assert_eq!(render("{mods:-}{key|lower}", "Ctrl+Shift+T", 1), "Ctrl-Shift-t");
+        assert_eq!(render("{mods} {key}[ ({count})]", "Ctrl+S", 3), "Ctrl S (3)");
+        assert_eq!(render("{mods} {key}[ ({count})]", "A", 1), "A");
+        assert_eq!(render("{mods:+}{key}", "Ctrl++", 1), "Ctrl++");
+        assert_eq!(render("{{{key}}}", "Esc", 1), "{Esc}");
+
+        let settings = |format: &str, separator: &str, spaces: bool| Settings {
+            combo_format: format.to_string(),
+            combo_separator: separator.to_string(),
+            separator_spaces: spaces,
+            ..Settings::default()
+        };
+        let spell_with = |settings: Settings| {
+            let format = ComboFormat::from_settings(&settings).unwrap();
+            spell("Ctrl+Shift+T", format.as_ref(), 1)
+        };
+        assert_eq!(spell_with(settings("", "+", true)), "Ctrl + Shift + T");
+        assert_eq!(spell_with(settings("", "-", false)), "Ctrl-Shift-T");
+        assert_eq!(spell_with(settings("", " ", false)), "Ctrl Shift T");
+        assert_eq!(spell_with(settings("{mods} {key}", "-", true)), "Ctrl - Shift T");
+        assert_eq!(ComboFormat::from_settings(&Settings::default()).unwrap(), None);
+
+        let emacs = |text: &str| {
+            let settings = Settings {
+                notation: Notation::Emacs,
+                ..Settings::default()
+            };
+            spell(text, ComboFormat::from_settings(&settings).unwrap().as_ref(), 1)
+        };
+        assert_eq!(emacs("Ctrl+X"), "C-x");
+        assert_eq!(emacs("Alt+W"), "M-w");
+        assert_eq!(emacs("Shift+Tab"), "S-Tab");
+        assert_eq!(emacs("Super+Enter"), "s-Enter");
+
+        assert!(ComboFormat::parse("{keys}").is_err());
+        assert!(ComboFormat::parse("{key").is_err());
+        assert!(ComboFormat::parse("[{count}").is_err());
+        assert!(ComboFormat::parse("{key:+}").is_err());
+    }
+}
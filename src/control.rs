@@ -0,0 +1,74 @@
+use crate::settings::Position;
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+
+/// Remote commands accepted from IPC front-ends (D-Bus, control socket).
+#[derive(Debug, Clone)]
+pub enum ControlCommand {
+    Pause,
+    Resume,
+    Toggle,
+    Clear,
+    Show,
+    Hide,
+    SetPosition(Position),
+    ReloadConfig,
+    /// Show arbitrary text as a bubble (e.g. a Stream Deck macro naming itself)
+    Announce(String),
+    /// Pin a profile by name, or go back to focus-based switching with `None`
+    SetProfile(Option<String>),
+    /// Record the overlay's region for some seconds
+    Capture { seconds: u32, format: CaptureFormat },
+    /// Save the overlay as it is now to a PNG
+    Screenshot,
+}
+
+/// File format of an overlay recording.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "snake_case")]
+pub enum CaptureFormat {
+    #[default]
+    Gif,
+    Webm,
+}
+
+impl CaptureFormat {
+    pub fn extension(self) -> &'static str {
+        match self {
+            CaptureFormat::Gif => "gif",
+            CaptureFormat::Webm => "webm",
+        }
+    }
+}
+
+/// State published to IPC clients.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ControlStatus {
+    pub paused: bool,
+    pub visible: bool,
+    pub position: Position,
+    /// Active profile, if any
+    pub profile: Option<String>,
+}
+
+impl Default for ControlStatus {
+    fn default() -> Self {
+        Self {
+            paused: false,
+            visible: true,
+            position: Position::BottomRight,
+            profile: None,
+        }
+    }
+}
+
+pub fn parse_position(value: &str) -> Option<Position> {
+    Position::from_str(value.trim(), true).ok()
+}
+
+pub fn position_name(position: Position) -> String {
+    position
+        .to_possible_value()
+        .map(|v| v.get_name().to_string())
+        .unwrap_or_default()
+}
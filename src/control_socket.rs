@@ -0,0 +1,253 @@
+use crate::control::{CaptureFormat, ControlCommand, ControlStatus};
+use crate::settings::Position;
+use anyhow::{bail, Context, Result};
+use async_channel::Sender;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::{BufRead, BufReader, Write};
+use std::os::unix::fs::PermissionsExt;
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use tracing::{debug, info, warn};
+
+/// One request per line, e.g. `{"cmd":"set_position","position":"top-left"}`.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "cmd", rename_all = "snake_case")]
+pub enum Request {
+    Pause,
+    Resume,
+    Toggle,
+    Clear,
+    Show,
+    Hide,
+    SetPosition { position: Position },
+    ReloadConfig,
+    Announce { text: String },
+    /// `null` or a missing name returns to automatic switching
+    SetProfile {
+        #[serde(default)]
+        name: Option<String>,
+    },
+    Capture {
+        #[serde(default = "default_capture_seconds")]
+        seconds: u32,
+        #[serde(default)]
+        format: CaptureFormat,
+    },
+    Screenshot,
+    Status,
+}
+
+fn default_capture_seconds() -> u32 {
+    5
+}
+
+/// One response line per request.
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct Response {
+    pub ok: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status: Option<ControlStatus>,
+}
+
+impl Response {
+    fn ok() -> Self {
+        Self {
+            ok: true,
+            ..Default::default()
+        }
+    }
+
+    fn error(message: impl Into<String>) -> Self {
+        Self {
+            ok: false,
+            error: Some(message.into()),
+            status: None,
+        }
+    }
+}
+
+impl Request {
+    fn into_command(self) -> Option<ControlCommand> {
+        Some(match self {
+            Request::Pause => ControlCommand::Pause,
+            Request::Resume => ControlCommand::Resume,
+            Request::Toggle => ControlCommand::Toggle,
+            Request::Clear => ControlCommand::Clear,
+            Request::Show => ControlCommand::Show,
+            Request::Hide => ControlCommand::Hide,
+            Request::SetPosition { position } => ControlCommand::SetPosition(position),
+            Request::ReloadConfig => ControlCommand::ReloadConfig,
+            Request::Announce { text } => ControlCommand::Announce(text),
+            Request::SetProfile { name } => ControlCommand::SetProfile(name),
+            Request::Capture { seconds, format } => ControlCommand::Capture { seconds, format },
+            Request::Screenshot => ControlCommand::Screenshot,
+            Request::Status => return None,
+        })
+    }
+}
+
+/// `$XDG_RUNTIME_DIR/keyway.sock`. There is no fallback: anywhere shared,
+/// such as the temp dir, would let other users drive the overlay.
+pub fn socket_path() -> Result<PathBuf> {
+    let dir = dirs::runtime_dir().context("XDG_RUNTIME_DIR is not set")?;
+    Ok(dir.join("keyway.sock"))
+}
+
+/// Keeps the status answered to `status` requests current. The socket file
+/// is removed when the last handle is dropped.
+#[derive(Clone)]
+pub struct SocketHandle {
+    status: Arc<Mutex<ControlStatus>>,
+    _file: Arc<SocketFile>,
+}
+
+struct SocketFile(PathBuf);
+
+impl Drop for SocketFile {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.0);
+    }
+}
+
+impl SocketHandle {
+    pub fn set_status(&self, status: &ControlStatus) {
+        if let Ok(mut current) = self.status.lock() {
+            *current = status.clone();
+        }
+    }
+}
+
+/// Listens on the control socket and forwards commands to the GTK thread.
+pub fn start_control_socket(command_tx: Sender<ControlCommand>) -> Result<SocketHandle> {
+    let path = socket_path()?;
+    if path.exists() {
+        if UnixStream::connect(&path).is_ok() {
+            bail!("Control socket {:?} is already in use", path);
+        }
+        // Left behind by an instance that did not shut down cleanly.
+        fs::remove_file(&path)
+            .with_context(|| format!("Failed to remove stale socket {:?}", path))?;
+    }
+
+    let listener =
+        UnixListener::bind(&path).with_context(|| format!("Failed to bind {:?}", path))?;
+    let file = Arc::new(SocketFile(path.clone()));
+    fs::set_permissions(&path, fs::Permissions::from_mode(0o600))
+        .with_context(|| format!("Failed to restrict {:?}", path))?;
+    info!("Control socket listening on {:?}", path);
+
+    let status = Arc::new(Mutex::new(ControlStatus::default()));
+    let handle = SocketHandle {
+        status: Arc::clone(&status),
+        _file: file,
+    };
+
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            match stream {
+                Ok(stream) => {
+                    let command_tx = command_tx.clone();
+                    let status = Arc::clone(&status);
+                    thread::spawn(move || {
+                        if let Err(e) = serve_client(stream, &command_tx, &status) {
+                            debug!("Control client disconnected: {:#}", e);
+                        }
+                    });
+                }
+                Err(e) => warn!("Control socket accept failed: {}", e),
+            }
+        }
+    });
+
+    Ok(handle)
+}
+
+fn serve_client(
+    stream: UnixStream,
+    command_tx: &Sender<ControlCommand>,
+    status: &Mutex<ControlStatus>,
+) -> Result<()> {
+    let mut writer = stream.try_clone().context("Failed to clone control stream")?;
+    for line in BufReader::new(stream).lines() {
+        let line = line.context("Failed to read request")?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let response = handle_line(&line, command_tx, status);
+        let mut out = serde_json::to_string(&response)?;
+        out.push('\n');
+        writer
+            .write_all(out.as_bytes())
+            .context("Failed to write response")?;
+    }
+    Ok(())
+}
+
+fn handle_line(
+    line: &str,
+    command_tx: &Sender<ControlCommand>,
+    status: &Mutex<ControlStatus>,
+) -> Response {
+    let request: Request = match serde_json::from_str(line) {
+        Ok(request) => request,
+        Err(e) => return Response::error(format!("Invalid request: {}", e)),
+    };
+
+    match request.into_command() {
+        Some(command) => match command_tx.send_blocking(command) {
+            Ok(()) => Response::ok(),
+            Err(_) => Response::error("Overlay is shutting down"),
+        },
+        None => match status.lock() {
+            Ok(status) => Response {
+                status: Some(status.clone()),
+                ..Response::ok()
+            },
+            Err(_) => Response::error("Status unavailable"),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_requests() {
+        let request: Request = serde_json::from_str(r#"{"cmd":"toggle"}"#).unwrap();
+        assert_eq!(request, Request::Toggle);
+
+        let request: Request =
+            serde_json::from_str(r#"{"cmd":"set_position","position":"top-center"}"#).unwrap();
+        assert_eq!(
+            request,
+            Request::SetPosition {
+                position: Position::TopCenter
+            }
+        );
+
+        assert!(serde_json::from_str::<Request>(r#"{"cmd":"explode"}"#).is_err());
+    }
+
+    #[test]
+    fn status_request_returns_current_status() {
+        let (tx, _rx) = async_channel::bounded(1);
+        let status = Mutex::new(ControlStatus {
+            paused: true,
+            ..Default::default()
+        });
+
+        let response = handle_line(r#"{"cmd":"status"}"#, &tx, &status);
+        let json = serde_json::to_string(&response).unwrap();
+        assert!(json.contains(r#""paused":true"#));
+        assert!(json.contains(r#""position":"bottom-right""#));
+
+        let response = handle_line("not json", &tx, &status);
+        assert!(!response.ok);
+    }
+}
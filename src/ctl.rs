@@ -0,0 +1,148 @@
+use crate::control::CaptureFormat;
+use crate::control_socket::{self, Request, Response};
+use crate::settings::Position;
+use anyhow::{bail, Context, Result};
+use clap::{Parser, Subcommand};
+use std::io::{BufRead, BufReader, Write};
+use std::os::unix::net::UnixStream;
+use std::time::Duration;
+
+const REPLY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Talks to a running overlay over the control socket.
+#[derive(Parser, Debug, Clone)]
+#[command(name = "keyway-ctl")]
+#[command(about = "Control a running keyway-visualizer")]
+pub struct CtlArgs {
+    #[command(subcommand)]
+    pub action: CtlAction,
+}
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum CtlAction {
+    /// Stop showing keystrokes
+    Pause,
+    /// Resume showing keystrokes
+    Resume,
+    /// Toggle pause
+    Toggle,
+    /// Clear the visible keystrokes
+    Clear,
+    /// Show the overlay
+    Show,
+    /// Hide the overlay (capture keeps running)
+    Hide,
+    /// Re-read the config file
+    Reload,
+    /// Show a message in the overlay
+    Announce {
+        text: String,
+    },
+    /// Record the overlay to a GIF or WebM in the capture directory
+    Capture {
+        /// Length of the recording
+        #[arg(long, default_value_t = 5, value_parser = clap::value_parser!(u32).range(1..=60))]
+        seconds: u32,
+        #[arg(long, value_enum, default_value_t = CaptureFormat::Gif)]
+        format: CaptureFormat,
+    },
+    /// Save the overlay as it is now to a PNG in the capture directory
+    Screenshot,
+    /// Change a setting on the running overlay
+    #[command(subcommand)]
+    Set(SetAction),
+    /// Print the overlay state
+    Status {
+        /// Print the state as JSON
+        #[arg(long)]
+        json: bool,
+    },
+}
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum SetAction {
+    /// Move the overlay (e.g. top-center)
+    Position {
+        #[arg(value_enum)]
+        position: Position,
+    },
+    /// Pin a profile by name, or `auto` to follow the focused app again
+    Profile { name: String },
+}
+
+/// True when started through a `keyway-ctl` symlink.
+pub fn invoked_as_ctl() -> bool {
+    std::env::args_os()
+        .next()
+        .map(std::path::PathBuf::from)
+        .and_then(|p| p.file_name().map(|n| n == "keyway-ctl"))
+        .unwrap_or(false)
+}
+
+pub fn run(args: &CtlArgs) -> Result<()> {
+    let (request, json) = match &args.action {
+        CtlAction::Pause => (Request::Pause, false),
+        CtlAction::Resume => (Request::Resume, false),
+        CtlAction::Toggle => (Request::Toggle, false),
+        CtlAction::Clear => (Request::Clear, false),
+        CtlAction::Show => (Request::Show, false),
+        CtlAction::Hide => (Request::Hide, false),
+        CtlAction::Reload => (Request::ReloadConfig, false),
+        CtlAction::Set(SetAction::Position { position }) => (
+            Request::SetPosition {
+                position: *position,
+            },
+            false,
+        ),
+        CtlAction::Announce { text } => (Request::Announce { text: text.clone() }, false),
+        CtlAction::Capture { seconds, format } => (
+            Request::Capture {
+                seconds: *seconds,
+                format: *format,
+            },
+            false,
+        ),
+        CtlAction::Screenshot => (Request::Screenshot, false),
+        CtlAction::Set(SetAction::Profile { name }) => {
+            let name = (name != "auto").then(|| name.clone());
+            (Request::SetProfile { name }, false)
+        }
+        CtlAction::Status { json } => (Request::Status, *json),
+    };
+
+    let response = send(&request)?;
+    if !response.ok {
+        bail!(response.error.unwrap_or_else(|| "Request failed".to_string()));
+    }
+
+    if let Some(status) = response.status {
+        if json {
+            println!("{}", serde_json::to_string(&status)?);
+        } else {
+            println!("paused: {}", status.paused);
+            println!("visible: {}", status.visible);
+            println!("position: {}", crate::control::position_name(status.position));
+            println!("profile: {}", status.profile.as_deref().unwrap_or("default"));
+        }
+    }
+    Ok(())
+}
+
+fn send(request: &Request) -> Result<Response> {
+    let path = control_socket::socket_path()?;
+    let mut stream = UnixStream::connect(&path)
+        .with_context(|| format!("keyway-visualizer is not running (no socket at {:?})", path))?;
+    stream.set_read_timeout(Some(REPLY_TIMEOUT))?;
+
+    let mut line = serde_json::to_string(request)?;
+    line.push('\n');
+    stream
+        .write_all(line.as_bytes())
+        .context("Failed to send request")?;
+
+    let mut reply = String::new();
+    BufReader::new(stream)
+        .read_line(&mut reply)
+        .context("No reply from keyway-visualizer")?;
+    serde_json::from_str(&reply).context("Invalid reply from keyway-visualizer")
+}
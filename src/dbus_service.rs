@@ -0,0 +1,196 @@
+use crate::control::{self, ControlCommand, ControlStatus};
+use anyhow::{Context, Result};
+use async_channel::{Receiver, Sender};
+use dbus::blocking::LocalConnection;
+use dbus::channel::Sender as _;
+use dbus::strings::Interface;
+use dbus::Path;
+use dbus_tree::{Access, Factory, MTFn, Method, MethodErr};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+use tracing::{error, info, warn};
+
+pub const BUS_NAME: &str = "dev.keyway.Visualizer";
+const OBJECT_PATH: &str = "/dev/keyway/Visualizer";
+const INTERFACE: &str = "dev.keyway.Visualizer";
+
+/// Updates the properties exposed on the bus and emits change signals.
+#[derive(Clone)]
+pub struct DbusHandle {
+    status: Arc<Mutex<ControlStatus>>,
+    paused_tx: Sender<bool>,
+}
+
+impl DbusHandle {
+    pub fn set_status(&self, status: &ControlStatus) {
+        let Ok(mut current) = self.status.lock() else {
+            return;
+        };
+        if current.paused != status.paused {
+            let _ = self.paused_tx.try_send(status.paused);
+        }
+        *current = status.clone();
+    }
+}
+
+/// Exports `dev.keyway.Visualizer` on the session bus. Method calls are
+/// forwarded to the GTK thread as `ControlCommand`s.
+pub fn start_dbus_service(command_tx: Sender<ControlCommand>) -> DbusHandle {
+    let (paused_tx, paused_rx) = async_channel::bounded(8);
+    let status = Arc::new(Mutex::new(ControlStatus::default()));
+
+    let handle = DbusHandle {
+        status: Arc::clone(&status),
+        paused_tx,
+    };
+
+    thread::spawn(move || {
+        if let Err(e) = serve(command_tx, paused_rx, status) {
+            warn!("D-Bus control interface unavailable: {:#}", e);
+        }
+    });
+
+    handle
+}
+
+fn serve(
+    command_tx: Sender<ControlCommand>,
+    paused_rx: Receiver<bool>,
+    status: Arc<Mutex<ControlStatus>>,
+) -> Result<()> {
+    let conn = LocalConnection::new_session().context("Failed to connect to the session bus")?;
+    conn.request_name(BUS_NAME, false, true, true)
+        .with_context(|| format!("Failed to acquire {}", BUS_NAME))?;
+
+    let f = Factory::new_fn::<()>();
+    let paused_changed = Arc::new(f.signal("PausedChanged", ()).sarg::<bool, _>("paused"));
+
+    let position_tx = command_tx.clone();
+    let set_position = f
+        .method("SetPosition", (), move |m| {
+            let value: &str = m.msg.read1()?;
+            let position = control::parse_position(value)
+                .ok_or_else(|| MethodErr::invalid_arg(&value))?;
+            send_command(&position_tx, ControlCommand::SetPosition(position))?;
+            Ok(vec![m.msg.method_return()])
+        })
+        .inarg::<&str, _>("position");
+
+    let announce_tx = command_tx.clone();
+    let announce = f
+        .method("Announce", (), move |m| {
+            let text: &str = m.msg.read1()?;
+            send_command(&announce_tx, ControlCommand::Announce(text.to_string()))?;
+            Ok(vec![m.msg.method_return()])
+        })
+        .inarg::<&str, _>("text");
+
+    let profile_tx = command_tx.clone();
+    let set_profile = f
+        .method("SetProfile", (), move |m| {
+            let name: &str = m.msg.read1()?;
+            let name = (!name.is_empty()).then(|| name.to_string());
+            send_command(&profile_tx, ControlCommand::SetProfile(name))?;
+            Ok(vec![m.msg.method_return()])
+        })
+        .inarg::<&str, _>("name");
+
+    let paused_status = Arc::clone(&status);
+    let visible_status = Arc::clone(&status);
+    let position_status = Arc::clone(&status);
+    let profile_status = Arc::clone(&status);
+
+    let interface = f
+        .interface(INTERFACE, ())
+        .add_m(command_method(&f, "Pause", ControlCommand::Pause, &command_tx))
+        .add_m(command_method(&f, "Resume", ControlCommand::Resume, &command_tx))
+        .add_m(command_method(&f, "Toggle", ControlCommand::Toggle, &command_tx))
+        .add_m(command_method(&f, "Clear", ControlCommand::Clear, &command_tx))
+        .add_m(command_method(&f, "Show", ControlCommand::Show, &command_tx))
+        .add_m(command_method(&f, "Hide", ControlCommand::Hide, &command_tx))
+        .add_m(command_method(&f, "ReloadConfig", ControlCommand::ReloadConfig, &command_tx))
+        .add_m(set_position)
+        .add_m(announce)
+        .add_m(set_profile)
+        .add_p(
+            f.property::<bool, _>("Paused", ())
+                .access(Access::Read)
+                .on_get(move |iter, _| {
+                    iter.append(read_status(&paused_status)?.paused);
+                    Ok(())
+                }),
+        )
+        .add_p(
+            f.property::<bool, _>("Visible", ())
+                .access(Access::Read)
+                .on_get(move |iter, _| {
+                    iter.append(read_status(&visible_status)?.visible);
+                    Ok(())
+                }),
+        )
+        .add_p(
+            f.property::<&str, _>("Position", ())
+                .access(Access::Read)
+                .on_get(move |iter, _| {
+                    iter.append(control::position_name(read_status(&position_status)?.position));
+                    Ok(())
+                }),
+        )
+        .add_p(
+            f.property::<&str, _>("Profile", ())
+                .access(Access::Read)
+                .on_get(move |iter, _| {
+                    iter.append(read_status(&profile_status)?.profile.unwrap_or_default());
+                    Ok(())
+                }),
+        )
+        .add_s(Arc::clone(&paused_changed));
+
+    let tree = f
+        .tree(())
+        .add(f.object_path(OBJECT_PATH, ()).introspectable().add(interface))
+        .add(f.object_path("/", ()).introspectable());
+    tree.start_receive(&conn);
+
+    info!("D-Bus control interface exported as {}", BUS_NAME);
+
+    let path = Path::from(OBJECT_PATH);
+    let iface = Interface::from(INTERFACE);
+    loop {
+        conn.process(Duration::from_millis(250))
+            .context("Lost connection to the session bus")?;
+
+        while let Ok(paused) = paused_rx.try_recv() {
+            let signal = paused_changed.msg(&path, &iface).append1(paused);
+            if conn.send(signal).is_err() {
+                error!("Failed to emit PausedChanged");
+            }
+        }
+    }
+}
+
+fn command_method(
+    f: &Factory<MTFn<()>, ()>,
+    name: &'static str,
+    command: ControlCommand,
+    tx: &Sender<ControlCommand>,
+) -> Method<MTFn<()>, ()> {
+    let tx = tx.clone();
+    f.method(name, (), move |m| {
+        send_command(&tx, command.clone())?;
+        Ok(vec![m.msg.method_return()])
+    })
+}
+
+fn send_command(tx: &Sender<ControlCommand>, command: ControlCommand) -> Result<(), MethodErr> {
+    tx.try_send(command)
+        .map_err(|e| MethodErr::failed(&format!("Failed to queue command: {}", e)))
+}
+
+fn read_status(status: &Mutex<ControlStatus>) -> Result<ControlStatus, MethodErr> {
+    status
+        .lock()
+        .map(|s| s.clone())
+        .map_err(|_| MethodErr::failed(&"Status unavailable"))
+}
@@ -0,0 +1,126 @@
+use crate::combo::{combo_parts, ComboEvent, ComboKind};
+use anyhow::{bail, Context, Result};
+use async_channel::Receiver;
+use std::fs;
+use std::path::Path;
+use std::thread;
+use std::time::Duration;
+use tracing::info;
+
+/// Pause after each combo unless the script says otherwise.
+const DEFAULT_GAP: Duration = Duration::from_millis(600);
+
+/// One line of a demo script.
+#[derive(Debug, Clone, PartialEq)]
+pub enum DemoStep {
+    /// A combo such as `Ctrl+Shift+P`, shown as if it was typed
+    Combo(ComboEvent),
+    /// `wait 1500`: extra milliseconds before the next step
+    Wait(Duration),
+    /// `announce Build & run`: a message bubble
+    Announce(String),
+    /// `repeat`: start over from the top
+    Repeat,
+}
+
+/// What the overlay is asked to show.
+#[derive(Debug, Clone)]
+pub enum DemoEvent {
+    Combo(ComboEvent),
+    Announce(String),
+}
+
+/// Reads a demo script: one step per line, blank lines and `#` comments
+/// ignored.
+pub fn load(path: &Path) -> Result<Vec<DemoStep>> {
+    let text = fs::read_to_string(path).with_context(|| format!("Failed to read {:?}", path))?;
+    parse(&text).with_context(|| format!("Invalid demo script {:?}", path))
+}
+
+fn parse(text: &str) -> Result<Vec<DemoStep>> {
+    let mut steps = Vec::new();
+    for (number, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let (word, rest) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+        let step = match word {
+            "wait" => {
+                let ms: u64 = rest
+                    .trim()
+                    .parse()
+                    .with_context(|| format!("line {}: wait takes milliseconds", number + 1))?;
+                DemoStep::Wait(Duration::from_millis(ms))
+            }
+            "announce" if !rest.trim().is_empty() => DemoStep::Announce(rest.trim().to_string()),
+            "announce" => bail!("line {}: announce needs a message", number + 1),
+            "repeat" => DemoStep::Repeat,
+            _ => DemoStep::Combo(ComboEvent {
+                kind: if combo_parts(line).len() > 1 {
+                    ComboKind::Shortcut
+                } else {
+                    ComboKind::Key
+                },
+                text: line.to_string(),
+            }),
+        };
+        steps.push(step);
+    }
+    if steps.is_empty() {
+        bail!("the script has no steps");
+    }
+    Ok(steps)
+}
+
+/// Plays `steps` on a thread, stopping at the end or when the receiver is
+/// dropped.
+pub fn start_demo(steps: Vec<DemoStep>) -> Receiver<DemoEvent> {
+    let (tx, rx) = async_channel::bounded(16);
+
+    thread::spawn(move || {
+        info!("Playing a demo script of {} steps", steps.len());
+        let mut index = 0;
+        while let Some(step) = steps.get(index) {
+            index += 1;
+            let event = match step {
+                DemoStep::Combo(combo) => DemoEvent::Combo(combo.clone()),
+                DemoStep::Announce(text) => DemoEvent::Announce(text.clone()),
+                DemoStep::Wait(delay) => {
+                    thread::sleep(*delay);
+                    continue;
+                }
+                DemoStep::Repeat => {
+                    index = 0;
+                    thread::sleep(DEFAULT_GAP);
+                    continue;
+                }
+            };
+            if tx.send_blocking(event).is_err() {
+                return;
+            }
+            thread::sleep(DEFAULT_GAP);
+        }
+        info!("Demo script finished");
+    });
+
+    rx
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_script_lines() {
+        let steps = parse("# intro\nannounce Copy & paste\nCtrl+C\n\nwait 250\nv\nrepeat\n").unwrap();
+        assert_eq!(steps.len(), 5);
+        assert_eq!(steps[0], DemoStep::Announce("Copy & paste".to_string()));
+        assert!(matches!(&steps[1], DemoStep::Combo(c) if c.kind == ComboKind::Shortcut));
+        assert_eq!(steps[2], DemoStep::Wait(Duration::from_millis(250)));
+        assert!(matches!(&steps[3], DemoStep::Combo(c) if c.kind == ComboKind::Key));
+
+        assert!(parse("wait soon").is_err());
+        assert!(parse("# nothing\n").is_err());
+    }
+}
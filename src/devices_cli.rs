@@ -0,0 +1,93 @@
+use crate::input::device::{capabilities, scan_devices, DeviceKind};
+use crate::input::ListenerConfig;
+use crate::settings::Settings;
+use anyhow::Result;
+use std::path::{Path, PathBuf};
+
+struct Row {
+    path: PathBuf,
+    kind: DeviceKind,
+    name: String,
+    id: String,
+    capabilities: String,
+}
+
+/// `--list-devices`: every keyboard and mouse, and whether the overlay
+/// would listen to it with these settings.
+pub fn run(settings: &Settings) -> Result<()> {
+    let mut rows = Vec::new();
+    let scan = scan_devices(|found| {
+        let id = found.device.input_id();
+        rows.push(Row {
+            id: format!("{:04x}:{:04x}", id.vendor(), id.product()),
+            capabilities: capabilities(&found.device).join(", "),
+            path: found.path,
+            kind: found.kind,
+            name: found.name,
+        });
+    })?;
+    rows.sort_by_key(|row| event_number(&row.path));
+
+    let config = ListenerConfig {
+        include_mouse: settings.show_mouse,
+        ..ListenerConfig::default()
+    };
+    let mut keyboards = 0;
+    println!(
+        "{:<20} {:<9} {:<9} {:<10} {:<36} CAPABILITIES",
+        "PATH", "KIND", "CAPTURED", "ID", "NAME"
+    );
+    for row in &rows {
+        let captured = config.captures(row.kind, keyboards).is_some();
+        if row.kind == DeviceKind::Keyboard {
+            keyboards += 1;
+        }
+        println!(
+            "{:<20} {:<9} {:<9} {:<10} {:<36} {}",
+            row.path.display(),
+            row.kind.name(),
+            if captured { "yes" } else { "no" },
+            row.id,
+            row.name,
+            row.capabilities
+        );
+    }
+    if rows.is_empty() {
+        println!("No keyboards or mice found.");
+    }
+    if !settings.show_mouse && rows.iter().any(|row| row.kind == DeviceKind::Mouse) {
+        println!("Mice are not captured while show_mouse is off.");
+    }
+    if scan.denied > 0 {
+        println!(
+            "{} input devices could not be opened; join the input group to see them.",
+            scan.denied
+        );
+    }
+    Ok(())
+}
+
+/// `/dev/input/event10` sorts after `event9`.
+fn event_number(path: &Path) -> u32 {
+    path.file_name()
+        .and_then(|name| name.to_str())
+        .and_then(|name| name.strip_prefix("event"))
+        .and_then(|number| number.parse().ok())
+        .unwrap_or(u32::MAX)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn event_nodes_sort_by_number() {
+        let mut paths: Vec<PathBuf> = ["event10", "event9", "mice", "event0"]
+            .iter()
+            .map(|name| Path::new("/dev/input").join(name))
+            .collect();
+        paths.sort_by_key(|path| event_number(path));
+        let names: Vec<_> = paths.iter().map(|p| p.file_name().unwrap()).collect();
+        assert_eq!(names, ["event0", "event9", "event10", "mice"]);
+    }
+}
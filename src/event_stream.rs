@@ -0,0 +1,392 @@
+use crate::combo::ComboEvent;
+use crate::xkb::LockState;
+use anyhow::{bail, Context, Result};
+use async_channel::{Receiver, Sender};
+use serde_json::Value;
+use std::io::{ErrorKind, Read, Write};
+use std::net::{IpAddr, TcpListener, TcpStream};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+use tracing::{debug, info, warn};
+use tungstenite::handshake::server::{Callback, ErrorResponse, Request, Response};
+use tungstenite::http::StatusCode;
+use tungstenite::{Message, WebSocket};
+
+const ACCEPT_POLL: Duration = Duration::from_millis(200);
+const HEADER_LIMIT: usize = 4096;
+const WRITE_TIMEOUT: Duration = Duration::from_secs(1);
+
+type Clients = Arc<Mutex<Vec<WebSocket<TcpStream>>>>;
+
+/// Browser-source page served to plain HTTP requests on the stream port.
+const OBS_PAGE: &str = include_str!("obs.html");
+
+/// Broadcasts displayed combos to WebSocket clients (localhost unless
+/// `websocket_bind` says otherwise).
+///
+/// Plain HTTP requests to the same port get a small page that renders the
+/// stream as bubbles, for use as an OBS browser source. Browsers may only
+/// connect from that page: a handshake with any other `Origin` is refused,
+/// so websites open in the user's browser cannot read the keys.
+///
+/// With a `remote_token`, every client must send it, as
+/// `Authorization: Bearer <token>` or a `token` query parameter, and the page
+/// may also be loaded from another machine; without one the server only
+/// listens on a loopback address.
+///
+/// Each message is a JSON object such as
+/// `{"type":"combo","text":"Ctrl+S","kind":"shortcut","timestamp":1700000000000}`
+/// with the timestamp in Unix milliseconds. Lock key changes arrive as
+/// `{"type":"lock","caps":true,"num":false,"scroll":false,"timestamp":..}`.
+/// Dropping the last handle shuts the server down and frees the port.
+#[derive(Clone)]
+pub struct EventStream {
+    tx: Sender<String>,
+    bind: String,
+    port: u16,
+    token: String,
+}
+
+impl EventStream {
+    /// Whether the server is listening on this address, asking for `token`.
+    pub fn is_bound_to(&self, bind: &str, port: u16, token: &str) -> bool {
+        self.bind == bind && self.port == port && self.token == token
+    }
+
+    pub fn publish(&self, combo: &ComboEvent) {
+        self.publish_json(&combo.to_json());
+    }
+
+    pub fn publish_locks(&self, locks: &LockState) {
+        self.publish_json(&locks.to_json());
+    }
+
+    fn publish_json(&self, message: &Value) {
+        // A full queue means clients are not keeping up; drop rather than block the UI.
+        let _ = self.tx.try_send(message.to_string());
+    }
+}
+
+pub fn start_event_stream(bind: &str, port: u16, token: &str) -> Result<EventStream> {
+    let loopback = bind == "localhost" || bind.parse::<IpAddr>().is_ok_and(|ip| ip.is_loopback());
+    if !loopback && token.is_empty() {
+        bail!("Listening on {} needs a remote_token", bind);
+    }
+    let listener =
+        bind_listener(bind, port).with_context(|| format!("Failed to bind {}:{}", bind, port))?;
+    listener
+        .set_nonblocking(true)
+        .context("Failed to configure listener")?;
+    info!("WebSocket event stream on ws://{}:{}", bind, port);
+
+    let (tx, rx) = async_channel::bounded::<String>(256);
+    let clients: Clients = Arc::default();
+
+    let accept_clients = Arc::clone(&clients);
+    let accept_rx = rx.clone();
+    let check = HandshakeCheck {
+        bind: bind.to_string(),
+        port,
+        token: token.to_string(),
+    };
+    thread::spawn(move || accept_loop(listener, check, accept_clients, accept_rx));
+    thread::spawn(move || broadcast_loop(rx, clients));
+
+    Ok(EventStream {
+        tx,
+        bind: bind.to_string(),
+        port,
+        token: token.to_string(),
+    })
+}
+
+/// A server being replaced on the same port releases it within one accept
+/// poll, so retry briefly instead of failing.
+fn bind_listener(bind: &str, port: u16) -> std::io::Result<TcpListener> {
+    let mut attempts = 0;
+    loop {
+        match TcpListener::bind((bind, port)) {
+            Err(e) if e.kind() == ErrorKind::AddrInUse && attempts < 5 => {
+                attempts += 1;
+                thread::sleep(ACCEPT_POLL / 2);
+            }
+            result => return result,
+        }
+    }
+}
+
+fn accept_loop(
+    listener: TcpListener,
+    check: HandshakeCheck,
+    clients: Clients,
+    rx: Receiver<String>,
+) {
+    while !rx.is_closed() {
+        match listener.accept() {
+            Ok((stream, addr)) => {
+                // A slow or idle client must not hold up the others.
+                let check = check.clone();
+                let clients = Arc::clone(&clients);
+                thread::spawn(move || {
+                    if let Some(socket) = handle_connection(stream, check) {
+                        debug!("Event stream client connected: {}", addr);
+                        if let Ok(mut clients) = clients.lock() {
+                            clients.push(socket);
+                        }
+                    }
+                });
+            }
+            Err(e) if e.kind() == ErrorKind::WouldBlock => thread::sleep(ACCEPT_POLL),
+            Err(e) => {
+                warn!("Event stream accept failed: {}", e);
+                thread::sleep(ACCEPT_POLL);
+            }
+        }
+    }
+}
+
+fn handle_connection(stream: TcpStream, check: HandshakeCheck) -> Option<WebSocket<TcpStream>> {
+    stream.set_nonblocking(false).ok()?;
+    stream.set_write_timeout(Some(WRITE_TIMEOUT)).ok()?;
+    stream.set_read_timeout(Some(WRITE_TIMEOUT)).ok()?;
+
+    let (head, len) = peek_request_head(&stream)?;
+    if !is_upgrade_request(&head) {
+        serve_page(stream, &head, len);
+        return None;
+    }
+
+    match tungstenite::accept_hdr(stream, check) {
+        Ok(socket) => Some(socket),
+        Err(e) => {
+            debug!("WebSocket handshake failed: {}", e);
+            None
+        }
+    }
+}
+
+/// Browsers always send `Origin`; the only page allowed to connect is the
+/// browser source served from this port. Other clients send none. With a
+/// token set, every client has to present it, and the page may also come
+/// from the address the client connected to.
+#[derive(Clone)]
+struct HandshakeCheck {
+    bind: String,
+    port: u16,
+    token: String,
+}
+
+impl HandshakeCheck {
+    fn own_origin(&self, request: &Request) -> bool {
+        let Some(origin) = request.headers().get("origin") else {
+            return true;
+        };
+        let origin = origin.to_str().unwrap_or("");
+        let mut own = vec![
+            format!("http://127.0.0.1:{}", self.port),
+            format!("http://localhost:{}", self.port),
+        ];
+        if !self.token.is_empty() {
+            own.push(format!("http://{}:{}", self.bind, self.port));
+            if let Some(host) = request.headers().get("host").and_then(|h| h.to_str().ok()) {
+                own.push(format!("http://{}", host));
+            }
+        }
+        let allowed = own.iter().any(|own| origin.eq_ignore_ascii_case(own));
+        if !allowed {
+            debug!("Refused event stream client from {}", origin);
+        }
+        allowed
+    }
+
+    fn has_token(&self, request: &Request) -> bool {
+        if self.token.is_empty() {
+            return true;
+        }
+        let bearer = request
+            .headers()
+            .get("authorization")
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "));
+        let query = request.uri().query().unwrap_or("");
+        let mut given = query
+            .split('&')
+            .filter_map(|pair| pair.strip_prefix("token="));
+        let allowed = bearer.is_some_and(|token| same_token(token, &self.token))
+            || given.any(|token| same_token(token, &self.token));
+        if !allowed {
+            debug!("Refused event stream client without the remote token");
+        }
+        allowed
+    }
+}
+
+/// Compares every byte, so the time taken does not tell how much of a guess
+/// was right.
+fn same_token(given: &str, token: &str) -> bool {
+    given.len() == token.len()
+        && given
+            .bytes()
+            .zip(token.bytes())
+            .fold(0, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+impl Callback for HandshakeCheck {
+    fn on_request(self, request: &Request, response: Response) -> Result<Response, ErrorResponse> {
+        if self.own_origin(request) && self.has_token(request) {
+            return Ok(response);
+        }
+        let mut refusal = ErrorResponse::new(None);
+        *refusal.status_mut() = StatusCode::FORBIDDEN;
+        Err(refusal)
+    }
+}
+
+/// Peeks at the request headers without consuming them, so the WebSocket
+/// handshake can still read the full request. Returns them with the number
+/// of bytes peeked.
+fn peek_request_head(stream: &TcpStream) -> Option<(String, usize)> {
+    let mut buf = vec![0u8; HEADER_LIMIT];
+    for _ in 0..50 {
+        let n = stream.peek(&mut buf).ok()?;
+        if n == 0 {
+            return None;
+        }
+        let head = String::from_utf8_lossy(&buf[..n]);
+        if head.contains("\r\n\r\n") || n == buf.len() {
+            return Some((head.into_owned(), n));
+        }
+        thread::sleep(Duration::from_millis(10));
+    }
+    None
+}
+
+fn is_upgrade_request(head: &str) -> bool {
+    head.lines().any(|line| {
+        let line = line.to_ascii_lowercase();
+        line.starts_with("upgrade:") && line.contains("websocket")
+    })
+}
+
+fn serve_page(mut stream: TcpStream, head: &str, len: usize) {
+    let mut discard = vec![0u8; len];
+    let _ = stream.read_exact(&mut discard);
+
+    let path = head.split_whitespace().nth(1).unwrap_or("/");
+    let path = path.split('?').next().unwrap_or("/");
+    let response = if path == "/" || path == "/index.html" {
+        format!(
+            "HTTP/1.1 200 OK\r\nContent-Type: text/html; charset=utf-8\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            OBS_PAGE.len(),
+            OBS_PAGE
+        )
+    } else {
+        "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n".to_string()
+    };
+    if let Err(e) = stream.write_all(response.as_bytes()) {
+        debug!("Failed to serve browser-source page: {}", e);
+    }
+}
+
+fn broadcast_loop(rx: Receiver<String>, clients: Clients) {
+    while let Ok(message) = rx.recv_blocking() {
+        let Ok(mut clients) = clients.lock() else {
+            break;
+        };
+        clients.retain_mut(|socket| socket.send(Message::text(message.clone())).is_ok());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detects_websocket_upgrades() {
+        let upgrade = "GET / HTTP/1.1\r\nHost: localhost\r\nUpgrade: websocket\r\nConnection: Upgrade\r\n\r\n";
+        assert!(is_upgrade_request(upgrade));
+
+        let page = "GET /?ttl=1500 HTTP/1.1\r\nHost: localhost\r\n\r\n";
+        assert!(!is_upgrade_request(page));
+    }
+
+    /// Runs one handshake against `listener`, as a server asking for
+    /// `token`: whether the server accepted it, and what the client saw.
+    fn handshake(
+        listener: &TcpListener,
+        token: &str,
+        headers: &[(&'static str, String)],
+        query: &str,
+    ) -> (bool, Result<(), String>) {
+        use tungstenite::client::IntoClientRequest;
+
+        let port = listener.local_addr().unwrap().port();
+        let mut request = format!("ws://127.0.0.1:{}/{}", port, query)
+            .into_client_request()
+            .unwrap();
+        for (name, value) in headers {
+            request.headers_mut().insert(*name, value.parse().unwrap());
+        }
+        let client = thread::spawn(move || {
+            let stream = TcpStream::connect(("127.0.0.1", port)).unwrap();
+            tungstenite::client(request, stream)
+                .map(|_| ())
+                .map_err(|e| e.to_string())
+        });
+        let (stream, _) = listener.accept().unwrap();
+        let check = HandshakeCheck {
+            bind: "0.0.0.0".to_string(),
+            port,
+            token: token.to_string(),
+        };
+        let accepted = handle_connection(stream, check).is_some();
+        (accepted, client.join().unwrap())
+    }
+
+    #[test]
+    fn refuses_foreign_origins() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let evil = [("Origin", "https://evil.example".to_string())];
+        let (accepted, client) = handshake(&listener, "", &evil, "");
+        assert!(!accepted);
+        assert!(client.unwrap_err().contains("403"));
+        let own = [("Origin", format!("http://localhost:{}", port))];
+        assert!(handshake(&listener, "", &own, "").0);
+        assert!(handshake(&listener, "", &[], "").0);
+    }
+
+    #[test]
+    fn remote_clients_need_the_token() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let (accepted, client) = handshake(&listener, "s3cret", &[], "");
+        assert!(!accepted);
+        assert!(client.unwrap_err().contains("403"));
+        let wrong = [("Authorization", "Bearer s3cre7".to_string())];
+        assert!(!handshake(&listener, "s3cret", &wrong, "").0);
+        let bearer = [("Authorization", "Bearer s3cret".to_string())];
+        assert!(handshake(&listener, "s3cret", &bearer, "").0);
+        assert!(handshake(&listener, "s3cret", &[], "?ttl=1&token=s3cret").0);
+        assert!(!handshake(&listener, "s3cret", &[], "?token=s3cretx").0);
+
+        assert!(start_event_stream("0.0.0.0", 0, "").is_err());
+    }
+
+    #[test]
+    fn page_on_the_lan_address_may_connect_with_the_token() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let lan = [
+            ("Host", "192.168.1.5:8765".to_string()),
+            ("Origin", "http://192.168.1.5:8765".to_string()),
+        ];
+        assert!(!handshake(&listener, "", &lan, "").0);
+        assert!(handshake(&listener, "s3cret", &lan, "?token=s3cret").0);
+        let foreign = [
+            ("Host", "192.168.1.5:8765".to_string()),
+            ("Origin", "http://evil.example".to_string()),
+        ];
+        assert!(!handshake(&listener, "s3cret", &foreign, "?token=s3cret").0);
+    }
+}
@@ -0,0 +1,11 @@
+use std::path::Path;
+
+/// True when running inside a Flatpak sandbox, which always has this file.
+pub fn in_flatpak() -> bool {
+    Path::new("/.flatpak-info").exists()
+}
+
+/// The Flatpak app id, for commands the user runs on the host.
+pub fn app_id() -> String {
+    std::env::var("FLATPAK_ID").unwrap_or_else(|_| crate::dbus_service::BUS_NAME.to_string())
+}
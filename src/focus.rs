@@ -0,0 +1,237 @@
+use anyhow::{Context, Result};
+use async_channel::{Receiver, Sender};
+use serde_json::Value;
+use std::env;
+use std::io::{BufRead, BufReader};
+use std::os::unix::net::UnixStream;
+use std::path::PathBuf;
+use std::process::{Child, Command, Stdio};
+use std::thread;
+use tracing::{debug, info, warn};
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FocusInfo {
+    pub class: String,
+    pub title: String,
+    pub fullscreen: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FocusBackend {
+    Hyprland,
+    Sway,
+}
+
+impl FocusBackend {
+    pub fn detect() -> Option<Self> {
+        if env::var_os("HYPRLAND_INSTANCE_SIGNATURE").is_some() {
+            return Some(Self::Hyprland);
+        }
+        if env::var_os("SWAYSOCK").is_some() {
+            return Some(Self::Sway);
+        }
+        None
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Hyprland => "hyprland",
+            Self::Sway => "sway",
+        }
+    }
+}
+
+/// Starts a background thread that pushes a `FocusInfo` whenever the focused
+/// toplevel changes. The current focus is sent first so the receiver never has
+/// to poll.
+pub fn start_focus_tracker() -> Option<(Receiver<FocusInfo>, FocusBackend)> {
+    let backend = FocusBackend::detect()?;
+    let (tx, rx) = async_channel::bounded(32);
+
+    thread::spawn(move || {
+        let result = match backend {
+            FocusBackend::Hyprland => track_hyprland(&tx),
+            FocusBackend::Sway => track_sway(&tx),
+        };
+        if let Err(e) = result {
+            warn!("Focus tracking ({}) stopped: {:#}", backend.name(), e);
+        }
+    });
+
+    info!("Focus tracking via {}", backend.name());
+    Some((rx, backend))
+}
+
+fn track_hyprland(tx: &Sender<FocusInfo>) -> Result<()> {
+    if let Some(info) = hyprland_active_window() {
+        send_focus(tx, info)?;
+    }
+
+    let path = hyprland_event_socket().context("Hyprland event socket not found")?;
+    let stream = UnixStream::connect(&path)
+        .with_context(|| format!("Failed to connect to {:?}", path))?;
+
+    let mut current = FocusInfo::default();
+
+    for line in BufReader::new(stream).lines() {
+        let line = line.context("Failed to read Hyprland event")?;
+        let Some((event, data)) = line.split_once(">>") else {
+            continue;
+        };
+
+        match event {
+            "activewindow" => {
+                // The event doesn't carry the fullscreen state, so ask for the
+                // full window info and fall back to the event payload.
+                current = hyprland_active_window().unwrap_or_else(|| {
+                    // The class cannot contain commas, the title can.
+                    let (class, title) = data.split_once(',').unwrap_or((data, ""));
+                    FocusInfo {
+                        class: class.to_string(),
+                        title: title.to_string(),
+                        fullscreen: false,
+                    }
+                });
+            }
+            "fullscreen" => {
+                current.fullscreen = data == "1";
+            }
+            _ => continue,
+        }
+
+        debug!("Focus changed: {:?}", current);
+        send_focus(tx, current.clone())?;
+    }
+
+    Ok(())
+}
+
+pub fn hyprland_event_socket() -> Option<PathBuf> {
+    let signature = env::var("HYPRLAND_INSTANCE_SIGNATURE").ok()?;
+    let mut candidates = Vec::new();
+    if let Some(runtime) = dirs::runtime_dir() {
+        candidates.push(runtime.join("hypr").join(&signature).join(".socket2.sock"));
+    }
+    candidates.push(PathBuf::from("/tmp/hypr").join(&signature).join(".socket2.sock"));
+    candidates.into_iter().find(|p| p.exists())
+}
+
+fn hyprland_active_window() -> Option<FocusInfo> {
+    let output = Command::new("hyprctl")
+        .args(["-j", "activewindow"])
+        .output()
+        .ok()?;
+
+    if !output.status.success() {
+        return None;
+    }
+
+    let value: Value = serde_json::from_slice(&output.stdout).ok()?;
+    let class = value.get("class")?.as_str()?.to_string();
+    let title = value.get("title").and_then(|v| v.as_str()).unwrap_or("").to_string();
+    // Older Hyprland reports a bool, newer versions a mode bitmask where 2 is
+    // real fullscreen (1 is maximized).
+    let fullscreen = match value.get("fullscreen") {
+        Some(Value::Bool(b)) => *b,
+        Some(Value::Number(n)) => n.as_u64().unwrap_or(0) & 2 != 0,
+        _ => false,
+    };
+
+    Some(FocusInfo {
+        class,
+        title,
+        fullscreen,
+    })
+}
+
+fn track_sway(tx: &Sender<FocusInfo>) -> Result<()> {
+    if let Some(info) = sway_focused_window() {
+        send_focus(tx, info)?;
+    }
+
+    let mut child = KillOnDrop(
+        Command::new("swaymsg")
+            .args(["-t", "subscribe", "-m", r#"["window"]"#])
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .context("Failed to run swaymsg")?,
+    );
+    let stdout = child.0.stdout.take().context("swaymsg has no stdout")?;
+
+    for line in BufReader::new(stdout).lines() {
+        let line = line.context("Failed to read sway event")?;
+        let Ok(event) = serde_json::from_str::<Value>(&line) else {
+            continue;
+        };
+        let change = event.get("change").and_then(|v| v.as_str()).unwrap_or("");
+        if !matches!(change, "focus" | "title" | "fullscreen_mode") {
+            continue;
+        }
+        let Some(container) = event.get("container") else {
+            continue;
+        };
+        if change != "focus" && !container.get("focused").and_then(|v| v.as_bool()).unwrap_or(false) {
+            continue;
+        }
+        if let Some(info) = sway_node_info(container) {
+            send_focus(tx, info)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// A long-running helper such as the `swaymsg` subscription, stopped and
+/// reaped however the thread reading it ends.
+pub struct KillOnDrop(pub Child);
+
+impl Drop for KillOnDrop {
+    fn drop(&mut self) {
+        let _ = self.0.kill();
+        let _ = self.0.wait();
+    }
+}
+
+fn sway_focused_window() -> Option<FocusInfo> {
+    let output = Command::new("swaymsg").args(["-t", "get_tree"]).output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let tree: Value = serde_json::from_slice(&output.stdout).ok()?;
+    find_focused(&tree).and_then(sway_node_info)
+}
+
+fn find_focused(node: &Value) -> Option<&Value> {
+    if node.get("focused").and_then(|v| v.as_bool()).unwrap_or(false) {
+        return Some(node);
+    }
+    ["nodes", "floating_nodes"]
+        .iter()
+        .filter_map(|key| node.get(*key).and_then(|v| v.as_array()))
+        .flatten()
+        .find_map(find_focused)
+}
+
+fn sway_node_info(node: &Value) -> Option<FocusInfo> {
+    let class = node
+        .get("app_id")
+        .and_then(|v| v.as_str())
+        .or_else(|| {
+            node.get("window_properties")
+                .and_then(|p| p.get("class"))
+                .and_then(|v| v.as_str())
+        })?
+        .to_string();
+    let title = node.get("name").and_then(|v| v.as_str()).unwrap_or("").to_string();
+    let fullscreen = node.get("fullscreen_mode").and_then(|v| v.as_u64()).unwrap_or(0) != 0;
+    Some(FocusInfo {
+        class,
+        title,
+        fullscreen,
+    })
+}
+
+fn send_focus(tx: &Sender<FocusInfo>, info: FocusInfo) -> Result<()> {
+    tx.send_blocking(info).map_err(|_| anyhow::anyhow!("Channel closed"))
+}
@@ -0,0 +1,250 @@
+use crate::render_model::Bubble;
+use anyhow::{bail, Context, Result};
+use gtk4::cairo::{self, FontSlant, FontWeight, Format, ImageSurface};
+use nix::sys::stat::Mode;
+use nix::unistd::mkfifo;
+use std::fs::{self, File, OpenOptions};
+use std::io::{ErrorKind, Write};
+use std::os::unix::fs::FileTypeExt;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+use tracing::{debug, info, warn};
+
+/// Frames per second written, whether or not anything changed, so readers
+/// can treat the stream as constant-rate video.
+pub const FRAME_RATE: u32 = 30;
+const GAP: f64 = 8.0;
+const RADIUS: f64 = 8.0;
+
+/// Background of each bubble class, matching the overlay's dark theme.
+const BACKGROUNDS: &[(&str, [f64; 4])] = &[
+    ("held", [0.16, 0.35, 0.63, 0.90]),
+    ("modifier-chip", [0.20, 0.20, 0.20, 0.45]),
+    ("layout-badge", [0.20, 0.20, 0.20, 0.55]),
+    ("announce", [0.16, 0.35, 0.63, 0.85]),
+    ("lock", [0.78, 0.55, 0.08, 0.90]),
+    ("speed", [0.12, 0.43, 0.27, 0.85]),
+    ("status", [0.63, 0.24, 0.24, 0.85]),
+];
+const DEFAULT_BACKGROUND: [f64; 4] = [0.08, 0.08, 0.08, 0.70];
+
+/// Straight-alpha RGBA frames of the combo stream, drawn off-screen and
+/// written to a FIFO for compositing tools. Writing stops when this is
+/// dropped.
+pub struct FrameStream {
+    path: PathBuf,
+    width: i32,
+    height: i32,
+    latest: Arc<Mutex<Vec<u8>>>,
+    running: Arc<AtomicBool>,
+}
+
+impl FrameStream {
+    pub fn start(path: PathBuf, width: u32, height: u32) -> Result<Self> {
+        let width = i32::try_from(width).context("Frame stream width too large")?;
+        let height = i32::try_from(height).context("Frame stream height too large")?;
+        ensure_fifo(&path)?;
+        let blank = vec![0; width as usize * height as usize * 4];
+        let latest = Arc::new(Mutex::new(blank));
+        let running = Arc::new(AtomicBool::new(true));
+
+        let frames = Arc::clone(&latest);
+        let keep_running = Arc::clone(&running);
+        thread::Builder::new()
+            .name("frame-stream".to_string())
+            .spawn({
+                let path = path.clone();
+                move || write_frames(&path, &frames, &keep_running)
+            })
+            .context("Failed to start the frame stream thread")?;
+
+        Ok(Self {
+            path,
+            width,
+            height,
+            latest,
+            running,
+        })
+    }
+
+    /// Where the frames go and their size, to tell whether a settings
+    /// change needs a new stream.
+    pub fn target(&self) -> (&PathBuf, u32, u32) {
+        (&self.path, self.width as u32, self.height as u32)
+    }
+
+    /// Draws `bubbles` right-aligned in one row, newest last; the oldest are
+    /// left out when they do not fit.
+    pub fn render(&self, bubbles: &[Bubble]) {
+        match draw(bubbles, self.width, self.height) {
+            Ok(frame) => *self.latest.lock().unwrap() = frame,
+            Err(e) => warn!("Failed to draw a stream frame: {}", e),
+        }
+    }
+}
+
+impl Drop for FrameStream {
+    fn drop(&mut self) {
+        self.running.store(false, Ordering::Relaxed);
+    }
+}
+
+/// Creates the FIFO readable only by the user if nothing is at `path`, and
+/// refuses anything else there: frames written to a regular file would fill
+/// the disk at 30 fps.
+fn ensure_fifo(path: &Path) -> Result<()> {
+    match fs::metadata(path) {
+        Ok(meta) if meta.file_type().is_fifo() => Ok(()),
+        Ok(_) => bail!("Frame stream target {:?} is not a FIFO", path),
+        Err(e) if e.kind() == ErrorKind::NotFound => mkfifo(path, Mode::S_IRUSR | Mode::S_IWUSR)
+            .with_context(|| format!("Failed to create FIFO {:?}", path)),
+        Err(e) => Err(e).with_context(|| format!("Failed to inspect {:?}", path)),
+    }
+}
+
+/// Opens the FIFO for writing, which blocks until a reader comes. Checked
+/// again after opening in case something else was put at the path.
+fn open_fifo(path: &Path) -> Result<File> {
+    let file = OpenOptions::new()
+        .write(true)
+        .open(path)
+        .with_context(|| format!("Cannot open {:?}", path))?;
+    if !file.metadata()?.file_type().is_fifo() {
+        bail!("{:?} is no longer a FIFO", path);
+    }
+    Ok(file)
+}
+
+/// When a reader goes away the FIFO is opened again for the next one.
+fn write_frames(path: &Path, latest: &Mutex<Vec<u8>>, running: &AtomicBool) {
+    let interval = Duration::from_secs(1) / FRAME_RATE;
+    while running.load(Ordering::Relaxed) {
+        let mut output = match open_fifo(path) {
+            Ok(output) => output,
+            Err(e) => {
+                warn!("Frame stream stopped: {:#}", e);
+                return;
+            }
+        };
+        info!("Writing overlay frames to {:?}", path);
+        let mut next = Instant::now();
+        while running.load(Ordering::Relaxed) {
+            let frame = latest.lock().unwrap().clone();
+            if let Err(e) = output.write_all(&frame) {
+                debug!("Frame stream reader went away: {}", e);
+                break;
+            }
+            next += interval;
+            thread::sleep(next.saturating_duration_since(Instant::now()));
+        }
+    }
+}
+
+fn draw(bubbles: &[Bubble], width: i32, height: i32) -> Result<Vec<u8>, cairo::Error> {
+    let mut surface = ImageSurface::create(Format::ARgb32, width, height)?;
+    {
+        let cr = cairo::Context::new(&surface)?;
+        let bubble_h = f64::from(height) * 0.6;
+        let font_size = bubble_h * 0.45;
+        let padding = font_size * 0.7;
+        let top = (f64::from(height) - bubble_h) / 2.0;
+        cr.select_font_face("Sans", FontSlant::Normal, FontWeight::Bold);
+        cr.set_font_size(font_size);
+
+        let mut right = f64::from(width) - GAP;
+        for bubble in bubbles.iter().rev() {
+            let extents = cr.text_extents(&bubble.text)?;
+            let bubble_w = extents.x_advance() + padding * 2.0;
+            let left = right - bubble_w;
+            if left < 0.0 {
+                break;
+            }
+            let [r, g, b, a] = background(bubble);
+            cr.set_source_rgba(r, g, b, a);
+            rounded_rect(&cr, left, top, bubble_w, bubble_h);
+            cr.fill()?;
+
+            cr.set_source_rgb(1.0, 1.0, 1.0);
+            let baseline = top + (bubble_h - extents.height()) / 2.0 - extents.y_bearing();
+            cr.move_to(left + padding, baseline);
+            cr.show_text(&bubble.text)?;
+            right = left - GAP;
+        }
+    }
+    surface.flush();
+    let stride = surface.stride() as usize;
+    let data = surface.data().map_err(|_| cairo::Error::SurfaceFinished)?;
+    Ok(unpremultiply(&data, width as usize, height as usize, stride))
+}
+
+fn background(bubble: &Bubble) -> [f64; 4] {
+    BACKGROUNDS
+        .iter()
+        .find(|(class, _)| bubble.classes.iter().any(|c| c == class))
+        .map(|(_, color)| *color)
+        .unwrap_or(DEFAULT_BACKGROUND)
+}
+
+fn rounded_rect(cr: &cairo::Context, x: f64, y: f64, w: f64, h: f64) {
+    use std::f64::consts::{FRAC_PI_2, PI};
+    let radius = RADIUS.min(h / 2.0);
+    cr.new_sub_path();
+    cr.arc(x + w - radius, y + radius, radius, -FRAC_PI_2, 0.0);
+    cr.arc(x + w - radius, y + h - radius, radius, 0.0, FRAC_PI_2);
+    cr.arc(x + radius, y + h - radius, radius, FRAC_PI_2, PI);
+    cr.arc(x + radius, y + radius, radius, PI, PI + FRAC_PI_2);
+    cr.close_path();
+}
+
+/// Cairo's premultiplied native-endian ARGB32 to straight RGBA bytes.
+fn unpremultiply(data: &[u8], width: usize, height: usize, stride: usize) -> Vec<u8> {
+    let mut rgba = Vec::with_capacity(width * height * 4);
+    for row in data.chunks(stride).take(height) {
+        for pixel in row[..width * 4].chunks_exact(4) {
+            let argb = u32::from_ne_bytes([pixel[0], pixel[1], pixel[2], pixel[3]]);
+            let alpha = (argb >> 24) as u8;
+            let channel = |shift: u32| {
+                let value = (argb >> shift) & 0xff;
+                match alpha {
+                    0 => 0,
+                    _ => ((value * 255 + u32::from(alpha) / 2) / u32::from(alpha)).min(255) as u8,
+                }
+            };
+            rgba.extend_from_slice(&[channel(16), channel(8), channel(0), alpha]);
+        }
+    }
+    rgba
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pixels_come_out_straight_rgba() {
+        // Half-transparent pure red, premultiplied: 0x80800000.
+        let data = 0x8080_0000u32.to_ne_bytes();
+        assert_eq!(unpremultiply(&data, 1, 1, 4), vec![255, 0, 0, 128]);
+        assert_eq!(unpremultiply(&[0; 8], 1, 1, 8), vec![0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn only_writes_to_a_fifo() {
+        let dir = std::env::temp_dir().join(format!("keyway-frames-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let fifo = dir.join("frames.rgba");
+        let _ = fs::remove_file(&fifo);
+        ensure_fifo(&fifo).unwrap();
+        assert!(fs::metadata(&fifo).unwrap().file_type().is_fifo());
+        ensure_fifo(&fifo).unwrap();
+
+        let regular = dir.join("frames.txt");
+        fs::write(&regular, "notes").unwrap();
+        assert!(ensure_fifo(&regular).is_err());
+        assert_eq!(fs::read_to_string(&regular).unwrap(), "notes");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
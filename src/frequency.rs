@@ -0,0 +1,65 @@
+use std::collections::HashMap;
+
+/// Heat levels a combo can reach; the most pressed one is always at the top.
+const LEVELS: f64 = 4.0;
+/// Presses before a combo warms up at all, so the first keys of a session
+/// don't all glow.
+const MIN_PRESSES: u64 = 3;
+
+/// How often each combo was shown this session, for `frequency_emphasis`.
+#[derive(Debug, Default)]
+pub struct ComboFrequency {
+    counts: HashMap<String, u64>,
+    hottest: u64,
+}
+
+impl ComboFrequency {
+    /// Counts one more `text` and returns its heat, 0 for cold up to 4 for
+    /// the most pressed combo.
+    pub fn record(&mut self, text: &str) -> u8 {
+        let count = match self.counts.get_mut(text) {
+            Some(count) => {
+                *count += 1;
+                *count
+            }
+            None => {
+                self.counts.insert(text.to_string(), 1);
+                1
+            }
+        };
+        self.hottest = self.hottest.max(count);
+        self.heat(count)
+    }
+
+    /// On a log scale, since a few keys take most presses: with Backspace
+    /// at 1000, a combo pressed 30 times is still halfway up.
+    fn heat(&self, count: u64) -> u8 {
+        if count < MIN_PRESSES {
+            return 0;
+        }
+        let share = (count as f64).ln() / (self.hottest as f64).ln();
+        (share * LEVELS).ceil().clamp(1.0, LEVELS) as u8
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn heat_rises_with_presses_relative_to_the_hottest() {
+        let mut frequency = ComboFrequency::default();
+        assert_eq!(frequency.record("Ctrl+S"), 0);
+        assert_eq!(frequency.record("Ctrl+S"), 0);
+        assert_eq!(frequency.record("Ctrl+S"), 4);
+
+        let heats: Vec<u8> = (0..1000).map(|_| frequency.record("Backspace")).collect();
+        assert_eq!(heats[2], 4);
+        assert_eq!(frequency.record("Ctrl+S"), 1);
+        for _ in 0..30 {
+            frequency.record("Ctrl+S");
+        }
+        assert_eq!(frequency.record("Ctrl+S"), 3);
+        assert_eq!(frequency.record("Backspace"), 4);
+    }
+}
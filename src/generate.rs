@@ -0,0 +1,140 @@
+use crate::settings::{CliArgs, Settings};
+use anyhow::{Context, Result};
+use clap::{CommandFactory, Parser, Subcommand};
+use clap_complete::Shell;
+use std::io;
+
+/// Files for packaging and dotfiles, written to stdout.
+#[derive(Parser, Debug, Clone)]
+pub struct GenerateArgs {
+    #[command(subcommand)]
+    pub target: GenerateTarget,
+}
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum GenerateTarget {
+    /// Shell completions, e.g. `generate completions fish > keyway-visualizer.fish`
+    Completions {
+        #[arg(value_enum)]
+        shell: Shell,
+    },
+    /// A config with every setting at its default, commented out
+    Config,
+}
+
+/// Settings without a command-line option, so without help text to borrow.
+const TABLE_HELP: &[(&str, &str)] = &[
+    ("secure_input_rules", "App filter style entries for password prompts and sign-in windows"),
+    ("hooks", "Commands run when a combo is shown: [[hooks]] with combo, command, cooldown_ms"),
+    ("visual_bell_keys", "Keys that flash the visual bell, in hotkey syntax"),
+    ("aliases", "Action names by combo, e.g. \"Ctrl+Shift+T\" = \"Reopen closed tab\""),
+    ("device_tags", "Tags shown before combos from matching devices, e.g. \"Macro Pad\" = \"pad\""),
+    ("profiles", "Per-app overrides: [[profiles]] with name, apps and any of the settings above"),
+];
+
+pub fn run(args: &GenerateArgs) -> Result<()> {
+    match args.target {
+        GenerateTarget::Completions { shell } => {
+            let mut command = CliArgs::command();
+            let name = command.get_name().to_string();
+            clap_complete::generate(shell, &mut command, name, &mut io::stdout());
+        }
+        GenerateTarget::Config => print!("{}", sample_config()?),
+    }
+    Ok(())
+}
+
+/// The defaults as TOML, each setting under the help of its command-line
+/// option and everything commented out, so uncommenting a line changes just
+/// that setting.
+fn sample_config() -> Result<String> {
+    let mut out = String::from(
+        "# keyway-visualizer config: every setting at its default, commented out.\n\
+         # Save as ~/.config/keyway-visualizer/config.toml and uncomment what you change.\n",
+    );
+    for (help, toml) in sample_entries()? {
+        out.push('\n');
+        for line in help.lines().chain(toml.lines()) {
+            out.push_str(&format!("# {}\n", line));
+        }
+    }
+    Ok(out)
+}
+
+/// Each setting, or table of settings, with its help text.
+fn sample_entries() -> Result<Vec<(String, String)>> {
+    let defaults = Settings::default();
+    let toml = toml::to_string_pretty(&defaults).context("Failed to serialize defaults")?;
+    // TOML has no null, so settings that are unset by default are missing
+    // from the above; they go in with an empty value before the tables.
+    let unset: Vec<String> = match serde_json::to_value(&defaults)? {
+        serde_json::Value::Object(map) => map
+            .into_iter()
+            .filter(|(_, value)| value.is_null())
+            .map(|(key, _)| key)
+            .collect(),
+        _ => Vec::new(),
+    };
+
+    let command = CliArgs::command();
+    let help = |key: &str| {
+        command
+            .get_arguments()
+            .find(|arg| arg.get_id() == key)
+            .and_then(|arg| arg.get_help())
+            .map(|help| help.to_string())
+            .or_else(|| {
+                TABLE_HELP
+                    .iter()
+                    .find(|(name, _)| *name == key)
+                    .map(|(_, help)| help.to_string())
+            })
+            .unwrap_or_default()
+    };
+
+    let mut entries: Vec<(String, String)> = Vec::new();
+    let mut in_tables = false;
+    for line in toml.lines().filter(|line| !line.is_empty()) {
+        let table = line
+            .starts_with('[')
+            .then(|| line.trim_matches(|c| c == '[' || c == ']'));
+        if table.is_some() && !in_tables {
+            in_tables = true;
+            for key in &unset {
+                entries.push((help(key), format!("{} = \"\"\n", key)));
+            }
+        }
+        let key = table.or_else(|| {
+            line.split_once(" = ")
+                .map(|(key, _)| key)
+                .filter(|key| !in_tables && !key.starts_with(char::is_whitespace))
+        });
+        match (key, entries.last_mut()) {
+            (None, Some((_, toml))) => toml.push_str(&format!("{}\n", line)),
+            (key, _) => entries.push((help(key.unwrap_or_default()), format!("{}\n", line))),
+        }
+    }
+    if !in_tables {
+        for key in &unset {
+            entries.push((help(key), format!("{} = \"\"\n", key)));
+        }
+    }
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sample_config_parses_and_explains_every_setting() {
+        let entries = sample_entries().unwrap();
+        for (help, toml) in &entries {
+            assert!(!help.is_empty(), "no help for {}", toml);
+        }
+        let toml: String = entries.into_iter().map(|(_, toml)| toml).collect();
+        let parsed: Settings = toml::from_str(&toml).unwrap();
+        assert_eq!(parsed.max_items, Settings::default().max_items);
+        assert_eq!(parsed.capture_dir, Some("".into()));
+    }
+}
@@ -0,0 +1,155 @@
+use crate::settings::Settings;
+use anyhow::{anyhow, Context, Result};
+use async_channel::Receiver;
+use gtk4::gio::prelude::*;
+use gtk4::gio::{self, SettingsSchemaSource};
+use gtk4::glib::{Variant, VariantTy};
+use toml::Value;
+use tracing::{debug, warn};
+
+/// Installed from `packaging/dev.keyway.Visualizer.gschema.xml`.
+pub const SCHEMA_ID: &str = "dev.keyway.Visualizer";
+
+/// `settings_backend = "gsettings"`: the settings named in the schema live
+/// in dconf, where dconf-editor and `gsettings` can change them and vendor
+/// overrides set their defaults. Keys are the config names with dashes;
+/// everything else stays in the config file.
+pub struct GSettingsStore {
+    settings: gio::Settings,
+    keys: Vec<String>,
+}
+
+impl GSettingsStore {
+    /// `None`, with a warning, when the schema is not installed.
+    pub fn open() -> Option<Self> {
+        let schema =
+            SettingsSchemaSource::default().and_then(|source| source.lookup(SCHEMA_ID, true));
+        let Some(schema) = schema else {
+            warn!("GSettings schema {} is not installed, settings stay in the file", SCHEMA_ID);
+            return None;
+        };
+        let keys = schema.list_keys().iter().map(|key| key.to_string()).collect();
+        Some(Self {
+            settings: gio::Settings::new(SCHEMA_ID),
+            keys,
+        })
+    }
+
+    /// `base` with the fields in the schema replaced by their dconf values.
+    pub fn load_into(&self, base: &Settings) -> Result<Settings> {
+        let mut table = Value::try_from(base).context("Failed to serialize settings")?;
+        let fields = table.as_table_mut().context("Settings are not a table")?;
+        for key in &self.keys {
+            let field = key.replace('-', "_");
+            match variant_to_toml(&self.settings.value(key)) {
+                Some(value) => fields.insert(field, value),
+                None => fields.remove(&field),
+            };
+        }
+        table.try_into().context("Invalid value in GSettings")
+    }
+
+    /// Writes the fields in the schema to dconf, all at once.
+    pub fn store(&self, settings: &Settings) -> Result<()> {
+        let table = Value::try_from(settings).context("Failed to serialize settings")?;
+        self.settings.delay();
+        let result = self.keys.iter().try_for_each(|key| {
+            let current = self.settings.value(key);
+            let field = key.replace('-', "_");
+            let value = toml_to_variant(table.get(&field), current.type_())
+                .with_context(|| format!("{} does not fit GSettings key {}", field, key))?;
+            if value != current {
+                self.settings.set_value(key, &value).map_err(|e| anyhow!("{}: {}", key, e))?;
+            }
+            Ok(())
+        });
+        match result {
+            Ok(()) => self.settings.apply(),
+            Err(_) => self.settings.revert(),
+        }
+        result
+    }
+
+    /// Sends whenever a key changes, in dconf or through `store`.
+    pub fn watch(&self) -> Receiver<()> {
+        let (tx, rx) = async_channel::bounded(1);
+        self.settings.connect_changed(None, move |_, key| {
+            debug!("GSettings key {} changed", key);
+            let _ = tx.try_send(());
+        });
+        rx
+    }
+}
+
+fn variant_to_toml(variant: &Variant) -> Option<Value> {
+    let value = match variant.type_().as_str() {
+        "b" => Value::Boolean(variant.get::<bool>()?),
+        "i" => Value::Integer(variant.get::<i32>()?.into()),
+        "u" => Value::Integer(variant.get::<u32>()?.into()),
+        "x" => Value::Integer(variant.get::<i64>()?),
+        "t" => Value::Integer(i64::try_from(variant.get::<u64>()?).ok()?),
+        "d" => Value::Float(variant.get::<f64>()?),
+        "s" => Value::String(variant.get::<String>()?),
+        "as" => {
+            let items = variant.get::<Vec<String>>()?;
+            Value::Array(items.into_iter().map(Value::String).collect())
+        }
+        // Nothing stands for an unset optional field.
+        ty if ty.starts_with('m') => return variant_to_toml(&variant.as_maybe()?),
+        _ => return None,
+    };
+    Some(value)
+}
+
+fn toml_to_variant(value: Option<&Value>, ty: &VariantTy) -> Option<Variant> {
+    if ty.is_maybe() {
+        return match value {
+            Some(value) => Some(Variant::from_some(&toml_to_variant(Some(value), ty.element())?)),
+            None => Some(Variant::from_none(ty.element())),
+        };
+    }
+    let variant = match (ty.as_str(), value?) {
+        ("b", Value::Boolean(b)) => b.to_variant(),
+        ("i", Value::Integer(n)) => i32::try_from(*n).ok()?.to_variant(),
+        ("u", Value::Integer(n)) => u32::try_from(*n).ok()?.to_variant(),
+        ("x", Value::Integer(n)) => n.to_variant(),
+        ("t", Value::Integer(n)) => u64::try_from(*n).ok()?.to_variant(),
+        ("d", Value::Float(f)) => f.to_variant(),
+        ("s", Value::String(s)) => s.to_variant(),
+        ("as", Value::Array(items)) => items
+            .iter()
+            .map(|item| item.as_str().map(str::to_string))
+            .collect::<Option<Vec<String>>>()?
+            .to_variant(),
+        _ => return None,
+    };
+    Some(variant)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use regex::Regex;
+
+    /// Every key in the schema names a setting, with the same default.
+    #[test]
+    fn schema_matches_the_settings() {
+        let schema = include_str!("../packaging/dev.keyway.Visualizer.gschema.xml");
+        let key =
+            Regex::new(r#"(?s)<key name="([a-z-]+)" type="([^"]+)">.*?<default>(.*?)</default>"#)
+                .unwrap();
+        let defaults = Value::try_from(Settings::default()).unwrap();
+        let mut keys = 0;
+        for captures in key.captures_iter(schema) {
+            let field = captures[1].replace('-', "_");
+            let ty = VariantTy::new(&captures[2]).unwrap();
+            let default = Variant::parse(Some(ty), &captures[3]).unwrap();
+            let expected = defaults.get(&field);
+            assert!(expected.is_some(), "{} is not a setting", field);
+            assert_eq!(variant_to_toml(&default).as_ref(), expected, "default of {}", field);
+            assert_eq!(toml_to_variant(expected, ty), Some(default), "type of {}", field);
+            keys += 1;
+        }
+        assert!(keys > 20);
+    }
+}
@@ -0,0 +1,123 @@
+use crate::combo::{ComboKind, ComboState};
+use crate::combo_format::{self, ComboFormat};
+use crate::hotkey::Hotkey;
+use crate::i18n;
+use crate::input::{stdin, InputListener, ListenerConfig, ListenerStatus, EVENT_CHANNEL_CAPACITY};
+use crate::settings::Settings;
+use anyhow::Result;
+use clap::ColorChoice;
+use std::io::{self, IsTerminal, Write};
+use std::thread;
+use std::time::Duration;
+use tracing::info;
+
+const BOLD_CYAN: &str = "\x1b[1;36m";
+const MAGENTA: &str = "\x1b[35m";
+const DIM: &str = "\x1b[2m";
+const RESET: &str = "\x1b[0m";
+
+/// `--headless`: the same combos the overlay would show, one per line on
+/// stdout, without GTK. Ends quietly when the reader goes away.
+pub fn run(settings: &Settings, color: ColorChoice, stdin_events: bool) -> Result<()> {
+    i18n::set_language(&settings.language);
+    let color = match color {
+        ColorChoice::Always => true,
+        ColorChoice::Never => false,
+        ColorChoice::Auto => io::stdout().is_terminal(),
+    };
+
+    let (tx, rx) = async_channel::bounded(EVENT_CHANNEL_CAPACITY);
+    let (status_tx, status_rx) = async_channel::bounded(4);
+    let _listener = if stdin_events {
+        stdin::start_stdin_listener(tx);
+        None
+    } else {
+        let config = ListenerConfig {
+            include_mouse: settings.show_mouse,
+            ..ListenerConfig::default()
+        };
+        Some(InputListener::new(tx, status_tx, config).start()?)
+    };
+    thread::spawn(move || {
+        while let Ok(status) = status_rx.recv_blocking() {
+            match status {
+                ListenerStatus::NoKeyboards { denied: true } => {
+                    eprintln!("No keyboard access: join the input group")
+                }
+                ListenerStatus::NoKeyboards { denied: false } => {
+                    eprintln!("Waiting for a keyboard…")
+                }
+                ListenerStatus::KeyboardsFound | ListenerStatus::DevicesChanged => {}
+            }
+        }
+    });
+
+    let mut combo = ComboState::new(
+        settings.max_items,
+        Duration::from_millis(settings.ttl_ms),
+        Duration::from_millis(settings.repeat_coalesce_ms),
+        Duration::from_millis(settings.modifier_grace_ms),
+        Hotkey::parse(&settings.pause_hotkey)?,
+    );
+    combo.set_display_mode(settings.display_mode);
+    combo.set_show_composed(settings.show_composed);
+    combo.set_mouse_drag(settings.mouse_drag);
+    combo.set_teaching(settings.teaching_mode, &settings.aliases);
+    combo.set_device_tags(&settings.device_tags);
+    // Without a window there is no focus to follow, so `secure_input_rules`
+    // and the app filter cannot apply; the mask is all that protects input.
+    combo.set_masked(settings.privacy_mask);
+    let format = ComboFormat::from_settings(settings)?;
+    info!("Printing combos to stdout");
+
+    let mut out = io::stdout().lock();
+    while let Ok(event) = rx.recv_blocking() {
+        let action = combo.handle_event(event);
+        let line = match (action.combo, action.paused_changed) {
+            (Some(shown), _) => {
+                let text = combo_format::spell(&shown.text, format.as_ref(), 1);
+                format_line(&text, shown.kind, color)
+            }
+            (None, Some(paused)) => {
+                let text = i18n::tr(if paused { "Paused" } else { "Resumed" });
+                paint(text, DIM, color)
+            }
+            (None, None) => continue,
+        };
+        if let Err(e) = writeln!(out, "{}", line).and_then(|()| out.flush()) {
+            if e.kind() == io::ErrorKind::BrokenPipe {
+                return Ok(());
+            }
+            return Err(e.into());
+        }
+    }
+    Ok(())
+}
+
+fn format_line(text: &str, kind: ComboKind, color: bool) -> String {
+    match kind {
+        ComboKind::Shortcut => paint(text, BOLD_CYAN, color),
+        ComboKind::Mouse => paint(text, MAGENTA, color),
+        ComboKind::Key => text.to_string(),
+    }
+}
+
+fn paint(text: &str, style: &str, color: bool) -> String {
+    if color {
+        format!("{}{}{}", style, text, RESET)
+    } else {
+        text.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shortcuts_stand_out_only_with_color() {
+        assert_eq!(format_line("Ctrl+S", ComboKind::Shortcut, true), "\x1b[1;36mCtrl+S\x1b[0m");
+        assert_eq!(format_line("Ctrl+S", ComboKind::Shortcut, false), "Ctrl+S");
+        assert_eq!(format_line("a", ComboKind::Key, true), "a");
+    }
+}
@@ -0,0 +1,301 @@
+use gtk4::cairo::Context;
+use gtk4::prelude::*;
+use gtk4::{
+    Application, ApplicationWindow, Box as GtkBox, Button, DrawingArea, DropDown, Label,
+    Orientation, StringList,
+};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+const RANGES: [&str; 4] = ["Today", "Last 7 days", "Last 30 days", "All time"];
+const RANGE_DAYS: [Option<u32>; 4] = [Some(1), Some(7), Some(30), None];
+
+const KEY_UNIT: f64 = 44.0;
+const KEY_GAP: f64 = 4.0;
+/// Keys without presses, or not down in the rollover test
+pub const UNUSED_KEY: (f64, f64, f64) = (0.25, 0.25, 0.27);
+
+/// US ANSI layout as (evdev name, label, width in key units). Counts are
+/// stored per physical key, so other layouts light up the same positions.
+const ROWS: [&[(&str, &str, f64)]; 6] = [
+    &[
+        ("ESC", "Esc", 1.0),
+        ("F1", "F1", 1.0),
+        ("F2", "F2", 1.0),
+        ("F3", "F3", 1.0),
+        ("F4", "F4", 1.0),
+        ("F5", "F5", 1.0),
+        ("F6", "F6", 1.0),
+        ("F7", "F7", 1.0),
+        ("F8", "F8", 1.0),
+        ("F9", "F9", 1.0),
+        ("F10", "F10", 1.0),
+        ("F11", "F11", 1.0),
+        ("F12", "F12", 1.0),
+    ],
+    &[
+        ("GRAVE", "`", 1.0),
+        ("1", "1", 1.0),
+        ("2", "2", 1.0),
+        ("3", "3", 1.0),
+        ("4", "4", 1.0),
+        ("5", "5", 1.0),
+        ("6", "6", 1.0),
+        ("7", "7", 1.0),
+        ("8", "8", 1.0),
+        ("9", "9", 1.0),
+        ("0", "0", 1.0),
+        ("MINUS", "-", 1.0),
+        ("EQUAL", "=", 1.0),
+        ("BACKSPACE", "Bksp", 2.0),
+    ],
+    &[
+        ("TAB", "Tab", 1.5),
+        ("Q", "Q", 1.0),
+        ("W", "W", 1.0),
+        ("E", "E", 1.0),
+        ("R", "R", 1.0),
+        ("T", "T", 1.0),
+        ("Y", "Y", 1.0),
+        ("U", "U", 1.0),
+        ("I", "I", 1.0),
+        ("O", "O", 1.0),
+        ("P", "P", 1.0),
+        ("LEFTBRACE", "[", 1.0),
+        ("RIGHTBRACE", "]", 1.0),
+        ("BACKSLASH", "\\", 1.5),
+    ],
+    &[
+        ("CAPSLOCK", "Caps", 1.75),
+        ("A", "A", 1.0),
+        ("S", "S", 1.0),
+        ("D", "D", 1.0),
+        ("F", "F", 1.0),
+        ("G", "G", 1.0),
+        ("H", "H", 1.0),
+        ("J", "J", 1.0),
+        ("K", "K", 1.0),
+        ("L", "L", 1.0),
+        ("SEMICOLON", ";", 1.0),
+        ("APOSTROPHE", "'", 1.0),
+        ("ENTER", "Enter", 2.25),
+    ],
+    &[
+        ("LEFTSHIFT", "Shift", 2.25),
+        ("Z", "Z", 1.0),
+        ("X", "X", 1.0),
+        ("C", "C", 1.0),
+        ("V", "V", 1.0),
+        ("B", "B", 1.0),
+        ("N", "N", 1.0),
+        ("M", "M", 1.0),
+        ("COMMA", ",", 1.0),
+        ("DOT", ".", 1.0),
+        ("SLASH", "/", 1.0),
+        ("RIGHTSHIFT", "Shift", 2.75),
+    ],
+    &[
+        ("LEFTCTRL", "Ctrl", 1.25),
+        ("LEFTMETA", "Super", 1.25),
+        ("LEFTALT", "Alt", 1.25),
+        ("SPACE", "Space", 6.25),
+        ("RIGHTALT", "AltGr", 1.25),
+        ("RIGHTMETA", "Super", 1.25),
+        ("COMPOSE", "Menu", 1.25),
+        ("RIGHTCTRL", "Ctrl", 1.25),
+    ],
+];
+
+/// Keyboard drawn in colors by how often each key was pressed, read from
+/// the statistics store.
+pub struct HeatmapWindow {
+    pub window: ApplicationWindow,
+    area: DrawingArea,
+    range: DropDown,
+    status: Label,
+    refresh_button: Button,
+    counts: Rc<RefCell<HashMap<String, u64>>>,
+}
+
+impl HeatmapWindow {
+    pub fn new(app: &Application) -> Self {
+        let (width, height) = keyboard_size();
+
+        let window = ApplicationWindow::builder()
+            .application(app)
+            .title("Keyway Visualizer Heatmap")
+            .hide_on_close(true)
+            .build();
+
+        let content = GtkBox::new(Orientation::Vertical, 12);
+        content.set_margin_top(16);
+        content.set_margin_bottom(16);
+        content.set_margin_start(16);
+        content.set_margin_end(16);
+
+        let counts: Rc<RefCell<HashMap<String, u64>>> = Rc::default();
+        let area = DrawingArea::new();
+        area.set_content_width(width);
+        area.set_content_height(height);
+        let draw_counts = Rc::clone(&counts);
+        area.set_draw_func(move |_, cr, _, _| {
+            draw_keyboard(cr, &draw_counts.borrow());
+        });
+
+        let range = DropDown::new(Some(StringList::new(&RANGES)), None::<&gtk4::Expression>);
+        range.set_selected(3);
+        let refresh_button = Button::with_label("Refresh");
+        let controls = GtkBox::new(Orientation::Horizontal, 8);
+        controls.append(&Label::new(Some("Range")));
+        controls.append(&range);
+        controls.append(&refresh_button);
+
+        let status = Label::new(None);
+        status.set_halign(gtk4::Align::Start);
+        status.set_wrap(true);
+
+        content.append(&controls);
+        content.append(&area);
+        content.append(&status);
+        window.set_child(Some(&content));
+
+        Self {
+            window,
+            area,
+            range,
+            status,
+            refresh_button,
+            counts,
+        }
+    }
+
+    pub fn present(&self) {
+        self.window.present();
+    }
+
+    /// Days covered by the selected range; `None` is all time.
+    pub fn selected_days(&self) -> Option<u32> {
+        RANGE_DAYS
+            .get(self.range.selected() as usize)
+            .copied()
+            .flatten()
+    }
+
+    pub fn set_counts(&self, counts: HashMap<String, u64>) {
+        let total: u64 = counts.values().sum();
+        let unused: Vec<&str> = ROWS
+            .iter()
+            .flat_map(|row| row.iter())
+            .filter(|(name, _, _)| counts.get(*name).copied().unwrap_or(0) == 0)
+            .map(|(_, label, _)| *label)
+            .collect();
+        let message = if unused.is_empty() {
+            format!("{} key presses", total)
+        } else {
+            format!("{} key presses. Never used: {}", total, unused.join(" "))
+        };
+
+        *self.counts.borrow_mut() = counts;
+        self.status.set_text(&message);
+        self.area.queue_draw();
+    }
+
+    pub fn connect_refresh<F: Fn() + 'static>(&self, callback: F) {
+        let callback = Rc::new(callback);
+        let on_click = Rc::clone(&callback);
+        self.refresh_button.connect_clicked(move |_| on_click());
+        self.range.connect_selected_notify(move |_| callback());
+    }
+
+    pub fn set_status(&self, message: &str) {
+        self.status.set_text(message);
+    }
+}
+
+/// Width and height of the drawn keyboard in pixels.
+pub fn keyboard_size() -> (i32, i32) {
+    let width = 15.0 * (KEY_UNIT + KEY_GAP) + KEY_GAP;
+    let height = ROWS.len() as f64 * (KEY_UNIT + KEY_GAP) + KEY_GAP;
+    (width as i32, height as i32)
+}
+
+/// Whether the drawn keyboard has the key with this evdev name.
+pub fn on_keyboard(name: &str) -> bool {
+    ROWS.iter().flat_map(|row| row.iter()).any(|(key, _, _)| *key == name)
+}
+
+/// Draws the keyboard with each key in the color `paint` gives for its
+/// evdev name, and the note it gives, if any, under the label.
+pub fn draw_keys(cr: &Context, paint: impl Fn(&str) -> ((f64, f64, f64), Option<String>)) {
+    cr.select_font_face("Sans", gtk4::cairo::FontSlant::Normal, gtk4::cairo::FontWeight::Bold);
+    cr.set_font_size(12.0);
+
+    for (row_index, row) in ROWS.iter().enumerate() {
+        let y = KEY_GAP + row_index as f64 * (KEY_UNIT + KEY_GAP);
+        let mut x = KEY_GAP;
+        for (name, label, units) in row.iter() {
+            let width = units * KEY_UNIT + (units - 1.0) * KEY_GAP;
+            let ((r, g, b), note) = paint(name);
+
+            cr.set_source_rgb(r, g, b);
+            cr.rectangle(x, y, width, KEY_UNIT);
+            let _ = cr.fill();
+
+            cr.set_source_rgb(1.0, 1.0, 1.0);
+            if let Ok(extents) = cr.text_extents(label) {
+                cr.move_to(x + (width - extents.width()) / 2.0, y + KEY_UNIT / 2.0);
+                let _ = cr.show_text(label);
+            }
+            if let Some(text) = note {
+                cr.set_font_size(9.0);
+                if let Ok(extents) = cr.text_extents(&text) {
+                    cr.move_to(x + (width - extents.width()) / 2.0, y + KEY_UNIT - 8.0);
+                    let _ = cr.show_text(&text);
+                }
+                cr.set_font_size(12.0);
+            }
+
+            x += width + KEY_GAP;
+        }
+    }
+}
+
+fn draw_keyboard(cr: &Context, counts: &HashMap<String, u64>) {
+    // Log scale, so a few very common keys do not wash everything else out.
+    let max = counts.values().copied().max().unwrap_or(0);
+    let scale = (1.0 + max as f64).ln();
+
+    draw_keys(cr, |name| {
+        match counts.get(name).copied().unwrap_or(0) {
+            0 => (UNUSED_KEY, None),
+            count => (heat_color((1.0 + count as f64).ln() / scale), Some(count.to_string())),
+        }
+    });
+}
+
+/// Blue for rarely used keys through yellow to red for the most used.
+fn heat_color(fraction: f64) -> (f64, f64, f64) {
+    let t = fraction.clamp(0.0, 1.0);
+    if t < 0.5 {
+        let t = t * 2.0;
+        (0.2 + 0.7 * t, 0.35 + 0.45 * t, 0.7 - 0.5 * t)
+    } else {
+        let t = (t - 0.5) * 2.0;
+        (0.9, 0.8 - 0.6 * t, 0.2 - 0.05 * t)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn heat_color_runs_from_blue_to_red() {
+        let (r, _, b) = heat_color(0.0);
+        assert!(b > r);
+        let (r, g, b) = heat_color(1.0);
+        assert!(r > g && r > b);
+        assert_eq!(heat_color(2.0), heat_color(1.0));
+    }
+}
@@ -0,0 +1,135 @@
+use crate::app_filter;
+use crate::settings::CommandHook;
+use anyhow::Result;
+use regex::Regex;
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+use tracing::{debug, warn};
+
+enum ComboPattern {
+    Exact(String),
+    Regex(Regex),
+}
+
+impl ComboPattern {
+    fn parse(pattern: &str) -> Result<Self> {
+        let pattern = pattern.trim();
+        Ok(if let Some(re) = pattern.strip_prefix("re:") {
+            ComboPattern::Regex(app_filter::build_regex(re)?)
+        } else if let Some(glob) = pattern.strip_prefix("glob:") {
+            ComboPattern::Regex(app_filter::build_regex(&app_filter::glob_to_regex(glob))?)
+        } else {
+            ComboPattern::Exact(pattern.to_lowercase())
+        })
+    }
+
+    fn matches(&self, text: &str) -> bool {
+        match self {
+            ComboPattern::Exact(combo) => text.to_lowercase() == *combo,
+            ComboPattern::Regex(re) => re.is_match(text),
+        }
+    }
+}
+
+struct Hook {
+    pattern: ComboPattern,
+    command: String,
+    cooldown: Duration,
+    last_run: Option<Instant>,
+    running: Arc<AtomicBool>,
+}
+
+/// Configured `hooks`, run in the background when a displayed combo matches.
+///
+/// A hook is skipped while its previous run is still going or its cooldown
+/// has not passed, so held or repeated keys cannot pile up processes.
+pub struct HookSet {
+    hooks: Vec<Hook>,
+}
+
+impl HookSet {
+    pub fn new(configs: &[CommandHook]) -> Self {
+        let hooks = configs
+            .iter()
+            .filter(|config| !config.command.trim().is_empty())
+            .filter_map(|config| match ComboPattern::parse(&config.combo) {
+                Ok(pattern) => Some(Hook {
+                    pattern,
+                    command: config.command.clone(),
+                    cooldown: Duration::from_millis(config.cooldown_ms),
+                    last_run: None,
+                    running: Arc::new(AtomicBool::new(false)),
+                }),
+                Err(e) => {
+                    warn!("Ignoring hook for {:?}: {:#}", config.combo, e);
+                    None
+                }
+            })
+            .collect();
+
+        Self { hooks }
+    }
+
+    pub fn run_matching(&mut self, text: &str) {
+        let now = Instant::now();
+        for hook in &mut self.hooks {
+            if !hook.pattern.matches(text) {
+                continue;
+            }
+            if hook
+                .last_run
+                .is_some_and(|at| now.duration_since(at) < hook.cooldown)
+                || hook.running.load(Ordering::Acquire)
+            {
+                debug!("Hook for {:?} rate limited", text);
+                continue;
+            }
+
+            hook.last_run = Some(now);
+            spawn_hook(&hook.command, text, Arc::clone(&hook.running));
+        }
+    }
+}
+
+fn spawn_hook(command: &str, combo: &str, running: Arc<AtomicBool>) {
+    let child = Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .env("KEYWAY_COMBO", combo)
+        .stdin(Stdio::null())
+        .spawn();
+
+    match child {
+        Ok(mut child) => {
+            running.store(true, Ordering::Release);
+            // Reap the child off the GTK thread.
+            thread::spawn(move || {
+                let _ = child.wait();
+                running.store(false, Ordering::Release);
+            });
+        }
+        Err(e) => warn!("Failed to run hook {:?}: {}", command, e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn combo_patterns() {
+        let exact = ComboPattern::parse("Ctrl+S").unwrap();
+        assert!(exact.matches("ctrl+s"));
+        assert!(!exact.matches("Ctrl+Shift+S"));
+
+        let glob = ComboPattern::parse("glob:Ctrl+*").unwrap();
+        assert!(glob.matches("Ctrl+Shift+S"));
+        assert!(!glob.matches("Alt+S"));
+
+        let re = ComboPattern::parse("re:^F\\d+$").unwrap();
+        assert!(re.matches("F12"));
+    }
+}
@@ -81,6 +81,56 @@ impl Hotkey {
         parts.push(self.key.as_str());
         parts.join("+")
     }
+
+    /// Trigger in the XDG shortcuts format used by the GlobalShortcuts
+    /// portal, e.g. `CTRL+SHIFT+p`.
+    pub fn portal_trigger(&self) -> String {
+        let mut parts = Vec::new();
+        if self.ctrl {
+            parts.push("CTRL");
+        }
+        if self.shift {
+            parts.push("SHIFT");
+        }
+        if self.alt {
+            parts.push("ALT");
+        }
+        if self.super_key {
+            parts.push("LOGO");
+        }
+        let keysym = keysym_name(&self.key);
+        parts.push(&keysym);
+        parts.join("+")
+    }
+}
+
+fn keysym_name(key: &str) -> String {
+    let name = match key {
+        "Esc" => "Escape",
+        "Enter" => "Return",
+        "Space" => "space",
+        "Backspace" => "BackSpace",
+        "Del" => "Delete",
+        "Ins" => "Insert",
+        "PgUp" => "Prior",
+        "PgDn" => "Next",
+        "PrtSc" => "Print",
+        "+" => "plus",
+        "-" => "minus",
+        "=" => "equal",
+        "," => "comma",
+        "." => "period",
+        "/" => "slash",
+        "\\" => "backslash",
+        "`" => "grave",
+        "'" => "apostrophe",
+        ";" => "semicolon",
+        "[" => "bracketleft",
+        "]" => "bracketright",
+        _ if key.len() == 1 => return key.to_ascii_lowercase(),
+        _ => key,
+    };
+    name.to_string()
 }
 
 fn normalize_key_token(token: &str) -> String {
@@ -153,4 +203,13 @@ mod tests {
         mods.insert(Key::KEY_LEFTSHIFT);
         assert!(!hotkey.matches(&mods, "P"));
     }
+
+    #[test]
+    fn portal_triggers() {
+        let hotkey = Hotkey::parse("Ctrl+Shift+P").unwrap();
+        assert_eq!(hotkey.portal_trigger(), "CTRL+SHIFT+p");
+
+        let hotkey = Hotkey::parse("Super+PgUp").unwrap();
+        assert_eq!(hotkey.portal_trigger(), "LOGO+Prior");
+    }
 }
@@ -0,0 +1,347 @@
+use crate::combo::combo_parts;
+use std::env;
+use std::sync::atomic::{AtomicU8, Ordering};
+
+/// Language of the overlay, tray and settings window. Catalogs are compiled
+/// in; anything a catalog lacks stays in English.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum Language {
+    English,
+    German,
+    French,
+}
+
+static LANGUAGE: AtomicU8 = AtomicU8::new(Language::English as u8);
+
+impl Language {
+    /// "de", "de_DE.UTF-8" or "fr-CA"; unknown languages are English.
+    fn parse(tag: &str) -> Self {
+        let code = tag.split(['_', '-', '.', '@']).next().unwrap_or("");
+        match code.to_ascii_lowercase().as_str() {
+            "de" => Language::German,
+            "fr" => Language::French,
+            _ => Language::English,
+        }
+    }
+
+    /// The first of LC_ALL, LC_MESSAGES and LANG that is set.
+    fn from_locale() -> Self {
+        ["LC_ALL", "LC_MESSAGES", "LANG"]
+            .iter()
+            .filter_map(|name| env::var(name).ok())
+            .find(|value| !value.is_empty())
+            .map(|value| Self::parse(&value))
+            .unwrap_or(Language::English)
+    }
+
+    fn current() -> Self {
+        match LANGUAGE.load(Ordering::Relaxed) {
+            1 => Language::German,
+            2 => Language::French,
+            _ => Language::English,
+        }
+    }
+
+    fn messages(self) -> &'static [(&'static str, &'static str)] {
+        match self {
+            Language::English => &[],
+            Language::German => GERMAN_MESSAGES,
+            Language::French => FRENCH_MESSAGES,
+        }
+    }
+
+    fn keys(self) -> &'static [(&'static str, &'static str)] {
+        match self {
+            Language::English => &[],
+            Language::German => GERMAN_KEYS,
+            Language::French => FRENCH_KEYS,
+        }
+    }
+}
+
+/// Selects the language from the `language` setting, or from the locale
+/// when it is empty or "auto".
+pub fn set_language(setting: &str) {
+    let language = match setting.trim() {
+        "" | "auto" => Language::from_locale(),
+        tag => Language::parse(tag),
+    };
+    LANGUAGE.store(language as u8, Ordering::Relaxed);
+}
+
+/// `text` in the current language.
+pub fn tr(text: &'static str) -> &'static str {
+    lookup(Language::current().messages(), text).unwrap_or(text)
+}
+
+/// A combo with its key names in the current language, such as
+/// "Strg+Entf" for "Ctrl+Del" in German. Other text is left alone.
+pub fn localize_combo(text: &str) -> String {
+    localize_combo_in(Language::current(), text)
+}
+
+fn localize_combo_in(language: Language, text: &str) -> String {
+    let keys = language.keys();
+    if keys.is_empty() {
+        return text.to_string();
+    }
+    combo_parts(text)
+        .into_iter()
+        .map(|part| lookup(keys, part).unwrap_or(part))
+        .collect::<Vec<_>>()
+        .join("+")
+}
+
+/// One key or modifier name in the current language.
+pub fn localize_key(name: &str) -> &str {
+    lookup(Language::current().keys(), name).unwrap_or(name)
+}
+
+fn lookup(catalog: &'static [(&'static str, &'static str)], text: &str) -> Option<&'static str> {
+    catalog
+        .iter()
+        .find(|(english, _)| *english == text)
+        .map(|(_, translated)| *translated)
+}
+
+const GERMAN_KEYS: &[(&str, &str)] = &[
+    ("Ctrl", "Strg"),
+    ("Shift", "Umschalt"),
+    ("Del", "Entf"),
+    ("Ins", "Einfg"),
+    ("Home", "Pos1"),
+    ("End", "Ende"),
+    ("PgUp", "Bild↑"),
+    ("PgDn", "Bild↓"),
+    ("Space", "Leertaste"),
+    ("Enter", "Eingabe"),
+    ("Backspace", "Rücktaste"),
+    ("Caps", "Feststell"),
+    ("PrtSc", "Druck"),
+    ("Scroll", "Rollen"),
+    ("Left", "Links"),
+    ("Right", "Rechts"),
+    ("Up", "Hoch"),
+    ("Down", "Runter"),
+];
+
+const FRENCH_KEYS: &[(&str, &str)] = &[
+    ("Shift", "Maj"),
+    ("Del", "Suppr"),
+    ("Ins", "Inser"),
+    ("Home", "Début"),
+    ("End", "Fin"),
+    ("PgUp", "Pg préc"),
+    ("PgDn", "Pg suiv"),
+    ("Space", "Espace"),
+    ("Enter", "Entrée"),
+    ("Backspace", "Retour arrière"),
+    ("Esc", "Échap"),
+    ("Caps", "Verr Maj"),
+    ("PrtSc", "Impr écran"),
+    ("Scroll", "Arrêt défil"),
+    ("Num", "Verr Num"),
+    ("Left", "Gauche"),
+    ("Right", "Droite"),
+    ("Up", "Haut"),
+    ("Down", "Bas"),
+];
+
+const GERMAN_MESSAGES: &[(&str, &str)] = &[
+    // Status messages
+    ("Paused", "Pausiert"),
+    ("Resumed", "Fortgesetzt"),
+    ("Layout:", "Layout:"),
+    ("Presenter mode", "Präsentationsmodus"),
+    ("Presenter mode off", "Präsentationsmodus aus"),
+    ("Waiting for a keyboard…", "Warte auf eine Tastatur…"),
+    (
+        "No keyboard access: join the input group",
+        "Kein Tastaturzugriff: der Gruppe input beitreten",
+    ),
+    ("Caps Lock", "Feststelltaste"),
+    ("Num Lock", "Num-Taste"),
+    ("Caps Lock · Num Lock", "Feststelltaste · Num-Taste"),
+    ("Scroll Lock", "Rollen"),
+    ("ON", "EIN"),
+    ("OFF", "AUS"),
+    ("Recording failed", "Aufnahme fehlgeschlagen"),
+    ("Screenshot failed", "Bildschirmfoto fehlgeschlagen"),
+    ("Marker", "Markierung"),
+    ("Press this shortcut", "Dieses Tastenkürzel drücken"),
+    ("Round done:", "Runde geschafft:"),
+    ("Time for a break", "Zeit für eine Pause"),
+    ("Rest your hands and eyes.", "Gönn Händen und Augen etwas Ruhe."),
+    ("Quit Keyway Visualizer?", "Keyway Visualizer beenden?"),
+    (
+        "The overlay stops showing keys until it is started again.",
+        "Tasten werden erst nach einem Neustart wieder angezeigt.",
+    ),
+    ("Cancel", "Abbrechen"),
+    // Tray
+    ("Pause", "Pause"),
+    ("Resume", "Fortsetzen"),
+    ("Enable Drag", "Verschieben aktivieren"),
+    ("Disable Drag", "Verschieben deaktivieren"),
+    ("Presenter Mode", "Präsentationsmodus"),
+    ("Exit Presenter Mode", "Präsentationsmodus beenden"),
+    ("Devices", "Geräte"),
+    ("Muted", "Stummgeschaltet"),
+    ("Unmuted", "Wieder aktiv"),
+    ("Keeps failing", "Fällt immer wieder aus"),
+    ("Settings", "Einstellungen"),
+    ("Heatmap", "Heatmap"),
+    ("Rollover Test", "Rollover-Test"),
+    ("Clear Statistics", "Statistik löschen"),
+    ("Quit", "Beenden"),
+    ("Status:", "Status:"),
+    ("Running", "Aktiv"),
+    ("Hidden for this app", "Für diese App ausgeblendet"),
+    // Settings window
+    ("Keyway Visualizer Settings", "Keyway Visualizer – Einstellungen"),
+    ("Position", "Position"),
+    ("Margin", "Abstand"),
+    ("Max items", "Max. Einträge"),
+    ("TTL (ms)", "Anzeigedauer (ms)"),
+    ("Show mouse", "Maus anzeigen"),
+    ("Pause hotkey", "Tastenkürzel für Pause"),
+    ("Repeat coalesce (ms)", "Wiederholungen bündeln (ms)"),
+    ("Modifier grace (ms)", "Nachlauf der Modifikatoren (ms)"),
+    ("Drag mode", "Verschiebemodus"),
+    ("Custom X", "Eigenes X"),
+    ("Custom Y", "Eigenes Y"),
+    ("App filter", "App-Filter"),
+    ("Disabled apps", "Deaktivierte Apps"),
+    ("Pick window", "Fenster wählen"),
+    ("Theme", "Design"),
+    ("Palette", "Farbpalette"),
+    ("Display mode", "Anzeigemodus"),
+    ("Key separator", "Tastentrenner"),
+    ("Spaces", "Leerzeichen"),
+    ("Pause when locked", "Bei Bildschirmsperre pausieren"),
+    ("Screencast", "Bildschirmaufnahme"),
+    ("Privacy mask", "Datenschutzmaske"),
+    ("Mask secure input", "Sichere Eingaben maskieren"),
+    ("Secure input apps", "Apps mit sicherer Eingabe"),
+    ("Hide over fullscreen", "Bei Vollbild ausblenden"),
+    ("Filter indicator dot", "Hinweispunkt des Filters"),
+    ("Statistics", "Statistik"),
+    ("Key categories only", "Nur Tastenkategorien"),
+    ("Keep statistics (days)", "Statistik behalten (Tage)"),
+    ("Clear statistics", "Statistik löschen"),
+    ("Input devices", "Eingabegeräte"),
+    ("Fix input access...", "Eingabezugriff einrichten…"),
+    ("Apply", "Anwenden"),
+    ("Save", "Speichern"),
+    ("Close", "Schließen"),
+    ("Applied", "Übernommen"),
+    ("Saved", "Gespeichert"),
+    ("Statistics cleared", "Statistik gelöscht"),
+];
+
+const FRENCH_MESSAGES: &[(&str, &str)] = &[
+    // Status messages
+    ("Paused", "En pause"),
+    ("Resumed", "Reprise"),
+    ("Layout:", "Disposition :"),
+    ("Presenter mode", "Mode présentation"),
+    ("Presenter mode off", "Mode présentation désactivé"),
+    ("Waiting for a keyboard…", "En attente d'un clavier…"),
+    (
+        "No keyboard access: join the input group",
+        "Pas d'accès au clavier : rejoignez le groupe input",
+    ),
+    ("Caps Lock", "Verr Maj"),
+    ("Num Lock", "Verr Num"),
+    ("Caps Lock · Num Lock", "Verr Maj · Verr Num"),
+    ("Scroll Lock", "Arrêt défil"),
+    ("ON", "ACTIVÉ"),
+    ("OFF", "DÉSACTIVÉ"),
+    ("Recording failed", "Échec de l'enregistrement"),
+    ("Screenshot failed", "Échec de la capture d'écran"),
+    ("Marker", "Repère"),
+    ("Press this shortcut", "Appuyez sur ce raccourci"),
+    ("Round done:", "Tour terminé :"),
+    ("Time for a break", "C'est l'heure d'une pause"),
+    ("Rest your hands and eyes.", "Reposez vos mains et vos yeux."),
+    ("Quit Keyway Visualizer?", "Quitter Keyway Visualizer ?"),
+    (
+        "The overlay stops showing keys until it is started again.",
+        "Les touches ne s'affichent plus avant le prochain lancement.",
+    ),
+    ("Cancel", "Annuler"),
+    // Tray
+    ("Pause", "Pause"),
+    ("Resume", "Reprendre"),
+    ("Enable Drag", "Activer le déplacement"),
+    ("Disable Drag", "Désactiver le déplacement"),
+    ("Presenter Mode", "Mode présentation"),
+    ("Exit Presenter Mode", "Quitter le mode présentation"),
+    ("Devices", "Périphériques"),
+    ("Muted", "Ignoré"),
+    ("Unmuted", "Réactivé"),
+    ("Keeps failing", "Échoue sans cesse"),
+    ("Settings", "Paramètres"),
+    ("Heatmap", "Carte de chaleur"),
+    ("Rollover Test", "Test de rollover"),
+    ("Clear Statistics", "Effacer les statistiques"),
+    ("Quit", "Quitter"),
+    ("Status:", "État :"),
+    ("Running", "Actif"),
+    ("Hidden for this app", "Masqué pour cette application"),
+    // Settings window
+    ("Keyway Visualizer Settings", "Paramètres de Keyway Visualizer"),
+    ("Position", "Position"),
+    ("Margin", "Marge"),
+    ("Max items", "Éléments max."),
+    ("TTL (ms)", "Durée d'affichage (ms)"),
+    ("Show mouse", "Afficher la souris"),
+    ("Pause hotkey", "Raccourci de pause"),
+    ("Repeat coalesce (ms)", "Regrouper les répétitions (ms)"),
+    ("Modifier grace (ms)", "Délai des modificateurs (ms)"),
+    ("Drag mode", "Mode déplacement"),
+    ("Custom X", "X personnalisé"),
+    ("Custom Y", "Y personnalisé"),
+    ("App filter", "Filtre d'applications"),
+    ("Disabled apps", "Applications exclues"),
+    ("Pick window", "Choisir une fenêtre"),
+    ("Theme", "Thème"),
+    ("Palette", "Palette"),
+    ("Display mode", "Mode d'affichage"),
+    ("Key separator", "Séparateur de touches"),
+    ("Spaces", "Espaces"),
+    ("Pause when locked", "Pause au verrouillage"),
+    ("Screencast", "Partage d'écran"),
+    ("Privacy mask", "Masque de confidentialité"),
+    ("Mask secure input", "Masquer la saisie sécurisée"),
+    ("Secure input apps", "Applications à saisie sécurisée"),
+    ("Hide over fullscreen", "Masquer en plein écran"),
+    ("Filter indicator dot", "Point indicateur du filtre"),
+    ("Statistics", "Statistiques"),
+    ("Key categories only", "Catégories de touches uniquement"),
+    ("Keep statistics (days)", "Conserver les statistiques (jours)"),
+    ("Clear statistics", "Effacer les statistiques"),
+    ("Input devices", "Périphériques d'entrée"),
+    ("Fix input access...", "Réparer l'accès aux entrées…"),
+    ("Apply", "Appliquer"),
+    ("Save", "Enregistrer"),
+    ("Close", "Fermer"),
+    ("Applied", "Appliqué"),
+    ("Saved", "Enregistré"),
+    ("Statistics cleared", "Statistiques effacées"),
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn key_names_follow_the_language() {
+        assert_eq!(Language::parse("de_DE.UTF-8"), Language::German);
+        assert_eq!(Language::parse("pt_BR"), Language::English);
+        assert_eq!(localize_combo_in(Language::German, "Ctrl+Shift+Del"), "Strg+Umschalt+Entf");
+        assert_eq!(localize_combo_in(Language::French, "Ctrl++"), "Ctrl++");
+        assert_eq!(localize_combo_in(Language::German, "Reopen closed tab"), "Reopen closed tab");
+    }
+}
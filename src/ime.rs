@@ -0,0 +1,170 @@
+use anyhow::{anyhow, Context, Result};
+use async_channel::{Receiver, Sender};
+use dbus::arg::{RefArg, Variant};
+use dbus::blocking::Connection;
+use dbus::channel::Channel;
+use dbus::Message;
+use std::fs;
+use std::path::PathBuf;
+use std::thread;
+use std::time::Duration;
+use tracing::{debug, info};
+
+const FCITX_IFACE: &str = "org.fcitx.Fcitx.InputContext1";
+const IBUS_IFACE: &str = "org.freedesktop.IBus.InputContext";
+const CALL_TIMEOUT: Duration = Duration::from_secs(5);
+/// How often the watchers check whether the overlay still wants them.
+const STOP_CHECK: Duration = Duration::from_secs(1);
+
+/// What an input method told its client.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ImeEvent {
+    /// Whether there is preedit text, i.e. keys are being composed
+    Composing(bool),
+    /// Text the input method typed into the application
+    Commit(String),
+}
+
+/// `ime_text`: watches Fcitx5 on the session bus and IBus on its own bus
+/// for the text they commit. Each is optional; one that is not running (or
+/// does not let its signals be watched) leaves the raw keys showing.
+/// Dropping the receiver stops both.
+pub fn start_ime_monitor() -> Receiver<ImeEvent> {
+    let (tx, rx) = async_channel::bounded(64);
+    let fcitx_tx = tx.clone();
+    thread::spawn(move || match watch_fcitx(&fcitx_tx) {
+        Ok(()) => {}
+        Err(e) => info!("Not showing Fcitx5 text: {:#}", e),
+    });
+    thread::spawn(move || match watch_ibus(&tx) {
+        Ok(()) => {}
+        Err(e) => info!("Not showing IBus text: {:#}", e),
+    });
+    rx
+}
+
+/// Fcitx5 signals go from the daemon straight to each application, so
+/// they are only seen by monitoring the session bus.
+fn watch_fcitx(tx: &Sender<ImeEvent>) -> Result<()> {
+    let conn = Connection::new_session().context("Failed to connect to the session bus")?;
+    let rules: Vec<String> = ["CommitString", "UpdateFormattedPreedit"]
+        .iter()
+        .map(|member| format!("type='signal',interface='{}',member='{}'", FCITX_IFACE, member))
+        .collect();
+    let proxy = conn.with_proxy("org.freedesktop.DBus", "/org/freedesktop/DBus", CALL_TIMEOUT);
+    let () = proxy
+        .method_call("org.freedesktop.DBus.Monitoring", "BecomeMonitor", (rules, 0u32))
+        .context("The session bus does not allow monitoring")?;
+    info!("Watching Fcitx5 for committed text");
+    forward(conn.channel(), tx)
+}
+
+/// IBus runs its own bus; the address is in `IBUS_ADDRESS` or the file
+/// the daemon writes for the display.
+fn watch_ibus(tx: &Sender<ImeEvent>) -> Result<()> {
+    let address = ibus_address()?;
+    let mut channel =
+        Channel::open_private(&address).map_err(|e| anyhow!("Failed to connect to IBus: {}", e))?;
+    channel.register().map_err(|e| anyhow!("Failed to register with IBus: {}", e))?;
+    let conn = Connection::from(channel);
+    conn.add_match_no_cb(&format!("type='signal',interface='{}',eavesdrop='true'", IBUS_IFACE))
+        .context("IBus refused to share its signals")?;
+    info!("Watching IBus for committed text");
+    forward(conn.channel(), tx)
+}
+
+fn ibus_address() -> Result<String> {
+    if let Ok(address) = std::env::var("IBUS_ADDRESS") {
+        return Ok(address);
+    }
+    let dir = dirs::config_dir()
+        .map(|dir| dir.join("ibus").join("bus"))
+        .context("No config directory")?;
+    // One file per machine and display; the newest is the running daemon.
+    let newest: Option<PathBuf> = fs::read_dir(&dir)
+        .with_context(|| format!("IBus is not running ({:?} is missing)", dir))?
+        .flatten()
+        .max_by_key(|entry| entry.metadata().and_then(|meta| meta.modified()).ok())
+        .map(|entry| entry.path());
+    let path = newest.context("IBus is not running")?;
+    let text = fs::read_to_string(&path).with_context(|| format!("Failed to read {:?}", path))?;
+    text.lines()
+        .find_map(|line| line.strip_prefix("IBUS_ADDRESS="))
+        .map(str::to_string)
+        .with_context(|| format!("No address in {:?}", path))
+}
+
+fn forward(channel: &Channel, tx: &Sender<ImeEvent>) -> Result<()> {
+    while !tx.is_closed() {
+        channel
+            .read_write(Some(STOP_CHECK))
+            .map_err(|()| anyhow!("Lost the connection"))?;
+        while let Some(message) = channel.pop_message() {
+            if let Some(event) = ime_event(&message) {
+                debug!("Input method: {:?}", event);
+                let _ = tx.try_send(event);
+            }
+        }
+    }
+    Ok(())
+}
+
+fn ime_event(message: &Message) -> Option<ImeEvent> {
+    let interface = message.interface()?;
+    let member = message.member()?;
+    match (&*interface, &*member) {
+        (FCITX_IFACE, "CommitString") => message.read1::<String>().ok().map(ImeEvent::Commit),
+        (FCITX_IFACE, "UpdateFormattedPreedit") => {
+            let (preedit, _cursor): (Vec<(String, i32)>, i32) = message.read2().ok()?;
+            Some(ImeEvent::Composing(preedit.iter().any(|(text, _)| !text.is_empty())))
+        }
+        (IBUS_IFACE, "CommitText") => {
+            let text: Variant<Box<dyn RefArg>> = message.read1().ok()?;
+            ibus_text(&text).map(ImeEvent::Commit)
+        }
+        (IBUS_IFACE, "UpdatePreeditText") => {
+            let (text, _cursor, visible): (Variant<Box<dyn RefArg>>, u32, bool) =
+                message.read3().ok()?;
+            let empty = ibus_text(&text).is_none_or(|text| text.is_empty());
+            Some(ImeEvent::Composing(visible && !empty))
+        }
+        (IBUS_IFACE, "HidePreeditText") => Some(ImeEvent::Composing(false)),
+        _ => None,
+    }
+}
+
+/// The string in an `IBusText`, a struct of its type name, attachments, the
+/// text and its attributes.
+fn ibus_text(text: &Variant<Box<dyn RefArg>>) -> Option<String> {
+    let mut fields = text.0.as_iter()?;
+    fields.nth(2)?.as_str().map(str::to_string)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use dbus::arg::PropMap;
+
+    fn signal(interface: &str, member: &str) -> Message {
+        Message::new_signal("/org/freedesktop/IBus/InputContext_1", interface, member).unwrap()
+    }
+
+    fn text(text: &str) -> Variant<(String, PropMap, String, Variant<u32>)> {
+        Variant(("IBusText".to_string(), PropMap::new(), text.to_string(), Variant(0)))
+    }
+
+    #[test]
+    fn reads_both_input_methods() {
+        let commit = signal(FCITX_IFACE, "CommitString").append1("你好");
+        assert_eq!(ime_event(&commit), Some(ImeEvent::Commit("你好".to_string())));
+        let preedit = signal(FCITX_IFACE, "UpdateFormattedPreedit")
+            .append2(vec![("ni".to_string(), 0i32)], 2i32);
+        assert_eq!(ime_event(&preedit), Some(ImeEvent::Composing(true)));
+
+        let commit = signal(IBUS_IFACE, "CommitText").append1(text("日本"));
+        assert_eq!(ime_event(&commit), Some(ImeEvent::Commit("日本".to_string())));
+        let preedit = signal(IBUS_IFACE, "UpdatePreeditText").append3(text(""), 0u32, true);
+        assert_eq!(ime_event(&preedit), Some(ImeEvent::Composing(false)));
+        assert_eq!(ime_event(&signal(IBUS_IFACE, "ForwardKeyEvent")), None);
+    }
+}
@@ -1,28 +1,51 @@
 use anyhow::{Context, Result};
 use evdev::{Device, EventType, Key};
 use std::fs;
+use std::io::ErrorKind;
 use std::path::PathBuf;
 use tracing::{debug, info, warn};
 
-#[derive(Debug, Clone)]
-pub struct KeyboardDevice {
-    pub path: PathBuf,
-    pub name: String,
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DeviceKind {
+    Keyboard,
+    Mouse,
+}
+
+impl DeviceKind {
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Keyboard => "keyboard",
+            Self::Mouse => "mouse",
+        }
+    }
 }
 
-#[derive(Debug, Clone)]
-pub struct MouseDevice {
+/// An opened keyboard or mouse node.
+pub struct InputDevice {
     pub path: PathBuf,
     pub name: String,
+    pub kind: DeviceKind,
+    pub device: Device,
 }
 
-pub fn discover_keyboards() -> Result<Vec<KeyboardDevice>> {
-    let mut devices = Vec::new();
-    let input_dir = PathBuf::from("/dev/input");
+/// What one pass over `/dev/input` turned up.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DeviceScan {
+    pub keyboards: usize,
+    /// Nodes we were not allowed to open
+    pub denied: usize,
+}
 
+/// Opens each `/dev/input/event*` node once and hands keyboards and mice to
+/// `found` as soon as each is confirmed, so listening starts before slow
+/// nodes have been probed.
+pub fn scan_devices(mut found: impl FnMut(InputDevice)) -> Result<DeviceScan> {
+    let input_dir = PathBuf::from("/dev/input");
     let entries = fs::read_dir(&input_dir)
         .with_context(|| format!("Failed to read directory: {:?}", input_dir))?;
 
+    let mut scan = DeviceScan::default();
+    let mut mice = 0;
     for entry in entries.flatten() {
         let path = entry.path();
         let file_name = path
@@ -34,64 +57,72 @@ pub fn discover_keyboards() -> Result<Vec<KeyboardDevice>> {
             continue;
         }
 
-        match Device::open(&path) {
-            Ok(device) => {
-                if is_keyboard(&device) {
-                    let name = device.name().unwrap_or("Unknown Keyboard").to_string();
-                    info!("Found keyboard: {} at {:?}", name, path);
-                    devices.push(KeyboardDevice { path, name });
-                }
-            }
+        let device = match Device::open(&path) {
+            Ok(device) => device,
             Err(e) => {
+                if e.kind() == ErrorKind::PermissionDenied {
+                    scan.denied += 1;
+                }
                 debug!("Could not open {:?}: {}", path, e);
+                continue;
             }
-        }
+        };
+        let kind = if is_keyboard(&device) {
+            scan.keyboards += 1;
+            DeviceKind::Keyboard
+        } else if is_mouse(&device) {
+            mice += 1;
+            DeviceKind::Mouse
+        } else {
+            continue;
+        };
+
+        let name = device.name().unwrap_or("Unknown").to_string();
+        info!("Found {}: {} at {:?}", kind.name(), name, path);
+        found(InputDevice {
+            path,
+            name,
+            kind,
+            device,
+        });
     }
 
-    if devices.is_empty() {
+    if scan.keyboards == 0 {
         warn!("No keyboard devices found. Ensure you have permission to read /dev/input/event* (input group).");
     }
+    if mice == 0 {
+        debug!("No mouse devices found");
+    }
 
-    Ok(devices)
+    Ok(scan)
 }
 
-pub fn discover_mice() -> Result<Vec<MouseDevice>> {
-    let mut devices = Vec::new();
-    let input_dir = PathBuf::from("/dev/input");
-
-    let entries = fs::read_dir(&input_dir)
-        .with_context(|| format!("Failed to read directory: {:?}", input_dir))?;
-
-    for entry in entries.flatten() {
-        let path = entry.path();
-        let file_name = path
-            .file_name()
-            .and_then(|n| n.to_str())
-            .unwrap_or_default();
-
-        if !file_name.starts_with("event") {
-            continue;
-        }
-
-        match Device::open(&path) {
-            Ok(device) => {
-                if is_mouse(&device) {
-                    let name = device.name().unwrap_or("Unknown Mouse").to_string();
-                    info!("Found mouse: {} at {:?}", name, path);
-                    devices.push(MouseDevice { path, name });
-                }
-            }
-            Err(e) => {
-                debug!("Could not open {:?}: {}", path, e);
-            }
+/// What the device reports besides its keys, for `--list-devices`.
+pub fn capabilities(device: &Device) -> Vec<String> {
+    let mut found = Vec::new();
+    if let Some(keys) = device.supported_keys() {
+        let buttons = keys
+            .iter()
+            .filter(|key| format!("{:?}", key).starts_with("BTN_"))
+            .count();
+        found.push(format!("{} keys", keys.iter().count() - buttons));
+        if buttons > 0 {
+            found.push(format!("{} buttons", buttons));
         }
     }
-
-    if devices.is_empty() {
-        warn!("No mouse devices found. Ensure you have permission to read /dev/input/event* (input group).");
+    if let Some(leds) = device.supported_leds() {
+        found.push(format!("{} leds", leds.iter().count()));
     }
-
-    Ok(devices)
+    if device.supported_relative_axes().is_some() {
+        found.push("relative axes".to_string());
+    }
+    if device.supported_absolute_axes().is_some() {
+        found.push("absolute axes".to_string());
+    }
+    if device.supported_events().contains(EventType::REPEAT) {
+        found.push("repeat".to_string());
+    }
+    found
 }
 
 fn is_keyboard(device: &Device) -> bool {
@@ -0,0 +1,312 @@
+use crate::input::listener::{
+    send_event, CapturedDevices, InputEvent, InputListener, ListenerConfig, ListenerStatus,
+    EVENT_CHANNEL_CAPACITY,
+};
+use crate::input::key_from_name;
+use crate::input::sandbox;
+use anyhow::{anyhow, bail, Context, Result};
+use async_channel::Sender;
+use clap::Args;
+use nix::unistd::{getegid, geteuid, getgid, getuid};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::Path;
+use std::process::{Child, ChildStdin, ChildStdout, Command, Stdio};
+use std::sync::Arc;
+use std::thread;
+use tracing::{info, warn};
+
+/// Reads the input devices on behalf of an overlay, so only this process
+/// needs access to `/dev/input`. Events go to stdout, one per line; mutes
+/// come in on stdin, and the helper exits when the overlay closes it.
+#[derive(Args, Debug, Clone)]
+pub struct HelperArgs {
+    /// Also report mouse buttons
+    #[arg(long)]
+    pub mouse: bool,
+    /// Run without the Landlock and seccomp confinement (ignored by a setuid
+    /// or setgid copy)
+    #[arg(long)]
+    pub no_sandbox: bool,
+}
+
+/// One line between the helper and the overlay.
+#[derive(Debug, Clone)]
+enum Message {
+    Event(InputEvent),
+    Status(ListenerStatus),
+    /// Names of the devices being listened to, after one came or went
+    Devices(Vec<String>),
+}
+
+/// Whether this is a setuid or setgid copy, running with more than its
+/// caller may do.
+pub fn elevated() -> bool {
+    getegid() != getgid() || geteuid() != getuid()
+}
+
+/// The `listen-helper` side: runs the listener and writes what it reports.
+pub fn run(args: &HelperArgs) -> Result<()> {
+    // The whole helper only reads devices and its pipes, so every thread is
+    // confined: the listener's by the listener once it has made its stop
+    // socket, the rest by confining this one before they start. A setgid
+    // copy reads devices its caller cannot, so the caller does not get to
+    // lift the confinement.
+    let elevated = elevated();
+    if args.no_sandbox && elevated {
+        warn!("Ignoring --no-sandbox in a privileged listener helper");
+    }
+    let confine = !args.no_sandbox || elevated;
+    let (tx, rx) = async_channel::bounded(EVENT_CHANNEL_CAPACITY);
+    let (status_tx, status_rx) = async_channel::bounded(4);
+    let devices = CapturedDevices::default();
+    let listener = InputListener::new(
+        tx,
+        status_tx,
+        ListenerConfig {
+            all_keyboards: true,
+            include_mouse: args.mouse,
+            devices: devices.clone(),
+            sandbox: confine,
+        },
+    );
+    let _handle = listener.start()?;
+    if confine {
+        sandbox::confine_listener();
+    }
+
+    let muted = devices.clone();
+    thread::spawn(move || {
+        for line in io::stdin().lock().lines() {
+            let Ok(line) = line else {
+                break;
+            };
+            match line.split_once(' ') {
+                Some(("mute", name)) => muted.set_muted(name, true),
+                Some(("unmute", name)) => muted.set_muted(name, false),
+                _ => warn!("Ignoring helper command {:?}", line),
+            }
+        }
+        // The overlay is gone.
+        std::process::exit(0);
+    });
+
+    thread::spawn(move || {
+        while let Ok(status) = status_rx.recv_blocking() {
+            let message = match status {
+                ListenerStatus::DevicesChanged => Message::Devices(
+                    devices.list().into_iter().map(|(name, _)| name).collect(),
+                ),
+                status => Message::Status(status),
+            };
+            if write_message(&message).is_err() {
+                std::process::exit(0);
+            }
+        }
+    });
+
+    while let Ok(event) = rx.recv_blocking() {
+        if write_message(&Message::Event(event)).is_err() {
+            break;
+        }
+    }
+    Ok(())
+}
+
+fn write_message(message: &Message) -> io::Result<()> {
+    let mut out = io::stdout().lock();
+    writeln!(out, "{}", encode(message))?;
+    out.flush()
+}
+
+/// A running helper, seen from the overlay. Dropping it stops the helper.
+pub struct HelperProcess {
+    child: Child,
+    stdin: ChildStdin,
+}
+
+impl HelperProcess {
+    /// Starts `program listen-helper` and forwards what it reports as if a
+    /// listener in this process had sent it. Devices muted in `config` are
+    /// muted in the helper from the start.
+    pub fn spawn(
+        program: &Path,
+        sender: Sender<InputEvent>,
+        status: Sender<ListenerStatus>,
+        config: &ListenerConfig,
+    ) -> Result<Self> {
+        let mut command = Command::new(program);
+        command.arg("listen-helper");
+        if config.include_mouse {
+            command.arg("--mouse");
+        }
+        if !config.sandbox {
+            command.arg("--no-sandbox");
+        }
+        let mut child = command
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .with_context(|| format!("Failed to start listener helper {:?}", program))?;
+        info!("Reading input through listener helper {:?}", program);
+
+        let stdout = child.stdout.take().context("Helper has no stdout")?;
+        let stdin = child.stdin.take().context("Helper has no stdin")?;
+        let devices = config.devices.clone();
+        thread::Builder::new()
+            .name("listener-helper".to_string())
+            .spawn(move || forward(stdout, sender, status, devices))
+            .context("Failed to start listener helper reader")?;
+
+        let mut helper = Self { child, stdin };
+        for name in config.devices.muted() {
+            helper.set_muted(&name, true);
+        }
+        Ok(helper)
+    }
+
+    pub fn set_muted(&mut self, name: &str, muted: bool) {
+        let command = if muted { "mute" } else { "unmute" };
+        if let Err(e) = writeln!(self.stdin, "{} {}", command, name) {
+            warn!("Failed to tell the listener helper: {}", e);
+        }
+    }
+}
+
+impl Drop for HelperProcess {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+fn forward(
+    stdout: ChildStdout,
+    sender: Sender<InputEvent>,
+    status: Sender<ListenerStatus>,
+    devices: CapturedDevices,
+) {
+    for line in BufReader::new(stdout).lines() {
+        let Ok(line) = line else {
+            break;
+        };
+        match decode(&line) {
+            Ok(Message::Event(event)) => {
+                if send_event(&sender, event).is_err() {
+                    return;
+                }
+            }
+            Ok(Message::Status(update)) => {
+                let _ = status.send_blocking(update);
+            }
+            Ok(Message::Devices(names)) => {
+                devices.set_listening(names);
+                let _ = status.send_blocking(ListenerStatus::DevicesChanged);
+            }
+            Err(e) => warn!("Ignoring listener helper line {:?}: {:#}", line, e),
+        }
+    }
+    warn!("Listener helper stopped; no more input events");
+    devices.set_listening(Vec::new());
+    let _ = status.send_blocking(ListenerStatus::DevicesChanged);
+}
+
+/// `press KEY_A`, `release KEY_A`, `repeat KEY_A`, `click BTN_LEFT`,
+/// `unclick`, `drag BTN_LEFT 120 -40`, `device <name>`, `status <state>`,
+/// or `devices` and the names, tab separated.
+fn encode(message: &Message) -> String {
+    match message {
+        Message::Event(event) => match event {
+            InputEvent::KeyPressed(key) => format!("press {:?}", key),
+            InputEvent::KeyReleased(key) => format!("release {:?}", key),
+            InputEvent::KeyRepeat(key) => format!("repeat {:?}", key),
+            InputEvent::MouseButtonPressed(key) => format!("click {:?}", key),
+            InputEvent::MouseButtonReleased => "unclick".to_string(),
+            InputEvent::MouseDrag { button, dx, dy } => format!("drag {:?} {} {}", button, dx, dy),
+            InputEvent::Device(name) => format!("device {}", name),
+        },
+        Message::Status(status) => match status {
+            ListenerStatus::NoKeyboards { denied: true } => "status no-access".to_string(),
+            ListenerStatus::NoKeyboards { denied: false } => "status no-keyboards".to_string(),
+            ListenerStatus::KeyboardsFound => "status keyboards-found".to_string(),
+            ListenerStatus::DevicesChanged => "status devices-changed".to_string(),
+        },
+        Message::Devices(names) => {
+            let mut line = "devices".to_string();
+            for name in names {
+                line.push('\t');
+                line.push_str(name);
+            }
+            line
+        }
+    }
+}
+
+fn decode(line: &str) -> Result<Message> {
+    if let Some(names) = line.strip_prefix("devices") {
+        return Ok(Message::Devices(
+            names.split('\t').filter(|name| !name.is_empty()).map(str::to_string).collect(),
+        ));
+    }
+    let (action, args) = line.split_once(' ').unwrap_or((line, ""));
+    let event = match action {
+        "press" => InputEvent::KeyPressed(key_from_name(args)?),
+        "release" => InputEvent::KeyReleased(key_from_name(args)?),
+        "repeat" => InputEvent::KeyRepeat(key_from_name(args)?),
+        "click" => InputEvent::MouseButtonPressed(key_from_name(args)?),
+        "unclick" => InputEvent::MouseButtonReleased,
+        "drag" => {
+            let [button, dx, dy] = args.split(' ').collect::<Vec<_>>()[..] else {
+                bail!("expected a button and the distance");
+            };
+            InputEvent::MouseDrag {
+                button: key_from_name(button)?,
+                dx: dx.parse()?,
+                dy: dy.parse()?,
+            }
+        }
+        "device" => InputEvent::Device(Arc::from(args)),
+        "status" => {
+            return Ok(Message::Status(match args {
+                "no-access" => ListenerStatus::NoKeyboards { denied: true },
+                "no-keyboards" => ListenerStatus::NoKeyboards { denied: false },
+                "keyboards-found" => ListenerStatus::KeyboardsFound,
+                "devices-changed" => ListenerStatus::DevicesChanged,
+                other => return Err(anyhow!("unknown status {:?}", other)),
+            }))
+        }
+        other => bail!("unknown message {:?}", other),
+    };
+    Ok(Message::Event(event))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use evdev::Key;
+
+    #[test]
+    fn messages_survive_the_pipe() {
+        let messages = [
+            Message::Event(InputEvent::KeyPressed(Key::KEY_LEFTCTRL)),
+            Message::Event(InputEvent::KeyRepeat(Key::KEY_J)),
+            Message::Event(InputEvent::MouseButtonPressed(Key::BTN_LEFT)),
+            Message::Event(InputEvent::MouseButtonReleased),
+            Message::Event(InputEvent::MouseDrag {
+                button: Key::BTN_RIGHT,
+                dx: -120,
+                dy: 40,
+            }),
+            Message::Event(InputEvent::Device(Arc::from("Keychron K2 Keyboard"))),
+            Message::Status(ListenerStatus::NoKeyboards { denied: true }),
+            Message::Devices(vec!["AT Translated Set 2".to_string(), "Macro Pad".to_string()]),
+            Message::Devices(Vec::new()),
+        ];
+        for message in messages {
+            let line = encode(&message);
+            let decoded = decode(&line).unwrap();
+            assert_eq!(format!("{:?}", decoded), format!("{:?}", message), "{}", line);
+        }
+        assert!(decode("press NOPE").is_err());
+        assert!(decode("status sleepy").is_err());
+    }
+}
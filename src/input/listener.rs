@@ -1,16 +1,32 @@
-use crate::input::device::{discover_keyboards, discover_mice, KeyboardDevice, MouseDevice};
+use crate::metrics;
+use crate::input::device::{scan_devices, DeviceKind, InputDevice};
+use crate::input::helper::HelperProcess;
+use crate::input::sandbox;
+use crate::input::watchdog::{Heartbeat, Heartbeats, HEARTBEAT_INTERVAL};
+use crate::input::x11;
 use anyhow::{Context, Result};
 use async_channel::{Sender, TrySendError};
-use evdev::{Device, InputEventKind, Key};
+use evdev::{Device, InputEventKind, Key, RelativeAxisType};
 use nix::poll::{poll, PollFd, PollFlags, PollTimeout};
-use std::collections::HashSet;
-use std::os::fd::{AsRawFd, BorrowedFd};
-use std::path::PathBuf;
+use std::collections::{BTreeMap, HashSet};
+use std::os::fd::{AsFd, AsRawFd, BorrowedFd};
+use std::os::unix::net::UnixStream;
+use std::path::{Path, PathBuf};
 use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::Arc;
+use std::sync::{Arc, Mutex, PoisonError};
 use std::thread;
+use std::time::Duration;
 use tracing::{error, info, trace, warn};
 
+/// How long discovery waits before looking again while no keyboard is found.
+pub const DISCOVERY_RETRY: Duration = Duration::from_secs(5);
+/// Input events queued between the listener threads and the overlay; more
+/// are dropped (and counted) rather than blocking the devices.
+pub const EVENT_CHANNEL_CAPACITY: usize = 256;
+/// How far, in mouse units, the pointer has to travel with a button held
+/// for the release to count as a drag rather than a click.
+pub const DRAG_MIN_DISTANCE: i32 = 24;
+
 #[derive(Debug, Clone)]
 pub enum InputEvent {
     KeyPressed(Key),
@@ -18,12 +34,33 @@ pub enum InputEvent {
     KeyRepeat(Key),
     MouseButtonPressed(Key),
     MouseButtonReleased,
+    /// Sent just before `MouseButtonReleased` when the pointer moved at
+    /// least `DRAG_MIN_DISTANCE` while `button` was held; `dx` and `dy` are
+    /// in mouse units, `dy` growing downwards
+    MouseDrag { button: Key, dx: i32, dy: i32 },
+    /// The presses that follow come from the device with this name, until
+    /// the next `Device`
+    Device(Arc<str>),
+}
+
+/// Progress of device discovery, for telling the user why nothing shows.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ListenerStatus {
+    /// No keyboard yet; discovery tries again after `DISCOVERY_RETRY`.
+    /// `denied` is set when some devices could not be opened.
+    NoKeyboards { denied: bool },
+    KeyboardsFound,
+    /// A device was opened or went away; `CapturedDevices::list` has them.
+    DevicesChanged,
 }
 
 #[derive(Debug, Clone)]
 pub struct ListenerConfig {
     pub all_keyboards: bool,
     pub include_mouse: bool,
+    pub devices: CapturedDevices,
+    /// Confine the listener with `sandbox::confine_listener`
+    pub sandbox: bool,
 }
 
 impl Default for ListenerConfig {
@@ -31,168 +68,465 @@ impl Default for ListenerConfig {
         Self {
             all_keyboards: true,
             include_mouse: true,
+            devices: CapturedDevices::default(),
+            sandbox: true,
         }
     }
 }
 
-pub struct ListenerHandle {
-    running: Arc<AtomicBool>,
+/// The devices being listened to by name, and which of them are muted.
+/// Shared by the listener threads and the overlay, and kept by the overlay
+/// across listener restarts so a muted device stays muted when it is
+/// unplugged or the system sleeps.
+#[derive(Debug, Clone, Default)]
+pub struct CapturedDevices(Arc<Mutex<DeviceList>>);
+
+#[derive(Debug, Default)]
+struct DeviceList {
+    /// Listener threads by device name; identical keyboards share a name
+    listening: BTreeMap<String, usize>,
+    muted: HashSet<String>,
 }
 
-impl Drop for ListenerHandle {
-    fn drop(&mut self) {
-        self.running.store(false, Ordering::SeqCst);
+impl CapturedDevices {
+    /// Names of the devices being listened to, sorted, and whether each is
+    /// muted.
+    pub fn list(&self) -> Vec<(String, bool)> {
+        let devices = self.lock();
+        devices
+            .listening
+            .keys()
+            .map(|name| (name.clone(), devices.muted.contains(name)))
+            .collect()
+    }
+
+    pub fn is_muted(&self, name: &str) -> bool {
+        self.lock().muted.contains(name)
+    }
+
+    /// Mutes or unmutes every device called `name`; returns whether they
+    /// are muted now.
+    pub fn toggle_muted(&self, name: &str) -> bool {
+        let mut devices = self.lock();
+        if devices.muted.remove(name) {
+            return false;
+        }
+        devices.muted.insert(name.to_string());
+        true
+    }
+
+    pub fn set_muted(&self, name: &str, muted: bool) {
+        let mut devices = self.lock();
+        match muted {
+            true => devices.muted.insert(name.to_string()),
+            false => devices.muted.remove(name),
+        };
+    }
+
+    /// Names of the muted devices, whether or not they are plugged in.
+    pub fn muted(&self) -> Vec<String> {
+        self.lock().muted.iter().cloned().collect()
+    }
+
+    /// Replaces the devices being listened to, as reported by a listener
+    /// helper process.
+    pub fn set_listening(&self, names: Vec<String>) {
+        self.lock().listening = names.into_iter().map(|name| (name, 1)).collect();
+    }
+
+    fn add(&self, name: &str) {
+        *self.lock().listening.entry(name.to_string()).or_default() += 1;
+    }
+
+    fn remove(&self, name: &str) {
+        let mut devices = self.lock();
+        if let Some(count) = devices.listening.get_mut(name) {
+            *count -= 1;
+            if *count == 0 {
+                devices.listening.remove(name);
+            }
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, DeviceList> {
+        self.0.lock().unwrap_or_else(PoisonError::into_inner)
     }
 }
 
-pub struct InputListener {
-    sender: Sender<InputEvent>,
-    running: Arc<AtomicBool>,
-    config: ListenerConfig,
+impl ListenerConfig {
+    /// Whether a device of `kind` is listened to once `keyboards` keyboards
+    /// already are, and if so whether its mouse buttons are reported.
+    pub fn captures(&self, kind: DeviceKind, keyboards: usize) -> Option<bool> {
+        match kind {
+            DeviceKind::Keyboard if keyboards > 0 && !self.all_keyboards => None,
+            DeviceKind::Keyboard => Some(self.include_mouse),
+            DeviceKind::Mouse => self.include_mouse.then_some(true),
+        }
+    }
 }
 
-#[derive(Clone)]
-struct ListenerDevice {
-    path: PathBuf,
-    name: String,
-    kind: &'static str,
-    include_mouse_buttons: bool,
+pub enum ListenerHandle {
+    Threads {
+        running: Arc<AtomicBool>,
+        /// Closed on drop, which wakes the device threads blocked in `poll`
+        _stop: UnixStream,
+        heartbeats: Heartbeats,
+    },
+    /// Devices are read by a `listen-helper` process, killed on drop
+    Helper(HelperProcess),
 }
 
-impl ListenerDevice {
-    fn keyboard(device: KeyboardDevice, include_mouse_buttons: bool) -> Self {
-        Self {
-            path: device.path,
-            name: device.name,
-            kind: "keyboard",
-            include_mouse_buttons,
+impl ListenerHandle {
+    /// Passes a mute on to a helper process; listener threads in this
+    /// process see it in `CapturedDevices` by themselves.
+    pub fn sync_muted(&mut self, name: &str, muted: bool) {
+        if let ListenerHandle::Helper(helper) = self {
+            helper.set_muted(name, muted);
         }
     }
 
-    fn mouse(device: MouseDevice) -> Self {
-        Self {
-            path: device.path,
-            name: device.name,
-            kind: "mouse",
-            include_mouse_buttons: true,
+    /// The listener threads' heartbeats, for the watchdog. Threads in a
+    /// helper process are not watched.
+    pub fn heartbeats(&self) -> Option<&Heartbeats> {
+        match self {
+            ListenerHandle::Threads { heartbeats, .. } => Some(heartbeats),
+            ListenerHandle::Helper(_) => None,
         }
     }
+}
 
-    fn open(&self) -> Result<Device> {
-        Device::open(&self.path).with_context(|| format!("Failed to open device: {:?}", self.path))
+impl Drop for ListenerHandle {
+    fn drop(&mut self) {
+        if let ListenerHandle::Threads { running, .. } = self {
+            running.store(false, Ordering::SeqCst);
+        }
     }
 }
 
+pub struct InputListener {
+    sender: Sender<InputEvent>,
+    status: Sender<ListenerStatus>,
+    running: Arc<AtomicBool>,
+    config: ListenerConfig,
+}
+
 impl InputListener {
     #[must_use]
-    pub fn new(sender: Sender<InputEvent>, config: ListenerConfig) -> Self {
+    pub fn new(
+        sender: Sender<InputEvent>,
+        status: Sender<ListenerStatus>,
+        config: ListenerConfig,
+    ) -> Self {
         Self {
             sender,
+            status,
             running: Arc::new(AtomicBool::new(false)),
             config,
         }
     }
 
+    /// Returns right away; devices are found on a background thread and
+    /// each starts listening as soon as it is confirmed, so opening every
+    /// `/dev/input` node does not hold up the overlay. Without a keyboard
+    /// (udev still settling, permissions being fixed) discovery keeps
+    /// retrying until one shows up or the handle is dropped.
     pub fn start(&self) -> Result<ListenerHandle> {
-        let keyboards = discover_keyboards()?;
-        if keyboards.is_empty() {
-            anyhow::bail!(
-                "No keyboard devices found. Ensure you are in the 'input' group or have permission for /dev/input/event*"
-            );
-        }
-
-        let devices_to_use: Vec<KeyboardDevice> = if self.config.all_keyboards {
-            keyboards
-        } else {
-            keyboards.into_iter().take(1).collect()
-        };
+        self.running.store(true, Ordering::SeqCst);
+        let (stop, stop_watch) = UnixStream::pair().context("Failed to create stop socket")?;
+
+        let sender = self.sender.clone();
+        let status = self.status.clone();
+        let running = Arc::clone(&self.running);
+        let config = self.config.clone();
+        let heartbeats = Heartbeats::default();
+        let device_heartbeats = heartbeats.clone();
+        thread::Builder::new()
+            .name("input-discovery".to_string())
+            .spawn(move || {
+                discover(config, sender, status, running, stop_watch, device_heartbeats)
+            })
+            .context("Failed to start device discovery")?;
+
+        Ok(ListenerHandle::Threads {
+            running: self.running.clone(),
+            _stop: stop,
+            heartbeats,
+        })
+    }
 
-        let mut devices: Vec<ListenerDevice> = devices_to_use
-            .into_iter()
-            .map(|d| ListenerDevice::keyboard(d, self.config.include_mouse))
-            .collect();
-
-        if self.config.include_mouse {
-            match discover_mice() {
-                Ok(mice) => {
-                    let keyboard_paths: HashSet<PathBuf> = devices
-                        .iter()
-                        .map(|d| d.path.clone())
-                        .collect();
-
-                    devices.extend(
-                        mice.into_iter()
-                            .filter(|m| !keyboard_paths.contains(&m.path))
-                            .map(ListenerDevice::mouse),
-                    );
+    /// Like `start`, but keys and buttons come from the X server (see
+    /// `x11::listen`) instead of `/dev/input`.
+    pub fn start_x11(&self) -> Result<ListenerHandle> {
+        self.running.store(true, Ordering::SeqCst);
+        let (stop, stop_watch) = UnixStream::pair().context("Failed to create stop socket")?;
+
+        let sender = self.sender.clone();
+        let status = self.status.clone();
+        let running = Arc::clone(&self.running);
+        let config = self.config.clone();
+        let heartbeats = Heartbeats::default();
+        let heartbeat = heartbeats.register("x11", "X11 input");
+        thread::Builder::new()
+            .name("x11-input".to_string())
+            .spawn(move || {
+                match x11::listen(config, sender, status, running, stop_watch, &heartbeat) {
+                    Ok(()) => heartbeat.finish(),
+                    Err(e) => {
+                        error!("X11 input listener stopped: {:#}", e);
+                        heartbeat.fail();
+                    }
                 }
-                Err(e) => warn!("Failed to discover mouse devices: {}", e),
-            }
-        }
+            })
+            .context("Failed to start the X11 input listener")?;
 
-        self.running.store(true, Ordering::SeqCst);
+        Ok(ListenerHandle::Threads {
+            running: self.running.clone(),
+            _stop: stop,
+            heartbeats,
+        })
+    }
+
+    /// Like `start`, but the devices are read by `program listen-helper`,
+    /// which needs access to `/dev/input` while this process does not.
+    pub fn start_helper(&self, program: &Path) -> Result<ListenerHandle> {
+        let helper = HelperProcess::spawn(
+            program,
+            self.sender.clone(),
+            self.status.clone(),
+            &self.config,
+        )?;
+        Ok(ListenerHandle::Helper(helper))
+    }
+}
 
-        for device in devices {
-            let sender = self.sender.clone();
-            let running = Arc::clone(&self.running);
+/// The device the last press came from, shared by the listener threads so a
+/// press from a different device is sent right after its `Device` event.
+#[derive(Clone, Default)]
+pub(super) struct LastDevice(Arc<Mutex<Option<Arc<str>>>>);
+
+impl LastDevice {
+    pub(super) fn send_press(
+        &self,
+        sender: &Sender<InputEvent>,
+        name: &Arc<str>,
+        press: InputEvent,
+    ) -> Result<()> {
+        let mut last = self.0.lock().unwrap_or_else(PoisonError::into_inner);
+        if last.as_ref() != Some(name) {
+            send_event(sender, InputEvent::Device(Arc::clone(name)))?;
+            *last = Some(Arc::clone(name));
+        }
+        send_event(sender, press)
+    }
+}
 
+/// Scans until a keyboard turns up, starting a listener thread per device.
+fn discover(
+    config: ListenerConfig,
+    sender: Sender<InputEvent>,
+    status: Sender<ListenerStatus>,
+    running: Arc<AtomicBool>,
+    stop_watch: UnixStream,
+    heartbeats: Heartbeats,
+) {
+    // Before any device is opened; the device threads inherit it.
+    if config.sandbox {
+        sandbox::confine_listener();
+    }
+    let mut keyboards = 0;
+    let last_device = LastDevice::default();
+    // Devices found by an earlier pass are already being listened to.
+    let mut listening: HashSet<PathBuf> = HashSet::new();
+    loop {
+        let result = scan_devices(|found| {
+            if !running.load(Ordering::SeqCst) || listening.contains(&found.path) {
+                return;
+            }
+            let Some(include_mouse_buttons) = config.captures(found.kind, keyboards) else {
+                return;
+            };
+            if found.kind == DeviceKind::Keyboard {
+                keyboards += 1;
+            }
+            let stop_watch = match stop_watch.try_clone() {
+                Ok(stop_watch) => stop_watch,
+                Err(e) => {
+                    error!("Failed to clone stop socket: {}", e);
+                    return;
+                }
+            };
+
+            listening.insert(found.path.clone());
+            let sender = sender.clone();
+            let status = status.clone();
+            let devices = config.devices.clone();
+            let running = Arc::clone(&running);
+            let last_device = last_device.clone();
+            let heartbeat = heartbeats.register(&found.path.to_string_lossy(), &found.name);
             thread::spawn(move || {
-                if let Err(e) = listen_device(device, sender, running) {
-                    error!("Input listener error: {}", e);
+                match listen_device(
+                    found,
+                    include_mouse_buttons,
+                    (sender, status),
+                    (last_device, devices),
+                    running,
+                    stop_watch,
+                    &heartbeat,
+                ) {
+                    Ok(()) => heartbeat.finish(),
+                    Err(e) => {
+                        error!("Input listener error: {:#}", e);
+                        heartbeat.fail();
+                    }
                 }
             });
+        });
+
+        let denied = match result {
+            Ok(scan) => scan.denied > 0,
+            Err(e) => {
+                error!("Device discovery failed: {:#}", e);
+                true
+            }
+        };
+        if keyboards > 0 {
+            let _ = status.try_send(ListenerStatus::KeyboardsFound);
+            return;
         }
+        let _ = status.try_send(ListenerStatus::NoKeyboards { denied });
 
-        Ok(ListenerHandle {
-            running: self.running.clone(),
-        })
+        // Sleep until the next attempt, or stop when the handle is dropped.
+        let mut poll_fds = [PollFd::new(stop_watch.as_fd(), PollFlags::POLLIN)];
+        let timeout = PollTimeout::try_from(DISCOVERY_RETRY).unwrap_or(PollTimeout::MAX);
+        if !matches!(poll(&mut poll_fds, timeout), Ok(0)) || !running.load(Ordering::SeqCst) {
+            return;
+        }
+        info!("Looking for keyboards again");
     }
 }
 
-fn listen_device(device_info: ListenerDevice, sender: Sender<InputEvent>, running: Arc<AtomicBool>) -> Result<()> {
-    let mut device = device_info.open()?;
-    info!("Listening to {}: {}", device_info.kind, device_info.name);
+const DEVICE_GONE: PollFlags = PollFlags::POLLERR
+    .union(PollFlags::POLLHUP)
+    .union(PollFlags::POLLNVAL);
+
+/// Blocks in `poll` until the device has events or the handle is dropped,
+/// waking only every `HEARTBEAT_INTERVAL` to beat for the watchdog while the
+/// keyboard is idle.
+fn listen_device(
+    found: InputDevice,
+    include_mouse_buttons: bool,
+    (sender, status): (Sender<InputEvent>, Sender<ListenerStatus>),
+    (last_device, devices): (LastDevice, CapturedDevices),
+    running: Arc<AtomicBool>,
+    stop_watch: UnixStream,
+    heartbeat: &Heartbeat,
+) -> Result<()> {
+    let InputDevice {
+        path,
+        name,
+        kind,
+        mut device,
+    } = found;
+    let _guard = metrics::track_listener();
+    info!("Listening to {}: {} at {:?}", kind.name(), name, path);
 
     let raw_fd = device.as_raw_fd();
     let borrowed_fd = unsafe { BorrowedFd::borrow_raw(raw_fd) };
-    let mut poll_fds = [PollFd::new(borrowed_fd, PollFlags::POLLIN)];
+    let mut poll_fds = [
+        PollFd::new(borrowed_fd, PollFlags::POLLIN),
+        PollFd::new(stop_watch.as_fd(), PollFlags::POLLIN),
+    ];
 
     let mut pressed_keys: HashSet<Key> = HashSet::new();
+    let mut drag: Option<Drag> = None;
+    let source: Arc<str> = Arc::from(name.as_str());
+    devices.add(&name);
+    let _ = status.try_send(ListenerStatus::DevicesChanged);
 
+    let timeout = PollTimeout::try_from(HEARTBEAT_INTERVAL).unwrap_or(PollTimeout::MAX);
+    let mut result = Ok(());
     while running.load(Ordering::SeqCst) {
-        let poll_result = poll(&mut poll_fds, PollTimeout::from(100_u16));
+        heartbeat.beat();
+        let poll_result = poll(&mut poll_fds, timeout);
 
         match poll_result {
+            Ok(_) if poll_fds[0].revents().is_none_or(|events| events.is_empty()) => {}
+            Ok(_) if poll_fds[0].revents().is_some_and(|events| events.intersects(DEVICE_GONE)) => {
+                // Unplugged, or invalidated by a suspend; a later discovery
+                // opens it again.
+                info!("{} {} went away", kind.name(), name);
+                break;
+            }
             Ok(_) => {
-                if let Err(e) = process_events(&mut device, &sender, device_info.include_mouse_buttons, &mut pressed_keys) {
+                if let Err(e) = process_events(
+                    &mut device,
+                    &sender,
+                    (&last_device, &source),
+                    include_mouse_buttons,
+                    devices.is_muted(&name),
+                    &mut pressed_keys,
+                    &mut drag,
+                ) {
                     if e.to_string().contains("Channel closed") {
-                        info!("Channel closed, stopping listener for {}", device_info.name);
+                        info!("Channel closed, stopping listener for {}", name);
                         break;
                     }
                     warn!("Error processing events: {}", e);
                 }
             }
             Err(e) => {
-                error!("Poll error: {}", e);
+                result = Err(e).context("Poll error");
                 break;
             }
         }
     }
 
-    info!("Stopped listening to {}: {}", device_info.kind, device_info.name);
-    Ok(())
+    // Keys held when the device vanished never report a release.
+    for key in pressed_keys.drain() {
+        let _ = send_event(&sender, InputEvent::KeyReleased(key));
+    }
+    devices.remove(&name);
+    let _ = status.try_send(ListenerStatus::DevicesChanged);
+    info!("Stopped listening to {}: {}", kind.name(), name);
+    result
+}
+
+/// A mouse button held down, and how far the pointer moved since.
+struct Drag {
+    button: Key,
+    dx: i32,
+    dy: i32,
 }
 
+impl Drag {
+    fn moved_far_enough(&self) -> bool {
+        let (dx, dy) = (i64::from(self.dx), i64::from(self.dy));
+        dx * dx + dy * dy >= i64::from(DRAG_MIN_DISTANCE).pow(2)
+    }
+}
+
+/// A `muted` device only lets go of what it pressed before it was muted.
 fn process_events(
     device: &mut Device,
     sender: &Sender<InputEvent>,
+    (last_device, source): (&LastDevice, &Arc<str>),
     include_mouse_buttons: bool,
+    muted: bool,
     pressed_keys: &mut HashSet<Key>,
+    drag: &mut Option<Drag>,
 ) -> Result<()> {
     let events = device.fetch_events().context("Failed to fetch events")?;
     let mut activity = false;
 
     for event in events {
+        if let (InputEventKind::RelAxis(axis), Some(drag)) = (event.kind(), drag.as_mut()) {
+            match axis {
+                RelativeAxisType::REL_X => drag.dx = drag.dx.saturating_add(event.value()),
+                RelativeAxisType::REL_Y => drag.dy = drag.dy.saturating_add(event.value()),
+                _ => {}
+            }
+            continue;
+        }
         if let InputEventKind::Key(key) = event.kind() {
             let value = event.value();
 
@@ -201,14 +535,30 @@ fn process_events(
                     continue;
                 }
 
-                let mouse_event = match value {
-                    1 => Some(InputEvent::MouseButtonPressed(key)),
-                    0 => Some(InputEvent::MouseButtonReleased),
-                    _ => None,
-                };
-
-                if let Some(mouse_event) = mouse_event {
-                    send_event(sender, mouse_event)?;
+                match value {
+                    1 if muted => {}
+                    1 => {
+                        metrics::note_press(event.timestamp());
+                        if drag.is_none() {
+                            *drag = Some(Drag {
+                                button: key,
+                                dx: 0,
+                                dy: 0,
+                            });
+                        }
+                        let press = InputEvent::MouseButtonPressed(key);
+                        last_device.send_press(sender, source, press)?;
+                    }
+                    0 => {
+                        if let Some(held) = drag.take_if(|held| held.button == key) {
+                            if held.moved_far_enough() {
+                                let Drag { button, dx, dy } = held;
+                                send_event(sender, InputEvent::MouseDrag { button, dx, dy })?;
+                            }
+                        }
+                        send_event(sender, InputEvent::MouseButtonReleased)?;
+                    }
+                    _ => {}
                 }
 
                 continue;
@@ -216,14 +566,19 @@ fn process_events(
 
             activity = true;
             let key_event = match value {
+                1 | 2 if muted => continue,
                 1 => {
                     trace!("Key pressed: {:?}", key);
+                    metrics::note_press(event.timestamp());
                     pressed_keys.insert(key);
-                    InputEvent::KeyPressed(key)
+                    last_device.send_press(sender, source, InputEvent::KeyPressed(key))?;
+                    continue;
                 }
                 0 => {
                     trace!("Key released: {:?}", key);
-                    pressed_keys.remove(&key);
+                    if !pressed_keys.remove(&key) && muted {
+                        continue;
+                    }
                     InputEvent::KeyReleased(key)
                 }
                 2 => {
@@ -255,12 +610,15 @@ fn process_events(
     Ok(())
 }
 
-fn send_event(sender: &Sender<InputEvent>, event: InputEvent) -> Result<()> {
-    if let Err(e) = sender.try_send(event) {
-        match e {
-            TrySendError::Full(_) => warn!("Channel full, dropping event"),
-            TrySendError::Closed(_) => return Err(anyhow::anyhow!("Channel closed")),
+/// Queues `event` without blocking, counting it as received or dropped.
+pub fn send_event(sender: &Sender<InputEvent>, event: InputEvent) -> Result<()> {
+    match sender.try_send(event) {
+        Ok(()) => metrics::inc(&metrics::EVENTS_RECEIVED),
+        Err(TrySendError::Full(_)) => {
+            metrics::inc(&metrics::EVENTS_DROPPED);
+            warn!("Channel full, dropping event");
         }
+        Err(TrySendError::Closed(_)) => return Err(anyhow::anyhow!("Channel closed")),
     }
     Ok(())
 }
@@ -268,3 +626,28 @@ fn send_event(sender: &Sender<InputEvent>, event: InputEvent) -> Result<()> {
 fn is_mouse_button(key: Key) -> bool {
     matches!(key, Key::BTN_LEFT | Key::BTN_RIGHT | Key::BTN_MIDDLE)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mutes_outlive_the_device_going_away() {
+        let devices = CapturedDevices::default();
+        devices.add("KVM Keyboard");
+        devices.add("KVM Keyboard");
+        devices.add("Laptop Keyboard");
+        assert!(devices.toggle_muted("KVM Keyboard"));
+        assert_eq!(
+            devices.list(),
+            vec![("KVM Keyboard".to_string(), true), ("Laptop Keyboard".to_string(), false)]
+        );
+
+        devices.remove("KVM Keyboard");
+        devices.remove("KVM Keyboard");
+        assert_eq!(devices.list().len(), 1);
+        devices.add("KVM Keyboard");
+        assert!(devices.is_muted("KVM Keyboard"));
+        assert!(!devices.toggle_muted("KVM Keyboard"));
+    }
+}
@@ -1,4 +1,28 @@
 pub mod device;
+pub mod helper;
 pub mod listener;
+pub mod sandbox;
+pub mod stdin;
+pub mod watchdog;
+pub mod x11;
 
-pub use listener::{InputEvent, InputListener, ListenerConfig, ListenerHandle};
+use anyhow::{anyhow, Result};
+use evdev::Key;
+use std::str::FromStr;
+
+pub use listener::{
+    send_event, CapturedDevices, InputEvent, InputListener, ListenerConfig, ListenerHandle,
+    ListenerStatus, DISCOVERY_RETRY, EVENT_CHANNEL_CAPACITY,
+};
+
+/// An evdev key by its code name, with or without the `KEY_` prefix and in
+/// any case: `RightCtrl`, `KEY_F13`, `BTN_LEFT`.
+pub fn key_from_name(name: &str) -> Result<Key> {
+    let name = name.to_ascii_uppercase();
+    let code = if name.starts_with("KEY_") || name.starts_with("BTN_") {
+        name
+    } else {
+        format!("KEY_{}", name)
+    };
+    Key::from_str(&code).map_err(|_| anyhow!("unknown key {:?}", code))
+}
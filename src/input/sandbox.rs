@@ -0,0 +1,184 @@
+use anyhow::{bail, Context, Result};
+use landlock::{
+    make_bitflags, path_beneath_rules, Access, AccessFs, AccessNet, Ruleset, RulesetAttr,
+    RulesetCreatedAttr, RulesetStatus, ABI,
+};
+use nix::libc;
+use tracing::{debug, info, warn};
+
+/// Where the listener reads from; everything else on disk is off limits.
+const INPUT_DIR: &str = "/dev/input";
+
+/// Calls the listener never makes. Running programs, opening sockets and
+/// reaching into other processes are what a hijacked keystroke reader would
+/// need to pass keystrokes on. io_uring is refused as a whole because its
+/// operations never pass through this filter.
+const DENIED_SYSCALLS: &[libc::c_long] = &[
+    libc::SYS_execve,
+    libc::SYS_execveat,
+    libc::SYS_socket,
+    libc::SYS_socketpair,
+    libc::SYS_connect,
+    libc::SYS_bind,
+    libc::SYS_listen,
+    libc::SYS_ptrace,
+    libc::SYS_process_vm_readv,
+    libc::SYS_process_vm_writev,
+    libc::SYS_pidfd_getfd,
+    libc::SYS_io_uring_setup,
+    libc::SYS_io_uring_enter,
+    libc::SYS_io_uring_register,
+];
+
+/// `AUDIT_ARCH_*` of the syscall numbers above.
+#[cfg(target_arch = "x86_64")]
+const AUDIT_ARCH: Option<u32> = Some(0xC000_003E);
+#[cfg(target_arch = "aarch64")]
+const AUDIT_ARCH: Option<u32> = Some(0xC000_00B7);
+#[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+const AUDIT_ARCH: Option<u32> = None;
+
+/// x32 syscalls share the x86_64 arch, marked by this bit in the number.
+const X32_SYSCALL_BIT: u32 = 0x4000_0000;
+
+/// Offsets into `struct seccomp_data`
+const SECCOMP_DATA_NR: u32 = 0;
+const SECCOMP_DATA_ARCH: u32 = 4;
+
+/// Confines the calling thread, and the threads it starts afterwards, to
+/// what reading input devices needs: Landlock leaves only `/dev/input`
+/// readable and no TCP, and a seccomp filter refuses the calls in
+/// `DENIED_SYSCALLS`. File descriptors opened before, like the stop socket
+/// or the helper's pipes, keep working.
+///
+/// Best effort: whatever the kernel does not support is logged and skipped,
+/// so the listener still runs on older kernels.
+pub fn confine_listener() {
+    match restrict_paths() {
+        Ok(RulesetStatus::FullyEnforced) => debug!("Listener limited to {} by Landlock", INPUT_DIR),
+        Ok(RulesetStatus::PartiallyEnforced) => {
+            info!("Listener partly limited to {}; the kernel's Landlock is older", INPUT_DIR)
+        }
+        Ok(RulesetStatus::NotEnforced) => {
+            info!("Landlock is not available; the listener is not limited to {}", INPUT_DIR)
+        }
+        Err(e) => warn!("Failed to limit the listener to {}: {:#}", INPUT_DIR, e),
+    }
+    if let Err(e) = filter_syscalls() {
+        warn!("Failed to filter the listener's system calls: {:#}", e);
+    }
+}
+
+fn restrict_paths() -> Result<RulesetStatus> {
+    let abi = ABI::V5;
+    let status = Ruleset::default()
+        .handle_access(AccessFs::from_all(abi))?
+        .handle_access(AccessNet::from_all(abi))?
+        .create()?
+        .add_rules(path_beneath_rules(
+            [INPUT_DIR],
+            make_bitflags!(AccessFs::{ReadFile | ReadDir | IoctlDev}),
+        ))?
+        .restrict_self()?;
+    Ok(status.ruleset)
+}
+
+fn filter_syscalls() -> Result<()> {
+    let Some(arch) = AUDIT_ARCH else {
+        info!("No system call filter for this architecture");
+        return Ok(());
+    };
+    let mut program = seccomp_program(arch, DENIED_SYSCALLS);
+    let fprog = libc::sock_fprog {
+        len: program.len() as u16,
+        filter: program.as_mut_ptr(),
+    };
+    // SAFETY: plain prctl calls; `fprog` points at `program`, which outlives
+    // them, and the kernel copies the filter.
+    unsafe {
+        if libc::prctl(libc::PR_SET_NO_NEW_PRIVS, 1, 0, 0, 0) != 0 {
+            bail!("no_new_privs: {}", std::io::Error::last_os_error());
+        }
+        if libc::prctl(
+            libc::PR_SET_SECCOMP,
+            libc::SECCOMP_MODE_FILTER,
+            &fprog as *const libc::sock_fprog,
+        ) != 0
+        {
+            return Err(std::io::Error::last_os_error()).context("seccomp");
+        }
+    }
+    debug!("Listener system calls filtered");
+    Ok(())
+}
+
+/// Classic BPF that fails the `denied` calls with EPERM and allows the rest.
+/// Calls from another arch, or x32 ones, are failed too: their numbers
+/// would not match `denied`.
+fn seccomp_program(arch: u32, denied: &[libc::c_long]) -> Vec<libc::sock_filter> {
+    let load = |offset| stmt(libc::BPF_LD | libc::BPF_W | libc::BPF_ABS, offset);
+    let deny = stmt(libc::BPF_RET | libc::BPF_K, libc::SECCOMP_RET_ERRNO | libc::EPERM as u32);
+    let allow = stmt(libc::BPF_RET | libc::BPF_K, libc::SECCOMP_RET_ALLOW);
+
+    let mut program = vec![
+        load(SECCOMP_DATA_ARCH),
+        jump(libc::BPF_JEQ, arch, 1, 0),
+        deny,
+        load(SECCOMP_DATA_NR),
+        jump(libc::BPF_JGE, X32_SYSCALL_BIT, 0, 1),
+        deny,
+    ];
+    for &nr in denied {
+        program.push(jump(libc::BPF_JEQ, nr as u32, 0, 1));
+        program.push(deny);
+    }
+    program.push(allow);
+    program
+}
+
+fn stmt(code: u32, k: u32) -> libc::sock_filter {
+    libc::sock_filter {
+        code: code as u16,
+        jt: 0,
+        jf: 0,
+        k,
+    }
+}
+
+fn jump(test: u32, k: u32, jt: u8, jf: u8) -> libc::sock_filter {
+    libc::sock_filter {
+        code: (libc::BPF_JMP | test | libc::BPF_K) as u16,
+        jt,
+        jf,
+        k,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::UdpSocket;
+    use std::os::unix::net::UnixStream;
+    use std::process::Command;
+    use std::thread;
+
+    #[test]
+    fn confined_thread_cannot_run_programs_or_open_sockets() {
+        let program = seccomp_program(0xC000_003E, DENIED_SYSCALLS);
+        assert_eq!(program.len(), 6 + 2 * DENIED_SYSCALLS.len() + 1);
+        assert_eq!(program.last().unwrap().k, libc::SECCOMP_RET_ALLOW);
+
+        if AUDIT_ARCH.is_none() {
+            return;
+        }
+        // Only the spawned thread is confined, not the rest of the tests.
+        thread::spawn(|| {
+            confine_listener();
+            assert!(Command::new("true").status().is_err());
+            assert!(UdpSocket::bind("127.0.0.1:0").is_err());
+            assert!(UnixStream::pair().is_err());
+        })
+        .join()
+        .unwrap();
+    }
+}
@@ -0,0 +1,115 @@
+use crate::input::{key_from_name, InputEvent};
+use anyhow::{anyhow, bail, Result};
+use async_channel::Sender;
+use std::io::{self, BufRead};
+use std::thread;
+use tracing::{info, warn};
+
+/// Reads input events from stdin instead of devices, one per line:
+/// `press LEFTCTRL`, `release LEFTCTRL`, `repeat J`, `tap C` for a press
+/// and release, or `drag BTN_LEFT 120 -40` for a click that moves the
+/// pointer by that much. Names are evdev codes with or without the `KEY_`
+/// prefix; `BTN_` codes are mouse buttons.
+pub fn start_stdin_listener(tx: Sender<InputEvent>) {
+    thread::spawn(move || {
+        info!("Reading input events from stdin");
+        for line in io::stdin().lock().lines() {
+            let Ok(line) = line else {
+                break;
+            };
+            match parse_line(&line) {
+                Ok(events) => {
+                    for event in events {
+                        if tx.send_blocking(event).is_err() {
+                            return;
+                        }
+                    }
+                }
+                Err(e) => warn!("Ignoring stdin event {:?}: {}", line, e),
+            }
+        }
+        info!("Stdin closed; no more input events");
+    });
+}
+
+fn parse_line(line: &str) -> Result<Vec<InputEvent>> {
+    let line = line.trim();
+    if line.is_empty() || line.starts_with('#') {
+        return Ok(Vec::new());
+    }
+    let (action, name) = line
+        .split_once(char::is_whitespace)
+        .ok_or_else(|| anyhow!("expected an action and a key"))?;
+    if action == "drag" {
+        return parse_drag(name);
+    }
+    let key = key_from_name(name.trim())?;
+    let mouse = name.trim().to_ascii_uppercase().starts_with("BTN_");
+
+    let press = if mouse {
+        InputEvent::MouseButtonPressed(key)
+    } else {
+        InputEvent::KeyPressed(key)
+    };
+    let release = if mouse {
+        InputEvent::MouseButtonReleased
+    } else {
+        InputEvent::KeyReleased(key)
+    };
+    Ok(match action {
+        "press" => vec![press],
+        "release" => vec![release],
+        "repeat" if !mouse => vec![InputEvent::KeyRepeat(key)],
+        "tap" => vec![press, release],
+        _ => bail!("unknown action {:?}", action),
+    })
+}
+
+fn parse_drag(args: &str) -> Result<Vec<InputEvent>> {
+    let [name, dx, dy] = args.split_whitespace().collect::<Vec<_>>()[..] else {
+        bail!("expected a button and the distance, like drag BTN_LEFT 120 -40");
+    };
+    if !name.to_ascii_uppercase().starts_with("BTN_") {
+        bail!("only mouse buttons drag");
+    }
+    let button = key_from_name(name)?;
+    let (dx, dy) = (dx.parse()?, dy.parse()?);
+    Ok(vec![
+        InputEvent::MouseButtonPressed(button),
+        InputEvent::MouseDrag { button, dx, dy },
+        InputEvent::MouseButtonReleased,
+    ])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use evdev::Key;
+
+    #[test]
+    fn parses_event_lines() {
+        assert!(matches!(
+            parse_line("press LEFTCTRL").unwrap()[..],
+            [InputEvent::KeyPressed(Key::KEY_LEFTCTRL)]
+        ));
+        assert!(matches!(
+            parse_line("tap key_c").unwrap()[..],
+            [InputEvent::KeyPressed(Key::KEY_C), InputEvent::KeyReleased(Key::KEY_C)]
+        ));
+        assert!(matches!(
+            parse_line("press BTN_LEFT").unwrap()[..],
+            [InputEvent::MouseButtonPressed(Key::BTN_LEFT)]
+        ));
+        assert!(matches!(
+            parse_line("drag BTN_LEFT 120 -40").unwrap()[..],
+            [
+                InputEvent::MouseButtonPressed(Key::BTN_LEFT),
+                InputEvent::MouseDrag { dx: 120, dy: -40, .. },
+                InputEvent::MouseButtonReleased
+            ]
+        ));
+        assert!(parse_line("# comment").unwrap().is_empty());
+        assert!(parse_line("press NOPE").is_err());
+        assert!(parse_line("hold A").is_err());
+    }
+}
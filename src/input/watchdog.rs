@@ -0,0 +1,193 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::{Duration, Instant};
+
+/// How often an idle listener thread wakes up to beat.
+pub const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(10);
+/// A thread whose heartbeat has not moved for this long is stalled.
+const STALL_AFTER: Duration = Duration::from_secs(30);
+/// Failures of one device within `FAILURE_WINDOW` that get a status bubble.
+const REPEATED_FAILURES: usize = 3;
+const FAILURE_WINDOW: Duration = Duration::from_secs(600);
+
+/// The listener threads of one `ListenerHandle`, by device: each counts up
+/// a heartbeat while it runs and is marked failed if it ends any other way
+/// than the device going away or the listener being stopped.
+#[derive(Debug, Clone, Default)]
+pub struct Heartbeats(Arc<Mutex<HashMap<String, Beat>>>);
+
+#[derive(Debug)]
+struct Beat {
+    name: String,
+    count: Arc<AtomicU64>,
+    failed: bool,
+}
+
+impl Heartbeats {
+    /// Adds the thread reading `key` (a device path), shown as `name`.
+    pub(super) fn register(&self, key: &str, name: &str) -> Heartbeat {
+        let count = Arc::new(AtomicU64::new(0));
+        let beat = Beat {
+            name: name.to_string(),
+            count: Arc::clone(&count),
+            failed: false,
+        };
+        self.lock().insert(key.to_string(), beat);
+        Heartbeat {
+            beats: self.clone(),
+            key: key.to_string(),
+            count,
+            finished: false,
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<String, Beat>> {
+        self.0.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+/// A listener thread's entry in `Heartbeats`. Dropped without `finish`, on
+/// an error return or a panic, it marks the thread failed.
+pub(super) struct Heartbeat {
+    beats: Heartbeats,
+    key: String,
+    count: Arc<AtomicU64>,
+    finished: bool,
+}
+
+impl Heartbeat {
+    pub(super) fn beat(&self) {
+        self.count.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// The thread is ending on purpose.
+    pub(super) fn finish(mut self) {
+        self.finished = true;
+        let mut beats = self.beats.lock();
+        if beats.get(&self.key).is_some_and(|beat| self.owns(beat)) {
+            beats.remove(&self.key);
+        }
+    }
+
+    /// The thread is ending on an error it handled itself.
+    pub(super) fn fail(self) {}
+
+    /// A replacement thread for the same device has its own entry.
+    fn owns(&self, beat: &Beat) -> bool {
+        Arc::ptr_eq(&beat.count, &self.count)
+    }
+}
+
+impl Drop for Heartbeat {
+    fn drop(&mut self) {
+        if self.finished {
+            return;
+        }
+        if let Some(beat) = self.beats.lock().get_mut(&self.key) {
+            beat.failed |= self.owns(beat);
+        }
+    }
+}
+
+/// What the event pump should do about the listener threads.
+#[derive(Debug, Default, PartialEq)]
+pub struct Verdict {
+    /// A thread died or stalled; the listener should be started again
+    pub restart: bool,
+    /// Devices that failed `REPEATED_FAILURES` times lately
+    pub failing: Vec<String>,
+}
+
+/// Run by the event pump every `HEARTBEAT_INTERVAL` or so: finds listener
+/// threads that ended with an error or whose heartbeat stopped, and keeps
+/// count of how often each device failed.
+#[derive(Debug, Default)]
+pub struct ListenerWatchdog {
+    /// The registry `seen` is about; a restarted listener has a new one
+    checked: Option<Heartbeats>,
+    /// Last heartbeat count seen per device, and when it last moved
+    seen: HashMap<String, (u64, Instant)>,
+    failures: HashMap<String, Vec<Instant>>,
+}
+
+impl ListenerWatchdog {
+    pub fn check(&mut self, heartbeats: &Heartbeats, now: Instant) -> Verdict {
+        let mut verdict = Verdict::default();
+        if !self.checked.as_ref().is_some_and(|checked| Arc::ptr_eq(&checked.0, &heartbeats.0)) {
+            self.checked = Some(heartbeats.clone());
+            self.seen.clear();
+        }
+        let mut beats = heartbeats.lock();
+        self.seen.retain(|key, _| beats.contains_key(key));
+        let mut dead = Vec::new();
+        for (key, beat) in beats.iter() {
+            let count = beat.count.load(Ordering::Relaxed);
+            let stalled = match self.seen.get(key) {
+                Some(&(seen, since)) if seen == count => now.duration_since(since) >= STALL_AFTER,
+                _ => {
+                    self.seen.insert(key.clone(), (count, now));
+                    false
+                }
+            };
+            if beat.failed || stalled {
+                dead.push((key.clone(), beat.name.clone()));
+            }
+        }
+        for (key, name) in dead {
+            // Its replacement registers afresh; a stalled thread that wakes
+            // up later finds nothing to beat for.
+            beats.remove(&key);
+            self.seen.remove(&key);
+            verdict.restart = true;
+            if self.record_failure(&name, now) {
+                verdict.failing.push(name);
+            }
+        }
+        verdict
+    }
+
+    /// True when `name` has now failed `REPEATED_FAILURES` times within
+    /// `FAILURE_WINDOW`; the count starts over after that.
+    fn record_failure(&mut self, name: &str, now: Instant) -> bool {
+        let times = self.failures.entry(name.to_string()).or_default();
+        times.retain(|at| now.duration_since(*at) < FAILURE_WINDOW);
+        times.push(now);
+        if times.len() < REPEATED_FAILURES {
+            return false;
+        }
+        times.clear();
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dead_and_stalled_threads_are_restarted() {
+        let mut watchdog = ListenerWatchdog::default();
+        let start = Instant::now();
+        let heartbeats = Heartbeats::default();
+        let keyboard = heartbeats.register("/dev/input/event3", "Keyboard");
+        let gone = heartbeats.register("/dev/input/event4", "Dock");
+        assert_eq!(watchdog.check(&heartbeats, start), Verdict::default());
+
+        gone.finish();
+        keyboard.beat();
+        let later = start + STALL_AFTER;
+        assert_eq!(watchdog.check(&heartbeats, later), Verdict::default());
+        let verdict = watchdog.check(&heartbeats, later + STALL_AFTER);
+        assert!(verdict.restart && verdict.failing.is_empty());
+
+        for attempt in 1..REPEATED_FAILURES {
+            let keyboard = heartbeats.register("/dev/input/event3", "Keyboard");
+            keyboard.fail();
+            let verdict = watchdog.check(&heartbeats, later + STALL_AFTER * attempt as u32);
+            assert!(verdict.restart);
+            let failing = attempt + 1 == REPEATED_FAILURES;
+            assert_eq!(verdict.failing, if failing { vec!["Keyboard"] } else { vec![] });
+        }
+    }
+}
@@ -0,0 +1,235 @@
+use crate::input::listener::{send_event, InputEvent, LastDevice, ListenerConfig, ListenerStatus};
+use crate::input::sandbox;
+use crate::input::watchdog::{Heartbeat, HEARTBEAT_INTERVAL};
+use anyhow::{Context, Result};
+use async_channel::Sender;
+use evdev::Key;
+use nix::poll::{poll, PollFd, PollFlags, PollTimeout};
+use std::collections::{HashMap, HashSet};
+use std::os::fd::AsFd;
+use std::os::unix::net::UnixStream;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use tracing::{info, trace};
+use x11rb::connection::Connection;
+use x11rb::protocol::xinput::{
+    self, ConnectionExt as _, Device, DeviceType, EventMask, KeyEventFlags, XIEventMask,
+};
+use x11rb::protocol::xproto::Window;
+use x11rb::protocol::Event;
+use x11rb::rust_connection::RustConnection;
+
+/// X keycodes are evdev codes shifted by this, on every X server that reads
+/// devices through evdev or libinput.
+const KEYCODE_OFFSET: u32 = 8;
+
+/// Reads keys and mouse buttons from the X server as XInput2 raw events,
+/// which any client may select on the root window, so an X11 session needs
+/// no `/dev/input` access. Raw events (unlike XRecord's core events) tell a
+/// held key's repeats from new presses. Returns when `stop_watch` closes.
+pub(super) fn listen(
+    config: ListenerConfig,
+    sender: Sender<InputEvent>,
+    status: Sender<ListenerStatus>,
+    running: Arc<AtomicBool>,
+    stop_watch: UnixStream,
+    heartbeat: &Heartbeat,
+) -> Result<()> {
+    let (conn, screen) = x11rb::connect(None).context("Failed to connect to the X server")?;
+    let root = conn.setup().roots[screen].root;
+    // After connecting: the sandbox refuses new sockets.
+    if config.sandbox {
+        sandbox::confine_listener();
+    }
+    let version = conn
+        .xinput_xi_query_version(2, 0)?
+        .reply()
+        .context("The X server has no XInput2")?;
+    info!("Reading input through XInput {}.{}", version.major_version, version.minor_version);
+    select_events(&conn, root, config.include_mouse)?;
+
+    let mut raw = RawEvents::default();
+    let last_device = LastDevice::default();
+    let mut names = list_devices(&conn, &config, &status)?;
+
+    let mut poll_fds = [
+        PollFd::new(conn.stream().as_fd(), PollFlags::POLLIN),
+        PollFd::new(stop_watch.as_fd(), PollFlags::POLLIN),
+    ];
+    let timeout = PollTimeout::try_from(HEARTBEAT_INTERVAL).unwrap_or(PollTimeout::MAX);
+    while running.load(Ordering::SeqCst) {
+        heartbeat.beat();
+        poll(&mut poll_fds, timeout).context("Poll error")?;
+        if poll_fds[1].any().unwrap_or(true) || !running.load(Ordering::SeqCst) {
+            break;
+        }
+        while let Some(event) = conn.poll_for_event()? {
+            let (device, input) = match event {
+                Event::XinputHierarchy(_) => {
+                    names = list_devices(&conn, &config, &status)?;
+                    continue;
+                }
+                Event::XinputRawKeyPress(event) => {
+                    let repeat = event.flags.contains(KeyEventFlags::KEY_REPEAT);
+                    (event.sourceid, raw.key(event.detail, true, repeat))
+                }
+                Event::XinputRawKeyRelease(event) => {
+                    (event.sourceid, raw.key(event.detail, false, false))
+                }
+                Event::XinputRawButtonPress(event) => {
+                    (event.sourceid, raw.button(event.detail, true))
+                }
+                Event::XinputRawButtonRelease(event) => {
+                    (event.sourceid, raw.button(event.detail, false))
+                }
+                _ => continue,
+            };
+            let (Some(input), Some(name)) = (input, names.get(&device)) else {
+                continue;
+            };
+            trace!("X11 input from {}: {:?}", name, input);
+            let result = match input {
+                InputEvent::KeyPressed(_) | InputEvent::MouseButtonPressed(_)
+                    if config.devices.is_muted(name) =>
+                {
+                    continue;
+                }
+                InputEvent::KeyPressed(_) | InputEvent::MouseButtonPressed(_) => {
+                    last_device.send_press(&sender, name, input)
+                }
+                InputEvent::KeyRepeat(_) if config.devices.is_muted(name) => continue,
+                input => send_event(&sender, input),
+            };
+            if result.is_err() {
+                info!("Channel closed, stopping the X11 listener");
+                return Ok(());
+            }
+        }
+    }
+    config.devices.set_listening(Vec::new());
+    Ok(())
+}
+
+fn select_events(conn: &RustConnection, root: Window, include_mouse: bool) -> Result<()> {
+    let mut raw = XIEventMask::RAW_KEY_PRESS | XIEventMask::RAW_KEY_RELEASE;
+    if include_mouse {
+        raw = raw | XIEventMask::RAW_BUTTON_PRESS | XIEventMask::RAW_BUTTON_RELEASE;
+    }
+    let masks = [
+        // Raw events only come through the master devices; `sourceid` still
+        // names the keyboard or mouse they came from.
+        EventMask {
+            deviceid: Device::ALL_MASTER.into(),
+            mask: vec![raw],
+        },
+        EventMask {
+            deviceid: Device::ALL.into(),
+            mask: vec![XIEventMask::HIERARCHY],
+        },
+    ];
+    conn.xinput_xi_select_events(root, &masks)?
+        .check()
+        .context("Failed to select XInput2 raw events")?;
+    Ok(())
+}
+
+/// The keyboards (and mice, with `include_mouse`) by device id, also put in
+/// `config.devices` for the tray's device list.
+fn list_devices(
+    conn: &RustConnection,
+    config: &ListenerConfig,
+    status: &Sender<ListenerStatus>,
+) -> Result<HashMap<xinput::DeviceId, Arc<str>>> {
+    let reply = conn.xinput_xi_query_device(Device::ALL)?.reply()?;
+    let mut names: HashMap<xinput::DeviceId, Arc<str>> = HashMap::new();
+    let mut keyboards = 0;
+    for info in reply.infos {
+        let name = String::from_utf8_lossy(&info.name).into_owned();
+        match info.type_ {
+            DeviceType::SLAVE_KEYBOARD => keyboards += 1,
+            DeviceType::SLAVE_POINTER if config.include_mouse => {}
+            _ => continue,
+        }
+        names.insert(info.deviceid, Arc::from(name));
+    }
+    config
+        .devices
+        .set_listening(names.values().map(|name| name.to_string()).collect());
+    let _ = status.try_send(ListenerStatus::DevicesChanged);
+    let _ = status.try_send(match keyboards {
+        0 => ListenerStatus::NoKeyboards { denied: false },
+        _ => ListenerStatus::KeyboardsFound,
+    });
+    Ok(names)
+}
+
+/// Turns raw key codes and button numbers into the listener's events.
+#[derive(Debug, Default)]
+struct RawEvents {
+    /// Keys down, so a second press without a release counts as a repeat
+    down: HashSet<Key>,
+}
+
+impl RawEvents {
+    fn key(&mut self, keycode: u32, pressed: bool, repeat: bool) -> Option<InputEvent> {
+        let code = u16::try_from(keycode.checked_sub(KEYCODE_OFFSET)?).ok()?;
+        let key = Key::new(code);
+        if !pressed {
+            self.down.remove(&key);
+            return Some(InputEvent::KeyReleased(key));
+        }
+        if !self.down.insert(key) || repeat {
+            return Some(InputEvent::KeyRepeat(key));
+        }
+        Some(InputEvent::KeyPressed(key))
+    }
+
+    /// Buttons 4 to 7 are the scroll wheel, which is not shown.
+    fn button(&mut self, button: u32, pressed: bool) -> Option<InputEvent> {
+        let key = match button {
+            1 => Key::BTN_LEFT,
+            2 => Key::BTN_MIDDLE,
+            3 => Key::BTN_RIGHT,
+            8 => Key::BTN_SIDE,
+            9 => Key::BTN_EXTRA,
+            _ => return None,
+        };
+        Some(match pressed {
+            true => InputEvent::MouseButtonPressed(key),
+            false => InputEvent::MouseButtonReleased,
+        })
+    }
+}
+
+/// Whether to read input from the X server rather than `/dev/input`: when
+/// the session says it is X11, or there is an X display but no Wayland one.
+pub fn is_x11_session() -> bool {
+    match std::env::var("XDG_SESSION_TYPE").as_deref() {
+        Ok("x11") => true,
+        Ok("wayland") => false,
+        _ => std::env::var_os("WAYLAND_DISPLAY").is_none() && std::env::var_os("DISPLAY").is_some(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn raw_codes_become_listener_events() {
+        let mut raw = RawEvents::default();
+        // Keycode 38 is evdev's KEY_A (30).
+        assert!(matches!(raw.key(38, true, false), Some(InputEvent::KeyPressed(Key::KEY_A))));
+        assert!(matches!(raw.key(38, true, false), Some(InputEvent::KeyRepeat(Key::KEY_A))));
+        assert!(matches!(raw.key(38, false, false), Some(InputEvent::KeyReleased(Key::KEY_A))));
+        assert!(matches!(raw.key(38, true, true), Some(InputEvent::KeyRepeat(Key::KEY_A))));
+        assert!(raw.key(3, true, false).is_none());
+
+        assert!(matches!(
+            raw.button(3, true),
+            Some(InputEvent::MouseButtonPressed(Key::BTN_RIGHT))
+        ));
+        assert!(matches!(raw.button(3, false), Some(InputEvent::MouseButtonReleased)));
+        assert!(raw.button(4, true).is_none());
+    }
+}
@@ -0,0 +1,92 @@
+use crate::combo::{unix_millis, ComboEvent};
+use crate::input::InputEvent;
+use crate::xkb::LockState;
+use anyhow::{Context, Result};
+use async_channel::Receiver;
+use serde_json::{json, Value};
+use std::fs::OpenOptions;
+use std::io::{self, LineWriter, Write};
+use std::path::Path;
+use std::thread;
+use tracing::{info, warn};
+
+/// Writes input, combo and lock events as JSON lines (`--emit-json`).
+///
+/// Lines are handed to a writer thread so a slow pipe never stalls the
+/// overlay; events are dropped while the queue is full.
+pub struct JsonOutput {
+    tx: async_channel::Sender<Value>,
+    stdout: bool,
+}
+
+impl JsonOutput {
+    /// `-` writes to stdout, anything else is appended to as a file.
+    pub fn open(target: &Path) -> Result<Self> {
+        let stdout = target == Path::new("-");
+        let writer: Box<dyn Write + Send> = if stdout {
+            Box::new(LineWriter::new(io::stdout()))
+        } else {
+            let file = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(target)
+                .with_context(|| format!("Failed to open {:?}", target))?;
+            info!("Writing JSON events to {:?}", target);
+            Box::new(LineWriter::new(file))
+        };
+
+        let (tx, rx) = async_channel::bounded(1024);
+        thread::spawn(move || write_loop(rx, writer));
+        Ok(Self { tx, stdout })
+    }
+
+    pub fn uses_stdout(&self) -> bool {
+        self.stdout
+    }
+
+    pub fn input(&self, event: &InputEvent) {
+        if let InputEvent::MouseDrag { button, dx, dy } = event {
+            let _ = self.tx.try_send(json!({
+                "type": "mouse",
+                "state": "drag",
+                "code": format!("{:?}", button),
+                "dx": dx,
+                "dy": dy,
+                "timestamp": unix_millis(),
+            }));
+            return;
+        }
+        let (kind, state, key) = match event {
+            InputEvent::KeyPressed(key) => ("key", "pressed", Some(key)),
+            InputEvent::KeyRepeat(key) => ("key", "repeat", Some(key)),
+            InputEvent::KeyReleased(key) => ("key", "released", Some(key)),
+            InputEvent::MouseButtonPressed(key) => ("mouse", "pressed", Some(key)),
+            InputEvent::MouseButtonReleased => ("mouse", "released", None),
+            InputEvent::MouseDrag { .. } | InputEvent::Device(_) => return,
+        };
+        let _ = self.tx.try_send(json!({
+            "type": kind,
+            "state": state,
+            "code": key.map(|key| format!("{:?}", key)),
+            "timestamp": unix_millis(),
+        }));
+    }
+
+    pub fn combo(&self, combo: &ComboEvent) {
+        let _ = self.tx.try_send(combo.to_json());
+    }
+
+    pub fn locks(&self, locks: &LockState) {
+        let _ = self.tx.try_send(locks.to_json());
+    }
+}
+
+fn write_loop(rx: Receiver<Value>, mut writer: Box<dyn Write + Send>) {
+    while let Ok(value) = rx.recv_blocking() {
+        if let Err(e) = writeln!(writer, "{}", value) {
+            // A closed pipe (e.g. `| head`) ends the output, not the overlay.
+            warn!("Stopped writing JSON events: {}", e);
+            break;
+        }
+    }
+}
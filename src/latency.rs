@@ -0,0 +1,88 @@
+use std::time::{Duration, SystemTime};
+use tracing::info;
+
+/// Samples collected between percentile log lines.
+const LOG_EVERY: usize = 100;
+
+/// `latency_debug`: time from the kernel's event timestamp to the render
+/// that first showed the bubble.
+pub struct LatencyProbe {
+    /// Bubbles added since the last render, with the press behind each
+    pending: Vec<(u64, SystemTime)>,
+    samples: Vec<Duration>,
+}
+
+impl Default for LatencyProbe {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl LatencyProbe {
+    pub fn new() -> Self {
+        Self {
+            pending: Vec::new(),
+            samples: Vec::with_capacity(LOG_EVERY),
+        }
+    }
+
+    /// Bubble `id` was added for a press the kernel stamped `pressed_at`.
+    pub fn track(&mut self, id: u64, pressed_at: SystemTime) {
+        self.pending.retain(|(pending, _)| *pending != id);
+        self.pending.push((id, pressed_at));
+    }
+
+    /// Called right after a render: the measured latency of each bubble it
+    /// showed for the first time.
+    pub fn rendered(&mut self) -> Vec<(u64, Duration)> {
+        let now = SystemTime::now();
+        let measured: Vec<(u64, Duration)> = self
+            .pending
+            .drain(..)
+            .map(|(id, at)| (id, now.duration_since(at).unwrap_or_default()))
+            .collect();
+        for (_, latency) in &measured {
+            self.samples.push(*latency);
+            if self.samples.len() >= LOG_EVERY {
+                self.log_percentiles();
+            }
+        }
+        measured
+    }
+
+    fn log_percentiles(&mut self) {
+        self.samples.sort_unstable();
+        info!(
+            "Input-to-render latency over {} presses: p50 {} ms, p90 {} ms, p99 {} ms, max {} ms",
+            self.samples.len(),
+            percentile(&self.samples, 50).as_millis(),
+            percentile(&self.samples, 90).as_millis(),
+            percentile(&self.samples, 99).as_millis(),
+            self.samples.last().copied().unwrap_or_default().as_millis()
+        );
+        self.samples.clear();
+    }
+}
+
+/// Nearest-rank percentile of sorted `samples`.
+fn percentile(samples: &[Duration], p: usize) -> Duration {
+    if samples.is_empty() {
+        return Duration::ZERO;
+    }
+    let rank = (p * samples.len()).div_ceil(100).max(1);
+    samples[rank - 1]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn nearest_rank_percentiles() {
+        let samples: Vec<Duration> = (1..=100).map(Duration::from_millis).collect();
+        assert_eq!(percentile(&samples, 50), Duration::from_millis(50));
+        assert_eq!(percentile(&samples, 99), Duration::from_millis(99));
+        assert_eq!(percentile(&samples[..1], 90), Duration::from_millis(1));
+        assert_eq!(percentile(&[], 50), Duration::ZERO);
+    }
+}
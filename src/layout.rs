@@ -0,0 +1,116 @@
+use crate::focus::{hyprland_event_socket, FocusBackend};
+use anyhow::{Context, Result};
+use async_channel::{Receiver, Sender};
+use serde_json::Value;
+use std::io::{BufRead, BufReader};
+use std::os::unix::net::UnixStream;
+use std::process::{Command, Stdio};
+use std::thread;
+use tracing::{debug, warn};
+
+/// Follows the compositor's active keyboard layout, sending its name once at
+/// start and again on every switch. Only Hyprland and Sway report it.
+pub fn start_layout_tracker(backend: FocusBackend) -> Receiver<String> {
+    let (tx, rx) = async_channel::bounded(8);
+
+    thread::spawn(move || {
+        let result = match backend {
+            FocusBackend::Hyprland => track_hyprland(&tx),
+            FocusBackend::Sway => track_sway(&tx),
+        };
+        if let Err(e) = result {
+            warn!("Layout tracking ({}) stopped: {:#}", backend.name(), e);
+        }
+    });
+
+    rx
+}
+
+fn track_hyprland(tx: &Sender<String>) -> Result<()> {
+    if let Some(name) = hyprland_active_layout() {
+        send_layout(tx, name)?;
+    }
+
+    let path = hyprland_event_socket().context("Hyprland event socket not found")?;
+    let stream = UnixStream::connect(&path)
+        .with_context(|| format!("Failed to connect to {:?}", path))?;
+
+    for line in BufReader::new(stream).lines() {
+        let line = line.context("Failed to read Hyprland event")?;
+        // `activelayout>>KEYBOARD,LAYOUT`, once per keyboard on every switch.
+        let Some(data) = line.strip_prefix("activelayout>>") else {
+            continue;
+        };
+        if let Some((_, name)) = data.rsplit_once(',') {
+            debug!("Layout changed: {}", name);
+            send_layout(tx, name.to_string())?;
+        }
+    }
+
+    Ok(())
+}
+
+fn hyprland_active_layout() -> Option<String> {
+    let output = Command::new("hyprctl").args(["-j", "devices"]).output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let devices: Value = serde_json::from_slice(&output.stdout).ok()?;
+    let keyboards = devices.get("keyboards")?.as_array()?;
+    let main = keyboards
+        .iter()
+        .find(|keyboard| keyboard.get("main").and_then(|v| v.as_bool()).unwrap_or(false))
+        .or_else(|| keyboards.first())?;
+    Some(main.get("active_keymap")?.as_str()?.to_string())
+}
+
+fn track_sway(tx: &Sender<String>) -> Result<()> {
+    if let Some(name) = sway_active_layout() {
+        send_layout(tx, name)?;
+    }
+
+    let mut child = Command::new("swaymsg")
+        .args(["-t", "subscribe", "-m", r#"["input"]"#])
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .context("Failed to run swaymsg")?;
+    let stdout = child.stdout.take().context("swaymsg has no stdout")?;
+
+    for line in BufReader::new(stdout).lines() {
+        let line = line.context("Failed to read sway event")?;
+        let Ok(event) = serde_json::from_str::<Value>(&line) else {
+            continue;
+        };
+        if event.get("change").and_then(|v| v.as_str()) != Some("xkb_layout") {
+            continue;
+        }
+        if let Some(name) = event.get("input").and_then(sway_layout_name) {
+            debug!("Layout changed: {}", name);
+            send_layout(tx, name)?;
+        }
+    }
+
+    let _ = child.kill();
+    Ok(())
+}
+
+fn sway_active_layout() -> Option<String> {
+    let output = Command::new("swaymsg").args(["-t", "get_inputs"]).output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let inputs: Value = serde_json::from_slice(&output.stdout).ok()?;
+    inputs.as_array()?.iter().find_map(sway_layout_name)
+}
+
+fn sway_layout_name(input: &Value) -> Option<String> {
+    if input.get("type").and_then(|v| v.as_str()) != Some("keyboard") {
+        return None;
+    }
+    Some(input.get("xkb_active_layout_name")?.as_str()?.to_string())
+}
+
+fn send_layout(tx: &Sender<String>, name: String) -> Result<()> {
+    tx.send_blocking(name).map_err(|_| anyhow::anyhow!("Channel closed"))
+}
@@ -0,0 +1,65 @@
+//! The overlay's modules; `main.rs` wires them into the app. Kept as a
+//! library so the benches can use them too.
+
+pub mod app_filter;
+pub mod appearance;
+pub mod autostart;
+pub mod bench_cli;
+pub mod breaks;
+pub mod capture;
+pub mod combo;
+pub mod combo_format;
+pub mod control;
+pub mod control_socket;
+pub mod ctl;
+pub mod dbus_service;
+pub mod demo;
+pub mod devices_cli;
+pub mod event_stream;
+pub mod flatpak;
+pub mod focus;
+pub mod frequency;
+pub mod headless;
+pub mod frame_stream;
+pub mod generate;
+pub mod gsettings;
+pub mod heatmap_window;
+pub mod hooks;
+pub mod hotkey;
+pub mod i18n;
+pub mod ime;
+pub mod input;
+pub mod metrics;
+pub mod obs;
+pub mod json_output;
+pub mod latency;
+pub mod layout;
+pub mod markers;
+pub mod overlay;
+pub mod permission_window;
+pub mod plugins;
+pub mod portal;
+pub mod portal_shortcuts;
+pub mod practice;
+pub mod profile;
+pub mod remote;
+pub mod render_model;
+pub mod saved_state;
+pub mod schedule;
+pub mod ring;
+pub mod rollover_window;
+pub mod settings;
+pub mod screencast;
+pub mod session;
+pub mod session_log;
+pub mod speech;
+pub mod speed;
+pub mod service;
+pub mod settings_window;
+pub mod stats;
+pub mod stats_cli;
+pub mod summary;
+pub mod tray;
+pub mod wakeup;
+pub mod x11_window;
+pub mod xkb;
@@ -1,54 +1,208 @@
-mod combo;
-mod hotkey;
-mod input;
-mod overlay;
-mod settings;
-mod settings_window;
-mod tray;
-mod xkb;
-
-use anyhow::Result;
-use async_channel::{Receiver, Sender};
-use combo::{ComboAction, ComboState};
+use keyway_visualizer::{
+    app_filter, appearance, autostart, bench_cli, breaks, capture, combo, combo_format, control,
+    control_socket, ctl, dbus_service, demo, devices_cli, event_stream, flatpak, focus,
+    frame_stream, frequency, generate, gsettings, headless, heatmap_window, hooks, hotkey, i18n,
+    ime, input, json_output, latency, layout, markers, metrics, obs, overlay, permission_window,
+    plugins, portal_shortcuts, practice, profile, remote, rollover_window, saved_state, schedule,
+    screencast, service, session, session_log, settings, settings_window, speech, stats, stats_cli,
+    summary, tray, wakeup, xkb,
+};
+
+use anyhow::{Context, Result};
+use app_filter::FilterRule;
+use appearance::ColorScheme;
+use async_channel::Sender;
+use breaks::BreakTimer;
+use capture::{CaptureEvent, CaptureRequest, CropRegion};
+use combo::{ComboAction, ComboEvent, ComboKind, ComboState};
+use combo_format::ComboFormat;
+use control::{CaptureFormat, ControlCommand, ControlStatus};
+use control_socket::SocketHandle;
+use dbus_service::DbusHandle;
+use evdev::Key;
+use demo::{DemoEvent, DemoStep};
+use event_stream::EventStream;
 use clap::Parser;
+use focus::{FocusBackend, FocusInfo};
+use frame_stream::FrameStream;
+use frequency::ComboFrequency;
+use gsettings::GSettingsStore;
+use latency::LatencyProbe;
+use heatmap_window::HeatmapWindow;
+use hooks::HookSet;
 use hotkey::Hotkey;
 use gtk4::glib::{self, ControlFlow};
 use gtk4::prelude::*;
+use gtk4::gio::{self, ApplicationFlags};
 use gtk4::Application;
-use input::{InputListener, ListenerConfig};
+use input::watchdog::{ListenerWatchdog, HEARTBEAT_INTERVAL};
+use input::{CapturedDevices, InputListener, ListenerConfig, ListenerStatus};
+use json_output::JsonOutput;
+use markers::MarkerLog;
+use metrics::MetricsServer;
+use obs::{ObsEvent, ObsOutput};
 use overlay::OverlayWindow;
-use settings::{CliArgs, Settings};
-use serde_json::Value;
+use permission_window::PermissionWindow;
+use plugins::PluginHost;
+use portal_shortcuts::PortalShortcuts;
+use practice::{Attempt, Practice};
+use profile::ProfileSet;
+use rollover_window::RolloverWindow;
+use saved_state::SavedState;
+use schedule::Schedule;
+use session::{SessionEvent, SessionLock};
+use service::Notifier;
+use session_log::SessionLog;
+use settings::{
+    CliArgs, Command, Emphasis, ObsReaction, ScreencastMode, Settings, SettingsBackend, SpeedWidget,
+    SummaryMode, Theme,
+};
 use settings_window::SettingsWindow;
-use std::cell::RefCell;
-use std::path::PathBuf;
-use std::process::Command;
+use stats::StatsStore;
+use speech::Announcer;
+use summary::SessionSummary;
+use nix::sys::signal::Signal;
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
 use std::rc::Rc;
 use std::time::{Duration, Instant};
 use tracing::{error, info, warn};
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt, EnvFilter};
 use tray::{TrayAction, TrayHandle};
+use wakeup::{Inbox, PumpWaker};
+use xkb::LockState;
+
+const APP_ID: &str = "dev.keyway.visualizer";
+/// Slack after an item's expiry before the pump prunes it, so the timer
+/// does not fire just short of the deadline.
+const PRUNE_SLACK: Duration = Duration::from_millis(5);
+/// Shown while discovery finds no keyboard.
+const NO_KEYBOARD_NOTICE: &str = "Waiting for a keyboard…";
+const NO_ACCESS_NOTICE: &str = "No keyboard access: join the input group";
+/// How often the speed widget is refreshed while its rate settles to zero.
+const SPEED_INTERVAL: Duration = Duration::from_millis(500);
+const PRESENTER_NOTICE_TTL: Duration = Duration::from_secs(2);
+const CAPTURE_NOTICE_TTL: Duration = Duration::from_secs(4);
+const MARKER_NOTICE_TTL: Duration = Duration::from_millis(1200);
+const DEVICE_NOTICE_TTL: Duration = Duration::from_secs(2);
+const LISTENER_FAILURE_TTL: Duration = Duration::from_secs(10);
+const PRACTICE_NOTICE_TTL: Duration = Duration::from_secs(4);
+const BREAK_NOTICE_TTL: Duration = Duration::from_secs(10);
+/// How often the pump checks `active_hours` while a schedule is set.
+const SCHEDULE_INTERVAL: Duration = Duration::from_secs(15);
+/// Room around the overlay in a recording, for the bubbles' shadows
+const CAPTURE_PADDING: i32 = 8;
 
 fn main() {
     if let Err(e) = run() {
-        eprintln!("{e}");
+        eprintln!("{e:#}");
+        std::process::exit(1);
     }
 }
 
 fn run() -> Result<()> {
-    init_logging();
+    // A setgid `input` copy may only be the listener helper: anything else
+    // would run the config's hooks, plugins and paths with the group.
+    let helper = std::env::args_os().nth(1).is_some_and(|arg| arg == "listen-helper");
+    if input::helper::elevated() && !helper {
+        anyhow::bail!("Running setuid or setgid, keyway-visualizer only works as listen-helper");
+    }
+    if ctl::invoked_as_ctl() {
+        return ctl::run(&ctl::CtlArgs::parse());
+    }
 
     let cli = CliArgs::parse();
-    let (settings, config_path) = settings::Settings::load(&cli)?;
+    match &cli.command {
+        Some(Command::Ctl(args)) => return ctl::run(args),
+        Some(Command::Stats(args)) => {
+            return stats_cli::run(args, cli.config.clone(), cli.stats_path.clone())
+        }
+        Some(Command::Bench(args)) => return bench_cli::run(args),
+        Some(Command::Generate(args)) => return generate::run(args),
+        Some(Command::ListenHelper(args)) => {
+            init_logging("warn");
+            return input::helper::run(args);
+        }
+        None => {}
+    }
 
+    init_logging(if cli.headless || cli.list_devices {
+        "warn"
+    } else {
+        "info,keyway_visualizer=debug"
+    });
+    let (mut settings, config_path) = settings::Settings::load(&cli)?;
+    if settings.settings_backend == SettingsBackend::Gsettings {
+        match GSettingsStore::open().map(|store| store.load_into(&settings)) {
+            Some(Ok(loaded)) => {
+                settings = loaded;
+                settings.apply_cli(&cli);
+            }
+            Some(Err(e)) => warn!("Ignoring GSettings: {:#}", e),
+            None => {}
+        }
+    }
+    if cli.list_devices {
+        return devices_cli::run(&settings);
+    }
+    if cli.headless {
+        return headless::run(&settings, cli.color, cli.stdin_events);
+    }
+    let json_output = cli
+        .emit_json
+        .as_deref()
+        .map(JsonOutput::open)
+        .transpose()?
+        .map(Rc::new);
+    let service = cli.service;
+    let demo = cli.demo.as_deref().map(demo::load).transpose()?;
+    let stdin_events = cli.stdin_events;
+
+    // The unique application id makes a second launch hand its command line
+    // to the running instance instead of starting another overlay.
     let app = Application::builder()
-        .application_id("dev.keyway.visualizer")
+        .application_id(APP_ID)
+        .flags(ApplicationFlags::HANDLES_COMMAND_LINE)
         .build();
 
-    app.connect_activate(move |app| {
-        if let Err(e) = build_ui(app, settings.clone(), config_path.clone()) {
-            error!("Failed to start app: {}", e);
-            app.quit();
+    let primary: Rc<RefCell<Option<Rc<RefCell<AppState>>>>> = Rc::default();
+    let running = Rc::clone(&primary);
+    // However the application ends, the control socket file goes with it.
+    app.connect_shutdown(move |_| {
+        if let Some(Ok(mut state)) = running.borrow().as_ref().map(|state| state.try_borrow_mut()) {
+            state.socket_handle = None;
+        }
+    });
+    app.connect_command_line(move |app, command_line| {
+        if let Some(state) = primary.borrow().as_ref() {
+            info!("Already running; applying arguments from the new launch");
+            forward_args(app, state, &command_line.arguments());
+            return 0;
+        }
+
+        match build_ui(
+            app,
+            settings.clone(),
+            config_path.clone(),
+            json_output.clone(),
+            demo.clone(),
+            stdin_events,
+        ) {
+            Ok(state) => {
+                if service {
+                    start_service(&state);
+                }
+                handle_termination(app, &state);
+                *primary.borrow_mut() = Some(state);
+                0
+            }
+            Err(e) => {
+                error!("Failed to start app: {}", e);
+                app.quit();
+                1
+            }
         }
     });
 
@@ -56,19 +210,37 @@ fn run() -> Result<()> {
     Ok(())
 }
 
-fn build_ui(app: &Application, settings: Settings, config_path: PathBuf) -> Result<()> {
+fn build_ui(
+    app: &Application,
+    settings: Settings,
+    config_path: PathBuf,
+    json_output: Option<Rc<JsonOutput>>,
+    demo: Option<Vec<DemoStep>>,
+    stdin_events: bool,
+) -> Result<Rc<RefCell<AppState>>> {
     info!("Starting keyway-visualizer");
+    i18n::set_language(&settings.language);
 
-    let (tx, rx) = async_channel::bounded(256);
+    let (tx, rx) = async_channel::bounded(input::EVENT_CHANNEL_CAPACITY);
+    let (listener_status_tx, listener_status_rx) = async_channel::bounded(4);
     let hotkey = Hotkey::parse(&settings.pause_hotkey)?;
     info!("Pause hotkey: {}", hotkey.describe());
-    let combo = ComboState::new(
+    let mut combo = ComboState::new(
         settings.max_items,
         Duration::from_millis(settings.ttl_ms),
         Duration::from_millis(settings.repeat_coalesce_ms),
         Duration::from_millis(settings.modifier_grace_ms),
         hotkey,
     );
+    combo.set_presenter_hotkey(parse_optional_hotkey(&settings.presenter_hotkey)?);
+    combo.set_screenshot_hotkey(parse_optional_hotkey(&settings.screenshot_hotkey)?);
+    combo.set_marker_hotkey(parse_optional_hotkey(&settings.marker_hotkey)?);
+    combo.set_bell_keys(parse_bell_keys(&settings.visual_bell_keys));
+    combo.set_teaching(settings.teaching_mode, &settings.aliases);
+    combo.set_device_tags(&settings.device_tags);
+    combo.set_lock_announcements(settings.lock_announcements);
+    combo.set_mouse_drag(settings.mouse_drag);
+    combo.set_ghost_preview(settings.ghost_preview);
 
     let tray = tray::start_tray().ok();
     let (tray_rx, tray_handle) = tray
@@ -77,28 +249,143 @@ fn build_ui(app: &Application, settings: Settings, config_path: PathBuf) -> Resu
 
     let overlay = OverlayWindow::new(app, &settings);
     overlay.set_drag_enabled(settings.drag_enabled);
-    let listener_handle = start_listener(&tx, settings.show_mouse)?;
+    overlay.set_theme(settings.theme);
+    overlay.set_palette(settings.palette);
+    overlay.set_emphasis(settings.frequency_emphasis);
+    overlay.set_combo_format(ComboFormat::from_settings(&settings)?);
+    let devices = CapturedDevices::default();
+    let listener_handle = if settings.remote_receive.is_some() {
+        info!("Remote display receiver: local input is not captured");
+        None
+    } else if demo.is_some() {
+        info!("Demo mode: local input is not captured");
+        None
+    } else if stdin_events {
+        input::stdin::start_stdin_listener(tx.clone());
+        None
+    } else {
+        Some(start_listener(&tx, &listener_status_tx, &devices, &settings)?)
+    };
+    let app_filter_rules = compile_filter_rules(&settings.disabled_apps);
+    let profiles = ProfileSet::new(&settings.profiles);
+    let secure_input_rules = compile_filter_rules(&settings.secure_input_rules);
+    let hooks = HookSet::new(&settings.hooks);
+    let focus = focus::start_focus_tracker();
+    let (focus_rx, focus_backend) = focus
+        .map(|(rx, backend)| (Some(rx), Some(backend)))
+        .unwrap_or((None, None));
+    let (control_tx, control_rx) = async_channel::bounded(32);
+    let dbus_handle = dbus_service::start_dbus_service(control_tx.clone());
+    let socket_handle = match control_socket::start_control_socket(control_tx.clone()) {
+        Ok(handle) => Some(handle),
+        Err(e) => {
+            warn!("Control socket unavailable: {:#}", e);
+            None
+        }
+    };
 
+    let (capture_tx, capture_rx) = async_channel::bounded(4);
+    let practice = settings.practice_file.as_deref().and_then(load_practice);
+    let breaks = break_timer(&settings);
+
+    let waker = PumpWaker::default();
     let state = Rc::new(RefCell::new(AppState {
         settings,
         config_path,
         overlay,
         combo,
         input_tx: tx,
+        listener_status_tx,
+        listener_notice: None,
         listener_handle,
+        devices,
+        listener_watchdog: ListenerWatchdog::default(),
+        watchdog_checked: Instant::now(),
         tray_handle,
         settings_window: None,
+        heatmap_window: None,
+        permission_window: None,
+        rollover_window: None,
+        access_prompted: false,
         dragging: false,
         drag_base_x: 0,
         drag_base_y: 0,
+        app_filter_rules,
         app_filter_suppressed: false,
-        last_app_check: Instant::now(),
         app_filter_warned: false,
+        focus: None,
+        focus_backend,
+        layout_known: false,
+        profiles,
+        active_profile: None,
+        session_lock: SessionLock::default(),
+        overlay_hidden: false,
+        presenter: false,
+        screencast_rx: None,
+        screencast_active: false,
+        secure_input_rules,
+        secure_input_active: false,
+        picking_app: false,
+        filter_dot_shown: false,
+        dbus_handle,
+        socket_handle,
+        manually_hidden: false,
+        confirming_quit: false,
+        quit_confirmed: false,
+        event_stream: None,
+        json_output,
+        session_log: None,
+        markers: MarkerLog::new(),
+        hooks,
+        pinned_profile: None,
+        notifier: None,
+        position_dirty: false,
+        metrics_server: None,
+        frame_stream: None,
+        latency: None,
+        remote: None,
+        ime: None,
+        appearance: None,
+        gsettings: None,
+        color_scheme: ColorScheme::default(),
+        external_input: demo.is_some() || stdin_events,
+        obs: None,
+        obs_recording: false,
+        obs_streaming: false,
+        obs_pausing: false,
+        obs_paused: false,
+        schedule: Schedule::default(),
+        peek_key: None,
+        peeking: false,
+        schedule_pausing: false,
+        schedule_paused: false,
+        control_tx,
+        capture_tx,
+        capturing: false,
+        portal_shortcuts: None,
+        stats: None,
+        speed_text: None,
+        locks: LockState::default(),
+        lock_text: None,
+        modifier_panel: None,
+        layout_badge: None,
+        shortcut_counts: HashMap::new(),
+        tally: None,
+        frequency: ComboFrequency::default(),
+        practice,
+        practice_prompt: None,
+        breaks,
+        summary: None,
+        announcer: None,
+        render_pending: false,
+        last_render: None,
+        waker: waker.clone(),
     }));
 
     if let Some(handle) = &state.borrow().tray_handle {
         handle.set_drag_enabled(state.borrow().settings.drag_enabled);
     }
+    state.borrow().publish_status();
 
     {
         let state_begin = Rc::clone(&state);
@@ -118,25 +405,154 @@ fn build_ui(app: &Application, settings: Settings, config_path: PathBuf) -> Resu
         );
     }
 
-    start_event_pump(app.clone(), rx, tray_rx, Rc::clone(&state));
+    {
+        let mut app_state = state.borrow_mut();
+        app_state.apply_overlay_settings();
+        app_state.ensure_screencast_monitor();
+        app_state.update_event_stream();
+        app_state.update_metrics_server();
+        app_state.update_frame_stream();
+        app_state.update_latency_probe();
+        app_state.update_remote_receiver();
+        app_state.update_ime_monitor();
+        app_state.update_appearance_monitor();
+        app_state.update_settings_backend();
+        app_state.update_obs_monitor();
+        app_state.update_portal_shortcuts();
+        autostart::sync(app_state.settings.autostart, true);
+        app_state.update_session_log();
+        app_state.update_stats();
+        app_state.update_summary();
+        app_state.update_announcer();
+        app_state.update_plugins();
+        let _ = app_state.update_app_filter();
+        app_state.restore_state();
+        app_state.sync_visibility();
+        app_state.update_privacy_mask();
+    }
 
-    Ok(())
+    let sources = EventSources {
+        input: Inbox::new(rx, &waker),
+        listener: Inbox::new(listener_status_rx, &waker),
+        tray: Inbox::new(tray_rx.unwrap_or_else(|| async_channel::bounded(1).1), &waker),
+        focus: Inbox::new(focus_rx.unwrap_or_else(|| async_channel::bounded(1).1), &waker),
+        layout: Inbox::new(
+            focus_backend
+                .map(layout::start_layout_tracker)
+                .unwrap_or_else(|| async_channel::bounded(1).1),
+            &waker,
+        ),
+        session: Inbox::new(session::start_session_monitor(), &waker),
+        control: Inbox::new(control_rx, &waker),
+        capture: Inbox::new(capture_rx, &waker),
+        demo: Inbox::new(
+            demo.map(demo::start_demo)
+                .unwrap_or_else(|| async_channel::bounded(1).1),
+            &waker,
+        ),
+    };
+
+    start_event_pump(app.clone(), sources, Rc::clone(&state), &waker);
+
+    Ok(state)
+}
+
+/// Reports readiness to systemd.
+fn start_service(state: &Rc<RefCell<AppState>>) {
+    let notifier = Notifier::from_env();
+    match &notifier {
+        Some(notifier) => notifier.ready(),
+        None => warn!("--service given but NOTIFY_SOCKET is not set"),
+    }
+    if let Some(interval) = notifier.as_ref().and_then(Notifier::ping_interval) {
+        let state = Rc::clone(state);
+        glib::timeout_add_local(interval, move || {
+            if let Some(notifier) = &state.borrow().notifier {
+                notifier.ping_watchdog();
+            }
+            ControlFlow::Continue
+        });
+    }
+    state.borrow_mut().notifier = notifier;
+}
+
+/// Shuts down cleanly on SIGTERM/SIGINT, so the summary and a dragged
+/// position are not lost.
+fn handle_termination(app: &Application, state: &Rc<RefCell<AppState>>) {
+    for signal in [Signal::SIGTERM, Signal::SIGINT] {
+        let app = app.clone();
+        let state = Rc::clone(state);
+        glib::unix_signal_add_local(signal as i32, move || {
+            info!("Received {}, shutting down", signal);
+            state.borrow_mut().shutdown();
+            app.quit();
+            ControlFlow::Break
+        });
+    }
+}
+
+/// Applies CLI overrides from a second launch and brings up the settings window.
+fn forward_args(app: &Application, state: &Rc<RefCell<AppState>>, args: &[OsString]) {
+    match CliArgs::try_parse_from(args) {
+        Ok(cli) => {
+            let ignored = cli.startup_only();
+            if !ignored.is_empty() {
+                warn!(
+                    "Already running; {} only take effect on the first start",
+                    ignored.join(", ")
+                );
+            }
+            let mut app_state = state.borrow_mut();
+            let mut settings = app_state.settings.clone();
+            settings.apply_cli(&cli);
+            if let Err(e) = app_state.apply_settings(settings) {
+                warn!("Failed to apply forwarded arguments: {}", e);
+            }
+        }
+        Err(e) => warn!("Ignoring forwarded arguments: {}", e),
+    }
+    open_settings_window(app, Rc::clone(state));
+}
+
+/// Channels drained by the event pump; each wakes it when something arrives.
+struct EventSources {
+    input: Inbox<input::InputEvent>,
+    listener: Inbox<ListenerStatus>,
+    tray: Inbox<TrayAction>,
+    focus: Inbox<FocusInfo>,
+    layout: Inbox<String>,
+    session: Inbox<SessionEvent>,
+    control: Inbox<ControlCommand>,
+    capture: Inbox<CaptureEvent>,
+    demo: Inbox<DemoEvent>,
 }
 
+/// The pump runs when a channel delivers something and, while items are
+/// shown or a counter is settling, on a short timer. With nothing on screen
+/// it does not wake up at all.
 fn start_event_pump(
     app: Application,
-    rx: Receiver<input::InputEvent>,
-    tray_rx: Option<Receiver<TrayAction>>,
+    sources: EventSources,
     state: Rc<RefCell<AppState>>,
+    waker: &PumpWaker,
 ) {
-    let tray_rx = tray_rx.unwrap_or_else(|| async_channel::bounded(1).1);
-    glib::timeout_add_local(Duration::from_millis(16), move || {
+    let timer_waker = waker.clone();
+    let stopped = Cell::new(false);
+    waker.set_pump(move || {
+        if stopped.get() {
+            return;
+        }
         let mut changed = false;
         let mut paused_changed: Option<bool> = None;
         let mut open_settings = false;
-        let mut quit = false;
-
-        while let Ok(action) = tray_rx.try_recv() {
+        let mut open_heatmap = false;
+        let mut open_permission = false;
+        let mut open_rollover = false;
+        let mut remind_break = false;
+        let mut confirm_quit = false;
+        let mut quit = std::mem::take(&mut state.borrow_mut().quit_confirmed);
+
+        while let Some(action) = sources.tray.pop() {
             match action {
                 TrayAction::TogglePause => {
                     let mut app_state = state.borrow_mut();
@@ -145,9 +561,24 @@ fn start_event_pump(
                     }
                     paused_changed = Some(app_state.combo.paused());
                 }
+                TrayAction::TogglePresenter => {
+                    state.borrow_mut().toggle_presenter();
+                    changed = true;
+                }
                 TrayAction::OpenSettings => {
                     open_settings = true;
                 }
+                TrayAction::OpenHeatmap => {
+                    open_heatmap = true;
+                }
+                TrayAction::OpenRolloverTest => {
+                    open_rollover = true;
+                }
+                TrayAction::ClearStats => {
+                    if let Err(e) = state.borrow_mut().clear_stats() {
+                        warn!("{:#}", e);
+                    }
+                }
                 TrayAction::ToggleDrag => {
                     let mut app_state = state.borrow_mut();
                     app_state.toggle_drag();
@@ -155,77 +586,349 @@ fn start_event_pump(
                         handle.set_drag_enabled(app_state.settings.drag_enabled);
                     }
                 }
+                TrayAction::ToggleDevice(name) => {
+                    if state.borrow_mut().toggle_device(&name) {
+                        changed = true;
+                    }
+                }
                 TrayAction::Quit => {
-                    quit = true;
+                    if state.borrow().settings.confirm_quit {
+                        confirm_quit = true;
+                    } else {
+                        quit = true;
+                    }
                 }
             }
         }
 
         {
             let mut app_state = state.borrow_mut();
-            let now = Instant::now();
-            if app_state.update_app_filter(now) {
+            while let Some(command) = sources.control.pop() {
+                let action = app_state.handle_control(command);
+                apply_combo_action(&mut changed, &mut paused_changed, action);
+            }
+            while let Some(event) = sources.demo.pop() {
+                let shown = match event {
+                    DemoEvent::Combo(combo) => app_state.combo.show_remote(combo).render,
+                    DemoEvent::Announce(text) => app_state.combo.announce(text),
+                };
+                if shown {
+                    changed = true;
+                }
+            }
+            while let Some(event) = sources.capture.pop() {
+                if app_state.finish_capture(event) {
+                    changed = true;
+                }
+            }
+
+            let mut focus_changed = false;
+            while let Some(event) = sources.session.pop() {
+                if let Some(paused) = app_state.handle_session_event(event) {
+                    paused_changed = Some(paused);
+                    changed = true;
+                }
+            }
+
+            while let Some(info) = sources.focus.pop() {
+                if app_state.picking_app {
+                    app_state.finish_app_pick(&info);
+                }
+                if let Some(stats) = &mut app_state.stats {
+                    stats.set_app(Some(&info.class));
+                }
+                app_state.focus = Some(info);
+                focus_changed = true;
+            }
+            while let Some(name) = sources.layout.pop() {
+                // The first report is the layout already in use.
+                let announce = std::mem::replace(&mut app_state.layout_known, true);
+                if app_state.combo.set_layout(&name, announce) {
+                    changed = true;
+                }
+            }
+
+            if focus_changed {
+                app_state.update_profile();
+                app_state.update_privacy_mask();
+                if app_state.update_app_filter() {
+                    changed = true;
+                }
+                if app_state.sync_visibility() {
+                    changed = true;
+                }
+            }
+
+            if app_state.update_screencast() {
+                changed = true;
+            }
+
+            if app_state.drain_remote() {
+                changed = true;
+            }
+
+            if app_state.drain_appearance() {
+                changed = true;
+            }
+
+            if app_state.drain_gsettings() {
+                changed = true;
+            }
+
+            let (obs_changed, obs_paused) = app_state.drain_obs();
+            if obs_changed {
+                changed = true;
+            }
+            if obs_paused.is_some() {
+                paused_changed = obs_paused;
+            }
+
+            if let Some(paused) = app_state.update_schedule() {
+                paused_changed = Some(paused);
                 changed = true;
             }
 
-            while let Ok(event) = rx.try_recv() {
-                if app_state.app_filter_suppressed {
+            let portal_active = app_state
+                .portal_shortcuts
+                .as_ref()
+                .is_some_and(PortalShortcuts::is_active);
+            app_state.combo.set_hotkey_enabled(!portal_active);
+
+            let mut listener_changed = false;
+            while let Some(status) = sources.listener.pop() {
+                listener_changed = true;
+                if app_state.handle_listener_status(status) {
+                    changed = true;
+                }
+            }
+            if listener_changed {
+                app_state.update_portal_shortcuts();
+            }
+            if app_state.listener_notice == Some(NO_ACCESS_NOTICE)
+                && !std::mem::replace(&mut app_state.access_prompted, true)
+            {
+                open_permission = true;
+            }
+
+            while let Some(event) = sources.input.pop() {
+                app_state.update_peek(&event);
+                if app_state.check_break(&event) {
+                    remind_break = true;
+                    changed = true;
+                }
+                if let Some(window) = &app_state.rollover_window {
+                    window.handle_event(&event);
+                }
+                if app_state.capture_suppressed() {
                     app_state.combo.handle_event_suppressed(event);
                 } else {
+                    app_state.emit_input(&event);
+                    app_state.record_usage(&event);
                     let action = app_state.combo.handle_event(event);
+                    if let Some(combo) = &action.combo {
+                        app_state.publish_combo(combo);
+                        app_state.track_latency();
+                    }
+                    if action.bell && !app_state.overlay_hidden {
+                        app_state.overlay.flash_bell(app_state.settings.visual_bell_style);
+                    }
+                    if action.presenter_toggled {
+                        app_state.toggle_presenter();
+                        changed = true;
+                    }
+                    if action.screenshot && app_state.save_screenshot() {
+                        changed = true;
+                    }
+                    if action.marker && app_state.add_marker() {
+                        changed = true;
+                    }
                     apply_combo_action(&mut changed, &mut paused_changed, action);
                 }
             }
 
-            if !app_state.app_filter_suppressed && app_state.combo.prune_expired() {
+            // After the keys: a composition that starts takes back the key
+            // that started it.
+            if app_state.drain_ime() {
+                changed = true;
+            }
+
+            if !app_state.overlay_hidden && app_state.combo.prune_expired() {
+                changed = true;
+            }
+
+            if app_state.update_speed() {
+                changed = true;
+            }
+
+            if app_state.update_locks() {
                 changed = true;
             }
 
-            if changed && !app_state.app_filter_suppressed {
-                app_state
-                    .overlay
-                    .render(app_state.combo.items(), app_state.combo.paused());
+            if app_state.check_listeners() {
+                changed = true;
+            }
+
+            if app_state.update_modifier_panel() {
+                changed = true;
+            }
+
+            if app_state.update_layout_badge() {
+                changed = true;
+            }
+
+            if app_state.update_tally() {
+                changed = true;
+            }
+
+            if app_state.update_practice_prompt() {
+                changed = true;
+            }
+
+            if changed && !app_state.overlay_hidden {
+                app_state.render_pending = true;
+            }
+            app_state.render_if_due();
+
+            if changed || focus_changed || paused_changed.is_some() {
+                app_state.publish_status();
+            }
+
+            if let Some(stats) = &mut app_state.stats {
+                stats.tick();
+            }
+
+            if let Some(delay) = app_state.next_tick() {
+                timer_waker.wake_after(delay);
             }
         }
 
-        if let Some(paused) = paused_changed {
-            if let Some(handle) = &state.borrow().tray_handle {
+        if let Some(handle) = &state.borrow().tray_handle {
+            if let Some(paused) = paused_changed {
                 handle.set_paused(paused);
             }
+            // Everything this run changed goes out as one menu update.
+            handle.flush();
         }
 
         if open_settings {
             open_settings_window(&app, Rc::clone(&state));
         }
 
+        if open_heatmap {
+            open_heatmap_window(&app, Rc::clone(&state));
+        }
+
+        if open_permission {
+            open_permission_window(&app, &state);
+        }
+
+        if open_rollover {
+            open_rollover_window(&app, &state);
+        }
+
+        if remind_break {
+            send_break_notification(&app);
+        }
+
+        if confirm_quit && !quit {
+            confirm_quit_dialog(&state, &timer_waker);
+        }
+
         if quit {
+            stopped.set(true);
+            state.borrow_mut().shutdown();
             app.quit();
-            return ControlFlow::Break;
         }
-
-        ControlFlow::Continue
     });
+    waker.wake();
 }
 
-fn init_logging() {
-    let filter = EnvFilter::try_from_default_env()
-        .unwrap_or_else(|_| EnvFilter::new("info,keyway_visualizer=debug"));
+/// `default` applies unless RUST_LOG is set.
+fn init_logging(default: &str) {
+    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(default));
 
     tracing_subscriber::registry()
         .with(filter)
-        .with(tracing_subscriber::fmt::layer().with_target(false))
+        .with(
+            tracing_subscriber::fmt::layer()
+                .with_target(false)
+                .with_writer(std::io::stderr),
+        )
         .init();
 }
 
-fn start_listener(tx: &Sender<input::InputEvent>, include_mouse: bool) -> Result<input::ListenerHandle> {
+fn start_listener(
+    tx: &Sender<input::InputEvent>,
+    status_tx: &Sender<ListenerStatus>,
+    devices: &CapturedDevices,
+    settings: &Settings,
+) -> Result<input::ListenerHandle> {
     let listener = InputListener::new(
         tx.clone(),
+        status_tx.clone(),
         ListenerConfig {
             all_keyboards: true,
-            include_mouse,
+            include_mouse: settings.show_mouse,
+            devices: devices.clone(),
+            sandbox: settings.sandbox_listener,
         },
     );
-    listener.start()
+    if !settings.listener_helper {
+        if input::x11::is_x11_session() {
+            return listener.start_x11();
+        }
+        return listener.start();
+    }
+    let program = match settings.listener_helper_path.clone() {
+        Some(path) if !path.as_os_str().is_empty() => path,
+        _ => std::env::current_exe().context("Failed to find this executable")?,
+    };
+    listener.start_helper(&program)
+}
+
+/// An empty hotkey setting turns that hotkey off.
+fn parse_optional_hotkey(text: &str) -> Result<Option<Hotkey>> {
+    if text.trim().is_empty() {
+        return Ok(None);
+    }
+    Hotkey::parse(text).map(Some)
+}
+
+fn break_timer(settings: &Settings) -> Option<BreakTimer> {
+    let minutes = |n: u64| Duration::from_secs(n * 60);
+    (settings.break_reminder_minutes > 0).then(|| {
+        BreakTimer::new(
+            minutes(settings.break_reminder_minutes),
+            minutes(settings.break_idle_minutes.max(1)),
+        )
+    })
+}
+
+/// The practice list, or `None` with a warning when it cannot be used. An
+/// empty path turns practice mode off.
+fn load_practice(path: &Path) -> Option<Practice> {
+    if path.as_os_str().is_empty() {
+        return None;
+    }
+    match Practice::load(path) {
+        Ok(practice) => Some(practice),
+        Err(e) => {
+            warn!("Practice mode is off: {:#}", e);
+            None
+        }
+    }
+}
+
+fn parse_bell_keys(keys: &[String]) -> Vec<Hotkey> {
+    keys.iter()
+        .filter_map(|key| match Hotkey::parse(key) {
+            Ok(hotkey) => Some(hotkey),
+            Err(e) => {
+                warn!("Ignoring visual bell key {:?}: {:#}", key, e);
+                None
+            }
+        })
+        .collect()
 }
 
 fn apply_combo_action(
@@ -264,6 +967,34 @@ fn open_settings_window(app: &Application, state: Rc<RefCell<AppState>>) {
             apply_settings_from_window(&window_save, &state_save, true);
         });
 
+        let window_pick = Rc::clone(&window);
+        let state_pick = Rc::clone(&state);
+        window.connect_pick(move || {
+            let mut app_state = state_pick.borrow_mut();
+            if app_state.focus_backend.is_none() {
+                window_pick.set_status("Picking needs Hyprland or Sway focus tracking");
+                return;
+            }
+            app_state.picking_app = true;
+            window_pick.set_status("Focus the window to add...");
+        });
+
+        let window_clear = Rc::clone(&window);
+        let state_clear = Rc::clone(&state);
+        window.connect_clear_stats(move || {
+            let result = state_clear.borrow_mut().clear_stats();
+            match result {
+                Ok(()) => window_clear.set_status(i18n::tr("Statistics cleared")),
+                Err(e) => window_clear.set_status(&format!("Error: {:#}", e)),
+            }
+        });
+
+        let app_access = app.clone();
+        let state_access = Rc::clone(&state);
+        window.connect_fix_access(move || {
+            open_permission_window(&app_access, &state_access);
+        });
+
         let window_close = Rc::clone(&window);
         window.connect_close(move || {
             window_close.window.set_visible(false);
@@ -276,6 +1007,94 @@ fn open_settings_window(app: &Application, state: Rc<RefCell<AppState>>) {
     window.present();
 }
 
+fn open_heatmap_window(app: &Application, state: Rc<RefCell<AppState>>) {
+    let existing = state.borrow().heatmap_window.clone();
+    let window = match existing {
+        Some(window) => window,
+        None => {
+            let window = Rc::new(HeatmapWindow::new(app));
+            let window_refresh = Rc::clone(&window);
+            let state_refresh = Rc::clone(&state);
+            window.connect_refresh(move || {
+                refresh_heatmap(&window_refresh, &state_refresh);
+            });
+            state.borrow_mut().heatmap_window = Some(Rc::clone(&window));
+            window
+        }
+    };
+
+    refresh_heatmap(&window, &state);
+    window.present();
+}
+
+fn open_permission_window(app: &Application, state: &Rc<RefCell<AppState>>) {
+    let window = Rc::clone(
+        state
+            .borrow_mut()
+            .permission_window
+            .get_or_insert_with(|| PermissionWindow::new(app)),
+    );
+    window.present();
+}
+
+/// Asks before quitting; a yes is picked up by the next pump run, which
+/// quits the way an unconfirmed Quit would.
+fn confirm_quit_dialog(state: &Rc<RefCell<AppState>>, waker: &PumpWaker) {
+    if std::mem::replace(&mut state.borrow_mut().confirming_quit, true) {
+        return;
+    }
+    let dialog = gtk4::AlertDialog::builder()
+        .message(i18n::tr("Quit Keyway Visualizer?"))
+        .detail(i18n::tr("The overlay stops showing keys until it is started again."))
+        .buttons([i18n::tr("Cancel"), i18n::tr("Quit")])
+        .cancel_button(0)
+        .default_button(1)
+        .build();
+    let state = Rc::clone(state);
+    let waker = waker.clone();
+    dialog.choose(None::<&gtk4::Window>, None::<&gio::Cancellable>, move |choice| {
+        let mut app_state = state.borrow_mut();
+        app_state.confirming_quit = false;
+        if choice.is_ok_and(|button| button == 1) {
+            app_state.quit_confirmed = true;
+            waker.wake();
+        }
+    });
+}
+
+/// The desktop notification that goes with the break reminder bubble.
+fn send_break_notification(app: &Application) {
+    let notification = gio::Notification::new(i18n::tr("Time for a break"));
+    notification.set_body(Some(i18n::tr("Rest your hands and eyes.")));
+    app.send_notification(Some("break-reminder"), &notification);
+}
+
+fn open_rollover_window(app: &Application, state: &Rc<RefCell<AppState>>) {
+    let window = Rc::clone(
+        state
+            .borrow_mut()
+            .rollover_window
+            .get_or_insert_with(|| RolloverWindow::new(app)),
+    );
+    window.present();
+}
+
+fn refresh_heatmap(window: &HeatmapWindow, state: &Rc<RefCell<AppState>>) {
+    let mut app_state = state.borrow_mut();
+    if app_state.settings.stats_categories_only {
+        window.set_status("Statistics only record key categories, so there is no heatmap.");
+        return;
+    }
+    let Some(stats) = &mut app_state.stats else {
+        window.set_status("Statistics are off. Set stats_enabled = true to build a heatmap.");
+        return;
+    };
+    match stats.key_totals(window.selected_days(), None) {
+        Ok(counts) => window.set_counts(counts),
+        Err(e) => window.set_status(&format!("Failed to read statistics: {:#}", e)),
+    }
+}
+
 fn apply_settings_from_window(window: &SettingsWindow, state: &Rc<RefCell<AppState>>, save: bool) {
     let base_settings = state.borrow().settings.clone();
     let new_settings = window.read_settings(&base_settings);
@@ -296,24 +1115,24 @@ fn apply_settings_from_window(window: &SettingsWindow, state: &Rc<RefCell<AppSta
     match result {
         Ok(_) => {
             if save {
-                let (settings, path) = {
+                let saved = {
                     let app_state = state.borrow();
-                    (app_state.settings.clone(), app_state.config_path.clone())
+                    app_state.save_config(&app_state.settings)
                 };
-                if let Err(e) = settings.save_to(&path) {
+                if let Err(e) = saved {
                     window.set_status(&format!("Save failed: {}", e));
                     return;
                 }
                 if warn_empty_filter {
                     window.set_status("Saved (app filter enabled but list is empty)");
                 } else {
-                    window.set_status("Saved");
+                    window.set_status(i18n::tr("Saved"));
                 }
             } else {
                 if warn_empty_filter {
                     window.set_status("Applied (app filter enabled but list is empty)");
                 } else {
-                    window.set_status("Applied");
+                    window.set_status(i18n::tr("Applied"));
                 }
             }
         }
@@ -329,27 +1148,163 @@ struct AppState {
     overlay: OverlayWindow,
     combo: ComboState,
     input_tx: Sender<input::InputEvent>,
-    listener_handle: input::ListenerHandle,
+    listener_status_tx: Sender<ListenerStatus>,
+    /// Status message explaining why no keys are captured
+    listener_notice: Option<&'static str>,
+    listener_handle: Option<input::ListenerHandle>,
+    /// Devices being listened to, and the ones muted from the tray
+    devices: CapturedDevices,
+    listener_watchdog: ListenerWatchdog,
+    watchdog_checked: Instant,
     tray_handle: Option<TrayHandle>,
     settings_window: Option<Rc<SettingsWindow>>,
+    heatmap_window: Option<Rc<HeatmapWindow>>,
+    permission_window: Option<Rc<PermissionWindow>>,
+    rollover_window: Option<Rc<RolloverWindow>>,
+    /// The access dialog opens by itself once per run
+    access_prompted: bool,
     dragging: bool,
     drag_base_x: i32,
     drag_base_y: i32,
+    app_filter_rules: Vec<FilterRule>,
     app_filter_suppressed: bool,
-    last_app_check: Instant,
     app_filter_warned: bool,
+    focus: Option<FocusInfo>,
+    focus_backend: Option<FocusBackend>,
+    /// Set once the compositor has reported the current layout
+    layout_known: bool,
+    profiles: ProfileSet,
+    active_profile: Option<usize>,
+    session_lock: SessionLock,
+    overlay_hidden: bool,
+    /// Presenter mode, layered over the settings until toggled off
+    presenter: bool,
+    screencast_rx: Option<Inbox<bool>>,
+    screencast_active: bool,
+    secure_input_rules: Vec<FilterRule>,
+    secure_input_active: bool,
+    picking_app: bool,
+    filter_dot_shown: bool,
+    dbus_handle: DbusHandle,
+    socket_handle: Option<SocketHandle>,
+    manually_hidden: bool,
+    /// The quit confirmation is open
+    confirming_quit: bool,
+    /// Set by the quit confirmation, for the pump to act on
+    quit_confirmed: bool,
+    event_stream: Option<EventStream>,
+    json_output: Option<Rc<JsonOutput>>,
+    session_log: Option<SessionLog>,
+    markers: MarkerLog,
+    hooks: HookSet,
+    /// Profile chosen over the control interface, overriding focus matching
+    pinned_profile: Option<String>,
+    notifier: Option<Notifier>,
+    /// Dragged position not yet written to the config
+    position_dirty: bool,
+    metrics_server: Option<MetricsServer>,
+    frame_stream: Option<Rc<FrameStream>>,
+    latency: Option<LatencyProbe>,
+    /// Receiver-mode connection: sender URL and token, and its combos
+    remote: Option<((String, String), Inbox<ComboEvent>)>,
+    /// Input method watcher, with `ime_text`
+    ime: Option<Inbox<ime::ImeEvent>>,
+    /// Color scheme watcher, while a theme is `auto`
+    appearance: Option<Inbox<ColorScheme>>,
+    color_scheme: ColorScheme,
+    /// dconf store and its changes, with `settings_backend = "gsettings"`
+    gsettings: Option<(GSettingsStore, Inbox<()>)>,
+    /// Input comes from a demo script or stdin, so devices stay closed
+    external_input: bool,
+    /// obs-websocket connection: URL and password, and its output changes
+    obs: Option<((String, String), Inbox<ObsEvent>)>,
+    obs_recording: bool,
+    obs_streaming: bool,
+    /// OBS output state currently calls for a pause
+    obs_pausing: bool,
+    /// The pause came from OBS, so it is lifted when an output starts
+    obs_paused: bool,
+    schedule: Schedule,
+    /// Hold-to-peek key; the overlay only shows while it is down
+    peek_key: Option<Key>,
+    peeking: bool,
+    /// Outside `active_hours` right now
+    schedule_pausing: bool,
+    /// The pause came from the schedule, so it is lifted when a window opens
+    schedule_paused: bool,
+    /// Feeds the D-Bus, socket and portal shortcut commands to the pump
+    control_tx: Sender<ControlCommand>,
+    /// Where recordings report back, drained by the pump
+    capture_tx: Sender<CaptureEvent>,
+    /// A recording is under way; only one runs at a time
+    capturing: bool,
+    portal_shortcuts: Option<PortalShortcuts>,
+    stats: Option<StatsStore>,
+    /// What the speed widget currently shows
+    speed_text: Option<(String, &'static str)>,
+    /// Lock state last sent to event consumers
+    locks: LockState,
+    /// What the lock widget currently shows
+    lock_text: Option<&'static str>,
+    /// What the modifier panel currently shows
+    modifier_panel: Option<[bool; 4]>,
+    /// What the layout badge currently shows
+    layout_badge: Option<String>,
+    /// Shortcuts shown this session, for the tally panel
+    shortcut_counts: HashMap<String, u64>,
+    tally: Option<Vec<(String, u64)>>,
+    /// Loaded from `practice_file`, or `None` outside practice mode
+    practice: Option<Practice>,
+    /// What the practice prompt currently shows
+    practice_prompt: Option<(String, Option<String>)>,
+    /// `None` while break reminders are off
+    breaks: Option<BreakTimer>,
+    /// Every combo shown this session, for `frequency_emphasis`
+    frequency: ComboFrequency,
+    summary: Option<SessionSummary>,
+    announcer: Option<Announcer>,
+    /// A change not yet drawn because of `max_refresh_hz`
+    render_pending: bool,
+    last_render: Option<Instant>,
+    waker: PumpWaker,
 }
 
 impl AppState {
     fn apply_settings(&mut self, new_settings: Settings) -> Result<()> {
         let hotkey = Hotkey::parse(&new_settings.pause_hotkey)?;
-
-        if new_settings.show_mouse != self.settings.show_mouse {
-            let new_handle = start_listener(&self.input_tx, new_settings.show_mouse)?;
-            self.listener_handle = new_handle;
+        let presenter_hotkey = parse_optional_hotkey(&new_settings.presenter_hotkey)?;
+        let screenshot_hotkey = parse_optional_hotkey(&new_settings.screenshot_hotkey)?;
+        let marker_hotkey = parse_optional_hotkey(&new_settings.marker_hotkey)?;
+        self.schedule = Schedule::parse(&new_settings.active_hours)?;
+        // Checked here; profiles can switch the notation but not the format.
+        ComboFormat::from_settings(&new_settings)?;
+        let peek_key = match new_settings.peek_key.trim() {
+            "" => None,
+            name => Some(input::key_from_name(name)?),
+        };
+        if peek_key != self.peek_key {
+            self.peek_key = peek_key;
+            self.peeking = false;
+        }
+        self.overlay.set_peek_hidden(self.peek_key.is_some() && !self.peeking);
+
+        if new_settings.remote_receive.is_some() || self.external_input {
+            self.listener_handle = None;
+        } else if self.listener_handle.is_none()
+            || new_settings.show_mouse != self.settings.show_mouse
+            || new_settings.listener_helper != self.settings.listener_helper
+            || new_settings.listener_helper_path != self.settings.listener_helper_path
+            || new_settings.sandbox_listener != self.settings.sandbox_listener
+        {
+            let new_handle = start_listener(
+                &self.input_tx,
+                &self.listener_status_tx,
+                &self.devices,
+                &new_settings,
+            )?;
+            self.listener_handle = Some(new_handle);
         }
 
-        self.overlay.update_position(&new_settings);
         self.overlay.set_drag_enabled(new_settings.drag_enabled);
         if let Some(handle) = &self.tray_handle {
             handle.set_drag_enabled(new_settings.drag_enabled);
@@ -362,41 +1317,1402 @@ impl AppState {
             Duration::from_millis(new_settings.modifier_grace_ms),
             hotkey,
         );
-
+        self.combo.set_presenter_hotkey(presenter_hotkey);
+        self.combo.set_screenshot_hotkey(screenshot_hotkey);
+        self.combo.set_marker_hotkey(marker_hotkey);
+        self.combo.set_bell_keys(parse_bell_keys(&new_settings.visual_bell_keys));
+        self.combo.set_teaching(new_settings.teaching_mode, &new_settings.aliases);
+        self.combo.set_device_tags(&new_settings.device_tags);
+        self.combo.set_show_composed(new_settings.show_composed);
+        self.combo.set_lock_announcements(new_settings.lock_announcements);
+        self.combo.set_mouse_drag(new_settings.mouse_drag);
+        if new_settings.ghost_preview != self.settings.ghost_preview {
+            self.combo.set_ghost_preview(new_settings.ghost_preview);
+        }
+        if new_settings.break_reminder_minutes != self.settings.break_reminder_minutes
+            || new_settings.break_idle_minutes != self.settings.break_idle_minutes
+        {
+            self.breaks = break_timer(&new_settings);
+        }
+        if new_settings.practice_file != self.settings.practice_file {
+            self.practice = new_settings.practice_file.as_deref().and_then(load_practice);
+        }
+        self.overlay.set_emphasis(new_settings.frequency_emphasis);
+        i18n::set_language(&new_settings.language);
+
+        self.app_filter_rules = compile_filter_rules(&new_settings.disabled_apps);
+        self.profiles = ProfileSet::new(&new_settings.profiles);
+        self.secure_input_rules = compile_filter_rules(&new_settings.secure_input_rules);
+        self.hooks = HookSet::new(&new_settings.hooks);
+        self.overlay.update_layer(&new_settings);
+        if new_settings.autostart != self.settings.autostart {
+            autostart::sync(new_settings.autostart, false);
+        }
         self.settings = new_settings;
+        self.active_profile = None;
+        self.update_profile();
+        self.apply_overlay_settings();
         self.app_filter_warned = false;
-        self.last_app_check = Instant::now()
-            .checked_sub(Duration::from_millis(1000))
-            .unwrap_or_else(Instant::now);
-        let _ = self.update_app_filter(Instant::now());
-        self.overlay.render(self.combo.items(), self.combo.paused());
+        self.ensure_screencast_monitor();
+        self.update_event_stream();
+        self.update_metrics_server();
+        self.update_frame_stream();
+        self.update_latency_probe();
+        self.update_remote_receiver();
+        self.update_ime_monitor();
+        self.update_appearance_monitor();
+        self.update_settings_backend();
+        self.update_obs_monitor();
+        self.update_portal_shortcuts();
+        self.update_session_log();
+        self.update_stats();
+        self.update_summary();
+        self.update_announcer();
+        self.update_plugins();
+        let _ = self.update_app_filter();
+        self.sync_visibility();
+        self.update_privacy_mask();
+        self.render_pending = true;
+        self.render_if_due();
+        // Let the pump pick up timers the new settings need, such as the
+        // speed widget's or a render held back by the refresh limit.
+        self.waker.wake();
 
         Ok(())
     }
 
-    fn toggle_drag(&mut self) {
-        self.settings.drag_enabled = !self.settings.drag_enabled;
-        self.overlay.set_drag_enabled(self.settings.drag_enabled);
-        if let Some(window) = &self.settings_window {
-            window.set_from_settings(&self.settings);
+    fn effective_settings(&self) -> Settings {
+        let mut settings = self.profiles.effective(&self.settings, self.active_profile);
+        if self.presenter {
+            settings.apply_presenter();
         }
+        if settings.theme == Theme::Auto {
+            settings.theme = self.color_scheme.theme();
+        }
+        settings
     }
 
-    fn begin_drag(&mut self) {
-        if !self.settings.drag_enabled {
-            return;
+    /// Pushes the profile-adjusted settings to the overlay and combo state.
+    fn apply_overlay_settings(&mut self) {
+        let effective = self.effective_settings();
+        self.overlay.update_position(&effective);
+        self.overlay.set_theme(effective.theme);
+        self.overlay.set_palette(effective.palette);
+        self.overlay.set_combo_format(ComboFormat::from_settings(&effective).ok().flatten());
+        self.combo.set_display_mode(effective.display_mode);
+        self.combo.set_ttl(Duration::from_millis(effective.ttl_ms));
+    }
+
+    fn toggle_presenter(&mut self) {
+        self.presenter = !self.presenter;
+        info!("Presenter mode {}", if self.presenter { "on" } else { "off" });
+        self.overlay.set_presenter(self.presenter);
+        self.apply_overlay_settings();
+        if let Some(handle) = &self.tray_handle {
+            handle.set_presenter(self.presenter);
         }
+        let (shown, hidden) = if self.presenter {
+            ("Presenter mode", "Presenter mode off")
+        } else {
+            ("Presenter mode off", "Presenter mode")
+        };
+        self.combo.dismiss_status(i18n::tr(hidden));
+        self.combo.show_status(i18n::tr(shown), PRESENTER_NOTICE_TTL);
+    }
 
+    /// Records the overlay's current rectangle. Status messages only follow
+    /// the recording, so they do not end up in it.
+    fn start_capture(&mut self, seconds: u32, format: CaptureFormat) -> bool {
+        if self.capturing {
+            warn!("A recording is already running");
+            return false;
+        }
+        let Some(geometry) = self.overlay.monitor_geometry() else {
+            warn!("Cannot record: the overlay is not on a monitor");
+            return false;
+        };
+        let effective = self.effective_settings();
         let (window_w, window_h) = self.overlay.window_size();
-        let geometry = match self.overlay.monitor_geometry() {
-            Some(g) => g,
-            None => return,
+        let (x, y) = compute_custom_offsets(
+            effective.position,
+            effective.margin,
+            effective.custom_x,
+            effective.custom_y,
+            window_w,
+            window_h,
+            geometry.width(),
+            geometry.height(),
+        );
+        let Some(region) = CropRegion::new(
+            (x, y, window_w, window_h),
+            (geometry.width(), geometry.height()),
+            CAPTURE_PADDING,
+        ) else {
+            warn!("Cannot record: the overlay has no size yet");
+            return false;
         };
 
-        let monitor_w = geometry.width();
-        let monitor_h = geometry.height();
-
-        let (base_x, base_y) = compute_custom_offsets(
+        let dir = self.capture_dir();
+        info!("Recording the overlay for {}s", seconds);
+        self.capturing = true;
+        capture::start_capture(
+            CaptureRequest {
+                seconds,
+                format,
+                region,
+                dir,
+            },
+            self.capture_tx.clone(),
+        );
+        false
+    }
+
+    /// Saves the bubbles on screen to a PNG and names it in a status
+    /// message, which is not part of the picture.
+    fn save_screenshot(&mut self) -> bool {
+        let dir = self.capture_dir();
+        let path = dir.join(format!("keyway-{}.png", combo::unix_millis()));
+        let saved = std::fs::create_dir_all(&dir)
+            .with_context(|| format!("Failed to create {:?}", dir))
+            .and_then(|()| self.overlay.save_png(&path));
+        let text = match saved {
+            Ok(()) => {
+                info!("Saved overlay screenshot to {:?}", path);
+                let name = path.file_name().unwrap_or_default().to_string_lossy();
+                format!("{} {}", i18n::tr("Saved"), name)
+            }
+            Err(e) => {
+                warn!("Overlay screenshot failed: {:#}", e);
+                i18n::tr("Screenshot failed").to_string()
+            }
+        };
+        self.combo.show_status(&text, CAPTURE_NOTICE_TTL)
+    }
+
+    /// Stops or resumes showing input from every device called `name`.
+    fn toggle_device(&mut self, name: &str) -> bool {
+        let muted = self.devices.toggle_muted(name);
+        if let Some(handle) = &mut self.listener_handle {
+            handle.sync_muted(name, muted);
+        }
+        info!("{} {}", if muted { "Muted" } else { "Unmuted" }, name);
+        if let Some(handle) = &self.tray_handle {
+            handle.set_devices(self.devices.list());
+        }
+        let text = format!("{}: {}", i18n::tr(if muted { "Muted" } else { "Unmuted" }), name);
+        self.combo.show_status(&text, DEVICE_NOTICE_TTL)
+    }
+
+    /// Drops a timeline marker and flashes its number.
+    fn add_marker(&mut self) -> bool {
+        let path = self
+            .settings
+            .marker_file
+            .clone()
+            .unwrap_or_else(markers::default_marker_path);
+        match self.markers.append(&path) {
+            Ok(number) => {
+                info!("Marker {} written to {:?}", number, path);
+                let text = format!("{} {}", i18n::tr("Marker"), number);
+                self.combo.show_status(&text, MARKER_NOTICE_TTL)
+            }
+            Err(e) => {
+                warn!("Failed to write a marker: {:#}", e);
+                false
+            }
+        }
+    }
+
+    fn capture_dir(&self) -> PathBuf {
+        self.settings.capture_dir.clone().unwrap_or_else(|| {
+            dirs::video_dir()
+                .or_else(dirs::home_dir)
+                .unwrap_or_else(std::env::temp_dir)
+                .join("keyway")
+        })
+    }
+
+    fn finish_capture(&mut self, event: CaptureEvent) -> bool {
+        self.capturing = false;
+        let text = match event {
+            CaptureEvent::Finished(path) => {
+                let name = path.file_name().unwrap_or_default().to_string_lossy();
+                format!("{} {}", i18n::tr("Saved"), name)
+            }
+            CaptureEvent::Failed => i18n::tr("Recording failed").to_string(),
+        };
+        self.combo.show_status(&text, CAPTURE_NOTICE_TTL)
+    }
+
+    /// Puts back what the last run left paused, hidden or pinned.
+    fn restore_state(&mut self) {
+        if !self.settings.restore_state {
+            return;
+        }
+        let path = saved_state::default_state_path();
+        let saved = match SavedState::load(&path) {
+            Ok(saved) => saved,
+            Err(e) => {
+                warn!("Not restoring the last session: {:#}", e);
+                return;
+            }
+        };
+        if saved.paused && self.combo.set_paused(true) {
+            info!("Restored: paused");
+            if let Some(handle) = &self.tray_handle {
+                handle.set_paused(true);
+            }
+        }
+        if saved.hidden {
+            info!("Restored: hidden");
+            self.manually_hidden = true;
+        }
+        match saved.profile {
+            Some(name) if self.profiles.find_by_name(&name).is_some() => {
+                info!("Restored: profile {:?} pinned", name);
+                self.pinned_profile = Some(name);
+                self.update_profile();
+            }
+            Some(name) => info!("Pinned profile {:?} no longer exists", name),
+            None => {}
+        }
+    }
+
+    fn save_state(&self) {
+        let state = SavedState {
+            paused: self.combo.paused(),
+            hidden: self.manually_hidden,
+            profile: self.pinned_profile.clone(),
+        };
+        if let Err(e) = state.save(&saved_state::default_state_path()) {
+            warn!("Failed to save state: {:#}", e);
+        }
+    }
+
+    fn update_profile(&mut self) {
+        let active = match &self.pinned_profile {
+            Some(name) => self.profiles.find_by_name(name),
+            None => self
+                .focus
+                .as_ref()
+                .and_then(|info| self.profiles.find(&info.class, &info.title)),
+        };
+
+        if active == self.active_profile {
+            return;
+        }
+
+        match active.and_then(|i| self.profiles.name(i)) {
+            Some(name) => info!("Switching to profile {:?}", name),
+            None => info!("Switching to default profile"),
+        }
+        self.active_profile = active;
+        self.apply_overlay_settings();
+    }
+
+    /// Input is still tracked (modifier state, xkb) but nothing is displayed.
+    fn capture_suppressed(&self) -> bool {
+        self.overlay_hidden || (self.session_lock.is_locked() && self.settings.pause_on_lock)
+    }
+
+    fn screencast_hides(&self) -> bool {
+        match self.settings.screencast_mode {
+            ScreencastMode::Ignore | ScreencastMode::MaskWhileCasting => false,
+            ScreencastMode::ShowWhileCasting => !self.screencast_active,
+            ScreencastMode::HideWhileCasting => self.screencast_active,
+        }
+    }
+
+    fn fullscreen_hides(&self) -> bool {
+        self.settings.hide_on_fullscreen && self.focus.as_ref().is_some_and(|info| info.fullscreen)
+    }
+
+    /// Shows or hides the overlay window for every visibility rule at once,
+    /// clearing pending items when it goes away.
+    fn sync_visibility(&mut self) -> bool {
+        let hidden = self.manually_hidden
+            || self.app_filter_suppressed
+            || self.screencast_hides()
+            || self.fullscreen_hides()
+            || self.obs_holds(ObsReaction::Hide);
+        // Only the app filter gets the "hidden for this app" dot; the other
+        // rules hide the overlay on purpose.
+        let dot = hidden
+            && self.app_filter_suppressed
+            && !self.manually_hidden
+            && !self.screencast_hides()
+            && !self.fullscreen_hides()
+            && !self.obs_holds(ObsReaction::Hide)
+            && self.settings.filter_indicator;
+        // The filter can change while another rule keeps the overlay hidden.
+        if let Some(handle) = &self.tray_handle {
+            handle.set_filter_suppressed(self.app_filter_suppressed);
+        }
+        if hidden == self.overlay_hidden && dot == self.filter_dot_shown {
+            return false;
+        }
+
+        self.overlay_hidden = hidden;
+        self.filter_dot_shown = dot;
+        if hidden {
+            self.combo.clear_items();
+        }
+        if dot {
+            self.overlay.render_filter_dot();
+        }
+        self.overlay.set_visible(!hidden || dot);
+        true
+    }
+
+    fn handle_control(&mut self, command: ControlCommand) -> ComboAction {
+        let mut action = ComboAction::default();
+        match command {
+            ControlCommand::Pause | ControlCommand::Resume | ControlCommand::Toggle => {
+                let paused = match command {
+                    ControlCommand::Pause => true,
+                    ControlCommand::Resume => false,
+                    _ => !self.combo.paused(),
+                };
+                if self.combo.set_paused(paused) {
+                    action.render = true;
+                    action.paused_changed = Some(paused);
+                }
+            }
+            ControlCommand::Clear => {
+                self.combo.clear_items();
+                action.render = true;
+            }
+            ControlCommand::Show | ControlCommand::Hide => {
+                self.manually_hidden = matches!(command, ControlCommand::Hide);
+                action.render = self.sync_visibility();
+            }
+            ControlCommand::SetPosition(position) => {
+                let mut settings = self.settings.clone();
+                settings.position = position;
+                if let Err(e) = self.apply_settings(settings) {
+                    warn!("Failed to set position: {}", e);
+                }
+            }
+            ControlCommand::Announce(text) => {
+                action.render = self.combo.announce(text);
+            }
+            ControlCommand::SetProfile(name) => {
+                if let Some(name) = &name {
+                    if self.profiles.find_by_name(name).is_none() {
+                        warn!("Unknown profile {:?}", name);
+                        return action;
+                    }
+                }
+                self.pinned_profile = name;
+                self.update_profile();
+                action.render = true;
+            }
+            ControlCommand::Capture { seconds, format } => {
+                action.render = self.start_capture(seconds, format);
+            }
+            ControlCommand::Screenshot => {
+                action.render = self.save_screenshot();
+            }
+            ControlCommand::ReloadConfig => {
+                match self.read_config().and_then(|s| self.apply_settings(s)) {
+                    Ok(()) => {
+                        info!("Reloaded config from {:?}", self.config_path);
+                        if let Some(window) = &self.settings_window {
+                            window.set_from_settings(&self.settings);
+                        }
+                    }
+                    Err(e) => warn!("Failed to reload config: {:#}", e),
+                }
+                action.render = true;
+            }
+        }
+        action
+    }
+
+    fn publish_status(&self) {
+        let status = ControlStatus {
+            paused: self.combo.paused(),
+            visible: !self.overlay_hidden,
+            position: self.effective_settings().position,
+            profile: self
+                .active_profile
+                .and_then(|i| self.profiles.name(i))
+                .map(str::to_string),
+        };
+        self.dbus_handle.set_status(&status);
+        if let Some(handle) = &self.socket_handle {
+            handle.set_status(&status);
+        }
+    }
+
+    /// Masks plain keys when requested manually, while a secure-input app
+    /// (password prompt, sign-in page) has focus, or while the screen is
+    /// shared with `screencast_mode = "mask-while-casting"`.
+    fn update_privacy_mask(&mut self) {
+        let secure = self.settings.secure_input_enabled
+            && self.focus.as_ref().is_some_and(|info| {
+                app_filter::any_matches(&self.secure_input_rules, &info.class, &info.title)
+            });
+
+        if secure != self.secure_input_active {
+            self.secure_input_active = secure;
+            if secure {
+                info!("Secure input focused, masking keys");
+            } else {
+                info!("Secure input left");
+            }
+        }
+
+        let casting = self.settings.screencast_mode == ScreencastMode::MaskWhileCasting
+            && self.screencast_active;
+        self.combo.set_masked(self.settings.privacy_mask || secure || casting);
+    }
+
+    /// The PipeWire monitor is only started once a screencast mode needs it.
+    fn ensure_screencast_monitor(&mut self) {
+        if self.screencast_rx.is_none() && self.settings.screencast_mode != ScreencastMode::Ignore {
+            self.screencast_rx = Some(Inbox::new(
+                screencast::start_screencast_monitor(),
+                &self.waker,
+            ));
+        }
+    }
+
+    /// Starts, stops or moves the WebSocket server to match the settings.
+    fn update_event_stream(&mut self) {
+        if !self.settings.websocket_enabled {
+            self.event_stream = None;
+            return;
+        }
+        let bind = self.settings.websocket_bind.clone();
+        let port = self.settings.websocket_port;
+        let token = self.settings.remote_token.clone();
+        if self
+            .event_stream
+            .as_ref()
+            .is_some_and(|stream| stream.is_bound_to(&bind, port, &token))
+        {
+            return;
+        }
+
+        self.event_stream = None;
+        match event_stream::start_event_stream(&bind, port, &token) {
+            Ok(stream) => self.event_stream = Some(stream),
+            Err(e) => warn!("WebSocket event stream unavailable: {:#}", e),
+        }
+    }
+
+    fn update_remote_receiver(&mut self) {
+        let Some(url) = self.settings.remote_receive.clone() else {
+            self.remote = None;
+            return;
+        };
+        let target = (url, self.settings.remote_token.clone());
+        if self.remote.as_ref().is_some_and(|(current, _)| *current == target) {
+            return;
+        }
+        let (url, token) = target.clone();
+        let inbox = Inbox::new(remote::start_remote_receiver(url, token), &self.waker);
+        self.remote = Some((target, inbox));
+    }
+
+    fn update_ime_monitor(&mut self) {
+        if !self.settings.ime_text {
+            if self.ime.take().is_some() {
+                self.combo.set_ime_composing(false);
+            }
+            return;
+        }
+        if self.ime.is_none() {
+            self.ime = Some(Inbox::new(ime::start_ime_monitor(), &self.waker));
+        }
+    }
+
+    /// Watches the desktop color scheme while the base settings or a
+    /// profile use the `auto` theme.
+    fn update_appearance_monitor(&mut self) {
+        let auto = self.settings.theme == Theme::Auto
+            || self.settings.profiles.iter().any(|profile| profile.theme == Some(Theme::Auto));
+        if !auto {
+            self.appearance = None;
+        } else if self.appearance.is_none() {
+            let monitor = appearance::start_color_scheme_monitor();
+            self.appearance = Some(Inbox::new(monitor, &self.waker));
+        }
+    }
+
+    fn update_settings_backend(&mut self) {
+        if self.settings.settings_backend != SettingsBackend::Gsettings {
+            self.gsettings = None;
+        } else if self.gsettings.is_none() {
+            self.gsettings = GSettingsStore::open().map(|store| {
+                let changes = Inbox::new(store.watch(), &self.waker);
+                (store, changes)
+            });
+        }
+    }
+
+    /// The config file, with the GSettings keys from dconf when that
+    /// backend is on.
+    fn read_config(&self) -> Result<Settings> {
+        let settings = Settings::read_from(&self.config_path)?;
+        match &self.gsettings {
+            Some((store, _)) if settings.settings_backend == SettingsBackend::Gsettings => {
+                store.load_into(&settings)
+            }
+            _ => Ok(settings),
+        }
+    }
+
+    fn save_config(&self, settings: &Settings) -> Result<()> {
+        settings.save_to(&self.config_path)?;
+        if let Some((store, _)) = &self.gsettings {
+            store.store(settings)?;
+        }
+        Ok(())
+    }
+
+    fn update_obs_monitor(&mut self) {
+        let Some(url) = self.settings.obs_websocket.clone() else {
+            self.obs = None;
+            return;
+        };
+        let target = (url, self.settings.obs_password.clone());
+        if self.obs.as_ref().is_some_and(|(current, _)| *current == target) {
+            return;
+        }
+        let (url, password) = target.clone();
+        let inbox = Inbox::new(obs::start_obs_monitor(url, password), &self.waker);
+        self.obs = Some((target, inbox));
+        self.obs_recording = false;
+        self.obs_streaming = false;
+    }
+
+    /// Inside a Flatpak that may not read `/dev/input`, the portal is the
+    /// only way the hotkeys can work, so it is used whatever the setting.
+    fn update_portal_shortcuts(&mut self) {
+        let no_access = self.listener_notice == Some(NO_ACCESS_NOTICE);
+        let wanted = self.settings.portal_shortcuts || (no_access && flatpak::in_flatpak());
+        if !wanted {
+            self.portal_shortcuts = None;
+            return;
+        }
+        // apply_settings already rejected an unparsable hotkey.
+        let Ok(hotkey) = Hotkey::parse(&self.settings.pause_hotkey) else {
+            return;
+        };
+        let trigger = hotkey.portal_trigger();
+        if self
+            .portal_shortcuts
+            .as_ref()
+            .is_some_and(|shortcuts| shortcuts.trigger() == trigger)
+        {
+            return;
+        }
+        self.portal_shortcuts = Some(portal_shortcuts::start_portal_shortcuts(
+            trigger,
+            self.control_tx.clone(),
+        ));
+    }
+
+    /// Refreshes the speed widget text; true when it changed.
+    fn update_speed(&mut self) -> bool {
+        let now = Instant::now();
+        let text = match self.settings.speed_widget {
+            SpeedWidget::Off => None,
+            SpeedWidget::Wpm => Some((
+                format!("{:.0} WPM", self.combo.typing_rate(now) / 5.0),
+                "wpm",
+            )),
+            SpeedWidget::Kpm => Some((format!("{:.0} KPM", self.combo.typing_rate(now)), "kpm")),
+            SpeedWidget::Apm => Some((format!("{:.0} APM", self.combo.action_rate(now)), "apm")),
+        };
+        if text == self.speed_text {
+            return false;
+        }
+        self.overlay.set_speed(text.clone());
+        self.speed_text = text;
+        true
+    }
+
+    /// Sends lock changes to event consumers and refreshes the lock widget;
+    /// true when the widget changed.
+    fn update_locks(&mut self) -> bool {
+        let locks = self.combo.locks();
+        // Held back like every other event while paused or hidden, and sent
+        // once capture shows keys again.
+        if locks != self.locks && !self.overlay_hidden && !self.combo.paused() {
+            self.locks = locks;
+            if let Some(stream) = &self.event_stream {
+                stream.publish_locks(&locks);
+            }
+            if let Some(output) = &self.json_output {
+                output.locks(&locks);
+            }
+        }
+
+        let text = if self.settings.lock_indicator {
+            locks.label().map(i18n::tr)
+        } else {
+            None
+        };
+        if text == self.lock_text {
+            return false;
+        }
+        self.overlay.set_locks(text);
+        self.lock_text = text;
+        true
+    }
+
+    /// Everything shows as released while paused, like the keys themselves.
+    fn update_modifier_panel(&mut self) -> bool {
+        let panel = self.settings.modifier_panel.then(|| {
+            if self.combo.paused() {
+                [false; 4]
+            } else {
+                self.combo.modifiers_down()
+            }
+        });
+        if panel == self.modifier_panel {
+            return false;
+        }
+        self.overlay.set_modifiers(panel);
+        self.modifier_panel = panel;
+        true
+    }
+
+    /// Follows layout switches from both the compositor and the keymap.
+    fn update_layout_badge(&mut self) -> bool {
+        let badge = if self.settings.layout_badge {
+            self.combo.layout_abbreviation()
+        } else {
+            None
+        };
+        if badge == self.layout_badge {
+            return false;
+        }
+        self.overlay.set_layout_badge(badge.clone());
+        self.layout_badge = badge;
+        true
+    }
+
+    fn update_tally(&mut self) -> bool {
+        let tally = (self.settings.shortcut_tally > 0)
+            .then(|| summary::top_shortcuts(&self.shortcut_counts, self.settings.shortcut_tally));
+        if tally == self.tally {
+            return false;
+        }
+        self.overlay.set_tally(tally.clone());
+        self.tally = tally;
+        true
+    }
+
+    /// Counts typing towards the next break, whether or not the overlay
+    /// shows it. True when a break is due and its reminder went up.
+    fn check_break(&mut self, event: &input::InputEvent) -> bool {
+        let (Some(breaks), input::InputEvent::KeyPressed(_)) = (&mut self.breaks, event) else {
+            return false;
+        };
+        if !breaks.press(Instant::now()) {
+            return false;
+        }
+        info!("Break reminder after {} minutes of typing", self.settings.break_reminder_minutes);
+        self.combo.show_status(i18n::tr("Time for a break"), BREAK_NOTICE_TTL);
+        true
+    }
+
+    fn update_practice_prompt(&mut self) -> bool {
+        let prompt = self.practice.as_ref().map(|practice| {
+            let (combo, description) = practice.prompt();
+            (combo.to_string(), description.map(str::to_string))
+        });
+        if prompt == self.practice_prompt {
+            return false;
+        }
+        self.overlay.set_practice(prompt.clone());
+        self.practice_prompt = prompt;
+        true
+    }
+
+    /// Marks the newest bubble as a right or wrong answer to the practice
+    /// prompt, and sums up each finished round.
+    fn check_practice(&mut self, combo: &ComboEvent) {
+        let Some(attempt) = self.practice.as_mut().and_then(|practice| practice.attempt(combo))
+        else {
+            return;
+        };
+        let class = match attempt {
+            Attempt::Wrong => "practice-wrong",
+            Attempt::Correct | Attempt::RoundDone { .. } => "practice-correct",
+        };
+        if let Some(id) = self.combo.items().iter().last().map(|item| item.id) {
+            self.combo.set_class(id, class);
+        }
+        if let Attempt::RoundDone { first_try, total } = attempt {
+            info!("Practice round done: {} of {} on the first try", first_try, total);
+            let text = format!("{} {}/{}", i18n::tr("Round done:"), first_try, total);
+            self.combo.show_status(&text, PRACTICE_NOTICE_TTL);
+        }
+    }
+
+    /// Shortest time between renders, or `None` without a limit.
+    fn frame_interval(&self) -> Option<Duration> {
+        (self.settings.max_refresh_hz > 0)
+            .then(|| Duration::from_secs(1) / self.settings.max_refresh_hz)
+    }
+
+    /// Draws a pending change unless the last render was less than a frame
+    /// ago; the pump comes back for it once the frame is over.
+    fn render_if_due(&mut self) {
+        if !self.render_pending || self.render_delay().is_some() {
+            return;
+        }
+        self.render_pending = false;
+        if self.overlay_hidden {
+            return;
+        }
+        self.overlay
+            .render(self.combo.items(), self.combo.status(), self.combo.paused());
+        self.last_render = Some(Instant::now());
+
+        // The annotations themselves go up with the next frame.
+        if let Some(latency) = &mut self.latency {
+            for (id, measured) in latency.rendered() {
+                let note = format!("{} ms", measured.as_millis());
+                self.render_pending |= self.combo.annotate(id, &note);
+            }
+        }
+    }
+
+    /// Pairs the newest bubble with the press that caused it.
+    fn track_latency(&mut self) {
+        // Taken even when off, so turning the probe on never finds a stale press.
+        let pressed_at = metrics::take_press();
+        let (Some(latency), Some(pressed_at)) = (&mut self.latency, pressed_at) else {
+            return;
+        };
+        if let Some(item) = self.combo.items().iter().last() {
+            latency.track(item.id, pressed_at);
+        }
+    }
+
+    /// Time left until the next render is allowed.
+    fn render_delay(&self) -> Option<Duration> {
+        let interval = self.frame_interval()?;
+        let elapsed = self.last_render?.elapsed();
+        (elapsed < interval).then(|| interval - elapsed)
+    }
+
+    /// How soon the pump has to run again if no events arrive, or `None`
+    /// when nothing on screen or pending changes with time. Items are
+    /// pruned when the oldest expires rather than on a fixed tick.
+    fn next_tick(&self) -> Option<Duration> {
+        let now = Instant::now();
+        let prune = self
+            .combo
+            .next_expiry()
+            .filter(|_| !self.overlay_hidden)
+            .map(|expiry| expiry.saturating_duration_since(now) + PRUNE_SLACK);
+        let speed = (self.settings.speed_widget != SpeedWidget::Off
+            && !self.combo.rates_settled(now))
+        .then_some(SPEED_INTERVAL);
+        let stats = self
+            .stats
+            .as_ref()
+            .is_some_and(StatsStore::has_pending)
+            .then_some(stats::FLUSH_INTERVAL);
+        let render = self
+            .render_pending
+            .then(|| self.render_delay().unwrap_or_default());
+        let schedule = (!self.schedule.is_empty()).then_some(SCHEDULE_INTERVAL);
+        let watchdog = self
+            .listener_handle
+            .as_ref()
+            .and_then(input::ListenerHandle::heartbeats)
+            .map(|_| HEARTBEAT_INTERVAL.saturating_sub(self.watchdog_checked.elapsed()));
+        [render, prune, speed, stats, schedule, watchdog].into_iter().flatten().min()
+    }
+
+    fn drain_remote(&mut self) -> bool {
+        let Some((_, rx)) = &self.remote else {
+            return false;
+        };
+        let combos: Vec<ComboEvent> = std::iter::from_fn(|| rx.pop()).collect();
+        let mut changed = false;
+        for combo in combos {
+            changed |= self.combo.show_remote(combo).render;
+        }
+        changed
+    }
+
+    /// Applies changes made in dconf. Our own saves come back here too and
+    /// change nothing.
+    fn drain_gsettings(&mut self) -> bool {
+        let Some((store, changes)) = &self.gsettings else {
+            return false;
+        };
+        if std::iter::from_fn(|| changes.pop()).count() == 0 {
+            return false;
+        }
+        let new_settings = match store.load_into(&self.settings) {
+            Ok(settings) => settings,
+            Err(e) => {
+                warn!("Ignoring GSettings change: {:#}", e);
+                return false;
+            }
+        };
+        if toml::to_string(&new_settings).ok() == toml::to_string(&self.settings).ok() {
+            return false;
+        }
+        if let Err(e) = self.apply_settings(new_settings) {
+            warn!("Ignoring GSettings change: {:#}", e);
+            return false;
+        }
+        info!("Applied settings changed in dconf");
+        if let Some(window) = &self.settings_window {
+            window.set_from_settings(&self.settings);
+        }
+        true
+    }
+
+    /// Switches an `auto` theme when the desktop color scheme changes.
+    fn drain_appearance(&mut self) -> bool {
+        let Some(rx) = &self.appearance else {
+            return false;
+        };
+        let Some(scheme) = std::iter::from_fn(|| rx.pop()).last() else {
+            return false;
+        };
+        if scheme == self.color_scheme {
+            return false;
+        }
+        info!("Desktop color scheme is now {:?}", scheme);
+        self.color_scheme = scheme;
+        self.apply_overlay_settings();
+        true
+    }
+
+    /// Shows what the input method commits in place of the keys it took.
+    fn drain_ime(&mut self) -> bool {
+        let Some(rx) = &self.ime else {
+            return false;
+        };
+        let events: Vec<ime::ImeEvent> = std::iter::from_fn(|| rx.pop()).collect();
+        let suppressed = self.capture_suppressed();
+        let mut changed = false;
+        for event in events {
+            let action = self.combo.handle_ime(event, suppressed);
+            if let Some(combo) = &action.combo {
+                self.publish_combo(combo);
+            }
+            changed |= action.render;
+        }
+        changed
+    }
+
+    /// Follows OBS output changes, pausing and hiding the overlay while the
+    /// outputs it watches are stopped. Returns whether anything changed and
+    /// the new pause state if OBS changed it.
+    fn drain_obs(&mut self) -> (bool, Option<bool>) {
+        if let Some((_, rx)) = &self.obs {
+            while let Some(event) = rx.pop() {
+                match event.output {
+                    ObsOutput::Recording => self.obs_recording = event.active,
+                    ObsOutput::Streaming => self.obs_streaming = event.active,
+                }
+            }
+        }
+
+        let mut paused = None;
+        let pausing = self.obs_holds(ObsReaction::Pause);
+        if pausing != std::mem::replace(&mut self.obs_pausing, pausing) {
+            if pausing {
+                info!("OBS is idle, pausing capture");
+                self.obs_paused = self.combo.set_paused(true);
+                paused = self.obs_paused.then_some(true);
+            } else if std::mem::take(&mut self.obs_paused) {
+                info!("OBS output started, resuming capture");
+                self.combo.set_paused(false);
+                paused = Some(false);
+            }
+        }
+        (self.sync_visibility() || paused.is_some(), paused)
+    }
+
+    /// Shows the overlay while the peek key is held.
+    fn update_peek(&mut self, event: &input::InputEvent) {
+        let Some(peek_key) = self.peek_key else {
+            return;
+        };
+        let peeking = match event {
+            input::InputEvent::KeyPressed(key) if *key == peek_key => true,
+            input::InputEvent::KeyReleased(key) if *key == peek_key => false,
+            _ => return,
+        };
+        if peeking != std::mem::replace(&mut self.peeking, peeking) {
+            self.overlay.set_peek_hidden(!peeking);
+        }
+    }
+
+    /// Pauses capture when the last `active_hours` window closes and lifts
+    /// that pause when one opens; the user can still resume or pause by
+    /// hand in between. Returns the new pause state if it changed.
+    fn update_schedule(&mut self) -> Option<bool> {
+        let pausing = match glib::DateTime::now_local() {
+            Ok(now) => {
+                let minute = now.hour() * 60 + now.minute();
+                !self.schedule.is_active(now.day_of_week() as usize - 1, minute as u16)
+            }
+            Err(_) => false,
+        };
+        if pausing == std::mem::replace(&mut self.schedule_pausing, pausing) {
+            return None;
+        }
+        if pausing {
+            info!("Outside active hours, pausing capture");
+            self.schedule_paused = self.combo.set_paused(true);
+            self.schedule_paused.then_some(true)
+        } else if std::mem::take(&mut self.schedule_paused) {
+            info!("Active hours started, resuming capture");
+            self.combo.set_paused(false);
+            Some(false)
+        } else {
+            None
+        }
+    }
+
+    /// True when some output is watched with `reaction` and none of those
+    /// outputs is running.
+    fn obs_holds(&self, reaction: ObsReaction) -> bool {
+        if self.obs.is_none() {
+            return false;
+        }
+        let outputs = [
+            (self.settings.obs_recording, self.obs_recording),
+            (self.settings.obs_streaming, self.obs_streaming),
+        ];
+        let mut watched = outputs.iter().filter(|(watch, _)| *watch == reaction).peekable();
+        watched.peek().is_some() && !watched.any(|(_, active)| *active)
+    }
+
+    fn update_metrics_server(&mut self) {
+        if !self.settings.metrics_enabled {
+            self.metrics_server = None;
+            return;
+        }
+        let port = self.settings.metrics_port;
+        if self.metrics_server.as_ref().map(MetricsServer::port) == Some(port) {
+            return;
+        }
+        self.metrics_server = None;
+        match metrics::start_metrics_server(port) {
+            Ok(server) => self.metrics_server = Some(server),
+            Err(e) => warn!("Metrics endpoint unavailable: {:#}", e),
+        }
+    }
+
+    fn update_latency_probe(&mut self) {
+        if self.settings.latency_debug != self.latency.is_some() {
+            self.latency = self.settings.latency_debug.then(LatencyProbe::new);
+        }
+    }
+
+    fn update_frame_stream(&mut self) {
+        let Some(path) = &self.settings.frame_stream else {
+            self.frame_stream = None;
+            self.overlay.set_frame_stream(None);
+            return;
+        };
+        let target = (path, self.settings.frame_stream_width, self.settings.frame_stream_height);
+        if self.frame_stream.as_ref().map(|stream| stream.target()) == Some(target) {
+            return;
+        }
+        self.frame_stream = None;
+        match FrameStream::start(path.clone(), target.1, target.2) {
+            Ok(stream) => self.frame_stream = Some(Rc::new(stream)),
+            Err(e) => warn!("Frame stream unavailable: {:#}", e),
+        }
+        self.overlay.set_frame_stream(self.frame_stream.clone());
+    }
+
+    fn update_stats(&mut self) {
+        if !self.settings.stats_enabled {
+            self.stats = None;
+            return;
+        }
+        let path = self
+            .settings
+            .stats_path
+            .clone()
+            .unwrap_or_else(stats::default_stats_path);
+        if self.stats.as_ref().is_none_or(|stats| stats.path() != path) {
+            self.stats = None;
+            match StatsStore::open(&path) {
+                Ok(stats) => self.stats = Some(stats),
+                Err(e) => {
+                    warn!("Statistics unavailable: {:#}", e);
+                    return;
+                }
+            }
+        }
+        if let Some(stats) = &mut self.stats {
+            stats.set_categories_only(self.settings.stats_categories_only);
+            stats.set_app(self.focus.as_ref().map(|info| info.class.as_str()));
+            stats.prune(self.settings.stats_retention_days);
+        }
+    }
+
+    /// Wipes the statistics database, also when recording is turned off.
+    fn clear_stats(&mut self) -> Result<()> {
+        if let Some(stats) = &mut self.stats {
+            return stats.clear();
+        }
+        let path = self
+            .settings
+            .stats_path
+            .clone()
+            .unwrap_or_else(stats::default_stats_path);
+        if !path.exists() {
+            return Ok(());
+        }
+        StatsStore::open_existing(&path)?.clear()
+    }
+
+    fn update_summary(&mut self) {
+        if self.settings.session_summary == SummaryMode::Off {
+            self.summary = None;
+        } else if self.summary.is_none() {
+            self.summary = Some(SessionSummary::default());
+        }
+    }
+
+    fn update_announcer(&mut self) {
+        if !self.settings.screen_reader {
+            self.announcer = None;
+        } else if self.announcer.is_none() {
+            self.announcer = Some(Announcer::start());
+        }
+    }
+
+    /// Writes out what should survive quitting: pending statistics and the
+    /// session summary.
+    fn finish_session(&mut self) {
+        if let Some(stats) = &mut self.stats {
+            stats.flush();
+        }
+        let Some(summary) = self.summary.take() else {
+            return;
+        };
+        match self.settings.session_summary {
+            SummaryMode::Off => {}
+            SummaryMode::Print => {
+                let text = summary.render();
+                if self.json_output.as_ref().is_some_and(|output| output.uses_stdout()) {
+                    eprint!("{}", text);
+                } else {
+                    print!("{}", text);
+                }
+            }
+            SummaryMode::File => {
+                let dir = self
+                    .settings
+                    .session_log_dir
+                    .clone()
+                    .unwrap_or_else(session_log::default_log_dir);
+                match summary.write_to_dir(&dir) {
+                    Ok(path) => info!("Session summary written to {:?}", path),
+                    Err(e) => warn!("Failed to write session summary: {:#}", e),
+                }
+            }
+        }
+    }
+
+    fn update_session_log(&mut self) {
+        if !self.settings.session_log_enabled {
+            self.session_log = None;
+            return;
+        }
+        let dir = self
+            .settings
+            .session_log_dir
+            .clone()
+            .unwrap_or_else(session_log::default_log_dir);
+        let format = self.settings.session_log_format;
+        if self.session_log.as_ref().is_some_and(|log| log.matches(&dir, format)) {
+            return;
+        }
+
+        match SessionLog::create(&dir, format) {
+            Ok(log) => self.session_log = Some(log),
+            Err(e) => {
+                warn!("Session log unavailable: {:#}", e);
+                self.session_log = None;
+            }
+        }
+    }
+
+    /// (Re)loads plugins so edited scripts take effect on apply.
+    fn update_plugins(&mut self) {
+        let host = if self.settings.plugins_enabled {
+            let dir = self
+                .settings
+                .plugins_dir
+                .clone()
+                .unwrap_or_else(plugins::default_plugin_dir);
+            match PluginHost::load(&dir) {
+                Ok(host) if !host.is_empty() => Some(host),
+                Ok(_) => {
+                    info!("No plugins found in {:?}", dir);
+                    None
+                }
+                Err(e) => {
+                    warn!("Plugins unavailable: {:#}", e);
+                    None
+                }
+            }
+        } else {
+            None
+        };
+
+        self.overlay
+            .set_extra_css(host.as_ref().map(PluginHost::css).unwrap_or(""));
+        self.combo
+            .set_transform(host.map(|host| Box::new(host) as Box<dyn combo::ComboTransform>));
+    }
+
+    fn publish_combo(&mut self, combo: &ComboEvent) {
+        // Apps hidden by the filter stay private to external consumers too.
+        if self.overlay_hidden {
+            return;
+        }
+        metrics::inc(&metrics::COMBOS_SHOWN);
+        if combo.kind == ComboKind::Shortcut {
+            *self.shortcut_counts.entry(combo.text.clone()).or_default() += 1;
+        }
+        let heat = self.frequency.record(&combo.text);
+        if self.settings.frequency_emphasis != Emphasis::Off {
+            if let Some(id) = self.combo.items().iter().last().map(|item| item.id) {
+                self.combo.set_heat(id, heat);
+            }
+        }
+        self.check_practice(combo);
+        if let Some(stream) = &self.event_stream {
+            stream.publish(combo);
+        }
+        if let Some(output) = &self.json_output {
+            output.combo(combo);
+        }
+        if let Some(log) = &mut self.session_log {
+            log.record(combo);
+        }
+        if let Some(stats) = &mut self.stats {
+            stats.record_combo(combo);
+        }
+        if let Some(summary) = &mut self.summary {
+            summary.record_combo(combo);
+        }
+        if let Some(announcer) = &self.announcer {
+            // Masked input stays unspoken rather than read out as bullets.
+            if !combo.text.contains(combo::MASK_LABEL) {
+                announcer.announce(&combo.text);
+            }
+        }
+        self.hooks.run_matching(&combo.text);
+    }
+
+    /// Raw events are withheld whenever the overlay itself would not show
+    /// the key as typed.
+    fn emit_input(&self, event: &input::InputEvent) {
+        let Some(output) = &self.json_output else {
+            return;
+        };
+        if self.overlay_hidden || self.combo.paused() || self.combo.masked() {
+            return;
+        }
+        output.input(event);
+    }
+
+    /// Statistics and the session summary follow the same rule as raw
+    /// events: nothing is counted while the key would not be shown as typed.
+    fn record_usage(&mut self, event: &input::InputEvent) {
+        if self.overlay_hidden || self.combo.paused() || self.combo.masked() {
+            return;
+        }
+        let (input::InputEvent::KeyPressed(key) | input::InputEvent::MouseButtonPressed(key)) =
+            event
+        else {
+            return;
+        };
+        if let Some(stats) = &mut self.stats {
+            stats.record_key(*key);
+        }
+        if let Some(summary) = &mut self.summary {
+            match event {
+                input::InputEvent::MouseButtonPressed(_) => summary.record_click(),
+                _ if !xkb::is_modifier(*key) => summary.record_key(),
+                _ => {}
+            }
+        }
+    }
+
+    fn update_screencast(&mut self) -> bool {
+        let Some(rx) = &self.screencast_rx else {
+            return false;
+        };
+
+        let mut active = self.screencast_active;
+        while let Some(value) = rx.pop() {
+            active = value;
+        }
+        if active == self.screencast_active {
+            return false;
+        }
+
+        info!("Screencast {}", if active { "started" } else { "stopped" });
+        self.screencast_active = active;
+        self.update_privacy_mask();
+        self.sync_visibility()
+    }
+
+    /// Keeps a status message up while discovery finds no keyboard, and
+    /// takes it down once one appears. True when the overlay changed.
+    fn handle_listener_status(&mut self, status: ListenerStatus) -> bool {
+        let notice = match status {
+            ListenerStatus::NoKeyboards { denied: true } => Some(NO_ACCESS_NOTICE),
+            ListenerStatus::NoKeyboards { denied: false } => Some(NO_KEYBOARD_NOTICE),
+            ListenerStatus::KeyboardsFound => None,
+            ListenerStatus::DevicesChanged => {
+                if let Some(handle) = &self.tray_handle {
+                    handle.set_devices(self.devices.list());
+                }
+                return false;
+            }
+        };
+        let mut changed = false;
+        if let Some(old) = self.listener_notice.filter(|old| Some(*old) != notice) {
+            changed |= self.combo.dismiss_status(i18n::tr(old));
+        }
+        if let Some(text) = notice {
+            // Refreshed on every retry, so it stays up for as long as the
+            // problem lasts.
+            changed |= self.combo.show_status(i18n::tr(text), input::DISCOVERY_RETRY * 2);
+        }
+        self.listener_notice = notice;
+        changed
+    }
+
+    /// Restarts the listener when one of its threads died or stalled, and
+    /// says so on screen when a device keeps failing. True when the overlay
+    /// changed.
+    fn check_listeners(&mut self) -> bool {
+        let now = Instant::now();
+        if now.duration_since(self.watchdog_checked) < HEARTBEAT_INTERVAL {
+            return false;
+        }
+        self.watchdog_checked = now;
+        let Some(heartbeats) = self.listener_handle.as_ref().and_then(|h| h.heartbeats()) else {
+            return false;
+        };
+        let verdict = self.listener_watchdog.check(heartbeats, now);
+        if !verdict.restart {
+            return false;
+        }
+        warn!("An input listener thread died or stalled, restarting the listener");
+        if let Err(e) = self.restart_listener() {
+            warn!("Failed to restart the input listener: {:#}", e);
+        }
+        let mut changed = false;
+        for name in verdict.failing {
+            let text = format!("{}: {}", i18n::tr("Keeps failing"), name);
+            changed |= self.combo.show_status(&text, LISTENER_FAILURE_TTL);
+        }
+        changed
+    }
+
+    /// Reopens every input device, replacing handles that went stale while the
+    /// system was asleep. Does nothing in receiver mode.
+    fn restart_listener(&mut self) -> Result<()> {
+        if self.listener_handle.take().is_none() {
+            return Ok(());
+        }
+        self.listener_handle = Some(start_listener(
+            &self.input_tx,
+            &self.listener_status_tx,
+            &self.devices,
+            &self.settings,
+        )?);
+        Ok(())
+    }
+
+    /// Returns the new paused state if the lock change paused or resumed capture.
+    fn handle_session_event(&mut self, event: SessionEvent) -> Option<bool> {
+        if event == SessionEvent::Resumed {
+            info!("Resumed from suspend, looking for input devices again");
+            if let Err(e) = self.restart_listener() {
+                warn!("Failed to restart input capture after resume: {:#}", e);
+            }
+            return None;
+        }
+        let combo = &mut self.combo;
+        self.session_lock.handle(event, self.settings.pause_on_lock, |paused| {
+            let changed = combo.set_paused(paused);
+            if paused {
+                combo.clear_items();
+            }
+            changed
+        })
+    }
+
+    /// Completes a "Pick window" request with the newly focused window,
+    /// skipping our own windows (the settings window still has focus when the
+    /// button is clicked).
+    fn finish_app_pick(&mut self, info: &FocusInfo) {
+        if info.class.is_empty() || info.class == APP_ID {
+            return;
+        }
+        self.picking_app = false;
+
+        let Some(window) = &self.settings_window else {
+            return;
+        };
+        let entry = app_filter::exact_class_entry(&info.class);
+        if window.add_disabled_app(&entry) {
+            window.set_status(&format!("Added {} (apply or save to use it)", entry));
+        } else {
+            window.set_status(&format!("{} is already listed", entry));
+        }
+        window.present();
+    }
+
+    fn toggle_drag(&mut self) {
+        self.settings.drag_enabled = !self.settings.drag_enabled;
+        self.overlay.set_drag_enabled(self.settings.drag_enabled);
+        if let Some(window) = &self.settings_window {
+            window.set_from_settings(&self.settings);
+        }
+    }
+
+    fn begin_drag(&mut self) {
+        if !self.settings.drag_enabled {
+            return;
+        }
+
+        let effective = self.effective_settings();
+        if effective.position != self.settings.position {
+            info!("Drag ignored: a profile or presenter mode overrides the position");
+            return;
+        }
+
+        let (window_w, window_h) = self.overlay.window_size();
+        let geometry = match self.overlay.monitor_geometry() {
+            Some(g) => g,
+            None => return,
+        };
+
+        let monitor_w = geometry.width();
+        let monitor_h = geometry.height();
+
+        let (base_x, base_y) = compute_custom_offsets(
             self.settings.position,
             self.settings.margin,
             self.settings.custom_x,
@@ -444,6 +2760,7 @@ impl AppState {
         self.settings.custom_x = new_x;
         self.settings.custom_y = new_y;
         self.settings.position = settings::Position::Custom;
+        self.position_dirty = true;
 
         self.overlay.update_position(&self.settings);
 
@@ -452,82 +2769,82 @@ impl AppState {
         }
     }
 
-    fn end_drag(&mut self) {
-        self.dragging = false;
-    }
-
-    fn update_app_filter(&mut self, now: Instant) -> bool {
-        if !self.settings.app_filter_enabled {
-            if self.app_filter_suppressed {
-                self.app_filter_suppressed = false;
-                self.overlay.set_visible(true);
-                return true;
+    /// Teardown on quit and SIGTERM/SIGINT: tells systemd we are stopping,
+    /// keeps a dragged position and the runtime state, and removes the
+    /// control socket.
+    fn shutdown(&mut self) {
+        self.finish_session();
+        if self.settings.restore_state {
+            self.save_state();
+        }
+        if let Some(notifier) = &self.notifier {
+            notifier.stopping();
+        }
+        if self.position_dirty {
+            if let Err(e) = self.save_position() {
+                warn!("Failed to save position: {:#}", e);
             }
-            return false;
         }
+        // Removes the socket file.
+        self.socket_handle = None;
+    }
 
-        if now.duration_since(self.last_app_check) < Duration::from_millis(500) {
-            return false;
-        }
+    /// Writes only the position into the config file, leaving other unsaved
+    /// changes out.
+    fn save_position(&mut self) -> Result<()> {
+        let mut saved = if self.config_path.exists() {
+            self.read_config()?
+        } else {
+            self.settings.clone()
+        };
+        saved.position = self.settings.position;
+        saved.custom_x = self.settings.custom_x;
+        saved.custom_y = self.settings.custom_y;
+        self.save_config(&saved)?;
+        self.position_dirty = false;
+        Ok(())
+    }
 
-        self.last_app_check = now;
+    /// Saves where the overlay was dropped, so it survives a restart without
+    /// a trip to the settings window.
+    fn end_drag(&mut self) {
+        self.dragging = false;
+        if self.position_dirty {
+            if let Err(e) = self.save_position() {
+                warn!("Failed to save position: {:#}", e);
+            }
+        }
+    }
 
-        let Some(info) = get_active_app_info() else {
-            if !self.app_filter_warned {
-                warn!("App filter enabled but hyprctl is not available or returned no data.");
+    fn update_app_filter(&mut self) -> bool {
+        let disabled = if !self.settings.app_filter_enabled {
+            false
+        } else if let Some(info) = &self.focus {
+            app_filter::any_matches(&self.app_filter_rules, &info.class, &info.title)
+        } else {
+            if !self.app_filter_warned && self.focus_backend.is_none() {
+                warn!("App filter enabled but no supported compositor (Hyprland, Sway) was detected.");
                 self.app_filter_warned = true;
             }
-            if self.app_filter_suppressed {
-                self.app_filter_suppressed = false;
-                self.combo.clear_items();
-                self.overlay.set_visible(true);
-                return true;
-            }
-            return false;
+            false
         };
 
-        let class_lower = info.class.to_ascii_lowercase();
-        let title_lower = info.title.to_ascii_lowercase();
-        let disabled = self.settings.disabled_apps.iter().any(|entry| {
-            let needle = entry.to_ascii_lowercase();
-            class_lower.contains(&needle) || title_lower.contains(&needle)
-        });
-
-        if disabled != self.app_filter_suppressed {
-            self.app_filter_suppressed = disabled;
-            if disabled {
-                self.combo.clear_items();
-                self.overlay.set_visible(false);
-            } else {
-                self.overlay.set_visible(true);
-            }
-            return true;
+        if disabled == self.app_filter_suppressed {
+            return false;
         }
 
-        false
+        self.app_filter_suppressed = disabled;
+        self.sync_visibility();
+        true
     }
 }
 
-struct ActiveAppInfo {
-    class: String,
-    title: String,
-}
-
-fn get_active_app_info() -> Option<ActiveAppInfo> {
-    let output = Command::new("hyprctl")
-        .args(["-j", "activewindow"])
-        .output()
-        .ok()?;
-
-    if !output.status.success() {
-        return None;
+fn compile_filter_rules(entries: &[String]) -> Vec<FilterRule> {
+    let (rules, errors) = app_filter::compile_rules(entries);
+    for err in errors {
+        warn!("Ignoring invalid app filter entry {}", err);
     }
-
-    let value: Value = serde_json::from_slice(&output.stdout).ok()?;
-    let class = value.get("class")?.as_str()?.to_string();
-    let title = value.get("title").and_then(|v| v.as_str()).unwrap_or("").to_string();
-
-    Some(ActiveAppInfo { class, title })
+    rules
 }
 
 fn compute_custom_offsets(
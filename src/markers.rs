@@ -0,0 +1,82 @@
+use crate::combo::unix_millis;
+use anyhow::{Context, Result};
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+/// Timeline markers dropped from a hotkey, one line each:
+/// `unix_ms<TAB>elapsed<TAB>marker N`, with elapsed counted from the start
+/// of the run like the session log's.
+pub struct MarkerLog {
+    started: Instant,
+    count: u32,
+}
+
+impl Default for MarkerLog {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MarkerLog {
+    pub fn new() -> Self {
+        Self {
+            started: Instant::now(),
+            count: 0,
+        }
+    }
+
+    /// Appends a marker to `path` and returns its number.
+    pub fn append(&mut self, path: &Path) -> Result<u32> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).with_context(|| format!("Failed to create {:?}", dir))?;
+        }
+        let number = self.count + 1;
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .with_context(|| format!("Failed to open {:?}", path))?;
+        writeln!(
+            file,
+            "{}\t{}\tmarker {}",
+            unix_millis(),
+            format_elapsed(self.started.elapsed()),
+            number
+        )
+        .with_context(|| format!("Failed to write {:?}", path))?;
+        self.count = number;
+        Ok(number)
+    }
+}
+
+pub fn default_marker_path() -> PathBuf {
+    dirs::data_dir()
+        .unwrap_or_else(|| PathBuf::from("."))
+        .join("keyway-visualizer")
+        .join("markers.tsv")
+}
+
+/// `HH:MM:SS.mmm`, the form video editors accept for timecodes.
+fn format_elapsed(elapsed: Duration) -> String {
+    let secs = elapsed.as_secs();
+    format!(
+        "{:02}:{:02}:{:02}.{:03}",
+        secs / 3600,
+        secs / 60 % 60,
+        secs % 60,
+        elapsed.subsec_millis()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn elapsed_reads_as_a_timecode() {
+        assert_eq!(format_elapsed(Duration::from_millis(3_723_045)), "01:02:03.045");
+        assert_eq!(format_elapsed(Duration::ZERO), "00:00:00.000");
+    }
+}
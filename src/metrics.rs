@@ -0,0 +1,206 @@
+use anyhow::{Context, Result};
+use std::fmt::Write as _;
+use std::io::{ErrorKind, Read, Write};
+use std::net::{Ipv4Addr, TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, AtomicI64, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tracing::{debug, info, warn};
+
+/// Input events read from devices and queued for the GTK thread.
+pub static EVENTS_RECEIVED: AtomicU64 = AtomicU64::new(0);
+/// Input events dropped because the channel to the GTK thread was full.
+pub static EVENTS_DROPPED: AtomicU64 = AtomicU64::new(0);
+/// Overlay redraws.
+pub static RENDERS: AtomicU64 = AtomicU64::new(0);
+/// Combos added to the overlay.
+pub static COMBOS_SHOWN: AtomicU64 = AtomicU64::new(0);
+/// Device listener threads currently running.
+pub static LISTENER_THREADS: AtomicI64 = AtomicI64::new(0);
+/// Kernel timestamp of the latest key or button press, in microseconds since
+/// the epoch; zero once taken.
+static LAST_PRESS_MICROS: AtomicU64 = AtomicU64::new(0);
+
+const ACCEPT_POLL: Duration = Duration::from_millis(200);
+const IO_TIMEOUT: Duration = Duration::from_secs(1);
+
+pub fn inc(counter: &AtomicU64) {
+    counter.fetch_add(1, Ordering::Relaxed);
+}
+
+/// Remembers when the kernel saw a press, for the latency debug overlay.
+pub fn note_press(at: SystemTime) {
+    let micros = at.duration_since(UNIX_EPOCH).map_or(0, |d| d.as_micros() as u64);
+    LAST_PRESS_MICROS.store(micros, Ordering::Relaxed);
+}
+
+/// The press noted last, once: events from other sources (stdin, demos)
+/// must not pick up a stale one.
+pub fn take_press() -> Option<SystemTime> {
+    match LAST_PRESS_MICROS.swap(0, Ordering::Relaxed) {
+        0 => None,
+        micros => Some(UNIX_EPOCH + Duration::from_micros(micros)),
+    }
+}
+
+/// Counts a listener thread for as long as the guard lives.
+pub struct ListenerGuard;
+
+pub fn track_listener() -> ListenerGuard {
+    LISTENER_THREADS.fetch_add(1, Ordering::Relaxed);
+    ListenerGuard
+}
+
+impl Drop for ListenerGuard {
+    fn drop(&mut self) {
+        LISTENER_THREADS.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// Serves the counters in the Prometheus text format on
+/// `http://127.0.0.1:<port>/metrics` until dropped.
+pub struct MetricsServer {
+    port: u16,
+    running: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl MetricsServer {
+    pub fn port(&self) -> u16 {
+        self.port
+    }
+}
+
+impl Drop for MetricsServer {
+    /// Waits for the thread so the port is free again once this returns.
+    fn drop(&mut self) {
+        self.running.store(false, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+pub fn start_metrics_server(port: u16) -> Result<MetricsServer> {
+    let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, port))
+        .with_context(|| format!("Failed to bind 127.0.0.1:{}", port))?;
+    listener
+        .set_nonblocking(true)
+        .context("Failed to configure listener")?;
+    info!("Metrics on http://127.0.0.1:{}/metrics", port);
+
+    let running = Arc::new(AtomicBool::new(true));
+    let thread_running = Arc::clone(&running);
+    let thread = thread::spawn(move || {
+        while thread_running.load(Ordering::Relaxed) {
+            match listener.accept() {
+                Ok((stream, _)) => {
+                    if let Err(e) = serve(stream) {
+                        debug!("Metrics request failed: {}", e);
+                    }
+                }
+                Err(e) if e.kind() == ErrorKind::WouldBlock => thread::sleep(ACCEPT_POLL),
+                Err(e) => {
+                    warn!("Metrics accept failed: {}", e);
+                    thread::sleep(ACCEPT_POLL);
+                }
+            }
+        }
+    });
+
+    Ok(MetricsServer {
+        port,
+        running,
+        thread: Some(thread),
+    })
+}
+
+fn serve(mut stream: TcpStream) -> std::io::Result<()> {
+    stream.set_nonblocking(false)?;
+    stream.set_read_timeout(Some(IO_TIMEOUT))?;
+    stream.set_write_timeout(Some(IO_TIMEOUT))?;
+
+    let mut buf = [0u8; 1024];
+    let n = stream.read(&mut buf)?;
+    let request = String::from_utf8_lossy(&buf[..n]);
+    let path = request.split_whitespace().nth(1).unwrap_or("/");
+
+    let response = if path == "/metrics" {
+        let body = render();
+        format!(
+            "HTTP/1.1 200 OK\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            body.len(),
+            body
+        )
+    } else {
+        "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n".to_string()
+    };
+    stream.write_all(response.as_bytes())
+}
+
+fn render() -> String {
+    let mut out = String::new();
+    let counters = [
+        ("keyway_events_received_total", "Input events read from devices", &EVENTS_RECEIVED),
+        ("keyway_events_dropped_total", "Input events dropped on a full channel", &EVENTS_DROPPED),
+        ("keyway_renders_total", "Overlay redraws", &RENDERS),
+        ("keyway_combos_shown_total", "Combos added to the overlay", &COMBOS_SHOWN),
+    ];
+    for (name, help, counter) in counters {
+        let _ = writeln!(out, "# HELP {} {}", name, help);
+        let _ = writeln!(out, "# TYPE {} counter", name);
+        let _ = writeln!(out, "{} {}", name, counter.load(Ordering::Relaxed));
+    }
+
+    let threads = LISTENER_THREADS.load(Ordering::Relaxed);
+    let _ = writeln!(out, "# HELP keyway_listener_threads Device listener threads running");
+    let _ = writeln!(out, "# TYPE keyway_listener_threads gauge");
+    let _ = writeln!(out, "keyway_listener_threads {}", threads);
+    let _ = writeln!(out, "# HELP keyway_listener_up Whether any device listener is running");
+    let _ = writeln!(out, "# TYPE keyway_listener_up gauge");
+    let _ = writeln!(out, "keyway_listener_up {}", u8::from(threads > 0));
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn free_port() -> u16 {
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+        listener.local_addr().unwrap().port()
+    }
+
+    fn scrape(port: u16) -> String {
+        let mut stream = TcpStream::connect((Ipv4Addr::LOCALHOST, port)).unwrap();
+        stream.write_all(b"GET /metrics HTTP/1.1\r\nHost: localhost\r\n\r\n").unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        assert!(response.starts_with("HTTP/1.1 200 OK"), "{}", response);
+        response
+    }
+
+    fn value(response: &str, name: &str) -> u64 {
+        response
+            .lines()
+            .find_map(|line| line.strip_prefix(name)?.strip_prefix(' '))
+            .and_then(|v| v.parse().ok())
+            .unwrap_or_else(|| panic!("{} missing from {}", name, response))
+    }
+
+    #[test]
+    fn serves_counters_and_frees_port_on_drop() {
+        let port = free_port();
+        let server = start_metrics_server(port).unwrap();
+        let before = value(&scrape(port), "keyway_events_dropped_total");
+        inc(&EVENTS_DROPPED);
+        let response = scrape(port);
+        assert!(value(&response, "keyway_events_dropped_total") > before);
+        assert!(response.contains("# TYPE keyway_listener_up gauge"));
+
+        drop(server);
+        let server = start_metrics_server(port).unwrap();
+        scrape(server.port());
+    }
+}
@@ -0,0 +1,205 @@
+use anyhow::{anyhow, bail, Context, Result};
+use async_channel::{Receiver, Sender};
+use data_encoding::BASE64;
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use std::net::TcpStream;
+use std::thread;
+use std::time::Duration;
+use tracing::{debug, info, warn};
+use tungstenite::stream::MaybeTlsStream;
+use tungstenite::{Message, WebSocket};
+
+const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+const READ_TIMEOUT: Duration = Duration::from_secs(1);
+/// obs-websocket 5 `EventSubscription::Outputs`
+const OUTPUT_EVENTS: u64 = 1 << 6;
+
+type Socket = WebSocket<MaybeTlsStream<TcpStream>>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ObsOutput {
+    Recording,
+    Streaming,
+}
+
+/// An OBS output started or stopped. Both are reported stopped when OBS
+/// goes away.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ObsEvent {
+    pub output: ObsOutput,
+    pub active: bool,
+}
+
+/// Follows OBS's recording and streaming state over obs-websocket 5,
+/// reconnecting until the receiver is dropped.
+pub fn start_obs_monitor(url: String, password: String) -> Receiver<ObsEvent> {
+    let (tx, rx) = async_channel::bounded(8);
+
+    thread::spawn(move || {
+        let mut warned = false;
+        while !tx.is_closed() {
+            match tungstenite::connect(url.as_str()) {
+                Ok((socket, _)) => {
+                    warned = false;
+                    if let Err(e) = follow(socket, &password, &tx) {
+                        warn!("OBS connection lost: {:#}", e);
+                    }
+                    for output in [ObsOutput::Recording, ObsOutput::Streaming] {
+                        let _ = tx.send_blocking(ObsEvent {
+                            output,
+                            active: false,
+                        });
+                    }
+                }
+                Err(e) if !warned => {
+                    warn!("Cannot reach OBS at {}: {} (retrying)", url, e);
+                    warned = true;
+                }
+                Err(e) => debug!("Cannot reach OBS at {}: {}", url, e),
+            }
+            thread::sleep(RECONNECT_DELAY);
+        }
+    });
+
+    rx
+}
+
+fn follow(mut socket: Socket, password: &str, tx: &Sender<ObsEvent>) -> Result<()> {
+    let hello = read_message(&mut socket, tx)?.context("OBS closed the connection")?;
+    let mut identify = json!({ "rpcVersion": 1, "eventSubscriptions": OUTPUT_EVENTS });
+    if let Some(auth) = hello["d"].get("authentication") {
+        if password.is_empty() {
+            bail!("OBS requires a password (obs_password)");
+        }
+        let challenge = auth["challenge"].as_str().unwrap_or_default();
+        let salt = auth["salt"].as_str().unwrap_or_default();
+        identify["authentication"] = json!(auth_string(password, salt, challenge));
+    }
+    send(&mut socket, 1, identify)?;
+
+    // Ask for the current state, so an overlay started mid-recording shows.
+    for request in ["GetRecordStatus", "GetStreamStatus"] {
+        send(
+            &mut socket,
+            6,
+            json!({ "requestType": request, "requestId": request }),
+        )?;
+    }
+
+    if let MaybeTlsStream::Plain(stream) = socket.get_ref() {
+        let _ = stream.set_read_timeout(Some(READ_TIMEOUT));
+    }
+    while let Some(message) = read_message(&mut socket, tx)? {
+        match message["op"].as_u64() {
+            Some(2) => info!("Following OBS recording and streaming state"),
+            Some(_) => {
+                if let Some(event) = parse_event(&message) {
+                    debug!("OBS {:?}", event);
+                    if tx.send_blocking(event).is_err() {
+                        let _ = socket.close(None);
+                        return Ok(());
+                    }
+                }
+            }
+            None => {}
+        }
+    }
+    Ok(())
+}
+
+/// The next JSON message, or `None` once the socket closes or the receiver
+/// is dropped.
+fn read_message(socket: &mut Socket, tx: &Sender<ObsEvent>) -> Result<Option<Value>> {
+    loop {
+        if tx.is_closed() {
+            let _ = socket.close(None);
+            return Ok(None);
+        }
+        match socket.read() {
+            Ok(Message::Text(text)) => return Ok(Some(serde_json::from_str(text.as_str())?)),
+            Ok(Message::Close(frame)) => {
+                // 4009 is a failed authentication.
+                if let Some(frame) = frame {
+                    bail!("OBS closed the connection: {} {}", u16::from(frame.code), frame.reason);
+                }
+                return Ok(None);
+            }
+            Ok(_) => {}
+            Err(tungstenite::Error::Io(e))
+                if matches!(
+                    e.kind(),
+                    std::io::ErrorKind::WouldBlock | std::io::ErrorKind::TimedOut
+                ) => {}
+            Err(e) => return Err(anyhow!(e)),
+        }
+    }
+}
+
+fn send(socket: &mut Socket, op: u8, data: Value) -> Result<()> {
+    let message = json!({ "op": op, "d": data });
+    socket.send(Message::text(message.to_string()))?;
+    Ok(())
+}
+
+/// Output state from an `Event` (op 5) or a status `RequestResponse` (op 7).
+fn parse_event(message: &Value) -> Option<ObsEvent> {
+    let data = &message["d"];
+    let (kind, fields) = match message["op"].as_u64()? {
+        5 => (data["eventType"].as_str()?, &data["eventData"]),
+        7 => (data["requestType"].as_str()?, &data["responseData"]),
+        _ => return None,
+    };
+    let output = match kind {
+        "RecordStateChanged" | "GetRecordStatus" => ObsOutput::Recording,
+        "StreamStateChanged" | "GetStreamStatus" => ObsOutput::Streaming,
+        _ => return None,
+    };
+    Some(ObsEvent {
+        output,
+        active: fields["outputActive"].as_bool()?,
+    })
+}
+
+/// `base64(sha256(base64(sha256(password + salt)) + challenge))`
+fn auth_string(password: &str, salt: &str, challenge: &str) -> String {
+    let secret = BASE64.encode(&Sha256::digest(format!("{}{}", password, salt)));
+    BASE64.encode(&Sha256::digest(format!("{}{}", secret, challenge)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn handshake_and_output_events() {
+        assert_eq!(
+            auth_string(
+                "supersecretpassword",
+                "lM1GncleQOaCu9lT1yeUZhFYnqhsLLP1G5lAGo3ixaI=",
+                "+IxH4CnCiqpX1rM9scsNynZzbOe4KhDeYcTNS3PDaeY="
+            ),
+            "1Ct943GAT+6YQUUX47Ia/ncufilbe6+oD6lY+5kaCu4="
+        );
+
+        let event = serde_json::json!({
+            "op": 5,
+            "d": {
+                "eventType": "RecordStateChanged",
+                "eventData": { "outputActive": true, "outputState": "OBS_WEBSOCKET_OUTPUT_STARTED" }
+            }
+        });
+        assert_eq!(
+            parse_event(&event),
+            Some(ObsEvent {
+                output: ObsOutput::Recording,
+                active: true
+            })
+        );
+        let status = serde_json::json!({
+            "op": 7,
+            "d": { "requestType": "GetStreamStatus", "responseData": { "outputActive": false } }
+        });
+        assert_eq!(parse_event(&status).map(|e| e.output), Some(ObsOutput::Streaming));
+    }
+}
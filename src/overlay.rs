@@ -1,9 +1,26 @@
 use crate::combo::ComboItem;
-use crate::settings::{Position, Settings};
+use crate::combo_format::ComboFormat;
+use crate::frame_stream::FrameStream;
+use crate::metrics;
+use crate::render_model::{self, Bubble, Change, Widgets};
+use crate::ring::RingBuffer;
+use crate::settings::{
+    BellStyle, BubbleAlign, BubbleLayout, Emphasis, OverlayLayer, Palette, Position, Settings,
+    Theme, DEFAULT_NAMESPACE,
+};
+use crate::x11_window::{Anchor, FloatingWindow};
 use gtk4::prelude::*;
-use gtk4::{gdk, Application, ApplicationWindow, Box as GtkBox, CenterBox, CssProvider, GestureDrag, Label, Orientation};
+use gtk4::{cairo, gdk, glib, Application, ApplicationWindow, Box as GtkBox, CenterBox, CssProvider, GestureDrag, Label, Orientation};
 use gtk4_layer_shell::{Edge, KeyboardMode, Layer, LayerShell};
-use std::collections::VecDeque;
+use std::cell::{Cell, RefCell};
+use anyhow::{Context, Result};
+use std::collections::HashMap;
+use std::path::Path;
+use std::rc::Rc;
+use std::time::Duration;
+
+/// How long the visual bell stays lit.
+const BELL_FLASH: Duration = Duration::from_millis(250);
 
 const OVERLAY_CSS: &str = r#"
 .keyway-window {
@@ -23,10 +40,244 @@ const OVERLAY_CSS: &str = r#"
     background: rgba(160, 60, 60, 0.85);
 }
 
+/* Appears at once while the peek key is held, fades when it is let go. */
+.keyway-window.peek-hidden {
+    opacity: 0;
+    transition: opacity 400ms ease-in;
+}
+
 .keyway-window.paused .key-bubble {
     background: rgba(50, 50, 50, 0.60);
     color: #d8d8d8;
 }
+
+.key-bubble.speed {
+    background: rgba(30, 110, 70, 0.85);
+    font-feature-settings: "tnum";
+}
+
+.key-bubble.lock {
+    background: rgba(200, 140, 20, 0.90);
+    color: #1a1a1a;
+}
+
+.key-bubble.apm {
+    font-size: 32px;
+    font-weight: 800;
+    padding: 4px 14px;
+}
+
+.keyway-window.presenter .key-bubble {
+    font-size: 28px;
+    padding: 12px 20px;
+    border-radius: 12px;
+}
+
+.keyway-window.presenter .key-bubble.apm {
+    font-size: 48px;
+}
+
+.key-bubble.layout-badge {
+    background: rgba(50, 50, 50, 0.55);
+    padding: 2px 6px;
+    font-size: 11px;
+    font-weight: 700;
+    letter-spacing: 1px;
+}
+
+.key-bubble.modifier-chip {
+    background: rgba(50, 50, 50, 0.45);
+    color: rgba(255, 255, 255, 0.45);
+    padding: 4px 8px;
+    font-size: 12px;
+}
+
+.key-bubble.modifier-chip.held {
+    background: rgba(40, 90, 160, 0.90);
+    color: #ffffff;
+}
+
+.key-bubble.tally {
+    background: rgba(50, 50, 50, 0.55);
+    padding: 4px 8px;
+    font-size: 12px;
+    font-weight: 500;
+    font-feature-settings: "tnum";
+}
+
+.key-bubble.teaching {
+    font-size: 26px;
+    padding: 8px 16px;
+}
+
+.key-bubble.announce {
+    background: rgba(40, 90, 160, 0.85);
+}
+
+/* Practice mode: the shortcut to press next, and how each attempt went. */
+.key-bubble.practice {
+    background: rgba(40, 90, 160, 0.85);
+    border: 2px dashed rgba(255, 255, 255, 0.6);
+}
+
+.key-bubble.practice-correct {
+    background: rgba(0, 158, 115, 0.90);
+}
+
+.key-bubble.practice-wrong {
+    background: rgba(213, 94, 0, 0.90);
+}
+
+/* A key is still to come: the held modifiers before it, dimmed. */
+.key-bubble.ghost {
+    opacity: 0.45;
+}
+
+.key-bubble.ghost-fading {
+    opacity: 0;
+    transition: opacity 300ms ease-out;
+}
+
+/* frequency_emphasis: combos pressed more often this session run warmer
+   and larger, heat-4 being the most pressed one. */
+.keyway-window.emphasis-tint .key-bubble.heat-1 {
+    background: rgba(110, 80, 30, 0.80);
+}
+
+.keyway-window.emphasis-tint .key-bubble.heat-2 {
+    background: rgba(160, 100, 20, 0.85);
+}
+
+.keyway-window.emphasis-tint .key-bubble.heat-3 {
+    background: rgba(200, 80, 20, 0.90);
+}
+
+.keyway-window.emphasis-tint .key-bubble.heat-4 {
+    background: rgba(210, 40, 30, 0.92);
+}
+
+.keyway-window.emphasis-size .key-bubble.heat-1 {
+    font-size: 15px;
+}
+
+.keyway-window.emphasis-size .key-bubble.heat-2 {
+    font-size: 17px;
+}
+
+.keyway-window.emphasis-size .key-bubble.heat-3 {
+    font-size: 19px;
+}
+
+.keyway-window.emphasis-size .key-bubble.heat-4 {
+    font-size: 22px;
+}
+
+.keyway-window.bell .key-bubble {
+    background: rgba(240, 170, 40, 0.95);
+    color: #1a1a1a;
+}
+
+.keyway-bell {
+    background: transparent;
+    border: 8px solid rgba(240, 170, 40, 0.95);
+}
+
+.filter-dot {
+    background: rgba(240, 170, 40, 0.85);
+    min-width: 10px;
+    min-height: 10px;
+    border-radius: 5px;
+}
+
+.keyway-window.theme-light .key-bubble {
+    background: rgba(245, 245, 245, 0.85);
+    color: #1a1a1a;
+}
+
+.keyway-window.theme-light .key-bubble.status {
+    background: rgba(230, 150, 150, 0.90);
+}
+
+.keyway-window.theme-light .key-bubble.lock {
+    background: rgba(250, 200, 90, 0.90);
+}
+
+.keyway-window.theme-light .key-bubble.modifier-chip {
+    background: rgba(230, 230, 230, 0.60);
+    color: rgba(26, 26, 26, 0.45);
+}
+
+.keyway-window.theme-light .key-bubble.modifier-chip.held {
+    background: rgba(120, 170, 230, 0.95);
+    color: #1a1a1a;
+}
+
+.keyway-window.theme-light.paused .key-bubble {
+    background: rgba(210, 210, 210, 0.60);
+    color: #505050;
+}
+
+/* Color vision palettes, picked from the Okabe-Ito set. They override both
+   themes but leave the paused look alone. */
+.keyway-window.palette-deuteranopia:not(.paused) .key-bubble.status {
+    background: rgba(213, 94, 0, 0.90);
+    color: #ffffff;
+}
+
+.keyway-window.palette-deuteranopia:not(.paused) .key-bubble.lock {
+    background: rgba(240, 228, 66, 0.90);
+    color: #1a1a1a;
+}
+
+.keyway-window.palette-deuteranopia:not(.paused) .key-bubble.speed {
+    background: rgba(0, 114, 178, 0.90);
+    color: #ffffff;
+}
+
+.keyway-window.palette-deuteranopia:not(.paused) .key-bubble.announce {
+    background: rgba(204, 121, 167, 0.90);
+    color: #1a1a1a;
+}
+
+.keyway-window.palette-protanopia:not(.paused) .key-bubble.status {
+    background: rgba(230, 159, 0, 0.90);
+    color: #1a1a1a;
+}
+
+.keyway-window.palette-protanopia:not(.paused) .key-bubble.lock {
+    background: rgba(86, 180, 233, 0.90);
+    color: #1a1a1a;
+}
+
+.keyway-window.palette-protanopia:not(.paused) .key-bubble.speed {
+    background: rgba(0, 114, 178, 0.90);
+    color: #ffffff;
+}
+
+.keyway-window.palette-protanopia:not(.paused) .key-bubble.announce {
+    background: rgba(204, 121, 167, 0.90);
+    color: #1a1a1a;
+}
+
+.keyway-window.palette-tritanopia:not(.paused) .key-bubble.status {
+    background: rgba(213, 94, 0, 0.90);
+    color: #ffffff;
+}
+
+.keyway-window.palette-tritanopia:not(.paused) .key-bubble.lock {
+    background: rgba(235, 235, 235, 0.90);
+    color: #1a1a1a;
+}
+
+.keyway-window.palette-tritanopia:not(.paused) .key-bubble.speed {
+    background: rgba(0, 114, 178, 0.90);
+    color: #ffffff;
+}
+
+.keyway-window.palette-tritanopia:not(.paused) .key-bubble.announce {
+    background: rgba(86, 180, 233, 0.90);
+    color: #1a1a1a;
+}
 "#;
 
 #[derive(Clone)]
@@ -36,6 +287,39 @@ pub struct OverlayWindow {
     container: GtkBox,
     drag: GestureDrag,
     drag_enabled: std::cell::Cell<bool>,
+    extra_css: std::cell::RefCell<Option<CssProvider>>,
+    speed: std::cell::RefCell<Option<(String, &'static str)>>,
+    locks: std::cell::Cell<Option<&'static str>>,
+    modifiers: Cell<Option<[bool; 4]>>,
+    layout: RefCell<Option<String>>,
+    tally: RefCell<Option<Vec<(String, u64)>>>,
+    practice: RefCell<Option<(String, Option<String>)>>,
+    combo_format: RefCell<Option<ComboFormat>>,
+    /// Placement on X11, where there is no layer shell
+    floating: Option<Rc<FloatingWindow>>,
+    /// Full-screen frame for the edge bell, created on first use
+    bell_window: RefCell<Option<ApplicationWindow>>,
+    /// Counts flashes, so only the latest one turns the bell off
+    bell_flashes: Rc<Cell<u64>>,
+    /// Off-screen copy of every frame, for compositing tools
+    frame_stream: RefCell<Option<Rc<FrameStream>>>,
+    bubbles: Rc<BubbleView>,
+}
+
+/// The bubble labels, shared with the frame clock callback that updates them.
+struct BubbleView {
+    window: ApplicationWindow,
+    container: GtkBox,
+    filter_dot: GtkBox,
+    /// Bubbles on screen, as last applied
+    frame: RefCell<Vec<Bubble>>,
+    labels: RefCell<HashMap<u64, Label>>,
+    /// Labels of removed bubbles, reused for the next ones
+    spare: RefCell<Vec<Label>>,
+    /// Latest frame not yet applied; renders between two compositor frames
+    /// replace it, so only the last one reaches GTK
+    pending: RefCell<Option<Vec<Bubble>>>,
+    tick_scheduled: Cell<bool>,
 }
 
 impl OverlayWindow {
@@ -46,9 +330,11 @@ impl OverlayWindow {
             .resizable(false)
             .build();
 
-        window.init_layer_shell();
-        window.set_layer(Layer::Overlay);
-        window.set_namespace("keyway-visualizer");
+        let floating = FloatingWindow::new(&window).map(Rc::new);
+        if floating.is_none() {
+            window.init_layer_shell();
+            apply_layer(&window, settings.layer, &settings.namespace);
+        }
         let root = CenterBox::new();
         root.set_hexpand(true);
         root.set_vexpand(true);
@@ -59,18 +345,13 @@ impl OverlayWindow {
         container.set_margin_start(8);
         container.set_margin_end(8);
 
-        window.set_keyboard_mode(KeyboardMode::None);
-
-        apply_position(
-            &window,
-            &root,
-            &container,
-            settings.position,
-            settings.margin,
-            settings.custom_x,
-            settings.custom_y,
-        );
-        window.set_exclusive_zone(0);
+        let anchor = apply_position(&window, &root, &container, settings);
+        if let Some(floating) = &floating {
+            floating.set_anchor(anchor);
+        } else {
+            window.set_keyboard_mode(KeyboardMode::None);
+            window.set_exclusive_zone(0);
+        }
 
         window.set_child(Some(&root));
         window.add_css_class("keyway-window");
@@ -83,54 +364,287 @@ impl OverlayWindow {
         drag.set_button(0);
         root.add_controller(drag.clone());
 
+        let filter_dot = GtkBox::new(Orientation::Horizontal, 0);
+        filter_dot.add_css_class("filter-dot");
+        filter_dot.set_tooltip_text(Some("Hidden for this app"));
+        filter_dot.set_visible(false);
+        container.append(&filter_dot);
+
+        let bubbles = Rc::new(BubbleView {
+            window: window.clone(),
+            container: container.clone(),
+            filter_dot,
+            frame: RefCell::new(Vec::new()),
+            labels: RefCell::new(HashMap::new()),
+            spare: RefCell::new(Vec::new()),
+            pending: RefCell::new(None),
+            tick_scheduled: Cell::new(false),
+        });
+
         Self {
             window,
             root,
             container,
             drag,
             drag_enabled: std::cell::Cell::new(false),
+            extra_css: std::cell::RefCell::new(None),
+            speed: std::cell::RefCell::new(None),
+            locks: std::cell::Cell::new(None),
+            modifiers: Cell::new(None),
+            layout: RefCell::new(None),
+            combo_format: RefCell::new(None),
+            tally: RefCell::new(None),
+            practice: RefCell::new(None),
+            floating,
+            bell_window: RefCell::new(None),
+            bell_flashes: Rc::new(Cell::new(0)),
+            frame_stream: RefCell::new(None),
+            bubbles,
         }
     }
 
-    pub fn render(&self, combos: &VecDeque<ComboItem>, paused: bool) {
+    pub fn render(
+        &self,
+        combos: &RingBuffer<ComboItem>,
+        status: &RingBuffer<ComboItem>,
+        paused: bool,
+    ) {
+        metrics::inc(&metrics::RENDERS);
         if paused {
             self.window.add_css_class("paused");
         } else {
             self.window.remove_css_class("paused");
         }
 
-        while let Some(child) = self.container.first_child() {
-            self.container.remove(&child);
+        self.bubbles.filter_dot.set_visible(false);
+
+        let speed = self.speed.borrow();
+        let layout = self.layout.borrow();
+        let tally = self.tally.borrow();
+        let practice = self.practice.borrow();
+        let format = self.combo_format.borrow();
+        let widgets = Widgets {
+            format: format.as_ref(),
+            layout: layout.as_deref(),
+            locks: self.locks.get(),
+            speed: speed.as_ref().map(|(text, class)| (text.as_str(), *class)),
+            modifiers: self.modifiers.get(),
+            tally: tally.as_deref(),
+            practice: practice
+                .as_ref()
+                .map(|(combo, description)| (combo.as_str(), description.as_deref())),
+        };
+        let next = render_model::build(combos, status, &widgets);
+        if let Some(stream) = &*self.frame_stream.borrow() {
+            stream.render(&next);
         }
+        self.bubbles.schedule(next);
+    }
 
-        for combo in combos {
-            let label = Label::new(Some(&combo.text));
-            label.add_css_class("key-bubble");
-            if combo.text == "Paused" || combo.text == "Resumed" {
-                label.add_css_class("status");
-            }
-            self.container.append(&label);
-        }
+    /// How combos are spelled from the next render on; `None` for the
+    /// built-in "Ctrl+Shift+T".
+    pub fn set_combo_format(&self, format: Option<ComboFormat>) {
+        *self.combo_format.borrow_mut() = format;
+    }
+
+    /// Where every rendered frame is also drawn, or `None` to stop.
+    pub fn set_frame_stream(&self, stream: Option<Rc<FrameStream>>) {
+        *self.frame_stream.borrow_mut() = stream;
+    }
 
+    /// Text of the speed widget shown after the bubbles on the next render,
+    /// with an extra CSS class for its style.
+    pub fn set_speed(&self, speed: Option<(String, &'static str)>) {
+        *self.speed.borrow_mut() = speed;
+    }
+
+    /// Text of the lock widget shown on the next render; it stays up for as
+    /// long as it is set, whether or not any keys are on screen.
+    pub fn set_locks(&self, locks: Option<&'static str>) {
+        self.locks.set(locks);
+    }
+
+    /// Text of the layout badge for the next render, or `None` to leave it out.
+    pub fn set_layout_badge(&self, layout: Option<String>) {
+        *self.layout.borrow_mut() = layout;
+    }
+
+    /// Held state of the modifier panel chips for the next render, or `None`
+    /// to leave the panel out.
+    pub fn set_modifiers(&self, modifiers: Option<[bool; 4]>) {
+        self.modifiers.set(modifiers);
+    }
+
+    /// Shortcuts and counts of the tally panel for the next render, or
+    /// `None` to leave it out.
+    pub fn set_tally(&self, tally: Option<Vec<(String, u64)>>) {
+        *self.tally.borrow_mut() = tally;
+    }
+
+    /// The practice prompt for the next render, or `None` to leave it out.
+    pub fn set_practice(&self, practice: Option<(String, Option<String>)>) {
+        *self.practice.borrow_mut() = practice;
+    }
+
+    /// Replaces the bubbles with a small dot telling the user the overlay is
+    /// hidden on purpose for the focused app.
+    pub fn render_filter_dot(&self) {
+        self.bubbles.pending.take();
+        self.bubbles.apply(Vec::new());
+        self.bubbles.filter_dot.set_visible(true);
         self.window.queue_resize();
     }
 
+    /// Changing the namespace remaps the window, so only do it on change.
+    pub fn update_layer(&self, settings: &Settings) {
+        if !self.window.is_layer_window() {
+            return;
+        }
+        let namespace = namespace_or_default(&settings.namespace);
+        if self.window.namespace().as_deref() != Some(namespace) {
+            self.window.set_namespace(namespace);
+        }
+        self.window.set_layer(layer_shell_layer(settings.layer));
+    }
+
     pub fn update_position(&self, settings: &Settings) {
-        apply_position(
-            &self.window,
-            &self.root,
-            &self.container,
-            settings.position,
-            settings.margin,
-            settings.custom_x,
-            settings.custom_y,
-        );
+        let anchor = apply_position(&self.window, &self.root, &self.container, settings);
+        if let Some(floating) = &self.floating {
+            floating.set_anchor(anchor);
+        }
         self.window.queue_resize();
     }
 
+    /// Replaces the stylesheet layered on top of the built-in one (plugins).
+    pub fn set_extra_css(&self, css: &str) {
+        let display = gtk4::prelude::WidgetExt::display(&self.window);
+        if let Some(old) = self.extra_css.borrow_mut().take() {
+            gtk4::style_context_remove_provider_for_display(&display, &old);
+        }
+        if css.trim().is_empty() {
+            return;
+        }
+
+        let provider = CssProvider::new();
+        provider.load_from_string(css);
+        gtk4::style_context_add_provider_for_display(
+            &display,
+            &provider,
+            gtk4::STYLE_PROVIDER_PRIORITY_USER,
+        );
+        *self.extra_css.borrow_mut() = Some(provider);
+    }
+
+    /// `Theme::Auto` is resolved by the caller; unresolved it is dark.
+    pub fn set_theme(&self, theme: Theme) {
+        self.window.remove_css_class("theme-dark");
+        self.window.remove_css_class("theme-light");
+        self.window.add_css_class(match theme {
+            Theme::Dark | Theme::Auto => "theme-dark",
+            Theme::Light => "theme-light",
+        });
+    }
+
+    /// Renders the bubbles as they are on screen now to a PNG, with the
+    /// window's own renderer so it matches pixel for pixel.
+    pub fn save_png(&self, path: &Path) -> Result<()> {
+        let (width, height) = (self.container.width(), self.container.height());
+        if width <= 0 || height <= 0 {
+            anyhow::bail!("The overlay shows nothing");
+        }
+        let paintable = gtk4::WidgetPaintable::new(Some(&self.container));
+        let snapshot = gtk4::Snapshot::new();
+        paintable.snapshot(&snapshot, f64::from(width), f64::from(height));
+        let node = snapshot.to_node().context("The overlay shows nothing")?;
+        let renderer = self.window.renderer().context("The overlay is not realized")?;
+        let texture = renderer.render_texture(&node, None);
+        texture
+            .save_to_png(path)
+            .with_context(|| format!("Failed to write {:?}", path))
+    }
+
+    /// Lights the screen edge or the bubbles briefly.
+    pub fn flash_bell(&self, style: BellStyle) {
+        let target = match style {
+            BellStyle::Overlay => self.window.clone(),
+            BellStyle::Edge => self
+                .bell_window
+                .borrow_mut()
+                .get_or_insert_with(|| build_bell_window(&self.window))
+                .clone(),
+        };
+        match style {
+            BellStyle::Overlay => target.add_css_class("bell"),
+            BellStyle::Edge => target.set_visible(true),
+        }
+
+        let flash = self.bell_flashes.get() + 1;
+        self.bell_flashes.set(flash);
+        let flashes = Rc::clone(&self.bell_flashes);
+        glib::timeout_add_local_once(BELL_FLASH, move || {
+            if flashes.get() != flash {
+                return;
+            }
+            match style {
+                BellStyle::Overlay => target.remove_css_class("bell"),
+                BellStyle::Edge => target.set_visible(false),
+            }
+        });
+    }
+
+    pub fn set_presenter(&self, enabled: bool) {
+        if enabled {
+            self.window.add_css_class("presenter");
+        } else {
+            self.window.remove_css_class("presenter");
+        }
+    }
+
+    /// Hold-to-peek: fades the overlay out, keeping it mapped so it comes
+    /// back without a resize.
+    pub fn set_peek_hidden(&self, hidden: bool) {
+        if hidden {
+            self.window.add_css_class("peek-hidden");
+        } else {
+            self.window.remove_css_class("peek-hidden");
+        }
+    }
+
+    pub fn set_palette(&self, palette: Palette) {
+        for class in ["palette-deuteranopia", "palette-protanopia", "palette-tritanopia"] {
+            self.window.remove_css_class(class);
+        }
+        let class = match palette {
+            Palette::Default => return,
+            Palette::Deuteranopia => "palette-deuteranopia",
+            Palette::Protanopia => "palette-protanopia",
+            Palette::Tritanopia => "palette-tritanopia",
+        };
+        self.window.add_css_class(class);
+    }
+
+    pub fn set_emphasis(&self, emphasis: Emphasis) {
+        let (tint, size) = match emphasis {
+            Emphasis::Off => (false, false),
+            Emphasis::Tint => (true, false),
+            Emphasis::Size => (false, true),
+            Emphasis::Both => (true, true),
+        };
+        for (class, enabled) in [("emphasis-tint", tint), ("emphasis-size", size)] {
+            if enabled {
+                self.window.add_css_class(class);
+            } else {
+                self.window.remove_css_class(class);
+            }
+        }
+    }
+
     pub fn set_drag_enabled(&self, enabled: bool) {
         self.drag_enabled.set(enabled);
         self.window.set_can_target(enabled);
+        if let Some(floating) = &self.floating {
+            floating.set_click_through(!enabled);
+        }
     }
 
     pub fn set_visible(&self, visible: bool) {
@@ -175,6 +689,163 @@ impl OverlayWindow {
     }
 }
 
+impl BubbleView {
+    /// Applies `next` on the overlay's next frame clock tick, so bursts of
+    /// renders cost one relayout per compositor frame.
+    fn schedule(self: &Rc<Self>, next: Vec<Bubble>) {
+        *self.pending.borrow_mut() = Some(next);
+        if self.tick_scheduled.replace(true) {
+            return;
+        }
+        let view = Rc::clone(self);
+        self.window.add_tick_callback(move |_, _| {
+            view.tick_scheduled.set(false);
+            if let Some(next) = view.pending.take() {
+                view.apply(next);
+            }
+            glib::ControlFlow::Break
+        });
+    }
+
+    /// Applies only what changed since the last frame to the labels.
+    fn apply(&self, next: Vec<Bubble>) {
+        let mut frame = self.frame.borrow_mut();
+        let changes = render_model::diff(&frame, &next);
+        if changes.is_empty() {
+            return;
+        }
+
+        let mut labels = self.labels.borrow_mut();
+        let mut spare = self.spare.borrow_mut();
+        for change in changes {
+            match change {
+                Change::Remove(id) => {
+                    if let Some(label) = labels.remove(&id) {
+                        self.container.remove(&label);
+                        spare.push(label);
+                    }
+                }
+                Change::Update(bubble) => {
+                    if let Some(label) = labels.get(&bubble.id) {
+                        update_label(label, &bubble);
+                    }
+                }
+                Change::Insert { index, bubble } => {
+                    let label = spare.pop().unwrap_or_else(|| Label::new(None));
+                    label.set_halign(self.container.halign());
+                    update_label(&label, &bubble);
+                    let after: gtk4::Widget = match index.checked_sub(1) {
+                        Some(before) => labels[&next[before].id].clone().upcast(),
+                        None => self.filter_dot.clone().upcast(),
+                    };
+                    self.container.insert_child_after(&label, Some(&after));
+                    labels.insert(bubble.id, label);
+                }
+            }
+        }
+
+        *frame = next;
+        self.window.queue_resize();
+    }
+}
+
+fn update_label(label: &Label, bubble: &Bubble) {
+    if label.css_classes().iter().ne(bubble.classes.iter()) {
+        let classes: Vec<&str> = bubble.classes.iter().map(String::as_str).collect();
+        label.set_css_classes(&classes);
+    }
+    if let Some(caption) = &bubble.caption {
+        let markup = format!(
+            "{}\n<span size=\"55%\" weight=\"normal\">{}</span>",
+            glib::markup_escape_text(&bubble.text),
+            glib::markup_escape_text(caption)
+        );
+        if label.label() != markup {
+            label.set_markup(&markup);
+        }
+        return;
+    }
+    let shown = label.text();
+    // A recycled teaching label still holds markup; set_text clears it.
+    if label.uses_markup()
+        || (shown != bubble.text
+            && (bubble.text.is_ascii() || shown != render_model::code_points(&bubble.text)))
+    {
+        label.set_text(&bubble.text);
+        // Pango draws glyphs no font has as boxes; name the code points instead.
+        if !bubble.text.is_ascii() && label.layout().unknown_glyphs_count() > 0 {
+            label.set_text(&render_model::code_points(&bubble.text));
+        }
+    }
+}
+
+fn apply_layer(window: &ApplicationWindow, layer: OverlayLayer, namespace: &str) {
+    window.set_layer(layer_shell_layer(layer));
+    window.set_namespace(namespace_or_default(namespace));
+}
+
+fn layer_shell_layer(layer: OverlayLayer) -> Layer {
+    match layer {
+        OverlayLayer::Overlay => Layer::Overlay,
+        OverlayLayer::Top => Layer::Top,
+        OverlayLayer::Bottom => Layer::Bottom,
+        OverlayLayer::Background => Layer::Background,
+    }
+}
+
+fn namespace_or_default(namespace: &str) -> &str {
+    match namespace.trim() {
+        "" => DEFAULT_NAMESPACE,
+        namespace => namespace,
+    }
+}
+
+/// A click-through frame over the whole output the overlay is on.
+fn build_bell_window(overlay: &ApplicationWindow) -> ApplicationWindow {
+    let window = ApplicationWindow::builder()
+        .decorated(false)
+        .resizable(false)
+        .build();
+    window.set_application(overlay.application().as_ref());
+    let monitor = overlay
+        .surface()
+        .and_then(|surface| surface.display().monitor_at_surface(&surface));
+    if let Some(floating) = FloatingWindow::new(&window) {
+        // Sized to the monitor by hand; its realize handler keeps it.
+        if let Some(monitor) = &monitor {
+            let geometry = monitor.geometry();
+            window.set_default_size(geometry.width(), geometry.height());
+        }
+        floating.set_anchor(Anchor {
+            top: true,
+            bottom: true,
+            left: true,
+            right: true,
+            margins: [0; 4],
+        });
+        window.add_css_class("keyway-bell");
+        return window;
+    }
+    window.init_layer_shell();
+    window.set_layer(Layer::Overlay);
+    window.set_namespace("keyway-bell");
+    window.set_keyboard_mode(KeyboardMode::None);
+    for edge in [Edge::Top, Edge::Bottom, Edge::Left, Edge::Right] {
+        window.set_anchor(edge, true);
+    }
+    window.set_exclusive_zone(-1);
+    if let Some(monitor) = monitor {
+        window.set_monitor(&monitor);
+    }
+    window.add_css_class("keyway-bell");
+    window.connect_realize(|window| {
+        if let Some(surface) = window.surface() {
+            surface.set_input_region(&cairo::Region::create());
+        }
+    });
+    window
+}
+
 fn apply_css(window: &ApplicationWindow) {
     let provider = CssProvider::new();
     provider.load_from_string(OVERLAY_CSS);
@@ -187,106 +858,108 @@ fn apply_css(window: &ApplicationWindow) {
     );
 }
 
+/// Lines the bubbles up for `settings.position`; returns where the window
+/// goes, already applied when it is a layer-shell surface.
 fn apply_position(
     window: &ApplicationWindow,
     root: &CenterBox,
     container: &GtkBox,
-    position: Position,
-    margin: i32,
-    custom_x: i32,
-    custom_y: i32,
-) {
-    apply_size_for_position(window, position, margin);
+    settings: &Settings,
+) -> Anchor {
+    let (position, margin) = (settings.position, settings.margin);
+    // Custom positions have no edge to line up along.
+    let align = match position {
+        Position::Custom => BubbleAlign::Auto,
+        _ => settings.bubble_align,
+    };
+    apply_size_for_position(window, position, align, margin);
 
     root.set_start_widget(None::<&gtk4::Widget>);
     root.set_center_widget(None::<&gtk4::Widget>);
     root.set_end_widget(None::<&gtk4::Widget>);
 
-    match position {
-        Position::BottomRight => {
-            window.set_anchor(Edge::Top, false);
-            window.set_anchor(Edge::Bottom, true);
-            window.set_anchor(Edge::Left, false);
-            window.set_anchor(Edge::Right, true);
-            root.set_end_widget(Some(container));
-            container.set_valign(gtk4::Align::End);
-        }
-        Position::BottomCenter => {
-            window.set_anchor(Edge::Top, false);
-            window.set_anchor(Edge::Bottom, true);
-            window.set_anchor(Edge::Left, true);
-            window.set_anchor(Edge::Right, true);
-            root.set_center_widget(Some(container));
-            container.set_valign(gtk4::Align::End);
-        }
-        Position::BottomLeft => {
-            window.set_anchor(Edge::Top, false);
-            window.set_anchor(Edge::Bottom, true);
-            window.set_anchor(Edge::Left, true);
-            window.set_anchor(Edge::Right, false);
-            root.set_start_widget(Some(container));
-            container.set_valign(gtk4::Align::End);
-        }
-        Position::TopRight => {
-            window.set_anchor(Edge::Top, true);
-            window.set_anchor(Edge::Bottom, false);
-            window.set_anchor(Edge::Left, false);
-            window.set_anchor(Edge::Right, true);
-            root.set_end_widget(Some(container));
-            container.set_valign(gtk4::Align::Start);
-        }
-        Position::TopCenter => {
-            window.set_anchor(Edge::Top, true);
-            window.set_anchor(Edge::Bottom, false);
-            window.set_anchor(Edge::Left, true);
-            window.set_anchor(Edge::Right, true);
-            root.set_center_widget(Some(container));
-            container.set_valign(gtk4::Align::Start);
-        }
-        Position::TopLeft => {
-            window.set_anchor(Edge::Top, true);
-            window.set_anchor(Edge::Bottom, false);
-            window.set_anchor(Edge::Left, true);
-            window.set_anchor(Edge::Right, false);
-            root.set_start_widget(Some(container));
-            container.set_valign(gtk4::Align::Start);
-        }
-        Position::Center => {
-            window.set_anchor(Edge::Top, true);
-            window.set_anchor(Edge::Bottom, true);
-            window.set_anchor(Edge::Left, true);
-            window.set_anchor(Edge::Right, true);
-            root.set_center_widget(Some(container));
-            container.set_valign(gtk4::Align::Center);
-        }
-        Position::Custom => {
-            window.set_anchor(Edge::Top, true);
-            window.set_anchor(Edge::Bottom, false);
-            window.set_anchor(Edge::Left, true);
-            window.set_anchor(Edge::Right, false);
-            root.set_start_widget(Some(container));
-            container.set_valign(gtk4::Align::Start);
-        }
+    let (top, bottom, left, right) = match position {
+        Position::BottomRight => (false, true, false, true),
+        Position::BottomCenter => (false, true, true, true),
+        Position::BottomLeft => (false, true, true, false),
+        Position::TopRight => (true, false, false, true),
+        Position::TopCenter => (true, false, true, true),
+        Position::TopLeft | Position::Custom => (true, false, true, false),
+        Position::Center => (true, true, true, true),
+    };
+    // A set alignment spans the overlay across its edge, so the row can
+    // sit anywhere along it.
+    let spans = align != BubbleAlign::Auto;
+    container.set_valign(match (top, bottom) {
+        (true, false) => gtk4::Align::Start,
+        (false, true) => gtk4::Align::End,
+        _ => gtk4::Align::Center,
+    });
+
+    let align = match (align, left, right) {
+        (BubbleAlign::Auto, true, false) => BubbleAlign::Start,
+        (BubbleAlign::Auto, false, true) => BubbleAlign::End,
+        (BubbleAlign::Auto, _, _) => BubbleAlign::Center,
+        (align, _, _) => align,
+    };
+    match align {
+        BubbleAlign::Start => root.set_start_widget(Some(container)),
+        BubbleAlign::End => root.set_end_widget(Some(container)),
+        _ => root.set_center_widget(Some(container)),
+    }
+    // In the lines layout each bubble keeps its own width, lined up to the
+    // same side as the column.
+    container.set_orientation(match settings.bubble_layout {
+        BubbleLayout::Row => Orientation::Horizontal,
+        BubbleLayout::Lines => Orientation::Vertical,
+    });
+    container.set_halign(match align {
+        BubbleAlign::Start => gtk4::Align::Start,
+        BubbleAlign::End => gtk4::Align::End,
+        _ => gtk4::Align::Center,
+    });
+    let mut child = container.first_child();
+    while let Some(widget) = child {
+        widget.set_halign(container.halign());
+        child = widget.next_sibling();
     }
 
-    if matches!(position, Position::Custom) {
-        window.set_margin(Edge::Top, custom_y);
-        window.set_margin(Edge::Bottom, 0);
-        window.set_margin(Edge::Left, custom_x);
-        window.set_margin(Edge::Right, 0);
-    } else {
-        window.set_margin(Edge::Top, margin);
-        window.set_margin(Edge::Bottom, margin);
-        window.set_margin(Edge::Left, margin);
-        window.set_margin(Edge::Right, margin);
+    let anchor = Anchor {
+        top,
+        bottom,
+        left: left || spans,
+        right: right || spans,
+        margins: match position {
+            Position::Custom => [settings.custom_y, 0, settings.custom_x, 0],
+            _ => [margin; 4],
+        },
+    };
+    if window.is_layer_window() {
+        let [top_margin, bottom_margin, left_margin, right_margin] = anchor.margins;
+        for (edge, anchored, edge_margin) in [
+            (Edge::Top, anchor.top, top_margin),
+            (Edge::Bottom, anchor.bottom, bottom_margin),
+            (Edge::Left, anchor.left, left_margin),
+            (Edge::Right, anchor.right, right_margin),
+        ] {
+            window.set_anchor(edge, anchored);
+            window.set_margin(edge, edge_margin);
+        }
     }
+    anchor
 }
 
-fn apply_size_for_position(window: &ApplicationWindow, position: Position, margin: i32) {
-    let span_x = matches!(
-        position,
-        Position::BottomCenter | Position::TopCenter | Position::Center
-    );
+fn apply_size_for_position(
+    window: &ApplicationWindow,
+    position: Position,
+    align: BubbleAlign,
+    margin: i32,
+) {
+    let span_x = align != BubbleAlign::Auto
+        || matches!(
+            position,
+            Position::BottomCenter | Position::TopCenter | Position::Center
+        );
     let span_y = matches!(position, Position::Center);
 
     if !(span_x || span_y) {
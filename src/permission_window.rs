@@ -0,0 +1,162 @@
+use crate::flatpak;
+use gtk4::gio::{Cancellable, Subprocess, SubprocessFlags};
+use gtk4::prelude::*;
+use gtk4::{Application, ApplicationWindow, Box as GtkBox, Button, Label, Orientation};
+use std::ffi::OsStr;
+use std::path::Path;
+use std::rc::Rc;
+use tracing::{info, warn};
+
+/// `packaging/keyway-input-access`, which installs or removes the udev rule.
+/// Its polkit action names this path, so pkexec shows that action's message.
+const HELPER: &str = "/usr/libexec/keyway-input-access";
+
+const EXPLANATION: &str = "Keyway reads key presses from /dev/input/event*, which your user \
+     is not allowed to open.\n\n\
+     \"Grant access\" installs a udev rule (70-keyway-input.rules) that lets whoever is signed \
+     in at this seat read keyboards and mice. It asks for your password through polkit. Capture \
+     starts a few seconds later, without logging out. \"Remove access\" takes the rule away again.\n\n\
+     Alternatively, join the input group and log in again:\n\
+     sudo usermod -aG input $USER";
+
+/// Inside Flatpak neither udev nor pkexec are reachable; the sandbox itself
+/// has to let the devices in.
+const FLATPAK_EXPLANATION: &str = "Keyway reads key presses from /dev/input/event*, which this \
+     Flatpak is not allowed to open.\n\n\
+     Give it access to input devices from a terminal, then start it again:\n\
+     flatpak override --user --device=input {app_id}\n\n\
+     Until then the pause and clear shortcuts go through the desktop's global shortcuts.";
+
+/// Explains why no keys are captured and installs or removes the udev rule
+/// through pkexec on request.
+pub struct PermissionWindow {
+    window: ApplicationWindow,
+    status: Label,
+    grant_button: Button,
+    revoke_button: Button,
+}
+
+impl PermissionWindow {
+    pub fn new(app: &Application) -> Rc<Self> {
+        let window = ApplicationWindow::builder()
+            .application(app)
+            .title("Keyway Visualizer Input Access")
+            .default_width(420)
+            .hide_on_close(true)
+            .build();
+
+        let content = GtkBox::new(Orientation::Vertical, 12);
+        content.set_margin_top(16);
+        content.set_margin_bottom(16);
+        content.set_margin_start(16);
+        content.set_margin_end(16);
+
+        let sandboxed = flatpak::in_flatpak();
+        let text = if sandboxed {
+            FLATPAK_EXPLANATION.replace("{app_id}", &flatpak::app_id())
+        } else {
+            EXPLANATION.to_string()
+        };
+        let explanation = Label::new(Some(&text));
+        explanation.set_wrap(true);
+        explanation.set_xalign(0.0);
+        explanation.set_selectable(true);
+
+        let status = Label::new(None);
+        status.set_wrap(true);
+        status.set_xalign(0.0);
+        status.add_css_class("dim-label");
+
+        let grant_button = Button::with_label("Grant access");
+        let revoke_button = Button::with_label("Remove access");
+        let close_button = Button::with_label("Close");
+        let button_row = GtkBox::new(Orientation::Horizontal, 8);
+        grant_button.set_visible(!sandboxed);
+        revoke_button.set_visible(!sandboxed);
+        button_row.append(&grant_button);
+        button_row.append(&revoke_button);
+        button_row.append(&close_button);
+
+        content.append(&explanation);
+        content.append(&status);
+        content.append(&button_row);
+        window.set_child(Some(&content));
+
+        let this = Rc::new(Self {
+            window,
+            status,
+            grant_button,
+            revoke_button,
+        });
+
+        let grant = Rc::downgrade(&this);
+        this.grant_button.connect_clicked(move |_| {
+            if let Some(this) = grant.upgrade() {
+                this.run_helper("install");
+            }
+        });
+        let revoke = Rc::downgrade(&this);
+        this.revoke_button.connect_clicked(move |_| {
+            if let Some(this) = revoke.upgrade() {
+                this.run_helper("uninstall");
+            }
+        });
+        let close = this.window.clone();
+        close_button.connect_clicked(move |_| close.set_visible(false));
+
+        this
+    }
+
+    pub fn present(&self) {
+        self.window.present();
+    }
+
+    /// Runs the helper with `verb` (`install` or `uninstall`) as root.
+    fn run_helper(self: &Rc<Self>, verb: &'static str) {
+        if !Path::new(HELPER).exists() {
+            self.status.set_text(&format!(
+                "{} is not installed; see Permissions in the README.",
+                HELPER
+            ));
+            return;
+        }
+        let argv = ["pkexec", HELPER, verb].map(OsStr::new);
+        let process = match Subprocess::newv(&argv, SubprocessFlags::NONE) {
+            Ok(process) => process,
+            Err(e) => {
+                warn!("Failed to run pkexec: {}", e);
+                self.status.set_text(&format!("Could not run pkexec: {}", e));
+                return;
+            }
+        };
+
+        self.set_buttons_sensitive(false);
+        self.status.set_text("Waiting for authentication...");
+        let this = Rc::clone(self);
+        let waited = process.clone();
+        process.wait_async(None::<&Cancellable>, move |result| {
+            this.set_buttons_sensitive(true);
+            let message = match result.map(|()| waited.exit_status()) {
+                Ok(0) if verb == "install" => {
+                    info!("Installed the input access udev rule");
+                    "Access granted. Keys will show up within a few seconds.".to_string()
+                }
+                Ok(0) => {
+                    info!("Removed the input access udev rule");
+                    "Access removed. Devices already open stay readable until the next login."
+                        .to_string()
+                }
+                // pkexec's codes for a dismissed or refused authentication.
+                Ok(126 | 127) => "Authentication was cancelled or refused.".to_string(),
+                Ok(code) => format!("Setup failed (exit status {}).", code),
+                Err(e) => format!("Setup failed: {}", e),
+            };
+            this.status.set_text(&message);
+        });
+    }
+
+    fn set_buttons_sensitive(&self, sensitive: bool) {
+        self.grant_button.set_sensitive(sensitive);
+        self.revoke_button.set_sensitive(sensitive);
+    }
+}
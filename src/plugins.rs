@@ -0,0 +1,319 @@
+use crate::combo::{ComboEvent, ComboTransform, Transformed};
+use anyhow::{Context, Result};
+use mlua::{Function, HookTriggers, Lua, LuaOptions, StdLib, Table, Value};
+use std::fs;
+use std::path::{Path, PathBuf};
+use tracing::{info, warn};
+
+/// Lua instructions a plugin may run per call, and for loading, before it
+/// is stopped with an error; checked every `HOOK_INTERVAL` instructions.
+const INSTRUCTION_BUDGET: u32 = 1_000_000;
+const HOOK_INTERVAL: u32 = 1000;
+/// Memory each plugin's state may allocate; going over raises an error.
+const MEMORY_LIMIT: usize = 16 * 1024 * 1024;
+
+/// Hook checks left for the running call, kept in the state's app data.
+struct Budget(u32);
+
+struct Plugin {
+    name: String,
+    lua: Lua,
+    failed: bool,
+}
+
+/// Lua scripts from the plugins directory, applied in file name order.
+///
+/// A plugin may define `transform(combo)`, called with `{ text, kind }` for
+/// every new combo. Returning `nil` keeps it, `false` drops it, a string
+/// relabels it, and a table `{ text = .., class = .., merge = .. }` relabels,
+/// adds a CSS class to the bubble, or replaces the newest bubble. A global
+/// `css` string is added to the overlay stylesheet.
+///
+/// Plugins run on the UI thread, so each gets a state with only the
+/// `string`, `table` and `math` libraries, a memory limit and a budget of
+/// instructions per call; one that runs out of either is disabled like one
+/// that raises an error.
+pub struct PluginHost {
+    plugins: Vec<Plugin>,
+    css: String,
+}
+
+impl PluginHost {
+    pub fn load(dir: &Path) -> Result<Self> {
+        let mut paths: Vec<PathBuf> = fs::read_dir(dir)
+            .with_context(|| format!("Failed to read plugin dir {:?}", dir))?
+            .filter_map(|entry| entry.ok().map(|e| e.path()))
+            .filter(|path| path.extension().is_some_and(|ext| ext == "lua"))
+            .collect();
+        paths.sort();
+
+        let mut plugins = Vec::new();
+        let mut css = String::new();
+        for path in paths {
+            match load_plugin(&path) {
+                Ok((plugin, plugin_css)) => {
+                    info!("Loaded plugin {}", plugin.name);
+                    if let Some(plugin_css) = plugin_css {
+                        css.push_str(&plugin_css);
+                        css.push('\n');
+                    }
+                    plugins.push(plugin);
+                }
+                Err(e) => warn!("Skipping plugin {:?}: {:#}", path, e),
+            }
+        }
+
+        Ok(Self { plugins, css })
+    }
+
+    pub fn css(&self) -> &str {
+        &self.css
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.plugins.is_empty()
+    }
+}
+
+fn load_plugin(path: &Path) -> Result<(Plugin, Option<String>)> {
+    let source = fs::read_to_string(path)?;
+    let name = path
+        .file_stem()
+        .map(|s| s.to_string_lossy().into_owned())
+        .unwrap_or_default();
+
+    let lua = sandbox()?;
+    refill(&lua);
+    lua.load(&source)
+        .set_name(name.as_str())
+        .exec()
+        .map_err(|e| anyhow::anyhow!("{}", e))?;
+    let css = lua
+        .globals()
+        .get::<_, Option<String>>("css")
+        .map_err(|e| anyhow::anyhow!("`css` must be a string: {}", e))?;
+
+    Ok((
+        Plugin {
+            name,
+            lua,
+            failed: false,
+        },
+        css,
+    ))
+}
+
+/// A state without `io`, `os`, modules or file loading, whose calls fail
+/// once they have used up their `INSTRUCTION_BUDGET` or `MEMORY_LIMIT`.
+fn sandbox() -> Result<Lua> {
+    let lua = Lua::new_with(
+        StdLib::STRING | StdLib::TABLE | StdLib::MATH,
+        LuaOptions::default(),
+    )
+    .map_err(|e| anyhow::anyhow!("{}", e))?;
+    for name in ["dofile", "loadfile"] {
+        lua.globals()
+            .set(name, Value::Nil)
+            .map_err(|e| anyhow::anyhow!("{}", e))?;
+    }
+    lua.set_memory_limit(MEMORY_LIMIT)
+        .map_err(|e| anyhow::anyhow!("{}", e))?;
+    lua.set_app_data(Budget(0));
+    lua.set_hook(
+        HookTriggers::new().every_nth_instruction(HOOK_INTERVAL),
+        |lua, _| match lua.app_data_mut::<Budget>() {
+            Some(mut budget) if budget.0 > 0 => {
+                budget.0 -= 1;
+                Ok(())
+            }
+            _ => Err(mlua::Error::RuntimeError(format!(
+                "ran longer than {} instructions",
+                INSTRUCTION_BUDGET
+            ))),
+        },
+    );
+    Ok(lua)
+}
+
+fn refill(lua: &Lua) {
+    lua.set_app_data(Budget(INSTRUCTION_BUDGET / HOOK_INTERVAL));
+}
+
+impl ComboTransform for PluginHost {
+    fn transform(&mut self, combo: &ComboEvent) -> Transformed {
+        let mut text = combo.text.clone();
+        let mut class = None;
+        let mut merge = false;
+        let mut relabeled = false;
+
+        for plugin in &mut self.plugins {
+            if plugin.failed {
+                continue;
+            }
+            match call_transform(&plugin.lua, &text, combo) {
+                Ok(PluginResult::Keep) => {}
+                Ok(PluginResult::Drop) => return Transformed::Drop,
+                Ok(PluginResult::Relabel {
+                    text: new_text,
+                    class: new_class,
+                    merge: new_merge,
+                }) => {
+                    relabeled = true;
+                    text = new_text.unwrap_or(text);
+                    class = new_class.or(class);
+                    merge |= new_merge;
+                }
+                Err(e) => {
+                    // One broken script should not spam the log on every key.
+                    warn!("Disabling plugin {} after error: {}", plugin.name, e);
+                    plugin.failed = true;
+                }
+            }
+        }
+
+        if relabeled {
+            Transformed::Relabel { text, class, merge }
+        } else {
+            Transformed::Keep
+        }
+    }
+}
+
+enum PluginResult {
+    Keep,
+    Drop,
+    Relabel {
+        text: Option<String>,
+        class: Option<String>,
+        merge: bool,
+    },
+}
+
+fn call_transform(lua: &Lua, text: &str, combo: &ComboEvent) -> mlua::Result<PluginResult> {
+    let Some(transform) = lua.globals().get::<_, Option<Function>>("transform")? else {
+        return Ok(PluginResult::Keep);
+    };
+
+    refill(lua);
+    let arg = lua.create_table()?;
+    arg.set("text", text)?;
+    arg.set("kind", combo.kind.name())?;
+
+    Ok(match transform.call::<_, Value>(arg)? {
+        Value::Nil | Value::Boolean(true) => PluginResult::Keep,
+        Value::Boolean(false) => PluginResult::Drop,
+        Value::String(s) => PluginResult::Relabel {
+            text: Some(s.to_str()?.to_string()),
+            class: None,
+            merge: false,
+        },
+        Value::Table(table) => relabel_from_table(&table)?,
+        other => {
+            return Err(mlua::Error::RuntimeError(format!(
+                "transform returned a {}",
+                other.type_name()
+            )))
+        }
+    })
+}
+
+fn relabel_from_table(table: &Table) -> mlua::Result<PluginResult> {
+    Ok(PluginResult::Relabel {
+        text: table.get("text")?,
+        class: table.get("class")?,
+        merge: table.get::<_, Option<bool>>("merge")?.unwrap_or(false),
+    })
+}
+
+/// `~/.config/keyway-visualizer/plugins`
+pub fn default_plugin_dir() -> PathBuf {
+    dirs::config_dir()
+        .unwrap_or_else(|| PathBuf::from("."))
+        .join("keyway-visualizer")
+        .join("plugins")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::combo::ComboKind;
+
+    fn host(source: &str) -> PluginHost {
+        let lua = sandbox().unwrap();
+        lua.load(source).exec().unwrap();
+        PluginHost {
+            plugins: vec![Plugin {
+                name: "test".to_string(),
+                lua,
+                failed: false,
+            }],
+            css: String::new(),
+        }
+    }
+
+    fn combo(text: &str) -> ComboEvent {
+        ComboEvent {
+            text: text.to_string(),
+            kind: ComboKind::Shortcut,
+        }
+    }
+
+    #[test]
+    fn plugins_relabel_and_drop() {
+        let mut host = host(
+            r#"
+            function transform(combo)
+                if combo.text == "Ctrl+S" then return "Save" end
+                if combo.text == "Ctrl+Q" then return false end
+                if combo.kind == "shortcut" then return { class = "shortcut" } end
+            end
+            "#,
+        );
+
+        assert_eq!(
+            host.transform(&combo("Ctrl+S")),
+            Transformed::Relabel {
+                text: "Save".to_string(),
+                class: None,
+                merge: false
+            }
+        );
+        assert_eq!(host.transform(&combo("Ctrl+Q")), Transformed::Drop);
+        assert_eq!(
+            host.transform(&combo("Alt+X")),
+            Transformed::Relabel {
+                text: "Alt+X".to_string(),
+                class: Some("shortcut".to_string()),
+                merge: false
+            }
+        );
+    }
+
+    #[test]
+    fn failing_plugin_is_disabled() {
+        let mut host = host("function transform(combo) error('boom') end");
+        assert_eq!(host.transform(&combo("A")), Transformed::Keep);
+        assert!(host.plugins[0].failed);
+    }
+
+    #[test]
+    fn runaway_plugin_is_stopped() {
+        let mut runaway = host("function transform(combo) while true do end end");
+        assert_eq!(runaway.transform(&combo("A")), Transformed::Keep);
+        assert!(runaway.plugins[0].failed);
+
+        let mut greedy = host("function transform(combo) return string.rep('x', 1e10) end");
+        assert_eq!(greedy.transform(&combo("A")), Transformed::Keep);
+        assert!(greedy.plugins[0].failed);
+
+        let mut probe = host("function transform(combo) return tostring(io or os or dofile) end");
+        assert_eq!(
+            probe.transform(&combo("A")),
+            Transformed::Relabel {
+                text: "nil".to_string(),
+                class: None,
+                merge: false
+            }
+        );
+    }
+}
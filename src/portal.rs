@@ -0,0 +1,59 @@
+use anyhow::{anyhow, bail, Result};
+use dbus::arg::{PropMap, RefArg, Variant};
+use dbus::blocking::LocalConnection;
+use dbus::message::MatchRule;
+use dbus::Path;
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+pub const PORTAL_DEST: &str = "org.freedesktop.portal.Desktop";
+pub const PORTAL_PATH: &str = "/org/freedesktop/portal/desktop";
+const REQUEST_IFACE: &str = "org.freedesktop.portal.Request";
+pub const CALL_TIMEOUT: Duration = Duration::from_secs(10);
+pub const PROCESS_STEP: Duration = Duration::from_secs(1);
+
+/// Runs a portal call and waits for its `Request::Response`. The match is
+/// added before the call so a fast reply is not missed.
+pub fn portal_request(
+    conn: &LocalConnection,
+    running: &AtomicBool,
+    token: &str,
+    call: impl FnOnce(&str) -> Result<()>,
+) -> Result<PropMap> {
+    let sender = conn.unique_name().trim_start_matches(':').replace('.', "_");
+    let request_path = format!("{}/request/{}/{}", PORTAL_PATH, sender, token);
+    let request_path = Path::new(request_path).map_err(|e| anyhow!(e))?;
+
+    let response: Rc<RefCell<Option<(u32, PropMap)>>> = Rc::new(RefCell::new(None));
+    let response_slot = Rc::clone(&response);
+    let match_token = conn.add_match(
+        MatchRule::new_signal(REQUEST_IFACE, "Response").with_path(request_path),
+        move |reply: (u32, PropMap), _, _| {
+            *response_slot.borrow_mut() = Some(reply);
+            true
+        },
+    )?;
+
+    let result = call(token).and_then(|()| loop {
+        if let Some(reply) = response.borrow_mut().take() {
+            break Ok(reply);
+        }
+        if !running.load(Ordering::Relaxed) {
+            bail!("Stopped while waiting for the portal");
+        }
+        conn.process(PROCESS_STEP)?;
+    });
+    let _ = conn.remove_match(match_token);
+
+    match result? {
+        (0, results) => Ok(results),
+        (1, _) => bail!("Request was cancelled"),
+        (code, _) => bail!("Request failed with code {}", code),
+    }
+}
+
+pub fn variant(value: String) -> Variant<Box<dyn RefArg>> {
+    Variant(Box::new(value))
+}
@@ -0,0 +1,148 @@
+use crate::control::ControlCommand;
+use crate::portal::{portal_request, variant, CALL_TIMEOUT, PORTAL_DEST, PORTAL_PATH, PROCESS_STEP};
+use anyhow::{anyhow, Context, Result};
+use async_channel::Sender;
+use dbus::arg::PropMap;
+use dbus::blocking::LocalConnection;
+use dbus::message::MatchRule;
+use dbus::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use tracing::{debug, info, warn};
+
+const SHORTCUTS_IFACE: &str = "org.freedesktop.portal.GlobalShortcuts";
+
+const TOGGLE_PAUSE_ID: &str = "toggle-pause";
+const CLEAR_ID: &str = "clear";
+
+/// Pause and clear shortcuts registered with the xdg-desktop-portal
+/// GlobalShortcuts API. Activations arrive as `ControlCommand`s; the session
+/// is closed when this is dropped.
+pub struct PortalShortcuts {
+    trigger: String,
+    running: Arc<AtomicBool>,
+    active: Arc<AtomicBool>,
+}
+
+impl PortalShortcuts {
+    /// True once the portal has accepted the bindings. Until then (or if it
+    /// refused) the evdev hotkey stays in charge.
+    pub fn is_active(&self) -> bool {
+        self.active.load(Ordering::Relaxed)
+    }
+
+    pub fn trigger(&self) -> &str {
+        &self.trigger
+    }
+}
+
+impl Drop for PortalShortcuts {
+    fn drop(&mut self) {
+        self.running.store(false, Ordering::Relaxed);
+    }
+}
+
+pub fn start_portal_shortcuts(
+    trigger: String,
+    command_tx: Sender<ControlCommand>,
+) -> PortalShortcuts {
+    let running = Arc::new(AtomicBool::new(true));
+    let active = Arc::new(AtomicBool::new(false));
+
+    let handle = PortalShortcuts {
+        trigger: trigger.clone(),
+        running: Arc::clone(&running),
+        active: Arc::clone(&active),
+    };
+
+    thread::spawn(move || {
+        if let Err(e) = serve(&trigger, command_tx, &running, &active) {
+            warn!("GlobalShortcuts portal unavailable, using the evdev hotkey: {:#}", e);
+        }
+        active.store(false, Ordering::Relaxed);
+    });
+
+    handle
+}
+
+fn serve(
+    trigger: &str,
+    command_tx: Sender<ControlCommand>,
+    running: &AtomicBool,
+    active: &AtomicBool,
+) -> Result<()> {
+    let conn = LocalConnection::new_session().context("Failed to connect to the session bus")?;
+    let proxy = conn.with_proxy(PORTAL_DEST, PORTAL_PATH, CALL_TIMEOUT);
+
+    let results = portal_request(&conn, running, "keyway_session", |token| {
+        let mut options = PropMap::new();
+        options.insert("handle_token".to_string(), variant(token.to_string()));
+        options.insert(
+            "session_handle_token".to_string(),
+            variant("keyway".to_string()),
+        );
+        let (_,): (Path,) = proxy.method_call(SHORTCUTS_IFACE, "CreateSession", (options,))?;
+        Ok(())
+    })?;
+    let session = results
+        .get("session_handle")
+        .and_then(|v| v.0.as_str())
+        .ok_or_else(|| anyhow!("Portal returned no session handle"))?;
+    let session = Path::new(session.to_string()).map_err(|e| anyhow!(e))?;
+
+    let session_filter = session.clone();
+    conn.add_match(
+        MatchRule::new_signal(SHORTCUTS_IFACE, "Activated").with_path(PORTAL_PATH),
+        move |(signal_session, id, _, _): (Path, String, u64, PropMap), _, _| {
+            if signal_session == session_filter {
+                let command = match id.as_str() {
+                    TOGGLE_PAUSE_ID => Some(ControlCommand::Toggle),
+                    CLEAR_ID => Some(ControlCommand::Clear),
+                    _ => None,
+                };
+                if let Some(command) = command {
+                    debug!("Portal shortcut {} activated", id);
+                    let _ = command_tx.try_send(command);
+                }
+            }
+            true
+        },
+    )?;
+
+    // The portal may ask the user to confirm, so the reply can take a while.
+    portal_request(&conn, running, "keyway_bind", |token| {
+        let mut pause = PropMap::new();
+        pause.insert(
+            "description".to_string(),
+            variant("Pause or resume the keystroke overlay".to_string()),
+        );
+        pause.insert("preferred_trigger".to_string(), variant(trigger.to_string()));
+        let mut clear = PropMap::new();
+        clear.insert(
+            "description".to_string(),
+            variant("Clear the keystroke overlay".to_string()),
+        );
+        let shortcuts = vec![
+            (TOGGLE_PAUSE_ID.to_string(), pause),
+            (CLEAR_ID.to_string(), clear),
+        ];
+
+        let mut options = PropMap::new();
+        options.insert("handle_token".to_string(), variant(token.to_string()));
+        let (_,): (Path,) = proxy.method_call(
+            SHORTCUTS_IFACE,
+            "BindShortcuts",
+            (session.clone(), shortcuts, "", options),
+        )?;
+        Ok(())
+    })?;
+
+    info!("Pause and clear shortcuts registered with the GlobalShortcuts portal");
+    active.store(true, Ordering::Relaxed);
+
+    while running.load(Ordering::Relaxed) {
+        conn.process(PROCESS_STEP)?;
+    }
+    Ok(())
+}
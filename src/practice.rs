@@ -0,0 +1,151 @@
+use crate::combo::{ComboEvent, ComboKind};
+use crate::hotkey::Hotkey;
+use anyhow::{bail, Context, Result};
+use std::fs;
+use std::path::Path;
+
+/// `practice_file`: shortcuts to drill, prompted one at a time in the
+/// overlay. The prompt stays until its shortcut is pressed; after the last
+/// one the list starts over.
+#[derive(Debug, Clone)]
+pub struct Practice {
+    targets: Vec<Target>,
+    next: usize,
+    /// Wrong attempts at the current target
+    misses: u32,
+    /// Targets this round that were hit without a miss
+    first_try: usize,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Target {
+    /// In the spelling combos come in, e.g. "Ctrl+Shift+T"
+    combo: String,
+    description: Option<String>,
+}
+
+/// How an attempt at the prompted shortcut went.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Attempt {
+    Correct,
+    Wrong,
+    /// The last target was hit; `first_try` of `total` were right at once
+    RoundDone { first_try: usize, total: usize },
+}
+
+impl Practice {
+    pub fn load(path: &Path) -> Result<Self> {
+        let text = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read practice file {:?}", path))?;
+        Self::parse(&text).with_context(|| format!("Invalid practice file {:?}", path))
+    }
+
+    /// One shortcut per line, in hotkey syntax, optionally followed by what
+    /// it does: `Ctrl+Shift+T  Reopen closed tab`. Blank lines and lines
+    /// starting with `#` are skipped.
+    pub fn parse(text: &str) -> Result<Self> {
+        let mut targets = Vec::new();
+        for (number, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (combo, description) = match line.split_once(char::is_whitespace) {
+                Some((combo, description)) => (combo, Some(description.trim().to_string())),
+                None => (line, None),
+            };
+            let hotkey =
+                Hotkey::parse(combo).with_context(|| format!("line {}: {:?}", number + 1, combo))?;
+            targets.push(Target {
+                combo: hotkey.describe(),
+                description,
+            });
+        }
+        if targets.is_empty() {
+            bail!("no shortcuts to practice");
+        }
+        Ok(Self {
+            targets,
+            next: 0,
+            misses: 0,
+            first_try: 0,
+        })
+    }
+
+    /// The shortcut to press next and what it does, if the file says.
+    pub fn prompt(&self) -> (&str, Option<&str>) {
+        let target = &self.targets[self.next];
+        (&target.combo, target.description.as_deref())
+    }
+
+    /// Checks a combo against the prompt. Plain keys only count as attempts
+    /// when the prompt is a plain key too, so typing between drills is not
+    /// marked wrong. `None` when the combo was not an attempt.
+    pub fn attempt(&mut self, combo: &ComboEvent) -> Option<Attempt> {
+        let target = &self.targets[self.next].combo;
+        let pressed = Hotkey::parse(&combo.text).map(|hotkey| hotkey.describe());
+        if pressed.as_ref().is_ok_and(|pressed| pressed == target) {
+            if self.misses == 0 {
+                self.first_try += 1;
+            }
+            self.misses = 0;
+            self.next += 1;
+            if self.next < self.targets.len() {
+                return Some(Attempt::Correct);
+            }
+            let done = Attempt::RoundDone {
+                first_try: self.first_try,
+                total: self.targets.len(),
+            };
+            self.next = 0;
+            self.first_try = 0;
+            return Some(done);
+        }
+        let plain_target = !target.contains('+');
+        if combo.kind == ComboKind::Shortcut || (plain_target && combo.kind == ComboKind::Key) {
+            self.misses += 1;
+            return Some(Attempt::Wrong);
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn press(text: &str, kind: ComboKind) -> ComboEvent {
+        ComboEvent {
+            text: text.to_string(),
+            kind,
+        }
+    }
+
+    #[test]
+    fn prompts_in_order_and_marks_attempts() {
+        let mut practice = Practice::parse(
+            "# browser\nctrl+shift+t  Reopen closed tab\n\nCtrl+L\n",
+        )
+        .unwrap();
+        assert_eq!(practice.prompt(), ("Ctrl+Shift+T", Some("Reopen closed tab")));
+
+        assert_eq!(practice.attempt(&press("A", ComboKind::Key)), None);
+        assert_eq!(practice.attempt(&press("Ctrl+T", ComboKind::Shortcut)), Some(Attempt::Wrong));
+        assert_eq!(
+            practice.attempt(&press("Ctrl+Shift+T", ComboKind::Shortcut)),
+            Some(Attempt::Correct)
+        );
+        assert_eq!(practice.prompt(), ("Ctrl+L", None));
+        assert_eq!(
+            practice.attempt(&press("Ctrl+L", ComboKind::Shortcut)),
+            Some(Attempt::RoundDone {
+                first_try: 1,
+                total: 2
+            })
+        );
+        assert_eq!(practice.prompt().0, "Ctrl+Shift+T");
+
+        assert!(Practice::parse("# nothing\n").is_err());
+        assert!(Practice::parse("Ctrl+Shift\n").is_err());
+    }
+}
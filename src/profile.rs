@@ -0,0 +1,51 @@
+use crate::app_filter::{self, FilterRule};
+use crate::settings::{AppProfile, Settings};
+use tracing::warn;
+
+/// Compiled `profiles` from the settings, matched against the focused app.
+pub struct ProfileSet {
+    profiles: Vec<(AppProfile, Vec<FilterRule>)>,
+}
+
+impl ProfileSet {
+    pub fn new(profiles: &[AppProfile]) -> Self {
+        let profiles = profiles
+            .iter()
+            .map(|profile| {
+                let (rules, errors) = app_filter::compile_rules(&profile.apps);
+                for err in errors {
+                    warn!("Ignoring invalid entry in profile {:?}: {}", profile.name, err);
+                }
+                (profile.clone(), rules)
+            })
+            .collect();
+
+        Self { profiles }
+    }
+
+    /// Index of the first profile whose apps match, if any.
+    pub fn find(&self, class: &str, title: &str) -> Option<usize> {
+        self.profiles
+            .iter()
+            .position(|(_, rules)| app_filter::any_matches(rules, class, title))
+    }
+
+    pub fn find_by_name(&self, name: &str) -> Option<usize> {
+        self.profiles
+            .iter()
+            .position(|(profile, _)| profile.name.eq_ignore_ascii_case(name))
+    }
+
+    pub fn name(&self, index: usize) -> Option<&str> {
+        self.profiles.get(index).map(|(profile, _)| profile.name.as_str())
+    }
+
+    /// Base settings with the overrides of the given profile applied.
+    pub fn effective(&self, base: &Settings, active: Option<usize>) -> Settings {
+        let mut settings = base.clone();
+        if let Some((profile, _)) = active.and_then(|i| self.profiles.get(i)) {
+            profile.apply_to(&mut settings);
+        }
+        settings
+    }
+}
@@ -0,0 +1,113 @@
+use crate::combo::ComboEvent;
+use anyhow::Result;
+use async_channel::{Receiver, Sender};
+use serde_json::Value;
+use std::thread;
+use std::time::Duration;
+use tracing::{debug, info, warn};
+use tungstenite::client::IntoClientRequest;
+use tungstenite::stream::MaybeTlsStream;
+use tungstenite::Message;
+
+const RECONNECT_DELAY: Duration = Duration::from_secs(2);
+const READ_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Receiver side of remote display: follows another instance's WebSocket
+/// event stream and yields its combos. Reconnects until the receiver is
+/// dropped. A non-empty `token` is sent as `Authorization: Bearer`.
+pub fn start_remote_receiver(url: String, token: String) -> Receiver<ComboEvent> {
+    let (tx, rx) = async_channel::bounded(64);
+
+    thread::spawn(move || {
+        let mut warned = false;
+        while !tx.is_closed() {
+            match connect(&url, &token) {
+                Ok(socket) => {
+                    info!("Receiving combos from {}", url);
+                    warned = false;
+                    follow(socket, &tx);
+                    info!("Disconnected from {}", url);
+                }
+                Err(e) if !warned => {
+                    warn!("Cannot reach {}: {} (retrying)", url, e);
+                    warned = true;
+                }
+                Err(e) => debug!("Cannot reach {}: {}", url, e),
+            }
+            thread::sleep(RECONNECT_DELAY);
+        }
+    });
+
+    rx
+}
+
+type Socket = tungstenite::WebSocket<MaybeTlsStream<std::net::TcpStream>>;
+
+fn connect(url: &str, token: &str) -> Result<Socket> {
+    let mut request = url.into_client_request()?;
+    if !token.is_empty() {
+        let value = format!("Bearer {}", token).parse()?;
+        request.headers_mut().insert("Authorization", value);
+    }
+    let (socket, _) = tungstenite::connect(request)?;
+    Ok(socket)
+}
+
+fn follow(mut socket: Socket, tx: &Sender<ComboEvent>) {
+    // Wake up periodically so a disabled receiver lets go of the connection.
+    if let MaybeTlsStream::Plain(stream) = socket.get_ref() {
+        let _ = stream.set_read_timeout(Some(READ_TIMEOUT));
+    }
+
+    loop {
+        if tx.is_closed() {
+            let _ = socket.close(None);
+            return;
+        }
+        match socket.read() {
+            Ok(Message::Text(text)) => {
+                if let Some(combo) = parse_combo(text.as_str()) {
+                    if tx.send_blocking(combo).is_err() {
+                        return;
+                    }
+                }
+            }
+            Ok(Message::Close(_)) => return,
+            Ok(_) => {}
+            Err(tungstenite::Error::Io(e))
+                if matches!(
+                    e.kind(),
+                    std::io::ErrorKind::WouldBlock | std::io::ErrorKind::TimedOut
+                ) => {}
+            Err(e) => {
+                debug!("Remote stream error: {}", e);
+                return;
+            }
+        }
+    }
+}
+
+fn parse_combo(text: &str) -> Option<ComboEvent> {
+    let value: Value = serde_json::from_str(text).ok()?;
+    if value.get("type")?.as_str()? != "combo" {
+        return None;
+    }
+    serde_json::from_value(value).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::combo::ComboKind;
+
+    #[test]
+    fn parses_stream_messages() {
+        let combo =
+            parse_combo(r#"{"type":"combo","text":"Ctrl+S","kind":"shortcut","timestamp":1}"#)
+                .unwrap();
+        assert_eq!(combo.text, "Ctrl+S");
+        assert_eq!(combo.kind, ComboKind::Shortcut);
+
+        assert!(parse_combo(r#"{"type":"other","text":"x","kind":"key"}"#).is_none());
+    }
+}
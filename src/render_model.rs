@@ -0,0 +1,273 @@
+use crate::combo::ComboItem;
+use crate::combo_format::{self, ComboFormat};
+use crate::i18n;
+use crate::ring::RingBuffer;
+use std::collections::{HashMap, HashSet};
+
+/// Ids of the widget bubbles; combo item ids count up from zero.
+pub const SPEED_ID: u64 = u64::MAX;
+pub const LOCK_ID: u64 = u64::MAX - 1;
+/// The modifier panel takes four ids from here, one per chip.
+pub const MODIFIER_ID: u64 = u64::MAX - 5;
+pub const LAYOUT_ID: u64 = u64::MAX - 6;
+pub const TALLY_ID: u64 = u64::MAX - 7;
+pub const PRACTICE_ID: u64 = u64::MAX - 8;
+
+/// Chips of the modifier panel, in the order combos spell them.
+pub const MODIFIER_NAMES: [&str; 4] = ["Ctrl", "Shift", "Alt", "Super"];
+
+/// One bubble as it should appear on screen.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Bubble {
+    pub id: u64,
+    pub text: String,
+    /// Smaller second line, the keys under a teaching mode action name
+    pub caption: Option<String>,
+    pub classes: Vec<String>,
+}
+
+/// The bubbles that stay up for as long as they are set, beside the combos,
+/// and how the combos are spelled.
+#[derive(Debug, Default)]
+pub struct Widgets<'a> {
+    /// `combo_format`, or the built-in spelling
+    pub format: Option<&'a ComboFormat>,
+    /// Active layout abbreviation, shown first
+    pub layout: Option<&'a str>,
+    pub locks: Option<&'a str>,
+    /// Text and extra CSS class of the speed widget
+    pub speed: Option<(&'a str, &'a str)>,
+    /// Which of [`MODIFIER_NAMES`] are held
+    pub modifiers: Option<[bool; 4]>,
+    /// Most used shortcuts this session and their counts, shown last
+    pub tally: Option<&'a [(String, u64)]>,
+    /// The shortcut practice mode asks for and what it does, right after
+    /// the combos
+    pub practice: Option<(&'a str, Option<&'a str>)>,
+}
+
+/// One step from the previous frame to the next.
+#[derive(Debug, PartialEq)]
+pub enum Change {
+    Remove(u64),
+    /// `index` is the bubble's position once every earlier change is applied.
+    Insert { index: usize, bubble: Bubble },
+    Update(Bubble),
+}
+
+/// The frame for the current items and status messages in the order they
+/// were added, after the layout badge and before the other widgets.
+pub fn build(
+    items: &RingBuffer<ComboItem>,
+    status: &RingBuffer<ComboItem>,
+    widgets: &Widgets,
+) -> Vec<Bubble> {
+    let mut frame: Vec<Bubble> = items
+        .iter()
+        .map(|item| item_bubble(item, None, widgets.format))
+        .chain(status.iter().map(|item| item_bubble(item, Some("status"), widgets.format)))
+        .collect();
+    frame.sort_by_key(|bubble| bubble.id);
+
+    if let Some(text) = widgets.layout {
+        frame.insert(
+            0,
+            Bubble {
+                id: LAYOUT_ID,
+                text: text.to_string(),
+                caption: None,
+                classes: vec!["key-bubble".to_string(), "layout-badge".to_string()],
+            },
+        );
+    }
+    if let Some((combo, description)) = widgets.practice {
+        frame.push(Bubble {
+            id: PRACTICE_ID,
+            text: combo_format::spell(combo, widgets.format, 1),
+            caption: Some(description.unwrap_or(i18n::tr("Press this shortcut")).to_string()),
+            classes: vec!["key-bubble".to_string(), "practice".to_string()],
+        });
+    }
+    if let Some(text) = widgets.locks {
+        frame.push(Bubble {
+            id: LOCK_ID,
+            text: text.to_string(),
+            caption: None,
+            classes: vec!["key-bubble".to_string(), "lock".to_string()],
+        });
+    }
+    if let Some((text, class)) = widgets.speed {
+        frame.push(Bubble {
+            id: SPEED_ID,
+            text: text.to_string(),
+            caption: None,
+            classes: vec!["key-bubble".to_string(), "speed".to_string(), class.to_string()],
+        });
+    }
+    if let Some(held) = widgets.modifiers {
+        for (offset, (name, held)) in MODIFIER_NAMES.iter().zip(held).enumerate() {
+            let mut classes = vec!["key-bubble".to_string(), "modifier-chip".to_string()];
+            if held {
+                classes.push("held".to_string());
+            }
+            frame.push(Bubble {
+                id: MODIFIER_ID + offset as u64,
+                text: i18n::localize_combo(name),
+                caption: None,
+                classes,
+            });
+        }
+    }
+    if let Some(tally) = widgets.tally.filter(|tally| !tally.is_empty()) {
+        let text = tally
+            .iter()
+            .map(|(combo, count)| {
+                format!("{}  {}", combo_format::spell(combo, widgets.format, 1), count)
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+        frame.push(Bubble {
+            id: TALLY_ID,
+            text,
+            caption: None,
+            classes: vec!["key-bubble".to_string(), "tally".to_string()],
+        });
+    }
+    frame
+}
+
+/// Key names are translated here, at the last moment, so event consumers
+/// always see the English ones.
+fn item_bubble(item: &ComboItem, class: Option<&str>, format: Option<&ComboFormat>) -> Bubble {
+    let mut classes = vec!["key-bubble".to_string()];
+    classes.extend(class.map(str::to_string));
+    classes.extend(item.class.clone());
+    if item.heat > 0 {
+        classes.push(format!("heat-{}", item.heat));
+    }
+    let text = match (class, &item.caption) {
+        // Status messages are translated when they are made, and teaching
+        // mode puts an action name where the keys would be.
+        (Some(_), _) | (None, Some(_)) => item.text.clone(),
+        (None, None) => combo_format::spell(&item.text, format, item.count),
+    };
+    Bubble {
+        id: item.id,
+        text,
+        caption: item
+            .caption
+            .as_deref()
+            .map(|caption| combo_format::spell(caption, format, item.count)),
+        classes,
+    }
+}
+
+/// Fallback text for characters the font cannot draw, such as "U+1F600".
+pub fn code_points(text: &str) -> String {
+    text.chars()
+        .map(|c| format!("U+{:04X}", c as u32))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Changes that turn `prev` into `next`, removals first. Bubbles that stay
+/// keep their order between frames, since items can leave from anywhere but
+/// only join at the back, so inserting at each new bubble's final index works.
+pub fn diff(prev: &[Bubble], next: &[Bubble]) -> Vec<Change> {
+    let next_ids: HashSet<u64> = next.iter().map(|bubble| bubble.id).collect();
+    let prev_by_id: HashMap<u64, &Bubble> = prev.iter().map(|bubble| (bubble.id, bubble)).collect();
+
+    let mut changes: Vec<Change> = prev
+        .iter()
+        .filter(|bubble| !next_ids.contains(&bubble.id))
+        .map(|bubble| Change::Remove(bubble.id))
+        .collect();
+
+    for (index, bubble) in next.iter().enumerate() {
+        match prev_by_id.get(&bubble.id) {
+            Some(old) if *old == bubble => {}
+            Some(_) => changes.push(Change::Update(bubble.clone())),
+            None => changes.push(Change::Insert {
+                index,
+                bubble: bubble.clone(),
+            }),
+        }
+    }
+    changes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bubble(id: u64, text: &str) -> Bubble {
+        Bubble {
+            id,
+            text: text.to_string(),
+            caption: None,
+            classes: vec!["key-bubble".to_string()],
+        }
+    }
+
+    #[test]
+    fn only_changed_bubbles_are_touched() {
+        let prev = vec![bubble(1, "A"), bubble(2, "Ctrl+C"), bubble(SPEED_ID, "40 WPM")];
+        let next = vec![
+            bubble(2, "Ctrl+C"),
+            bubble(3, "B"),
+            bubble(SPEED_ID, "42 WPM"),
+        ];
+
+        assert_eq!(
+            diff(&prev, &next),
+            vec![
+                Change::Remove(1),
+                Change::Insert {
+                    index: 1,
+                    bubble: bubble(3, "B")
+                },
+                Change::Update(bubble(SPEED_ID, "42 WPM")),
+            ]
+        );
+        assert!(diff(&next, &next).is_empty());
+    }
+
+    #[test]
+    fn layout_badge_leads_the_frame() {
+        let items = RingBuffer::new(2);
+        let status = RingBuffer::new(1);
+        let widgets = Widgets {
+            layout: Some("DE"),
+            speed: Some(("40 WPM", "wpm")),
+            ..Widgets::default()
+        };
+        let ids: Vec<u64> = build(&items, &status, &widgets).iter().map(|b| b.id).collect();
+        assert_eq!(ids, vec![LAYOUT_ID, SPEED_ID]);
+    }
+
+    #[test]
+    fn tally_lists_one_shortcut_per_line() {
+        let items = RingBuffer::new(2);
+        let status = RingBuffer::new(1);
+        let tally = vec![("Ctrl+S".to_string(), 12), ("Alt+Tab".to_string(), 3)];
+        let widgets = Widgets {
+            tally: Some(&tally),
+            ..Widgets::default()
+        };
+        let frame = build(&items, &status, &widgets);
+        assert_eq!(frame[0].id, TALLY_ID);
+        assert_eq!(frame[0].text, "Ctrl+S  12\nAlt+Tab  3");
+
+        let widgets = Widgets {
+            tally: Some(&[]),
+            ..Widgets::default()
+        };
+        assert!(build(&items, &status, &widgets).is_empty());
+    }
+
+    #[test]
+    fn code_point_fallback() {
+        assert_eq!(code_points("é"), "U+00E9");
+        assert_eq!(code_points("👍🏽"), "U+1F44D U+1F3FD");
+    }
+}
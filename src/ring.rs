@@ -0,0 +1,153 @@
+use std::ops::Index;
+
+/// Fixed-capacity FIFO that overwrites its oldest entry when full. The
+/// slots are allocated once, so memory stays flat however long the session.
+pub struct RingBuffer<T> {
+    slots: Vec<Option<T>>,
+    head: usize,
+    len: usize,
+}
+
+impl<T> RingBuffer<T> {
+    /// A capacity of zero is raised to one.
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            slots: (0..capacity.max(1)).map(|_| None).collect(),
+            head: 0,
+            len: 0,
+        }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.slots.len()
+    }
+
+    /// Appends `value`, returning the oldest entry if it had to make room.
+    pub fn push_back(&mut self, value: T) -> Option<T> {
+        let capacity = self.capacity();
+        if self.len == capacity {
+            let evicted = self.slots[self.head].replace(value);
+            self.head = (self.head + 1) % capacity;
+            return evicted;
+        }
+        self.slots[(self.head + self.len) % capacity] = Some(value);
+        self.len += 1;
+        None
+    }
+
+    pub fn pop_front(&mut self) -> Option<T> {
+        if self.len == 0 {
+            return None;
+        }
+        let value = self.slots[self.head].take();
+        self.head = (self.head + 1) % self.capacity();
+        self.len -= 1;
+        value
+    }
+
+    pub fn get(&self, index: usize) -> Option<&T> {
+        if index >= self.len {
+            return None;
+        }
+        self.slots[(self.head + index) % self.capacity()].as_ref()
+    }
+
+    pub fn get_mut(&mut self, index: usize) -> Option<&mut T> {
+        if index >= self.len {
+            return None;
+        }
+        let capacity = self.capacity();
+        self.slots[(self.head + index) % capacity].as_mut()
+    }
+
+    pub fn back_mut(&mut self) -> Option<&mut T> {
+        let index = self.len.checked_sub(1)?;
+        self.get_mut(index)
+    }
+
+    /// Oldest first.
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = &T> + '_ {
+        (0..self.len).filter_map(move |index| self.get(index))
+    }
+
+    /// Drops the entries `keep` rejects, preserving the order of the rest.
+    /// True when anything was dropped.
+    pub fn retain(&mut self, mut keep: impl FnMut(&T) -> bool) -> bool {
+        let len = self.len;
+        for _ in 0..len {
+            if let Some(value) = self.pop_front() {
+                if keep(&value) {
+                    self.push_back(value);
+                }
+            }
+        }
+        self.len != len
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut T> + '_ {
+        let (head, len) = (self.head, self.len);
+        let (wrapped, rest) = self.slots.split_at_mut(head);
+        rest.iter_mut()
+            .chain(wrapped.iter_mut())
+            .take(len)
+            .filter_map(Option::as_mut)
+    }
+
+    pub fn clear(&mut self) {
+        self.slots.iter_mut().for_each(|slot| *slot = None);
+        self.head = 0;
+        self.len = 0;
+    }
+
+    /// Reallocates for a new capacity, dropping the oldest entries that no
+    /// longer fit.
+    pub fn set_capacity(&mut self, capacity: usize) {
+        if capacity.max(1) == self.capacity() {
+            return;
+        }
+        let mut resized = Self::new(capacity);
+        while let Some(value) = self.pop_front() {
+            resized.push_back(value);
+        }
+        *self = resized;
+    }
+}
+
+impl<T> Index<usize> for RingBuffer<T> {
+    type Output = T;
+
+    fn index(&self, index: usize) -> &T {
+        self.get(index).expect("ring buffer index out of range")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn full_buffer_overwrites_the_oldest() {
+        let mut ring = RingBuffer::new(3);
+        for value in 1..=3 {
+            assert_eq!(ring.push_back(value), None);
+        }
+        assert_eq!(ring.push_back(4), Some(1));
+        assert_eq!(ring.iter().copied().collect::<Vec<_>>(), vec![2, 3, 4]);
+
+        assert_eq!(ring.pop_front(), Some(2));
+        *ring.back_mut().unwrap() = 40;
+        ring.push_back(5);
+        assert_eq!(ring.iter().copied().collect::<Vec<_>>(), vec![3, 40, 5]);
+
+        assert!(ring.retain(|value| *value != 3));
+        assert!(!ring.retain(|_| true));
+        assert_eq!(ring.iter().copied().collect::<Vec<_>>(), vec![40, 5]);
+        ring.push_back(6);
+        ring.iter_mut().for_each(|value| *value *= 10);
+        assert_eq!(ring.iter().copied().collect::<Vec<_>>(), vec![400, 50, 60]);
+
+        ring.set_capacity(2);
+        assert_eq!(ring.iter().copied().collect::<Vec<_>>(), vec![50, 60]);
+        assert_eq!(ring[0], 50);
+    }
+}
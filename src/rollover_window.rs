@@ -0,0 +1,211 @@
+use crate::heatmap_window::{self, UNUSED_KEY};
+use crate::input::InputEvent;
+use crate::stats::key_name;
+use gtk4::prelude::*;
+use gtk4::{Application, ApplicationWindow, Box as GtkBox, Button, DrawingArea, Label, Orientation};
+use std::cell::RefCell;
+use std::collections::HashSet;
+use std::rc::Rc;
+
+const DOWN_KEY: (f64, f64, f64) = (0.2, 0.65, 0.35);
+const SEEN_KEY: (f64, f64, f64) = (0.2, 0.35, 0.3);
+
+const HINT: &str = "Hold down as many keys as you can. Keys lost to the keyboard's rollover \
+     limit stay dark.";
+
+/// Keys held down right now, for finding how many the keyboard reports at
+/// once.
+#[derive(Debug, Default)]
+pub struct Rollover {
+    /// Evdev names in the order they went down
+    down: Vec<String>,
+    /// Every key that went down since the last reset
+    seen: HashSet<String>,
+    /// Most keys down at the same time
+    most: usize,
+}
+
+impl Rollover {
+    /// True when the keys down changed. Repeats and the mouse are ignored.
+    pub fn handle(&mut self, event: &InputEvent) -> bool {
+        match event {
+            InputEvent::KeyPressed(key) => {
+                let name = key_name(*key);
+                if self.down.contains(&name) {
+                    return false;
+                }
+                self.seen.insert(name.clone());
+                self.down.push(name);
+                self.most = self.most.max(self.down.len());
+                true
+            }
+            InputEvent::KeyReleased(key) => {
+                let name = key_name(*key);
+                let before = self.down.len();
+                self.down.retain(|down| *down != name);
+                self.down.len() != before
+            }
+            _ => false,
+        }
+    }
+
+    pub fn reset(&mut self) {
+        *self = Self::default();
+    }
+
+    pub fn is_down(&self, name: &str) -> bool {
+        self.down.iter().any(|down| down == name)
+    }
+
+    /// "3 keys down, most at once: 6", with keys the drawn keyboard lacks
+    /// named at the end.
+    pub fn summary(&self) -> String {
+        let noun = if self.down.len() == 1 { "key" } else { "keys" };
+        let mut text = format!("{} {} down, most at once: {}", self.down.len(), noun, self.most);
+        let elsewhere: Vec<&str> = self
+            .down
+            .iter()
+            .filter(|name| !heatmap_window::on_keyboard(name))
+            .map(String::as_str)
+            .collect();
+        if !elsewhere.is_empty() {
+            text.push_str(&format!(" (also {})", elsewhere.join(" ")));
+        }
+        text
+    }
+}
+
+/// Keyboard with every key that is down lit at the same time, for testing
+/// how many keys the keyboard registers at once (its rollover).
+pub struct RolloverWindow {
+    window: ApplicationWindow,
+    area: DrawingArea,
+    status: Label,
+    rollover: Rc<RefCell<Rollover>>,
+}
+
+impl RolloverWindow {
+    pub fn new(app: &Application) -> Rc<Self> {
+        let (width, height) = heatmap_window::keyboard_size();
+
+        let window = ApplicationWindow::builder()
+            .application(app)
+            .title("Keyway Visualizer Rollover Test")
+            .hide_on_close(true)
+            .build();
+
+        let content = GtkBox::new(Orientation::Vertical, 12);
+        content.set_margin_top(16);
+        content.set_margin_bottom(16);
+        content.set_margin_start(16);
+        content.set_margin_end(16);
+
+        let hint = Label::new(Some(HINT));
+        hint.set_wrap(true);
+        hint.set_xalign(0.0);
+
+        let rollover: Rc<RefCell<Rollover>> = Rc::default();
+        let area = DrawingArea::new();
+        area.set_content_width(width);
+        area.set_content_height(height);
+        let draw_rollover = Rc::clone(&rollover);
+        area.set_draw_func(move |_, cr, _, _| {
+            let rollover = draw_rollover.borrow();
+            heatmap_window::draw_keys(cr, |name| {
+                let color = if rollover.is_down(name) {
+                    DOWN_KEY
+                } else if rollover.seen.contains(name) {
+                    SEEN_KEY
+                } else {
+                    UNUSED_KEY
+                };
+                (color, None)
+            });
+        });
+
+        let status = Label::new(None);
+        status.set_halign(gtk4::Align::Start);
+        status.set_wrap(true);
+
+        let reset_button = Button::with_label("Reset");
+        let controls = GtkBox::new(Orientation::Horizontal, 8);
+        controls.append(&status);
+        controls.append(&reset_button);
+        status.set_hexpand(true);
+
+        content.append(&hint);
+        content.append(&area);
+        content.append(&controls);
+        window.set_child(Some(&content));
+
+        let this = Rc::new(Self {
+            window,
+            area,
+            status,
+            rollover,
+        });
+
+        let reset = Rc::downgrade(&this);
+        reset_button.connect_clicked(move |_| {
+            if let Some(this) = reset.upgrade() {
+                this.reset();
+            }
+        });
+        this
+    }
+
+    /// Shows the window with nothing held or seen yet.
+    pub fn present(&self) {
+        self.reset();
+        self.window.present();
+    }
+
+    /// Events while the window is hidden are dropped, so keys typed
+    /// elsewhere do not count towards the next test.
+    pub fn handle_event(&self, event: &InputEvent) {
+        if !self.window.is_visible() {
+            return;
+        }
+        if self.rollover.borrow_mut().handle(event) {
+            self.refresh();
+        }
+    }
+
+    fn reset(&self) {
+        self.rollover.borrow_mut().reset();
+        self.refresh();
+    }
+
+    fn refresh(&self) {
+        self.status.set_text(&self.rollover.borrow().summary());
+        self.area.queue_draw();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use evdev::Key;
+
+    #[test]
+    fn counts_keys_held_together() {
+        let mut rollover = Rollover::default();
+        for key in [Key::KEY_A, Key::KEY_S, Key::KEY_D, Key::KEY_VOLUMEUP] {
+            assert!(rollover.handle(&InputEvent::KeyPressed(key)));
+        }
+        assert!(!rollover.handle(&InputEvent::KeyRepeat(Key::KEY_A)));
+        assert!(!rollover.handle(&InputEvent::KeyPressed(Key::KEY_A)));
+        assert!(rollover.is_down("S"));
+        assert_eq!(rollover.summary(), "4 keys down, most at once: 4 (also VOLUMEUP)");
+
+        assert!(rollover.handle(&InputEvent::KeyReleased(Key::KEY_S)));
+        assert!(rollover.handle(&InputEvent::KeyReleased(Key::KEY_VOLUMEUP)));
+        assert!(!rollover.handle(&InputEvent::KeyReleased(Key::KEY_F)));
+        assert!(!rollover.is_down("S"));
+        assert!(rollover.seen.contains("S"));
+        assert_eq!(rollover.summary(), "2 keys down, most at once: 4");
+
+        rollover.reset();
+        assert_eq!(rollover.summary(), "0 keys down, most at once: 0");
+    }
+}
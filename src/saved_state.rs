@@ -0,0 +1,70 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// What the user toggled at runtime, kept across restarts when
+/// `restore_state` is on. None of it belongs in the config file.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SavedState {
+    pub paused: bool,
+    /// Hidden through the control socket, D-Bus or keyway-ctl
+    pub hidden: bool,
+    /// Profile pinned by name, rather than picked by the focused app
+    pub profile: Option<String>,
+}
+
+impl SavedState {
+    /// The state left by the last run, or the defaults when there is none.
+    pub fn load(path: &Path) -> Result<Self> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let raw = fs::read_to_string(path).with_context(|| format!("Failed to read {:?}", path))?;
+        toml::from_str(&raw).with_context(|| format!("Failed to parse {:?}", path))
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create {:?}", parent))?;
+        }
+        let content = toml::to_string(self).context("Failed to serialize state")?;
+        fs::write(path, content).with_context(|| format!("Failed to write {:?}", path))
+    }
+}
+
+/// `~/.local/state/keyway-visualizer/state.toml`
+pub fn default_state_path() -> PathBuf {
+    dirs::state_dir()
+        .or_else(dirs::data_dir)
+        .unwrap_or_else(|| PathBuf::from("."))
+        .join("keyway-visualizer")
+        .join("state.toml")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trips_and_fills_in_missing_fields() {
+        let path = std::env::temp_dir()
+            .join(format!("keyway-state-{}", std::process::id()))
+            .join("state.toml");
+        assert_eq!(SavedState::load(&path).unwrap(), SavedState::default());
+
+        let state = SavedState {
+            paused: true,
+            hidden: false,
+            profile: Some("Streaming".to_string()),
+        };
+        state.save(&path).unwrap();
+        assert_eq!(SavedState::load(&path).unwrap(), state);
+
+        fs::write(&path, "paused = true\n").unwrap();
+        assert_eq!(SavedState::load(&path).unwrap().profile, None);
+        let _ = fs::remove_dir_all(path.parent().unwrap());
+    }
+}
@@ -0,0 +1,159 @@
+use anyhow::{anyhow, bail, Context, Result};
+
+const DAY_NAMES: [&str; 7] = [
+    "monday",
+    "tuesday",
+    "wednesday",
+    "thursday",
+    "friday",
+    "saturday",
+    "sunday",
+];
+const MINUTES_PER_DAY: u16 = 24 * 60;
+
+/// `active_hours`: when the overlay shows keys. Outside every window
+/// capture is paused; an empty schedule is always active.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Schedule {
+    windows: Vec<Window>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Window {
+    /// Monday first
+    days: [bool; 7],
+    /// Minutes after midnight; `end <= start` runs past midnight into the
+    /// next day
+    start: u16,
+    end: u16,
+}
+
+impl Schedule {
+    /// Entries such as `Mon-Fri 09:00-17:00`, `Sat,Sun 10:00-14:00`,
+    /// `weekdays 22:00-02:00` or just `09:00-17:00` for every day.
+    pub fn parse(entries: &[String]) -> Result<Self> {
+        let windows = entries
+            .iter()
+            .map(|entry| {
+                parse_window(entry).with_context(|| format!("Invalid active hours {:?}", entry))
+            })
+            .collect::<Result<_>>()?;
+        Ok(Self { windows })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.windows.is_empty()
+    }
+
+    /// `weekday` counts from Monday = 0, `minute` from midnight.
+    pub fn is_active(&self, weekday: usize, minute: u16) -> bool {
+        if self.windows.is_empty() {
+            return true;
+        }
+        let yesterday = (weekday + 6) % 7;
+        self.windows.iter().any(|window| {
+            if window.start < window.end {
+                window.days[weekday] && (window.start..window.end).contains(&minute)
+            } else {
+                (window.days[weekday] && minute >= window.start)
+                    || (window.days[yesterday] && minute < window.end)
+            }
+        })
+    }
+}
+
+fn parse_window(entry: &str) -> Result<Window> {
+    let entry = entry.trim();
+    let (days, hours) = match entry.rsplit_once(char::is_whitespace) {
+        Some((days, hours)) => (parse_days(days.trim())?, hours),
+        None => ([true; 7], entry),
+    };
+    let (start, end) = hours
+        .split_once('-')
+        .ok_or_else(|| anyhow!("expected hours like 09:00-17:00"))?;
+    let (start, end) = (parse_time(start)?, parse_time(end)?);
+    if start == end {
+        bail!("the window is empty");
+    }
+    Ok(Window { days, start, end })
+}
+
+fn parse_days(text: &str) -> Result<[bool; 7]> {
+    let mut days = [false; 7];
+    for part in text.split(',') {
+        let part = part.trim().to_lowercase();
+        match part.as_str() {
+            "daily" | "everyday" => days = [true; 7],
+            "weekdays" => days[..5].fill(true),
+            "weekends" => days[5..].fill(true),
+            _ => match part.split_once('-') {
+                Some((first, last)) => {
+                    let (first, last) = (day_index(first)?, day_index(last)?);
+                    let mut day = first;
+                    loop {
+                        days[day] = true;
+                        if day == last {
+                            break;
+                        }
+                        day = (day + 1) % 7;
+                    }
+                }
+                None => days[day_index(&part)?] = true,
+            },
+        }
+    }
+    Ok(days)
+}
+
+fn day_index(name: &str) -> Result<usize> {
+    let name = name.trim();
+    DAY_NAMES
+        .iter()
+        .position(|day| name.len() >= 3 && day.starts_with(name))
+        .ok_or_else(|| anyhow!("unknown day {:?}", name))
+}
+
+/// `HH:MM`, with `24:00` for the end of the day.
+fn parse_time(text: &str) -> Result<u16> {
+    let (hours, minutes) = text
+        .trim()
+        .split_once(':')
+        .ok_or_else(|| anyhow!("expected a time like 09:00, got {:?}", text))?;
+    let (hours, minutes): (u16, u16) = (hours.parse()?, minutes.parse()?);
+    // Checked before multiplying, which would overflow for large hours.
+    if hours > 24 || minutes >= 60 || hours * 60 + minutes > MINUTES_PER_DAY {
+        bail!("{:?} is not a time of day", text);
+    }
+    Ok(hours * 60 + minutes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn schedule(entries: &[&str]) -> Schedule {
+        Schedule::parse(&entries.iter().map(|e| e.to_string()).collect::<Vec<_>>()).unwrap()
+    }
+
+    #[test]
+    fn windows_by_day_and_across_midnight() {
+        let work = schedule(&["Mon-Fri 09:00-17:00"]);
+        assert!(work.is_active(0, 9 * 60));
+        assert!(!work.is_active(0, 17 * 60));
+        assert!(!work.is_active(5, 12 * 60));
+
+        let late = schedule(&["Fri 22:00-02:00", "weekends 10:00-12:00"]);
+        assert!(late.is_active(4, 23 * 60));
+        assert!(late.is_active(5, 60));
+        assert!(!late.is_active(5, 3 * 60));
+        assert!(late.is_active(6, 11 * 60));
+        assert!(!late.is_active(3, 23 * 60));
+
+        assert!(schedule(&[]).is_active(2, 0));
+        assert!(schedule(&["Sat-Mon 00:00-24:00"]).is_active(0, 600));
+        assert!(Schedule::parse(&["Mon 9-17".to_string()]).is_err());
+        assert!(Schedule::parse(&["Someday 09:00-10:00".to_string()]).is_err());
+        assert!(Schedule::parse(&["Mon 2000:00-24:00".to_string()]).is_err());
+        assert!(Schedule::parse(&["Mon 09:00-24:01".to_string()]).is_err());
+    }
+}
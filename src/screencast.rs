@@ -0,0 +1,94 @@
+use crate::focus::KillOnDrop;
+use anyhow::{Context, Result};
+use async_channel::{Receiver, Sender};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::io::BufReader;
+use std::process::{Command, Stdio};
+use std::thread;
+use tracing::{debug, info, warn};
+
+/// Reports whether a screencast is running, as a stream of changes.
+///
+/// Portal ScreenCast sessions (xdg-desktop-portal-wlr/-hyprland/-gnome) show
+/// up in the PipeWire graph as `Video/Source` nodes that are not backed by a
+/// capture device; one in the `running` state means something is recording or
+/// sharing the screen. This is a heuristic: the portal does not announce
+/// sessions to other clients, and any source node without a device counts.
+/// `pw-dump --monitor` pushes graph updates, so nothing is polled.
+pub fn start_screencast_monitor() -> Receiver<bool> {
+    let (tx, rx) = async_channel::bounded(8);
+
+    thread::spawn(move || {
+        if let Err(e) = monitor_pipewire(&tx) {
+            warn!("Screencast detection unavailable: {:#}", e);
+        }
+    });
+
+    rx
+}
+
+fn monitor_pipewire(tx: &Sender<bool>) -> Result<()> {
+    let mut child = KillOnDrop(
+        Command::new("pw-dump")
+            .args(["--monitor", "--no-colors"])
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .context("Failed to run pw-dump")?,
+    );
+    let stdout = child.0.stdout.take().context("pw-dump has no stdout")?;
+    info!("Watching PipeWire for screencasts");
+
+    let mut casts: HashMap<u64, bool> = HashMap::new();
+    let mut active = false;
+
+    for batch in serde_json::Deserializer::from_reader(BufReader::new(stdout)).into_iter::<Value>() {
+        let batch = batch.context("Failed to parse pw-dump output")?;
+        for object in batch.as_array().into_iter().flatten() {
+            let Some(id) = object.get("id").and_then(|v| v.as_u64()) else {
+                continue;
+            };
+            match object.get("info") {
+                Some(Value::Null) | None => {
+                    casts.remove(&id);
+                }
+                Some(info) => {
+                    if let Some(running) = screencast_state(object, info) {
+                        casts.insert(id, running);
+                    } else if let Some(running) = casts.get_mut(&id) {
+                        // Updates only carry the changed fields.
+                        if let Some(state) = info.get("state").and_then(|v| v.as_str()) {
+                            *running = state == "running";
+                        }
+                    }
+                }
+            }
+        }
+
+        let now_active = casts.values().any(|running| *running);
+        if now_active != active {
+            active = now_active;
+            debug!("Screencast active: {}", active);
+            if tx.send_blocking(active).is_err() {
+                break;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// `Some(running)` for screencast nodes, `None` for everything else.
+fn screencast_state(object: &Value, info: &Value) -> Option<bool> {
+    if object.get("type").and_then(|v| v.as_str()) != Some("PipeWire:Interface:Node") {
+        return None;
+    }
+    let props = info.get("props")?;
+    let class = props.get("media.class").and_then(|v| v.as_str())?;
+    if class != "Video/Source" || props.get("device.api").is_some() {
+        return None;
+    }
+    let state = info.get("state").and_then(|v| v.as_str()).unwrap_or("");
+    Some(state == "running")
+}
@@ -0,0 +1,74 @@
+use anyhow::{Context, Result};
+use std::env;
+use std::os::linux::net::SocketAddrExt;
+use std::os::unix::net::{SocketAddr, UnixDatagram};
+use std::time::Duration;
+use tracing::{debug, warn};
+
+/// Minimal `sd_notify` client for running as a `Type=notify` user unit.
+pub struct Notifier {
+    socket: UnixDatagram,
+    addr: SocketAddr,
+    watchdog: Option<Duration>,
+}
+
+impl Notifier {
+    /// Connects to `$NOTIFY_SOCKET`; `None` when not started by systemd.
+    pub fn from_env() -> Option<Self> {
+        let path = env::var("NOTIFY_SOCKET").ok()?;
+        match Self::connect(&path) {
+            Ok(notifier) => Some(notifier),
+            Err(e) => {
+                warn!("systemd notification unavailable: {:#}", e);
+                None
+            }
+        }
+    }
+
+    fn connect(path: &str) -> Result<Self> {
+        let addr = match path.strip_prefix('@') {
+            Some(name) => SocketAddr::from_abstract_name(name.as_bytes()),
+            None => SocketAddr::from_pathname(path),
+        }
+        .with_context(|| format!("Invalid NOTIFY_SOCKET {:?}", path))?;
+        let socket = UnixDatagram::unbound().context("Failed to create notify socket")?;
+
+        // systemd asks for pings at least every WATCHDOG_USEC.
+        let watchdog = env::var("WATCHDOG_USEC")
+            .ok()
+            .and_then(|usec| usec.parse::<u64>().ok())
+            .filter(|usec| *usec > 0)
+            .map(Duration::from_micros);
+
+        Ok(Self {
+            socket,
+            addr,
+            watchdog,
+        })
+    }
+
+    pub fn ready(&self) {
+        self.send("READY=1\nSTATUS=Showing keystrokes");
+    }
+
+    pub fn stopping(&self) {
+        self.send("STOPPING=1");
+    }
+
+    /// How often to ping, half the watchdog interval; `None` without one.
+    pub fn ping_interval(&self) -> Option<Duration> {
+        self.watchdog.map(|interval| interval / 2)
+    }
+
+    /// Pinged from a main loop timer, so a stalled loop gets the unit
+    /// restarted.
+    pub fn ping_watchdog(&self) {
+        self.send("WATCHDOG=1");
+    }
+
+    fn send(&self, message: &str) {
+        if let Err(e) = self.socket.send_to_addr(message.as_bytes(), &self.addr) {
+            debug!("sd_notify {:?} failed: {}", message, e);
+        }
+    }
+}
@@ -0,0 +1,207 @@
+use anyhow::{Context, Result};
+use async_channel::{Receiver, Sender};
+use dbus::arg;
+use dbus::blocking::stdintf::org_freedesktop_dbus::{Properties, PropertiesPropertiesChanged};
+use dbus::blocking::Connection;
+use dbus::message::{MatchRule, SignalArgs};
+use dbus::Path;
+use std::thread;
+use std::time::Duration;
+use tracing::{debug, info, warn};
+
+const LOGIN1: &str = "org.freedesktop.login1";
+const SESSION_IFACE: &str = "org.freedesktop.login1.Session";
+const MANAGER_IFACE: &str = "org.freedesktop.login1.Manager";
+const MANAGER_PATH: &str = "/org/freedesktop/login1";
+const DBUS_TIMEOUT: Duration = Duration::from_secs(5);
+/// Time for USB keyboards to come back after a resume before devices are
+/// looked up again.
+const RESUME_SETTLE: Duration = Duration::from_secs(2);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SessionEvent {
+    Locked,
+    Unlocked,
+    /// The system woke from suspend, sent once devices had time to settle
+    Resumed,
+}
+
+/// Lock state as the overlay sees it. logind may report one lock twice,
+/// through the signal and the hint, so only real changes count; capture is
+/// resumed on unlock only if the lock paused it.
+#[derive(Debug, Default)]
+pub struct SessionLock {
+    locked: bool,
+    paused_by_lock: bool,
+}
+
+impl SessionLock {
+    pub fn is_locked(&self) -> bool {
+        self.locked
+    }
+
+    /// Applies a lock change. `set_paused` pauses or resumes capture and
+    /// says whether that changed anything. Returns the new paused state
+    /// when the lock paused or resumed capture.
+    pub fn handle(
+        &mut self,
+        event: SessionEvent,
+        pause_on_lock: bool,
+        set_paused: impl FnOnce(bool) -> bool,
+    ) -> Option<bool> {
+        match event {
+            SessionEvent::Locked => {
+                if self.locked {
+                    return None;
+                }
+                self.locked = true;
+                if !pause_on_lock {
+                    return None;
+                }
+                info!("Session locked, pausing capture");
+                self.paused_by_lock = set_paused(true);
+                self.paused_by_lock.then_some(true)
+            }
+            SessionEvent::Unlocked => {
+                if !self.locked {
+                    return None;
+                }
+                self.locked = false;
+                if !std::mem::take(&mut self.paused_by_lock) {
+                    return None;
+                }
+                info!("Session unlocked, resuming capture");
+                set_paused(false);
+                Some(false)
+            }
+            SessionEvent::Resumed => None,
+        }
+    }
+}
+
+/// Watches the logind session for lock state changes. Both the explicit
+/// `Lock`/`Unlock` signals and the `LockedHint` property are followed, since
+/// lockers differ in which of the two they drive. Also reports resumes from
+/// suspend (`PrepareForSleep`), after which input devices may have changed.
+pub fn start_session_monitor() -> Receiver<SessionEvent> {
+    let (tx, rx) = async_channel::bounded(8);
+
+    thread::spawn(move || {
+        if let Err(e) = monitor_session(tx) {
+            warn!("Session lock monitoring unavailable: {:#}", e);
+        }
+    });
+
+    rx
+}
+
+fn monitor_session(tx: Sender<SessionEvent>) -> Result<()> {
+    let conn = Connection::new_system().context("Failed to connect to the system bus")?;
+    let path = current_session_path(&conn)?;
+    info!("Watching session lock state on {}", path);
+
+    let lock_tx = tx.clone();
+    conn.add_match(
+        MatchRule::new_signal(SESSION_IFACE, "Lock")
+            .with_sender(LOGIN1)
+            .with_path(path.clone()),
+        move |_: (), _, _| lock_tx.send_blocking(SessionEvent::Locked).is_ok(),
+    )
+    .context("Failed to subscribe to Lock")?;
+
+    let unlock_tx = tx.clone();
+    conn.add_match(
+        MatchRule::new_signal(SESSION_IFACE, "Unlock")
+            .with_sender(LOGIN1)
+            .with_path(path.clone()),
+        move |_: (), _, _| unlock_tx.send_blocking(SessionEvent::Unlocked).is_ok(),
+    )
+    .context("Failed to subscribe to Unlock")?;
+
+    let resume_tx = tx.clone();
+    conn.add_match(
+        MatchRule::new_signal(MANAGER_IFACE, "PrepareForSleep")
+            .with_sender(LOGIN1)
+            .with_path(MANAGER_PATH),
+        move |(sleeping,): (bool,), _, _| {
+            if !sleeping {
+                debug!("System resumed");
+                let tx = resume_tx.clone();
+                thread::spawn(move || {
+                    thread::sleep(RESUME_SETTLE);
+                    let _ = tx.send_blocking(SessionEvent::Resumed);
+                });
+            }
+            !resume_tx.is_closed()
+        },
+    )
+    .context("Failed to subscribe to PrepareForSleep")?;
+
+    let hint_rule = PropertiesPropertiesChanged::match_rule(Some(&LOGIN1.into()), Some(&path))
+        .static_clone();
+    conn.add_match(hint_rule, move |change: PropertiesPropertiesChanged, _, _| {
+        if change.interface_name != SESSION_IFACE {
+            return true;
+        }
+        match arg::prop_cast::<bool>(&change.changed_properties, "LockedHint") {
+            Some(true) => tx.send_blocking(SessionEvent::Locked).is_ok(),
+            Some(false) => tx.send_blocking(SessionEvent::Unlocked).is_ok(),
+            None => true,
+        }
+    })
+    .context("Failed to subscribe to LockedHint")?;
+
+    loop {
+        // Nothing to do between signals, so block for as long as possible.
+        conn.process(Duration::from_secs(3600))
+            .context("Lost connection to the system bus")?;
+    }
+}
+
+fn current_session_path(conn: &Connection) -> Result<Path<'static>> {
+    // "auto" resolves to the caller's session (or the user's display session
+    // when running as a user service).
+    let auto = conn.with_proxy(LOGIN1, "/org/freedesktop/login1/session/auto", DBUS_TIMEOUT);
+    let id: String = auto
+        .get(SESSION_IFACE, "Id")
+        .context("Failed to resolve the current logind session")?;
+    debug!("logind session id: {}", id);
+
+    let manager = conn.with_proxy(LOGIN1, MANAGER_PATH, DBUS_TIMEOUT);
+    let (path,): (Path<'static>,) = manager
+        .method_call(MANAGER_IFACE, "GetSession", (id,))
+        .context("GetSession failed")?;
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lock_changes_pause_once() {
+        let mut lock = SessionLock::default();
+        let paused = std::cell::Cell::new(false);
+        let set = |value: bool| paused.replace(value) != value;
+        assert_eq!(lock.handle(SessionEvent::Unlocked, true, set), None);
+        assert_eq!(lock.handle(SessionEvent::Locked, true, set), Some(true));
+        // The hint following the signal changes nothing.
+        assert_eq!(lock.handle(SessionEvent::Locked, true, set), None);
+        assert!(lock.is_locked());
+        assert_eq!(lock.handle(SessionEvent::Unlocked, true, set), Some(false));
+        assert_eq!(lock.handle(SessionEvent::Unlocked, true, set), None);
+        assert!(!lock.is_locked());
+
+        // Capture paused by hand stays paused after the unlock.
+        paused.set(true);
+        assert_eq!(lock.handle(SessionEvent::Locked, true, set), None);
+        assert_eq!(lock.handle(SessionEvent::Unlocked, true, set), None);
+        assert!(paused.get());
+
+        // Without pause_on_lock only the state is followed.
+        paused.set(false);
+        assert_eq!(lock.handle(SessionEvent::Locked, false, set), None);
+        assert!(lock.is_locked() && !paused.get());
+        assert_eq!(lock.handle(SessionEvent::Unlocked, false, set), None);
+    }
+}
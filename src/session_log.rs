@@ -0,0 +1,138 @@
+use crate::combo::{unix_millis, ComboEvent};
+use crate::settings::SessionLogFormat;
+use anyhow::{Context, Result};
+use std::fs::{self, File, OpenOptions};
+use std::io::{ErrorKind, LineWriter, Write};
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+use tracing::{info, warn};
+
+/// Opt-in record of the combos shown during one run, one file per session.
+///
+/// Only what the overlay displays is written, so masked keys stay masked and
+/// apps hidden by the filter are left out.
+pub struct SessionLog {
+    writer: LineWriter<File>,
+    format: SessionLogFormat,
+    started: Instant,
+    dir: PathBuf,
+    path: PathBuf,
+}
+
+impl SessionLog {
+    pub fn create(dir: &Path, format: SessionLogFormat) -> Result<Self> {
+        fs::create_dir_all(dir).with_context(|| format!("Failed to create log dir: {:?}", dir))?;
+        let extension = match format {
+            SessionLogFormat::Csv => "csv",
+            SessionLogFormat::Jsonl => "jsonl",
+        };
+        let stem = format!("session-{}", unix_millis() / 1000);
+        let (file, path) = create_private(dir, &stem, extension)?;
+        let mut writer = LineWriter::new(file);
+        if format == SessionLogFormat::Csv {
+            writeln!(writer, "timestamp_ms,elapsed_ms,kind,text")?;
+        }
+        info!("Logging displayed combos to {:?}", path);
+
+        Ok(Self {
+            writer,
+            format,
+            started: Instant::now(),
+            dir: dir.to_path_buf(),
+            path,
+        })
+    }
+
+    /// Whether this log was opened with the given settings.
+    pub fn matches(&self, dir: &Path, format: SessionLogFormat) -> bool {
+        self.dir == dir && self.format == format
+    }
+
+    pub fn record(&mut self, combo: &ComboEvent) {
+        let elapsed = self.started.elapsed().as_millis() as u64;
+        let result = match self.format {
+            SessionLogFormat::Csv => writeln!(
+                self.writer,
+                "{},{},{},{}",
+                unix_millis(),
+                elapsed,
+                combo.kind.name(),
+                csv_field(&combo.text)
+            ),
+            SessionLogFormat::Jsonl => {
+                let mut value = combo.to_json();
+                value["elapsed_ms"] = elapsed.into();
+                writeln!(self.writer, "{}", value)
+            }
+        };
+        if let Err(e) = result {
+            warn!("Failed to write session log {:?}: {}", self.path, e);
+        }
+    }
+}
+
+/// Creates `<stem>.<extension>` readable only by the user, or
+/// `<stem>-2.<extension>` and so on when a log started the same second.
+pub fn create_private(dir: &Path, stem: &str, extension: &str) -> Result<(File, PathBuf)> {
+    let mut path = dir.join(format!("{}.{}", stem, extension));
+    let mut attempt = 1;
+    loop {
+        match OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .mode(0o600)
+            .open(&path)
+        {
+            Ok(file) => return Ok((file, path)),
+            Err(e) if e.kind() == ErrorKind::AlreadyExists => {
+                attempt += 1;
+                path = dir.join(format!("{}-{}.{}", stem, attempt, extension));
+            }
+            Err(e) => return Err(e).with_context(|| format!("Failed to create {:?}", path)),
+        }
+    }
+}
+
+pub fn csv_field(text: &str) -> String {
+    if text.contains([',', '"', '\n']) {
+        format!("\"{}\"", text.replace('"', "\"\""))
+    } else {
+        text.to_string()
+    }
+}
+
+/// `~/.local/share/keyway-visualizer/logs`
+pub fn default_log_dir() -> PathBuf {
+    dirs::data_dir()
+        .unwrap_or_else(|| PathBuf::from("."))
+        .join("keyway-visualizer")
+        .join("logs")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn csv_fields_are_quoted_when_needed() {
+        assert_eq!(csv_field("Ctrl+S"), "Ctrl+S");
+        assert_eq!(csv_field(","), "\",\"");
+        assert_eq!(csv_field("\""), "\"\"\"\"");
+    }
+
+    #[test]
+    fn logs_are_private_and_never_reused() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = std::env::temp_dir().join(format!("keyway-logs-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let (_, first) = create_private(&dir, "session-1", "csv").unwrap();
+        let (_, second) = create_private(&dir, "session-1", "csv").unwrap();
+        assert_eq!(first.file_name().unwrap(), "session-1.csv");
+        assert_eq!(second.file_name().unwrap(), "session-1-2.csv");
+        let mode = fs::metadata(&first).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
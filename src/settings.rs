@@ -1,14 +1,38 @@
 use anyhow::{Context, Result};
-use clap::{Parser, ValueEnum};
+use crate::bench_cli::BenchArgs;
+use crate::ctl::CtlArgs;
+use crate::generate::GenerateArgs;
+use crate::input::helper::HelperArgs;
+use crate::stats_cli::StatsArgs;
+use clap::{Parser, Subcommand, ValueEnum};
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::fs;
 use std::path::{Path, PathBuf};
 use tracing::{info, warn};
 
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Control a running instance (also available as `keyway-ctl`)
+    Ctl(CtlArgs),
+    /// Reports from the statistics database
+    Stats(StatsArgs),
+    /// Push synthetic input through the event pipeline and report throughput
+    Bench(BenchArgs),
+    /// Shell completions or a sample config, for packaging and dotfiles
+    Generate(GenerateArgs),
+    /// Read input devices for a running overlay (started by it with `listener_helper`)
+    #[command(hide = true)]
+    ListenHelper(HelperArgs),
+}
+
 #[derive(Parser, Debug)]
 #[command(name = "keyway-visualizer")]
 #[command(about = "Minimal Wayland keystroke overlay")]
 pub struct CliArgs {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
     /// Path to config file
     #[arg(long)]
     pub config: Option<PathBuf>,
@@ -64,6 +88,355 @@ pub struct CliArgs {
     /// Disable overlay for matching app class/title (repeatable)
     #[arg(long = "disabled-app")]
     pub disabled_apps: Vec<String>,
+
+    /// Overlay color theme (dark, light, auto to follow the desktop)
+    #[arg(long, value_enum)]
+    pub theme: Option<Theme>,
+
+    /// Which combos to display (all, shortcuts-only, modifier-gated)
+    #[arg(long, value_enum)]
+    pub display_mode: Option<DisplayMode>,
+
+    /// Pause and clear the overlay while the session is locked (true/false)
+    #[arg(long)]
+    pub pause_on_lock: Option<bool>,
+
+    /// React to screen sharing (ignore, show-while-casting, hide-while-casting)
+    #[arg(long, value_enum)]
+    pub screencast_mode: Option<ScreencastMode>,
+
+    /// Always mask plain keys, showing only shortcuts (true/false)
+    #[arg(long)]
+    pub privacy_mask: Option<bool>,
+
+    /// Mask input automatically while a secure-input app has focus (true/false)
+    #[arg(long)]
+    pub secure_input_enabled: Option<bool>,
+
+    /// Hide the overlay while the focused window is fullscreen (true/false)
+    #[arg(long)]
+    pub hide_on_fullscreen: Option<bool>,
+
+    /// Show a small dot instead of nothing while the app filter hides the overlay (true/false)
+    #[arg(long)]
+    pub filter_indicator: Option<bool>,
+
+    /// Broadcast displayed combos over a local WebSocket (true/false)
+    #[arg(long)]
+    pub websocket_enabled: Option<bool>,
+
+    /// Port for the WebSocket event stream
+    #[arg(long)]
+    pub websocket_port: Option<u16>,
+
+    /// Write input and combo events as JSON lines to stdout, or to PATH
+    #[arg(long, value_name = "PATH", num_args = 0..=1, default_missing_value = "-")]
+    pub emit_json: Option<PathBuf>,
+
+    /// Play a script of combos and waits instead of showing local input
+    #[arg(long, value_name = "FILE")]
+    pub demo: Option<PathBuf>,
+
+    /// Read input events from stdin ("press LEFTCTRL", "tap C") instead of input devices
+    #[arg(long)]
+    pub stdin_events: bool,
+
+    /// Log displayed combos to a file for this session (true/false)
+    #[arg(long)]
+    pub session_log_enabled: Option<bool>,
+
+    /// Session log format (csv, jsonl)
+    #[arg(long, value_enum)]
+    pub session_log_format: Option<SessionLogFormat>,
+
+    /// Directory for session logs
+    #[arg(long)]
+    pub session_log_dir: Option<PathBuf>,
+
+    /// Load Lua plugins from the plugins directory (true/false)
+    #[arg(long)]
+    pub plugins_enabled: Option<bool>,
+
+    /// Directory with Lua plugins
+    #[arg(long)]
+    pub plugins_dir: Option<PathBuf>,
+
+    /// Run as a systemd user service: notify readiness, ping the watchdog, save on SIGTERM
+    #[arg(long)]
+    pub service: bool,
+
+    /// Print combos to stdout instead of showing the overlay (no GTK; works over SSH and in TTYs)
+    #[arg(long)]
+    pub headless: bool,
+
+    /// Print the keyboards and mice found and whether they would be captured, then exit
+    #[arg(long)]
+    pub list_devices: bool,
+
+    /// Color the headless output (auto, always, never)
+    #[arg(long, value_enum, default_value_t = clap::ColorChoice::Auto)]
+    pub color: clap::ColorChoice,
+
+    /// Serve Prometheus metrics on localhost (true/false)
+    #[arg(long)]
+    pub metrics_enabled: Option<bool>,
+
+    /// Port for the metrics endpoint
+    #[arg(long)]
+    pub metrics_port: Option<u16>,
+
+    /// Address the WebSocket server listens on (e.g. 0.0.0.0 for remote display)
+    #[arg(long)]
+    pub websocket_bind: Option<String>,
+
+    /// Show combos from another instance instead of local input (e.g. ws://192.168.1.10:8765)
+    #[arg(long)]
+    pub remote_receive: Option<String>,
+
+    /// Shared secret the WebSocket server asks for and the remote receiver sends
+    #[arg(long)]
+    pub remote_token: Option<String>,
+
+    /// Register the pause and clear hotkeys through the GlobalShortcuts portal (true/false)
+    #[arg(long)]
+    pub portal_shortcuts: Option<bool>,
+
+    /// Record key and combo counts to the local statistics database (true/false)
+    #[arg(long)]
+    pub stats_enabled: Option<bool>,
+
+    /// Days of statistics to keep (0 keeps everything)
+    #[arg(long)]
+    pub stats_retention_days: Option<u32>,
+
+    /// Statistics database (default ~/.local/share/keyway-visualizer/stats.sqlite3)
+    #[arg(long)]
+    pub stats_path: Option<PathBuf>,
+
+    /// Typing speed shown next to the keys (off, wpm, kpm, apm)
+    #[arg(long, value_enum)]
+    pub speed_widget: Option<SpeedWidget>,
+
+    /// Summary of the session when quitting (off, print, file)
+    #[arg(long, value_enum)]
+    pub session_summary: Option<SummaryMode>,
+
+    /// Count key categories (letter, number, modifier, shortcut) instead of exact keys (true/false)
+    #[arg(long)]
+    pub stats_categories_only: Option<bool>,
+
+    /// Highest overlay refresh rate in Hz; updates in between are merged, 0 for no limit
+    #[arg(long)]
+    pub max_refresh_hz: Option<u32>,
+
+    /// Show a Caps Lock / Num Lock bubble while either is on (true/false)
+    #[arg(long)]
+    pub lock_indicator: Option<bool>,
+
+    /// Show the character a dead-key or Compose sequence types instead of its keys (true/false)
+    #[arg(long)]
+    pub show_composed: Option<bool>,
+
+    /// Announce each combo through the screen reader's speech service (true/false)
+    #[arg(long)]
+    pub screen_reader: Option<bool>,
+
+    /// Status colors for color vision deficiencies (default, deuteranopia, protanopia, tritanopia)
+    #[arg(long, value_enum)]
+    pub palette: Option<Palette>,
+
+    /// Presenter mode hotkey (e.g. "Ctrl+Shift+F10"); empty disables it
+    #[arg(long)]
+    pub presenter_hotkey: Option<String>,
+
+    /// How the visual bell flashes (edge, overlay)
+    #[arg(long, value_enum)]
+    pub visual_bell_style: Option<BellStyle>,
+
+    /// Keep a panel of Ctrl/Shift/Alt/Super on screen that lights up while each is held (true/false)
+    #[arg(long)]
+    pub modifier_panel: Option<bool>,
+
+    /// Show action names from the aliases table in large text above their combo (true/false)
+    #[arg(long)]
+    pub teaching_mode: Option<bool>,
+
+    /// Interface and key name language, such as de or fr; empty follows the locale
+    #[arg(long)]
+    pub language: Option<String>,
+
+    /// Keep a badge with the active keyboard layout (US, DE, ...) on screen (true/false)
+    #[arg(long)]
+    pub layout_badge: Option<bool>,
+
+    /// Directory for overlay recordings and screenshots (default: ~/Videos/keyway)
+    #[arg(long)]
+    pub capture_dir: Option<PathBuf>,
+
+    /// Write transparent RGBA overlay frames to this file or FIFO
+    #[arg(long)]
+    pub frame_stream: Option<PathBuf>,
+
+    /// Width of the streamed frames in pixels
+    #[arg(long)]
+    pub frame_stream_width: Option<u32>,
+
+    /// Height of the streamed frames in pixels
+    #[arg(long)]
+    pub frame_stream_height: Option<u32>,
+
+    /// Follow OBS through obs-websocket (e.g. ws://localhost:4455)
+    #[arg(long)]
+    pub obs_websocket: Option<String>,
+
+    /// obs-websocket password, if authentication is on
+    #[arg(long)]
+    pub obs_password: Option<String>,
+
+    /// What the overlay does while OBS is not recording (ignore, pause, hide)
+    #[arg(long, value_enum)]
+    pub obs_recording: Option<ObsReaction>,
+
+    /// What the overlay does while OBS is not streaming (ignore, pause, hide)
+    #[arg(long, value_enum)]
+    pub obs_streaming: Option<ObsReaction>,
+
+    /// Hotkey that saves the overlay as a PNG (e.g. "Ctrl+Shift+F11"); empty disables it
+    #[arg(long)]
+    pub screenshot_hotkey: Option<String>,
+
+    /// Hotkey that appends a timeline marker to the marker file (e.g. "Ctrl+Shift+M"); empty disables it
+    #[arg(long)]
+    pub marker_hotkey: Option<String>,
+
+    /// File timeline markers are appended to (default: ~/.local/share/keyway-visualizer/markers.tsv)
+    #[arg(long)]
+    pub marker_file: Option<PathBuf>,
+
+    /// Annotate each bubble with its input-to-render latency and log percentiles
+    #[arg(long)]
+    pub latency_debug: Option<bool>,
+
+    /// Show a panel with this many of the session's most used shortcuts and their counts (0 hides it)
+    #[arg(long)]
+    pub shortcut_tally: Option<usize>,
+
+    /// Only show keys during these windows, e.g. "Mon-Fri 09:00-17:00" (repeatable)
+    #[arg(long = "active-hours")]
+    pub active_hours: Vec<String>,
+
+    /// Hide the overlay except while this key is held, e.g. RightCtrl; empty always shows it
+    #[arg(long)]
+    pub peek_key: Option<String>,
+
+    /// Layer-shell layer, which decides stacking against bars and windows (overlay, top, bottom, background)
+    #[arg(long, value_enum)]
+    pub layer: Option<OverlayLayer>,
+
+    /// Layer-shell namespace, for compositor rules such as blur or shadows
+    #[arg(long)]
+    pub namespace: Option<String>,
+
+    /// Where bubbles sit along the anchored edge (auto, start, center, end); auto follows position
+    #[arg(long, value_enum)]
+    pub bubble_align: Option<BubbleAlign>,
+
+    /// Bubbles in a row, or one per line like a rolling log (row, lines); max_items sets the line count
+    #[arg(long, value_enum)]
+    pub bubble_layout: Option<BubbleLayout>,
+
+    /// How combos are spelled, e.g. "{mods:-}{key|lower}" or "{mods} {key}[ ×{count}]"; empty for Ctrl+Shift+T
+    #[arg(long)]
+    pub combo_format: Option<String>,
+
+    /// Between the parts of a combo, such as - for Ctrl-C or a space for Ctrl C
+    #[arg(long)]
+    pub combo_separator: Option<String>,
+
+    /// Put spaces around the separator, as in Ctrl + C (true/false)
+    #[arg(long)]
+    pub separator_spaces: Option<bool>,
+
+    /// Modifier notation (standard, emacs); emacs shows C-x, M-w, S-Tab, s-Enter
+    #[arg(long, value_enum)]
+    pub notation: Option<Notation>,
+
+    /// Show a short ON/OFF bubble when Caps Lock, Num Lock or Scroll Lock toggles (true/false)
+    #[arg(long)]
+    pub lock_announcements: Option<bool>,
+
+    /// Label click-and-drag gestures (off, plain, direction, length)
+    #[arg(long, value_enum)]
+    pub mouse_drag: Option<DragLabel>,
+
+    /// Make combos pressed often this session stand out (off, tint, size, both)
+    #[arg(long, value_enum)]
+    pub frequency_emphasis: Option<Emphasis>,
+
+    /// Show held shortcut modifiers as a dimmed "Ctrl+…" bubble until the key comes (true/false)
+    #[arg(long)]
+    pub ghost_preview: Option<bool>,
+
+    /// File of shortcuts to practice, one per line; the overlay prompts for each in turn
+    #[arg(long)]
+    pub practice_file: Option<PathBuf>,
+
+    /// Remind to take a break after this many minutes of continuous typing; 0 turns reminders off
+    #[arg(long)]
+    pub break_reminder_minutes: Option<u64>,
+
+    /// Minutes without a key press that count as a break and restart the count
+    #[arg(long)]
+    pub break_idle_minutes: Option<u64>,
+
+    /// Ask before quitting from the tray menu (true/false)
+    #[arg(long)]
+    pub confirm_quit: Option<bool>,
+
+    /// Bring back pause, visibility and pinned profile from the last run (true/false)
+    #[arg(long)]
+    pub restore_state: Option<bool>,
+
+    /// Read input devices in a separate helper process, so only it needs input access (true/false)
+    #[arg(long)]
+    pub listener_helper: Option<bool>,
+
+    /// Program run as the listener helper (default: this executable)
+    #[arg(long)]
+    pub listener_helper_path: Option<PathBuf>,
+
+    /// Confine the input listener to reading /dev/input with Landlock and seccomp (true/false)
+    #[arg(long)]
+    pub sandbox_listener: Option<bool>,
+
+    /// Start with the desktop session, through the Background portal inside Flatpak (true/false)
+    #[arg(long)]
+    pub autostart: Option<bool>,
+
+    /// Show the text IBus or Fcitx5 types instead of the keys typed into it (true/false)
+    #[arg(long)]
+    pub ime_text: Option<bool>,
+
+    /// Keep the settings the GSettings schema covers in toml or gsettings (dconf)
+    #[arg(long, value_enum)]
+    pub settings_backend: Option<SettingsBackend>,
+}
+
+impl CliArgs {
+    /// Options that only take effect when the overlay starts, which a
+    /// second launch cannot pass on to the running instance.
+    pub fn startup_only(&self) -> Vec<&'static str> {
+        [
+            ("--config", self.config.is_some()),
+            ("--emit-json", self.emit_json.is_some()),
+            ("--demo", self.demo.is_some()),
+            ("--stdin-events", self.stdin_events),
+            ("--service", self.service),
+        ]
+        .into_iter()
+        .filter_map(|(name, set)| set.then_some(name))
+        .collect()
+    }
 }
 
 #[derive(Debug, Clone, Copy, Serialize, Deserialize, ValueEnum, PartialEq)]
@@ -80,6 +453,240 @@ pub enum Position {
     Custom,
 }
 
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, ValueEnum, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+#[value(rename_all = "kebab-case")]
+pub enum BubbleLayout {
+    Row,
+    /// One combo per line, newest at the bottom, for step-by-step tutorials
+    Lines,
+}
+
+/// How modifiers are written on screen.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, ValueEnum, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+#[value(rename_all = "kebab-case")]
+pub enum Notation {
+    /// Ctrl+Shift+T
+    Standard,
+    /// C-S-t, as Emacs and terminal docs write it
+    Emacs,
+}
+
+/// `bubble_align`: where the row sits along the edge the overlay is anchored to.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, ValueEnum, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+#[value(rename_all = "kebab-case")]
+pub enum BubbleAlign {
+    /// The corner or middle the position names
+    Auto,
+    Start,
+    Center,
+    End,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, ValueEnum, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+#[value(rename_all = "kebab-case")]
+pub enum Theme {
+    Dark,
+    Light,
+    /// Dark or light as the desktop's color scheme prefers
+    Auto,
+}
+
+/// Where the settings in the GSettings schema are kept.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, ValueEnum, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+#[value(rename_all = "kebab-case")]
+pub enum SettingsBackend {
+    /// This file
+    Toml,
+    /// dconf, through the `dev.keyway.Visualizer` schema
+    Gsettings,
+}
+
+/// Colors of the status, lock, speed and announcement bubbles. The presets
+/// keep them apart for viewers with the named color vision deficiency.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, ValueEnum, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+#[value(rename_all = "kebab-case")]
+pub enum Palette {
+    Default,
+    Deuteranopia,
+    Protanopia,
+    Tritanopia,
+}
+
+/// How bubbles of often pressed combos stand out.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, ValueEnum, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+#[value(rename_all = "kebab-case")]
+pub enum Emphasis {
+    Off,
+    /// Warmer colors
+    Tint,
+    /// Larger text
+    Size,
+    Both,
+}
+
+/// How a mouse button held down while the pointer moves is labeled.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, ValueEnum, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+#[value(rename_all = "kebab-case")]
+pub enum DragLabel {
+    /// Only the click, as before
+    Off,
+    /// "LMB drag"
+    Plain,
+    /// "LMB drag ↗"
+    Direction,
+    /// "LMB drag ↗ 240", with the distance in mouse units
+    Length,
+}
+
+/// Layer-shell layers, from above fullscreen windows down to the wallpaper.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, ValueEnum, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+#[value(rename_all = "kebab-case")]
+pub enum OverlayLayer {
+    Overlay,
+    Top,
+    Bottom,
+    Background,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, ValueEnum, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+#[value(rename_all = "kebab-case")]
+pub enum BellStyle {
+    Edge,
+    Overlay,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, ValueEnum, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+#[value(rename_all = "kebab-case")]
+pub enum DisplayMode {
+    All,
+    ShortcutsOnly,
+    /// Nothing until a modifier is held, then the combo as it is built
+    ModifierGated,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, ValueEnum, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+#[value(rename_all = "kebab-case")]
+pub enum SummaryMode {
+    Off,
+    /// Print to stdout (stderr while --emit-json uses stdout)
+    Print,
+    /// Write summary-<time>.md to the session log directory
+    File,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, ValueEnum, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+#[value(rename_all = "kebab-case")]
+pub enum SpeedWidget {
+    Off,
+    Wpm,
+    Kpm,
+    Apm,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, ValueEnum, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+#[value(rename_all = "kebab-case")]
+pub enum ScreencastMode {
+    Ignore,
+    ShowWhileCasting,
+    HideWhileCasting,
+    MaskWhileCasting,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, ValueEnum, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+#[value(rename_all = "kebab-case")]
+pub enum SessionLogFormat {
+    Csv,
+    Jsonl,
+}
+
+/// What an OBS output's state does to the overlay: while the output is
+/// stopped the overlay is paused or hidden, and it comes back when it starts.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, ValueEnum, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+#[value(rename_all = "kebab-case")]
+pub enum ObsReaction {
+    Ignore,
+    Pause,
+    Hide,
+}
+
+/// Shell command run when a displayed combo matches.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CommandHook {
+    /// Combo text such as `Print` or `Ctrl+Shift+S` (case-insensitive), or a
+    /// `re:`/`glob:` pattern
+    pub combo: String,
+    /// Run with `sh -c`; the combo text is in `$KEYWAY_COMBO`
+    pub command: String,
+    /// Minimum time between two runs of this hook
+    pub cooldown_ms: u64,
+}
+
+impl Default for CommandHook {
+    fn default() -> Self {
+        Self {
+            combo: String::new(),
+            command: String::new(),
+            cooldown_ms: 1000,
+        }
+    }
+}
+
+/// Overrides applied while a matching app has focus. Unset fields keep the
+/// base settings.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AppProfile {
+    pub name: String,
+    /// App filter style entries (see `disabled_apps`) that activate this profile
+    pub apps: Vec<String>,
+    pub position: Option<Position>,
+    pub margin: Option<i32>,
+    pub theme: Option<Theme>,
+    pub display_mode: Option<DisplayMode>,
+    pub notation: Option<Notation>,
+}
+
+impl AppProfile {
+    pub fn apply_to(&self, settings: &mut Settings) {
+        if let Some(position) = self.position {
+            settings.position = position;
+        }
+        if let Some(margin) = self.margin {
+            settings.margin = margin;
+        }
+        if let Some(theme) = self.theme {
+            settings.theme = theme;
+        }
+        if let Some(display_mode) = self.display_mode {
+            settings.display_mode = display_mode;
+        }
+        if let Some(notation) = self.notation {
+            settings.notation = notation;
+        }
+    }
+}
+
+pub const DEFAULT_NAMESPACE: &str = "keyway-visualizer";
+/// Shortest time presenter mode keeps a bubble up.
+const PRESENTER_MIN_TTL_MS: u64 = 4000;
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(default)]
 pub struct Settings {
@@ -96,6 +703,140 @@ pub struct Settings {
     pub custom_y: i32,
     pub app_filter_enabled: bool,
     pub disabled_apps: Vec<String>,
+    pub theme: Theme,
+    pub display_mode: DisplayMode,
+    pub pause_on_lock: bool,
+    pub screencast_mode: ScreencastMode,
+    pub privacy_mask: bool,
+    pub secure_input_enabled: bool,
+    /// App filter style entries for password prompts and sign-in windows
+    pub secure_input_rules: Vec<String>,
+    pub hide_on_fullscreen: bool,
+    pub filter_indicator: bool,
+    pub websocket_enabled: bool,
+    pub websocket_port: u16,
+    pub session_log_enabled: bool,
+    pub session_log_format: SessionLogFormat,
+    /// Defaults to ~/.local/share/keyway-visualizer/logs
+    pub session_log_dir: Option<PathBuf>,
+    pub hooks: Vec<CommandHook>,
+    pub plugins_enabled: bool,
+    /// Defaults to ~/.config/keyway-visualizer/plugins
+    pub plugins_dir: Option<PathBuf>,
+    pub metrics_enabled: bool,
+    pub metrics_port: u16,
+    /// Use 0.0.0.0 to let another machine receive the stream (remote display)
+    pub websocket_bind: String,
+    /// `ws://host:port` of a sending instance; local input is not captured
+    pub remote_receive: Option<String>,
+    /// Shared secret for remote display: the WebSocket server refuses
+    /// clients without it, and it is required to listen beyond localhost
+    pub remote_token: String,
+    pub portal_shortcuts: bool,
+    pub stats_enabled: bool,
+    /// Days of statistics to keep; 0 keeps everything
+    pub stats_retention_days: u32,
+    /// Defaults to ~/.local/share/keyway-visualizer/stats.sqlite3
+    pub stats_path: Option<PathBuf>,
+    /// Rolling typing speed shown after the bubbles
+    pub speed_widget: SpeedWidget,
+    /// Duration, key and click totals and top shortcuts, written on quit
+    pub session_summary: SummaryMode,
+    /// Record letter/number/modifier/shortcut instead of the exact keys
+    pub stats_categories_only: bool,
+    /// Renders per second at most; 0 renders every change
+    pub max_refresh_hz: u32,
+    /// Keep a bubble on screen while Caps Lock or Num Lock is on
+    pub lock_indicator: bool,
+    /// Show what dead-key and Compose sequences type instead of their keys
+    pub show_composed: bool,
+    /// Speak each displayed combo through speech-dispatcher
+    pub screen_reader: bool,
+    /// Status bubble colors, with presets for color vision deficiencies
+    pub palette: Palette,
+    /// Toggles presenter mode; empty for none
+    pub presenter_hotkey: String,
+    /// Keys that flash the visual bell, in hotkey syntax
+    pub visual_bell_keys: Vec<String>,
+    /// Flash the screen edge or the overlay bubbles
+    pub visual_bell_style: BellStyle,
+    /// Show which modifiers are held in a panel that stays on screen
+    pub modifier_panel: bool,
+    /// Show aliased combos as their action name with the keys beneath
+    pub teaching_mode: bool,
+    /// Action names by combo, e.g. "Ctrl+Shift+T" = "Reopen closed tab"
+    pub aliases: BTreeMap<String, String>,
+    /// Tags shown before combos from matching devices, by a fragment of the
+    /// device name, e.g. "Macro Pad" = "pad"
+    pub device_tags: BTreeMap<String, String>,
+    /// Language code for the interface and key names; empty follows the locale
+    pub language: String,
+    /// Show the active layout abbreviation in a badge that stays on screen
+    pub layout_badge: bool,
+    /// Where overlay recordings and screenshots are saved; defaults to ~/Videos/keyway
+    pub capture_dir: Option<PathBuf>,
+    /// File or FIFO receiving raw RGBA frames of the combo stream
+    pub frame_stream: Option<PathBuf>,
+    pub frame_stream_width: u32,
+    pub frame_stream_height: u32,
+    /// `ws://host:port` of obs-websocket; recording and streaming state drive the overlay
+    pub obs_websocket: Option<String>,
+    pub obs_password: String,
+    pub obs_recording: ObsReaction,
+    pub obs_streaming: ObsReaction,
+    /// Saves the overlay as a PNG; empty for none
+    pub screenshot_hotkey: String,
+    /// Appends a timestamped marker line to `marker_file`; empty for none
+    pub marker_hotkey: String,
+    pub marker_file: Option<PathBuf>,
+    /// Debug: annotate bubbles with the time from the kernel event to the render, and log percentiles
+    pub latency_debug: bool,
+    /// Size of the panel of most used shortcuts this session; 0 hides it
+    pub shortcut_tally: usize,
+    /// Windows such as "Mon-Fri 09:00-17:00" outside which capture pauses; empty for always
+    pub active_hours: Vec<String>,
+    /// Key that shows the overlay while held (evdev name such as RightCtrl); empty to always show it
+    pub peek_key: String,
+    /// Stacking against panels and windows
+    pub layer: OverlayLayer,
+    /// Layer-shell namespace the compositor sees, for blur or shadow rules
+    pub namespace: String,
+    /// Start, center or end of the anchored edge, whatever the position preset
+    pub bubble_align: BubbleAlign,
+    pub bubble_layout: BubbleLayout,
+    pub combo_format: String,
+    pub combo_separator: String,
+    pub separator_spaces: bool,
+    pub notation: Notation,
+    pub lock_announcements: bool,
+    pub mouse_drag: DragLabel,
+    pub frequency_emphasis: Emphasis,
+    pub ghost_preview: bool,
+    /// Practice mode: prompts for the shortcuts in this file and marks
+    /// each attempt; empty for none
+    pub practice_file: Option<PathBuf>,
+    /// Minutes of typing without a pause of `break_idle_minutes` before a
+    /// break reminder; 0 for none
+    pub break_reminder_minutes: u64,
+    pub break_idle_minutes: u64,
+    pub confirm_quit: bool,
+    /// Keeps pause, visibility and the pinned profile in
+    /// `saved_state::default_state_path` across restarts
+    pub restore_state: bool,
+    /// Runs `listener_helper_path listen-helper` to read the devices
+    /// instead of opening them in this process
+    pub listener_helper: bool,
+    pub listener_helper_path: Option<PathBuf>,
+    /// Confines the listener threads, or the helper, to reading
+    /// input devices (see `input::sandbox`)
+    pub sandbox_listener: bool,
+    /// Keeps an autostart entry for the session (see `autostart::sync`)
+    pub autostart: bool,
+    /// Show text committed by IBus or Fcitx5 in place of the keys composing it
+    pub ime_text: bool,
+    /// `gsettings` reads and saves the keys in the GSettings schema through dconf
+    pub settings_backend: SettingsBackend,
+    pub profiles: Vec<AppProfile>,
 }
 
 impl Default for Settings {
@@ -114,6 +855,88 @@ impl Default for Settings {
             custom_y: 40,
             app_filter_enabled: false,
             disabled_apps: Vec::new(),
+            theme: Theme::Dark,
+            display_mode: DisplayMode::All,
+            pause_on_lock: true,
+            screencast_mode: ScreencastMode::Ignore,
+            privacy_mask: false,
+            secure_input_enabled: true,
+            secure_input_rules: default_secure_input_rules(),
+            hide_on_fullscreen: false,
+            filter_indicator: false,
+            websocket_enabled: false,
+            websocket_port: 8765,
+            session_log_enabled: false,
+            session_log_format: SessionLogFormat::Csv,
+            session_log_dir: None,
+            hooks: Vec::new(),
+            plugins_enabled: false,
+            plugins_dir: None,
+            metrics_enabled: false,
+            metrics_port: 9464,
+            websocket_bind: "127.0.0.1".to_string(),
+            remote_receive: None,
+            remote_token: String::new(),
+            portal_shortcuts: false,
+            stats_enabled: false,
+            stats_retention_days: 365,
+            stats_path: None,
+            speed_widget: SpeedWidget::Off,
+            session_summary: SummaryMode::Off,
+            stats_categories_only: false,
+            max_refresh_hz: 60,
+            lock_indicator: false,
+            show_composed: true,
+            screen_reader: false,
+            palette: Palette::Default,
+            presenter_hotkey: "Ctrl+Shift+F10".to_string(),
+            visual_bell_keys: Vec::new(),
+            visual_bell_style: BellStyle::Edge,
+            modifier_panel: false,
+            teaching_mode: false,
+            aliases: BTreeMap::new(),
+            device_tags: BTreeMap::new(),
+            language: String::new(),
+            layout_badge: false,
+            capture_dir: None,
+            frame_stream: None,
+            frame_stream_width: 1280,
+            frame_stream_height: 120,
+            obs_websocket: None,
+            obs_password: String::new(),
+            obs_recording: ObsReaction::Hide,
+            obs_streaming: ObsReaction::Hide,
+            screenshot_hotkey: String::new(),
+            marker_hotkey: String::new(),
+            marker_file: None,
+            latency_debug: false,
+            shortcut_tally: 0,
+            active_hours: Vec::new(),
+            peek_key: String::new(),
+            layer: OverlayLayer::Overlay,
+            namespace: DEFAULT_NAMESPACE.to_string(),
+            bubble_align: BubbleAlign::Auto,
+            bubble_layout: BubbleLayout::Row,
+            combo_format: String::new(),
+            combo_separator: "+".to_string(),
+            separator_spaces: false,
+            notation: Notation::Standard,
+            lock_announcements: false,
+            mouse_drag: DragLabel::Direction,
+            frequency_emphasis: Emphasis::Off,
+            ghost_preview: false,
+            practice_file: None,
+            break_reminder_minutes: 0,
+            break_idle_minutes: 5,
+            confirm_quit: false,
+            restore_state: true,
+            listener_helper: false,
+            listener_helper_path: None,
+            sandbox_listener: true,
+            autostart: false,
+            ime_text: false,
+            settings_backend: SettingsBackend::Toml,
+            profiles: Vec::new(),
         }
     }
 }
@@ -147,7 +970,15 @@ impl Settings {
         Ok((settings, path))
     }
 
-    fn apply_cli(&mut self, cli: &CliArgs) {
+    /// Reads the config file without applying CLI overrides, failing on
+    /// parse errors instead of falling back to defaults.
+    pub fn read_from(path: &Path) -> Result<Self> {
+        let raw = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read config: {:?}", path))?;
+        toml::from_str(&raw).with_context(|| format!("Failed to parse config: {:?}", path))
+    }
+
+    pub fn apply_cli(&mut self, cli: &CliArgs) {
         if let Some(position) = cli.position {
             self.position = position;
         }
@@ -192,7 +1023,246 @@ impl Settings {
                 .filter(|s| !s.is_empty())
                 .collect();
         }
+        if let Some(theme) = cli.theme {
+            self.theme = theme;
+        }
+        if let Some(display_mode) = cli.display_mode {
+            self.display_mode = display_mode;
+        }
+        if let Some(pause_on_lock) = cli.pause_on_lock {
+            self.pause_on_lock = pause_on_lock;
+        }
+        if let Some(screencast_mode) = cli.screencast_mode {
+            self.screencast_mode = screencast_mode;
+        }
+        if let Some(privacy_mask) = cli.privacy_mask {
+            self.privacy_mask = privacy_mask;
+        }
+        if let Some(secure_input_enabled) = cli.secure_input_enabled {
+            self.secure_input_enabled = secure_input_enabled;
+        }
+        if let Some(hide_on_fullscreen) = cli.hide_on_fullscreen {
+            self.hide_on_fullscreen = hide_on_fullscreen;
+        }
+        if let Some(filter_indicator) = cli.filter_indicator {
+            self.filter_indicator = filter_indicator;
+        }
+        if let Some(websocket_enabled) = cli.websocket_enabled {
+            self.websocket_enabled = websocket_enabled;
+        }
+        if let Some(websocket_port) = cli.websocket_port {
+            self.websocket_port = websocket_port;
+        }
+        if let Some(session_log_enabled) = cli.session_log_enabled {
+            self.session_log_enabled = session_log_enabled;
+        }
+        if let Some(session_log_format) = cli.session_log_format {
+            self.session_log_format = session_log_format;
+        }
+        if let Some(session_log_dir) = &cli.session_log_dir {
+            self.session_log_dir = Some(session_log_dir.clone());
+        }
+        if let Some(plugins_enabled) = cli.plugins_enabled {
+            self.plugins_enabled = plugins_enabled;
+        }
+        if let Some(plugins_dir) = &cli.plugins_dir {
+            self.plugins_dir = Some(plugins_dir.clone());
+        }
+        if let Some(metrics_enabled) = cli.metrics_enabled {
+            self.metrics_enabled = metrics_enabled;
+        }
+        if let Some(metrics_port) = cli.metrics_port {
+            self.metrics_port = metrics_port;
+        }
+        if let Some(websocket_bind) = cli.websocket_bind.clone() {
+            self.websocket_bind = websocket_bind;
+        }
+        if let Some(remote_receive) = &cli.remote_receive {
+            self.remote_receive = Some(remote_receive.clone());
+        }
+        if let Some(remote_token) = cli.remote_token.clone() {
+            self.remote_token = remote_token;
+        }
+        if let Some(portal_shortcuts) = cli.portal_shortcuts {
+            self.portal_shortcuts = portal_shortcuts;
+        }
+        if let Some(stats_enabled) = cli.stats_enabled {
+            self.stats_enabled = stats_enabled;
+        }
+        if let Some(stats_retention_days) = cli.stats_retention_days {
+            self.stats_retention_days = stats_retention_days;
+        }
+        if let Some(stats_path) = &cli.stats_path {
+            self.stats_path = Some(stats_path.clone());
+        }
+        if let Some(speed_widget) = cli.speed_widget {
+            self.speed_widget = speed_widget;
+        }
+        if let Some(session_summary) = cli.session_summary {
+            self.session_summary = session_summary;
+        }
+        if let Some(stats_categories_only) = cli.stats_categories_only {
+            self.stats_categories_only = stats_categories_only;
+        }
+        if let Some(max_refresh_hz) = cli.max_refresh_hz {
+            self.max_refresh_hz = max_refresh_hz;
+        }
+        if let Some(lock_indicator) = cli.lock_indicator {
+            self.lock_indicator = lock_indicator;
+        }
+        if let Some(show_composed) = cli.show_composed {
+            self.show_composed = show_composed;
+        }
+        if let Some(screen_reader) = cli.screen_reader {
+            self.screen_reader = screen_reader;
+        }
+        if let Some(palette) = cli.palette {
+            self.palette = palette;
+        }
+        if let Some(presenter_hotkey) = cli.presenter_hotkey.clone() {
+            self.presenter_hotkey = presenter_hotkey;
+        }
+        if let Some(visual_bell_style) = cli.visual_bell_style {
+            self.visual_bell_style = visual_bell_style;
+        }
+        if let Some(modifier_panel) = cli.modifier_panel {
+            self.modifier_panel = modifier_panel;
+        }
+        if let Some(teaching_mode) = cli.teaching_mode {
+            self.teaching_mode = teaching_mode;
+        }
+        if let Some(language) = cli.language.clone() {
+            self.language = language;
+        }
+        if let Some(layout_badge) = cli.layout_badge {
+            self.layout_badge = layout_badge;
+        }
+        if let Some(capture_dir) = &cli.capture_dir {
+            self.capture_dir = Some(capture_dir.clone());
+        }
+        if let Some(frame_stream) = &cli.frame_stream {
+            self.frame_stream = Some(frame_stream.clone());
+        }
+        if let Some(frame_stream_width) = cli.frame_stream_width {
+            self.frame_stream_width = frame_stream_width;
+        }
+        if let Some(frame_stream_height) = cli.frame_stream_height {
+            self.frame_stream_height = frame_stream_height;
+        }
+        if let Some(obs_websocket) = &cli.obs_websocket {
+            self.obs_websocket = Some(obs_websocket.clone());
+        }
+        if let Some(obs_password) = cli.obs_password.clone() {
+            self.obs_password = obs_password;
+        }
+        if let Some(obs_recording) = cli.obs_recording {
+            self.obs_recording = obs_recording;
+        }
+        if let Some(obs_streaming) = cli.obs_streaming {
+            self.obs_streaming = obs_streaming;
+        }
+        if let Some(screenshot_hotkey) = cli.screenshot_hotkey.clone() {
+            self.screenshot_hotkey = screenshot_hotkey;
+        }
+        if let Some(marker_hotkey) = cli.marker_hotkey.clone() {
+            self.marker_hotkey = marker_hotkey;
+        }
+        if let Some(marker_file) = &cli.marker_file {
+            self.marker_file = Some(marker_file.clone());
+        }
+        if let Some(latency_debug) = cli.latency_debug {
+            self.latency_debug = latency_debug;
+        }
+        if let Some(shortcut_tally) = cli.shortcut_tally {
+            self.shortcut_tally = shortcut_tally;
+        }
+        if !cli.active_hours.is_empty() {
+            self.active_hours = cli.active_hours.clone();
+        }
+        if let Some(peek_key) = cli.peek_key.clone() {
+            self.peek_key = peek_key;
+        }
+        if let Some(layer) = cli.layer {
+            self.layer = layer;
+        }
+        if let Some(namespace) = cli.namespace.clone() {
+            self.namespace = namespace;
+        }
+        if let Some(bubble_align) = cli.bubble_align {
+            self.bubble_align = bubble_align;
+        }
+        if let Some(bubble_layout) = cli.bubble_layout {
+            self.bubble_layout = bubble_layout;
+        }
+        if let Some(combo_format) = cli.combo_format.clone() {
+            self.combo_format = combo_format;
+        }
+        if let Some(combo_separator) = cli.combo_separator.clone() {
+            self.combo_separator = combo_separator;
+        }
+        if let Some(separator_spaces) = cli.separator_spaces {
+            self.separator_spaces = separator_spaces;
+        }
+        if let Some(notation) = cli.notation {
+            self.notation = notation;
+        }
+        if let Some(lock_announcements) = cli.lock_announcements {
+            self.lock_announcements = lock_announcements;
+        }
+        if let Some(mouse_drag) = cli.mouse_drag {
+            self.mouse_drag = mouse_drag;
+        }
+        if let Some(frequency_emphasis) = cli.frequency_emphasis {
+            self.frequency_emphasis = frequency_emphasis;
+        }
+        if let Some(ghost_preview) = cli.ghost_preview {
+            self.ghost_preview = ghost_preview;
+        }
+        if let Some(practice_file) = &cli.practice_file {
+            self.practice_file = Some(practice_file.clone());
+        }
+        if let Some(break_reminder_minutes) = cli.break_reminder_minutes {
+            self.break_reminder_minutes = break_reminder_minutes;
+        }
+        if let Some(break_idle_minutes) = cli.break_idle_minutes {
+            self.break_idle_minutes = break_idle_minutes;
+        }
+        if let Some(confirm_quit) = cli.confirm_quit {
+            self.confirm_quit = confirm_quit;
+        }
+        if let Some(restore_state) = cli.restore_state {
+            self.restore_state = restore_state;
+        }
+        if let Some(listener_helper) = cli.listener_helper {
+            self.listener_helper = listener_helper;
+        }
+        if let Some(listener_helper_path) = &cli.listener_helper_path {
+            self.listener_helper_path = Some(listener_helper_path.clone());
+        }
+        if let Some(sandbox_listener) = cli.sandbox_listener {
+            self.sandbox_listener = sandbox_listener;
+        }
+        if let Some(autostart) = cli.autostart {
+            self.autostart = autostart;
+        }
+        if let Some(ime_text) = cli.ime_text {
+            self.ime_text = ime_text;
+        }
+        if let Some(settings_backend) = cli.settings_backend {
+            self.settings_backend = settings_backend;
+        }
     }
+
+    /// Presenter mode: large bubbles centered along the bottom edge that stay
+    /// up longer and only show shortcuts. Applied on top of the active profile
+    /// and never saved, so turning it off restores everything.
+    pub fn apply_presenter(&mut self) {
+        self.position = Position::BottomCenter;
+        self.bubble_align = BubbleAlign::Auto;
+        self.ttl_ms = (self.ttl_ms * 2).max(PRESENTER_MIN_TTL_MS);
+        self.display_mode = DisplayMode::ShortcutsOnly;
+    }
+
     pub fn save_to(&self, path: &Path) -> Result<()> {
         if let Some(parent) = path.parent() {
             fs::create_dir_all(parent)
@@ -208,7 +1278,23 @@ impl Settings {
     }
 }
 
-fn default_config_path() -> PathBuf {
+fn default_secure_input_rules() -> Vec<String> {
+    [
+        "class:polkit",
+        "class:pinentry",
+        "class:gcr-prompter",
+        "class:ssh-askpass",
+        "title:password",
+        "title:passphrase",
+        "title:sign in",
+        "title:log in",
+    ]
+    .iter()
+    .map(|s| s.to_string())
+    .collect()
+}
+
+pub fn default_config_path() -> PathBuf {
     if let Some(dir) = dirs::config_dir() {
         dir.join("keyway-visualizer").join("config.toml")
     } else {
@@ -1,10 +1,15 @@
-use crate::settings::{Position, Settings};
+use crate::app_filter;
+use crate::combo_format::{self, ComboFormat};
+use crate::settings::{DisplayMode, Palette, Position, ScreencastMode, Settings, Theme};
 use crate::hotkey::Hotkey;
+use crate::i18n::tr;
 use gtk4::prelude::*;
 use gtk4::{
     Adjustment, Application, ApplicationWindow, Box as GtkBox, Button, DropDown, Entry, Grid,
     Label, Orientation, ScrolledWindow, SpinButton, StringList, Switch, TextBuffer, TextView,
 };
+use std::cell::RefCell;
+use std::rc::Rc;
 
 const POSITIONS: [&str; 8] = [
     "bottom-right",
@@ -17,6 +22,15 @@ const POSITIONS: [&str; 8] = [
     "custom",
 ];
 
+const THEMES: [&str; 3] = ["dark", "light", "auto"];
+
+const PALETTES: [&str; 4] = ["default", "deuteranopia", "protanopia", "tritanopia"];
+
+const DISPLAY_MODES: [&str; 3] = ["all", "shortcuts-only", "modifier-gated"];
+
+const SCREENCAST_MODES: [&str; 4] =
+    ["ignore", "show-while-casting", "hide-while-casting", "mask-while-casting"];
+
 pub struct SettingsWindow {
     pub window: ApplicationWindow,
     position: DropDown,
@@ -32,6 +46,26 @@ pub struct SettingsWindow {
     custom_y: SpinButton,
     app_filter_enabled: Switch,
     disabled_apps: TextView,
+    pick_button: Button,
+    theme: DropDown,
+    palette: DropDown,
+    display_mode: DropDown,
+    combo_separator: Entry,
+    separator_spaces: Switch,
+    /// `combo_format`, which the separator preview is spelled with
+    format_template: Rc<RefCell<String>>,
+    pause_on_lock: Switch,
+    screencast_mode: DropDown,
+    privacy_mask: Switch,
+    secure_input_enabled: Switch,
+    secure_input_rules: TextView,
+    hide_on_fullscreen: Switch,
+    filter_indicator: Switch,
+    stats_enabled: Switch,
+    stats_categories_only: Switch,
+    stats_retention_days: SpinButton,
+    clear_stats_button: Button,
+    fix_access_button: Button,
     status: Label,
     apply_button: Button,
     save_button: Button,
@@ -42,7 +76,7 @@ impl SettingsWindow {
     pub fn new(app: &Application) -> Self {
         let window = ApplicationWindow::builder()
             .application(app)
-            .title("Keyway Visualizer Settings")
+            .title(tr("Keyway Visualizer Settings"))
             .default_width(420)
             .default_height(320)
             .build();
@@ -77,27 +111,138 @@ impl SettingsWindow {
         disabled_apps.set_wrap_mode(gtk4::WrapMode::WordChar);
         disabled_apps.set_vexpand(true);
         disabled_apps.set_tooltip_text(Some(
-            "One entry per line. Matches app class or title (case-insensitive).",
+            "One entry per line. Matches app class or title (case-insensitive).\n\
+             Prefix with class: or title: to match one field only.\n\
+             Patterns starting with ^ or ending with $ are regexes; * and ? are globs.",
         ));
 
+        let theme = DropDown::new(Some(StringList::new(&THEMES)), None::<&gtk4::Expression>);
+        let palette = DropDown::new(Some(StringList::new(&PALETTES)), None::<&gtk4::Expression>);
+        palette.set_tooltip_text(Some(
+            "Status, lock and speed bubble colors that stay distinct with color blindness.",
+        ));
+        let display_mode =
+            DropDown::new(Some(StringList::new(&DISPLAY_MODES)), None::<&gtk4::Expression>);
+
+        let combo_separator = Entry::new();
+        combo_separator.set_width_chars(4);
+        combo_separator.set_tooltip_text(Some("Between the keys of a combo, such as + or -."));
+        let separator_spaces = Switch::new();
+        separator_spaces.set_valign(gtk4::Align::Center);
+        let separator_preview = Label::new(None);
+        separator_preview.add_css_class("dim-label");
+        let format_template = Rc::new(RefCell::new(String::new()));
+        let separator_box = GtkBox::new(Orientation::Horizontal, 8);
+        separator_box.append(&combo_separator);
+        separator_box.append(&Label::new(Some(tr("Spaces"))));
+        separator_box.append(&separator_spaces);
+        separator_box.append(&separator_preview);
+        {
+            let update = {
+                let (entry, spaces) = (combo_separator.clone(), separator_spaces.clone());
+                let template = Rc::clone(&format_template);
+                move || {
+                    let text = separator_preview_text(
+                        &entry.text(),
+                        spaces.is_active(),
+                        &template.borrow(),
+                    );
+                    separator_preview.set_text(&text);
+                }
+            };
+            let update = Rc::new(update);
+            let on_change = Rc::clone(&update);
+            combo_separator.connect_changed(move |_| on_change());
+            separator_spaces.connect_active_notify(move |_| update());
+        }
+
+        let pause_on_lock = Switch::new();
+        let screencast_mode =
+            DropDown::new(Some(StringList::new(&SCREENCAST_MODES)), None::<&gtk4::Expression>);
+
         let disabled_scroll = ScrolledWindow::builder()
             .min_content_height(90)
             .child(&disabled_apps)
             .build();
 
-        attach_row(&grid, 0, "Position", &position);
-        attach_row(&grid, 1, "Margin", &margin);
-        attach_row(&grid, 2, "Max items", &max_items);
-        attach_row(&grid, 3, "TTL (ms)", &ttl_ms);
-        attach_row(&grid, 4, "Show mouse", &show_mouse);
-        attach_row(&grid, 5, "Pause hotkey", &pause_hotkey);
-        attach_row(&grid, 6, "Repeat coalesce (ms)", &repeat_coalesce_ms);
-        attach_row(&grid, 7, "Modifier grace (ms)", &modifier_grace_ms);
-        attach_row(&grid, 8, "Drag mode", &drag_enabled);
-        attach_row(&grid, 9, "Custom X", &custom_x);
-        attach_row(&grid, 10, "Custom Y", &custom_y);
-        attach_row(&grid, 11, "App filter", &app_filter_enabled);
-        attach_row(&grid, 12, "Disabled apps", &disabled_scroll);
+        let pick_button = Button::with_label(tr("Pick window"));
+        pick_button.set_tooltip_text(Some(
+            "Focus another window after clicking to add its class to the list.",
+        ));
+        pick_button.set_halign(gtk4::Align::Start);
+        let disabled_box = GtkBox::new(Orientation::Vertical, 6);
+        disabled_box.append(&disabled_scroll);
+        disabled_box.append(&pick_button);
+
+        let privacy_mask = Switch::new();
+        let secure_input_enabled = Switch::new();
+        let secure_buffer = TextBuffer::new(None::<&gtk4::TextTagTable>);
+        let secure_input_rules = TextView::with_buffer(&secure_buffer);
+        secure_input_rules.set_monospace(true);
+        secure_input_rules.set_wrap_mode(gtk4::WrapMode::WordChar);
+        secure_input_rules.set_tooltip_text(Some(
+            "Apps that mask typed keys while focused. Same syntax as disabled apps.",
+        ));
+
+        let secure_scroll = ScrolledWindow::builder()
+            .min_content_height(90)
+            .child(&secure_input_rules)
+            .build();
+        let hide_on_fullscreen = Switch::new();
+        let filter_indicator = Switch::new();
+        filter_indicator.set_tooltip_text(Some(
+            "Show a small dot in the overlay corner while the app filter hides it.",
+        ));
+
+        attach_row(&grid, 0, tr("Position"), &position);
+        attach_row(&grid, 1, tr("Margin"), &margin);
+        attach_row(&grid, 2, tr("Max items"), &max_items);
+        attach_row(&grid, 3, tr("TTL (ms)"), &ttl_ms);
+        attach_row(&grid, 4, tr("Show mouse"), &show_mouse);
+        attach_row(&grid, 5, tr("Pause hotkey"), &pause_hotkey);
+        attach_row(&grid, 6, tr("Repeat coalesce (ms)"), &repeat_coalesce_ms);
+        attach_row(&grid, 7, tr("Modifier grace (ms)"), &modifier_grace_ms);
+        attach_row(&grid, 8, tr("Drag mode"), &drag_enabled);
+        attach_row(&grid, 9, tr("Custom X"), &custom_x);
+        attach_row(&grid, 10, tr("Custom Y"), &custom_y);
+        attach_row(&grid, 11, tr("App filter"), &app_filter_enabled);
+        attach_row(&grid, 12, tr("Disabled apps"), &disabled_box);
+        attach_row(&grid, 13, tr("Theme"), &theme);
+        attach_row(&grid, 14, tr("Palette"), &palette);
+        attach_row(&grid, 15, tr("Display mode"), &display_mode);
+        attach_row(&grid, 16, tr("Key separator"), &separator_box);
+        attach_row(&grid, 17, tr("Pause when locked"), &pause_on_lock);
+        attach_row(&grid, 18, tr("Screencast"), &screencast_mode);
+        attach_row(&grid, 19, tr("Privacy mask"), &privacy_mask);
+        attach_row(&grid, 20, tr("Mask secure input"), &secure_input_enabled);
+        attach_row(&grid, 21, tr("Secure input apps"), &secure_scroll);
+        attach_row(&grid, 22, tr("Hide over fullscreen"), &hide_on_fullscreen);
+        attach_row(&grid, 23, tr("Filter indicator dot"), &filter_indicator);
+
+        let stats_enabled = Switch::new();
+        stats_enabled.set_tooltip_text(Some(
+            "Count keys and combos in a local database for the heatmap and reports.",
+        ));
+        let stats_categories_only = Switch::new();
+        stats_categories_only.set_tooltip_text(Some(
+            "Record only letter/number/modifier/shortcut totals, not which keys.",
+        ));
+        let stats_retention_days = spin_i32(365, 0, 3650, 1);
+        stats_retention_days.set_tooltip_text(Some("0 keeps statistics forever."));
+        let clear_stats_button = Button::with_label(tr("Clear statistics"));
+        clear_stats_button.set_halign(gtk4::Align::Start);
+
+        attach_row(&grid, 24, tr("Statistics"), &stats_enabled);
+        attach_row(&grid, 25, tr("Key categories only"), &stats_categories_only);
+        attach_row(&grid, 26, tr("Keep statistics (days)"), &stats_retention_days);
+        attach_row(&grid, 27, "", &clear_stats_button);
+
+        let fix_access_button = Button::with_label(tr("Fix input access..."));
+        fix_access_button.set_halign(gtk4::Align::Start);
+        fix_access_button.set_tooltip_text(Some(
+            "Set up read access to /dev/input when no keys are captured.",
+        ));
+        attach_row(&grid, 28, tr("Input devices"), &fix_access_button);
 
         let status = Label::new(None);
         status.set_wrap(true);
@@ -105,9 +250,9 @@ impl SettingsWindow {
         status.add_css_class("dim-label");
 
         let button_row = GtkBox::new(Orientation::Horizontal, 8);
-        let apply_button = Button::with_label("Apply");
-        let save_button = Button::with_label("Save");
-        let close_button = Button::with_label("Close");
+        let apply_button = Button::with_label(tr("Apply"));
+        let save_button = Button::with_label(tr("Save"));
+        let close_button = Button::with_label(tr("Close"));
 
         button_row.append(&apply_button);
         button_row.append(&save_button);
@@ -134,6 +279,25 @@ impl SettingsWindow {
             custom_y,
             app_filter_enabled,
             disabled_apps,
+            pick_button,
+            theme,
+            palette,
+            display_mode,
+            combo_separator,
+            separator_spaces,
+            format_template,
+            pause_on_lock,
+            screencast_mode,
+            privacy_mask,
+            secure_input_enabled,
+            secure_input_rules,
+            hide_on_fullscreen,
+            filter_indicator,
+            stats_enabled,
+            stats_categories_only,
+            stats_retention_days,
+            clear_stats_button,
+            fix_access_button,
             status,
             apply_button,
             save_button,
@@ -165,6 +329,30 @@ impl SettingsWindow {
         self.disabled_apps
             .buffer()
             .set_text(&disabled_text);
+        self.theme.set_selected(theme_to_index(settings.theme));
+        self.palette.set_selected(palette_to_index(settings.palette));
+        self.display_mode
+            .set_selected(display_mode_to_index(settings.display_mode));
+        *self.format_template.borrow_mut() = settings.combo_format.clone();
+        self.separator_spaces.set_active(settings.separator_spaces);
+        self.combo_separator.set_text(&settings.combo_separator);
+        self.pause_on_lock.set_active(settings.pause_on_lock);
+        self.screencast_mode
+            .set_selected(screencast_mode_to_index(settings.screencast_mode));
+        self.privacy_mask.set_active(settings.privacy_mask);
+        self.secure_input_enabled
+            .set_active(settings.secure_input_enabled);
+        self.secure_input_rules
+            .buffer()
+            .set_text(&settings.secure_input_rules.join("\n"));
+        self.hide_on_fullscreen
+            .set_active(settings.hide_on_fullscreen);
+        self.filter_indicator.set_active(settings.filter_indicator);
+        self.stats_enabled.set_active(settings.stats_enabled);
+        self.stats_categories_only
+            .set_active(settings.stats_categories_only);
+        self.stats_retention_days
+            .set_value(settings.stats_retention_days as f64);
         self.set_status("");
     }
 
@@ -183,6 +371,21 @@ impl SettingsWindow {
             custom_y: self.custom_y.value() as i32,
             app_filter_enabled: self.app_filter_enabled.is_active(),
             disabled_apps: read_text_lines(&self.disabled_apps),
+            theme: index_to_theme(self.theme.selected()),
+            palette: index_to_palette(self.palette.selected()),
+            display_mode: index_to_display_mode(self.display_mode.selected()),
+            combo_separator: self.combo_separator.text().to_string(),
+            separator_spaces: self.separator_spaces.is_active(),
+            pause_on_lock: self.pause_on_lock.is_active(),
+            screencast_mode: index_to_screencast_mode(self.screencast_mode.selected()),
+            privacy_mask: self.privacy_mask.is_active(),
+            secure_input_enabled: self.secure_input_enabled.is_active(),
+            secure_input_rules: read_text_lines(&self.secure_input_rules),
+            hide_on_fullscreen: self.hide_on_fullscreen.is_active(),
+            filter_indicator: self.filter_indicator.is_active(),
+            stats_enabled: self.stats_enabled.is_active(),
+            stats_categories_only: self.stats_categories_only.is_active(),
+            stats_retention_days: self.stats_retention_days.value() as u32,
             ..base.clone()
         }
     }
@@ -191,6 +394,8 @@ impl SettingsWindow {
         self.pause_hotkey.remove_css_class("error");
         self.custom_x.remove_css_class("error");
         self.custom_y.remove_css_class("error");
+        self.disabled_apps.remove_css_class("error");
+        self.secure_input_rules.remove_css_class("error");
 
         if Hotkey::parse(&settings.pause_hotkey).is_err() {
             self.pause_hotkey.add_css_class("error");
@@ -204,6 +409,18 @@ impl SettingsWindow {
             return Err("Custom position must be non-negative".to_string());
         }
 
+        let (_, errors) = app_filter::compile_rules(&settings.disabled_apps);
+        if let Some(first) = errors.into_iter().next() {
+            self.disabled_apps.add_css_class("error");
+            return Err(format!("Invalid app filter entry {}", first));
+        }
+
+        let (_, errors) = app_filter::compile_rules(&settings.secure_input_rules);
+        if let Some(first) = errors.into_iter().next() {
+            self.secure_input_rules.add_css_class("error");
+            return Err(format!("Invalid secure input entry {}", first));
+        }
+
         Ok(())
     }
 
@@ -215,6 +432,30 @@ impl SettingsWindow {
         self.save_button.connect_clicked(move |_| callback());
     }
 
+    pub fn connect_pick<F: Fn() + 'static>(&self, callback: F) {
+        self.pick_button.connect_clicked(move |_| callback());
+    }
+
+    pub fn connect_clear_stats<F: Fn() + 'static>(&self, callback: F) {
+        self.clear_stats_button.connect_clicked(move |_| callback());
+    }
+
+    pub fn connect_fix_access<F: Fn() + 'static>(&self, callback: F) {
+        self.fix_access_button.connect_clicked(move |_| callback());
+    }
+
+    /// Adds an entry to the disabled apps editor unless it is already listed.
+    /// The change takes effect on Apply/Save like manual edits.
+    pub fn add_disabled_app(&self, entry: &str) -> bool {
+        let mut lines = read_text_lines(&self.disabled_apps);
+        if lines.iter().any(|line| line.eq_ignore_ascii_case(entry)) {
+            return false;
+        }
+        lines.push(entry.to_string());
+        self.disabled_apps.buffer().set_text(&lines.join("\n"));
+        true
+    }
+
     pub fn connect_close<F: Fn() + 'static>(&self, callback: F) {
         self.close_button.connect_clicked(move |_| callback());
     }
@@ -254,6 +495,25 @@ fn read_text_lines(view: &TextView) -> Vec<String> {
         .collect()
 }
 
+/// "Ctrl+Shift+T" as the overlay would spell it with these settings.
+fn separator_preview_text(separator: &str, spaces: bool, template: &str) -> String {
+    let settings = Settings {
+        combo_format: template.to_string(),
+        combo_separator: separator.to_string(),
+        separator_spaces: spaces,
+        ..Settings::default()
+    };
+    // A broken template is reported on Apply; preview the separator alone.
+    let format = ComboFormat::from_settings(&settings).unwrap_or_else(|_| {
+        ComboFormat::from_settings(&Settings {
+            combo_format: String::new(),
+            ..settings
+        })
+        .unwrap_or_default()
+    });
+    combo_format::spell("Ctrl+Shift+T", format.as_ref(), 1)
+}
+
 fn spin_i32(value: i32, min: i32, max: i32, step: i32) -> SpinButton {
     let adjustment = Adjustment::new(value as f64, min as f64, max as f64, step as f64, 10.0, 0.0);
     SpinButton::new(Some(&adjustment), 1.0, 0)
@@ -291,3 +551,71 @@ fn index_to_position(index: u32) -> Position {
         _ => Position::BottomRight,
     }
 }
+
+fn theme_to_index(theme: Theme) -> u32 {
+    match theme {
+        Theme::Dark => 0,
+        Theme::Light => 1,
+        Theme::Auto => 2,
+    }
+}
+
+fn index_to_theme(index: u32) -> Theme {
+    match index {
+        1 => Theme::Light,
+        2 => Theme::Auto,
+        _ => Theme::Dark,
+    }
+}
+
+fn palette_to_index(palette: Palette) -> u32 {
+    match palette {
+        Palette::Default => 0,
+        Palette::Deuteranopia => 1,
+        Palette::Protanopia => 2,
+        Palette::Tritanopia => 3,
+    }
+}
+
+fn index_to_palette(index: u32) -> Palette {
+    match index {
+        1 => Palette::Deuteranopia,
+        2 => Palette::Protanopia,
+        3 => Palette::Tritanopia,
+        _ => Palette::Default,
+    }
+}
+
+fn display_mode_to_index(mode: DisplayMode) -> u32 {
+    match mode {
+        DisplayMode::All => 0,
+        DisplayMode::ShortcutsOnly => 1,
+        DisplayMode::ModifierGated => 2,
+    }
+}
+
+fn index_to_display_mode(index: u32) -> DisplayMode {
+    match index {
+        1 => DisplayMode::ShortcutsOnly,
+        2 => DisplayMode::ModifierGated,
+        _ => DisplayMode::All,
+    }
+}
+
+fn screencast_mode_to_index(mode: ScreencastMode) -> u32 {
+    match mode {
+        ScreencastMode::Ignore => 0,
+        ScreencastMode::ShowWhileCasting => 1,
+        ScreencastMode::HideWhileCasting => 2,
+        ScreencastMode::MaskWhileCasting => 3,
+    }
+}
+
+fn index_to_screencast_mode(index: u32) -> ScreencastMode {
+    match index {
+        1 => ScreencastMode::ShowWhileCasting,
+        2 => ScreencastMode::HideWhileCasting,
+        3 => ScreencastMode::MaskWhileCasting,
+        _ => ScreencastMode::Ignore,
+    }
+}
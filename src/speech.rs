@@ -0,0 +1,96 @@
+use crate::combo::combo_parts;
+use async_channel::{Receiver, Sender};
+use std::process::{Command, Stdio};
+use std::thread;
+use tracing::{debug, warn};
+
+/// Spoken names for labels a speech synthesizer would read badly.
+const SPOKEN_NAMES: &[(&str, &str)] = &[
+    ("Ctrl", "Control"),
+    ("Esc", "Escape"),
+    ("Del", "Delete"),
+    ("Ins", "Insert"),
+    ("PgUp", "Page Up"),
+    ("PgDn", "Page Down"),
+    ("Bksp", "Backspace"),
+    ("↑", "Up"),
+    ("↓", "Down"),
+    ("←", "Left"),
+    ("→", "Right"),
+    ("+", "Plus"),
+    ("-", "Minus"),
+    (".", "Period"),
+    (",", "Comma"),
+    ("/", "Slash"),
+];
+
+/// Reads displayed combos aloud through speech-dispatcher (`spd-say`), the
+/// speech service screen readers such as Orca talk through, so announcements
+/// use the voice and rate the user already has.
+///
+/// Combos typed faster than they can be spoken are skipped in favour of the
+/// newest one. Dropping the announcer stops the speaking thread.
+pub struct Announcer {
+    tx: Sender<String>,
+}
+
+impl Announcer {
+    pub fn start() -> Self {
+        let (tx, rx) = async_channel::bounded(32);
+        thread::Builder::new()
+            .name("speech".to_string())
+            .spawn(move || speak_loop(rx))
+            .expect("Failed to start speech thread");
+        Self { tx }
+    }
+
+    pub fn announce(&self, text: &str) {
+        let _ = self.tx.try_send(spoken_text(text));
+    }
+}
+
+fn speak_loop(rx: Receiver<String>) {
+    while let Ok(mut text) = rx.recv_blocking() {
+        while let Ok(newer) = rx.try_recv() {
+            text = newer;
+        }
+        debug!("Announcing {:?}", text);
+        let status = Command::new("spd-say")
+            .args(["--wait", "--application-name", "keyway", "--priority", "message", "--"])
+            .arg(&text)
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status();
+        if let Err(e) = status {
+            warn!("Screen reader announcements stopped, spd-say failed: {}", e);
+            return;
+        }
+    }
+}
+
+/// "Ctrl+Shift+PgUp" becomes "Control Shift Page Up".
+fn spoken_text(text: &str) -> String {
+    combo_parts(text)
+        .into_iter()
+        .map(|part| {
+            SPOKEN_NAMES
+                .iter()
+                .find(|(label, _)| *label == part)
+                .map_or(part, |(_, spoken)| *spoken)
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn combos_are_spelled_out() {
+        assert_eq!(spoken_text("Ctrl+Shift+PgUp"), "Control Shift Page Up");
+        assert_eq!(spoken_text("Ctrl++"), "Control Plus");
+        assert_eq!(spoken_text("+"), "Plus");
+        assert_eq!(spoken_text("é"), "é");
+    }
+}
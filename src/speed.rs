@@ -0,0 +1,84 @@
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+/// Shortest span a rate is averaged over, so the first few keys do not
+/// read as a burst of several hundred per minute.
+const MIN_SPAN: Duration = Duration::from_secs(5);
+
+/// Rolling per-minute rate of recorded events.
+pub struct RateMeter {
+    window: Duration,
+    events: VecDeque<Instant>,
+}
+
+impl RateMeter {
+    pub fn new(window: Duration) -> Self {
+        Self {
+            window,
+            events: VecDeque::new(),
+        }
+    }
+
+    pub fn record(&mut self, now: Instant) {
+        self.events.push_back(now);
+        self.prune(now);
+    }
+
+    /// Events per minute over the window, falling to zero once input stops.
+    pub fn per_minute(&mut self, now: Instant) -> f64 {
+        self.prune(now);
+        let Some(oldest) = self.events.front() else {
+            return 0.0;
+        };
+        let span = now
+            .duration_since(*oldest)
+            .clamp(MIN_SPAN, self.window)
+            .as_secs_f64();
+        self.events.len() as f64 * 60.0 / span
+    }
+
+    /// True once every recorded event has aged out of the window, so the
+    /// rate stays at zero until something new is recorded.
+    pub fn is_settled(&self, now: Instant) -> bool {
+        self.events
+            .back()
+            .is_none_or(|last| now.duration_since(*last) > self.window)
+    }
+
+    fn prune(&mut self, now: Instant) {
+        while let Some(front) = self.events.front() {
+            if now.duration_since(*front) > self.window {
+                self.events.pop_front();
+            } else {
+                break;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rate_is_scaled_to_a_minute_and_decays() {
+        let start = Instant::now();
+        let mut meter = RateMeter::new(Duration::from_secs(20));
+        for i in 0..50 {
+            meter.record(start + Duration::from_millis(200 * i));
+        }
+
+        // 50 keys over 10 seconds.
+        let rate = meter.per_minute(start + Duration::from_secs(10));
+        assert!((rate - 300.0).abs() < 1.0, "{}", rate);
+
+        // A single key is averaged over the minimum span.
+        let mut meter = RateMeter::new(Duration::from_secs(20));
+        meter.record(start);
+        assert_eq!(meter.per_minute(start), 12.0);
+
+        assert!(!meter.is_settled(start + Duration::from_secs(10)));
+        assert_eq!(meter.per_minute(start + Duration::from_secs(30)), 0.0);
+        assert!(meter.is_settled(start + Duration::from_secs(30)));
+    }
+}
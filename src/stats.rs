@@ -0,0 +1,461 @@
+use crate::combo::ComboEvent;
+use crate::xkb::is_modifier;
+use anyhow::{bail, Context, Result};
+use evdev::Key;
+use rusqlite::{params, Connection};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+use tracing::{info, warn};
+
+pub const FLUSH_INTERVAL: Duration = Duration::from_secs(10);
+const SCHEMA_VERSION: i32 = 2;
+
+/// Opt-in key and combo counters in a local SQLite database.
+///
+/// Counts are bucketed per local day, so reports and retention work on a
+/// few rows per day instead of individual keystrokes. Recording only bumps
+/// in-memory counters; they are written in one transaction every few
+/// seconds. Counts are attributed to the focused app when a compositor
+/// focus backend reports one, and to `""` otherwise.
+pub struct StatsStore {
+    conn: Connection,
+    path: PathBuf,
+    pending_keys: HashMap<(String, String), u64>,
+    pending_combos: HashMap<(String, String, &'static str), u64>,
+    last_flush: Instant,
+    categories_only: bool,
+    app: String,
+}
+
+impl StatsStore {
+    pub fn open(path: &Path) -> Result<Self> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)
+                .with_context(|| format!("Failed to create stats dir: {:?}", dir))?;
+        }
+        let conn = Connection::open(path)
+            .with_context(|| format!("Failed to open stats database {:?}", path))?;
+        migrate(&conn).context("Failed to prepare stats database")?;
+        info!("Recording statistics to {:?}", path);
+
+        Ok(Self {
+            conn,
+            path: path.to_path_buf(),
+            pending_keys: HashMap::new(),
+            pending_combos: HashMap::new(),
+            last_flush: Instant::now(),
+            categories_only: false,
+            app: String::new(),
+        })
+    }
+
+    /// Opens a database for reports, without creating one.
+    pub fn open_existing(path: &Path) -> Result<Self> {
+        if !path.exists() {
+            bail!(
+                "No statistics at {:?}; set stats_enabled = true to start recording",
+                path
+            );
+        }
+        Self::open(path)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Records `letter`, `modifier`, `shortcut`... instead of the exact key
+    /// or combo, for users who want totals without a keylog.
+    pub fn set_categories_only(&mut self, categories_only: bool) {
+        self.categories_only = categories_only;
+    }
+
+    /// App class the following keys and combos are counted for.
+    pub fn set_app(&mut self, app: Option<&str>) {
+        self.app.clear();
+        self.app.push_str(app.unwrap_or_default());
+    }
+
+    pub fn record_key(&mut self, key: Key) {
+        let name = if self.categories_only {
+            key_category(key).to_string()
+        } else {
+            key_name(key)
+        };
+        *self
+            .pending_keys
+            .entry((self.app.clone(), name))
+            .or_default() += 1;
+    }
+
+    pub fn record_combo(&mut self, combo: &ComboEvent) {
+        let kind = combo.kind.name();
+        let text = if self.categories_only {
+            kind.to_string()
+        } else {
+            combo.text.clone()
+        };
+        *self
+            .pending_combos
+            .entry((self.app.clone(), text, kind))
+            .or_default() += 1;
+    }
+
+    /// Deletes every recorded count, including ones not yet written.
+    pub fn clear(&mut self) -> Result<()> {
+        self.pending_keys.clear();
+        self.pending_combos.clear();
+        self.conn
+            .execute_batch("DELETE FROM key_counts; DELETE FROM combo_counts; VACUUM;")
+            .context("Failed to clear statistics")?;
+        info!("Cleared statistics in {:?}", self.path);
+        Ok(())
+    }
+
+    /// Called from the event pump; writes pending counts when due.
+    pub fn tick(&mut self) {
+        if self.last_flush.elapsed() >= FLUSH_INTERVAL {
+            self.flush();
+        }
+    }
+
+    pub fn has_pending(&self) -> bool {
+        !self.pending_keys.is_empty() || !self.pending_combos.is_empty()
+    }
+
+    pub fn flush(&mut self) {
+        self.last_flush = Instant::now();
+        if self.pending_keys.is_empty() && self.pending_combos.is_empty() {
+            return;
+        }
+        if let Err(e) = self.write_pending() {
+            // Keep the counts and try again on the next flush.
+            warn!("Failed to write statistics to {:?}: {}", self.path, e);
+            return;
+        }
+        self.pending_keys.clear();
+        self.pending_combos.clear();
+    }
+
+    fn write_pending(&mut self) -> rusqlite::Result<()> {
+        let tx = self.conn.transaction()?;
+        {
+            let mut insert_key = tx.prepare_cached(
+                "INSERT INTO key_counts (day, app, key, count)
+                 VALUES (date('now', 'localtime'), ?1, ?2, ?3)
+                 ON CONFLICT (day, app, key) DO UPDATE SET count = count + excluded.count",
+            )?;
+            for ((app, key), count) in &self.pending_keys {
+                insert_key.execute(params![app, key, count])?;
+            }
+
+            let mut insert_combo = tx.prepare_cached(
+                "INSERT INTO combo_counts (day, app, combo, kind, count)
+                 VALUES (date('now', 'localtime'), ?1, ?2, ?3, ?4)
+                 ON CONFLICT (day, app, combo) DO UPDATE SET count = count + excluded.count",
+            )?;
+            for ((app, combo, kind), count) in &self.pending_combos {
+                insert_combo.execute(params![app, combo, kind, count])?;
+            }
+        }
+        tx.commit()
+    }
+
+    /// Presses per key over the last `days` days including today, or all
+    /// time when `None`, optionally for one app. Pending counts are written
+    /// first.
+    pub fn key_totals(
+        &mut self,
+        days: Option<u32>,
+        app: Option<&str>,
+    ) -> Result<HashMap<String, u64>> {
+        self.flush();
+        let since = days.map(since_modifier);
+        let mut query = self.conn.prepare_cached(
+            "SELECT key, SUM(count) FROM key_counts
+             WHERE (?1 IS NULL OR day >= date('now', 'localtime', ?1))
+               AND (?2 IS NULL OR app = ?2 COLLATE NOCASE)
+             GROUP BY key",
+        )?;
+        let rows = query.query_map(params![since, app], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, i64>(1)? as u64))
+        })?;
+        Ok(rows.collect::<rusqlite::Result<_>>()?)
+    }
+
+    /// Most used combos over the last `days` days as (text, kind, count).
+    pub fn top_combos(
+        &mut self,
+        days: Option<u32>,
+        app: Option<&str>,
+        limit: usize,
+    ) -> Result<Vec<(String, String, u64)>> {
+        self.flush();
+        let since = days.map(since_modifier);
+        let mut query = self.conn.prepare_cached(
+            "SELECT combo, kind, SUM(count) AS total FROM combo_counts
+             WHERE (?1 IS NULL OR day >= date('now', 'localtime', ?1))
+               AND (?2 IS NULL OR app = ?2 COLLATE NOCASE)
+             GROUP BY combo ORDER BY total DESC, combo LIMIT ?3",
+        )?;
+        let rows = query.query_map(params![since, app, limit as i64], |row| {
+            Ok((row.get(0)?, row.get(1)?, row.get::<_, i64>(2)? as u64))
+        })?;
+        Ok(rows.collect::<rusqlite::Result<_>>()?)
+    }
+
+    /// Apps with the most combos over the last `days` days. Keys typed
+    /// without a known focused app are left out.
+    pub fn top_apps(&mut self, days: Option<u32>, limit: usize) -> Result<Vec<(String, u64)>> {
+        self.flush();
+        let since = days.map(since_modifier);
+        let mut query = self.conn.prepare_cached(
+            "SELECT app, SUM(count) AS total FROM combo_counts
+             WHERE (?1 IS NULL OR day >= date('now', 'localtime', ?1)) AND app != ''
+             GROUP BY app ORDER BY total DESC, app LIMIT ?2",
+        )?;
+        let rows = query.query_map(params![since, limit as i64], |row| {
+            Ok((row.get(0)?, row.get::<_, i64>(1)? as u64))
+        })?;
+        Ok(rows.collect::<rusqlite::Result<_>>()?)
+    }
+
+    /// Key and combo totals per period over the last `days` days, built from
+    /// the daily rows. `period_format` is an SQLite `strftime` format such
+    /// as `%Y-%m-%d` or `%Y-W%W`.
+    pub fn period_totals(
+        &mut self,
+        period_format: &str,
+        days: Option<u32>,
+        app: Option<&str>,
+    ) -> Result<Vec<PeriodTotal>> {
+        self.flush();
+        let since = days.map(since_modifier);
+        let mut query = self.conn.prepare_cached(
+            "SELECT strftime(?1, day) AS period, app, 'key', key, SUM(count) FROM key_counts
+             WHERE (?2 IS NULL OR day >= date('now', 'localtime', ?2))
+               AND (?3 IS NULL OR app = ?3 COLLATE NOCASE)
+             GROUP BY period, app, key
+             UNION ALL
+             SELECT strftime(?1, day) AS period, app, 'combo', combo, SUM(count)
+             FROM combo_counts
+             WHERE (?2 IS NULL OR day >= date('now', 'localtime', ?2))
+               AND (?3 IS NULL OR app = ?3 COLLATE NOCASE)
+             GROUP BY period, app, combo
+             ORDER BY 1, 2, 3, 5 DESC, 4",
+        )?;
+        let rows = query.query_map(params![period_format, since, app], |row| {
+            Ok(PeriodTotal {
+                period: row.get(0)?,
+                app: row.get(1)?,
+                kind: row.get(2)?,
+                name: row.get(3)?,
+                count: row.get::<_, i64>(4)? as u64,
+            })
+        })?;
+        Ok(rows.collect::<rusqlite::Result<_>>()?)
+    }
+
+    /// Drops days older than the retention window; 0 keeps everything.
+    pub fn prune(&self, retention_days: u32) {
+        if retention_days == 0 {
+            return;
+        }
+        let cutoff = format!("-{} days", retention_days);
+        let result = self
+            .conn
+            .execute(
+                "DELETE FROM key_counts WHERE day < date('now', 'localtime', ?1)",
+                [&cutoff],
+            )
+            .and_then(|_| {
+                self.conn.execute(
+                    "DELETE FROM combo_counts WHERE day < date('now', 'localtime', ?1)",
+                    [&cutoff],
+                )
+            });
+        if let Err(e) = result {
+            warn!("Failed to prune statistics: {}", e);
+        }
+    }
+}
+
+/// One row of an aggregated export.
+#[derive(Debug, Serialize)]
+pub struct PeriodTotal {
+    pub period: String,
+    /// Focused app class, empty when unknown
+    pub app: String,
+    /// `key` or `combo`
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub name: String,
+    pub count: u64,
+}
+
+impl Drop for StatsStore {
+    fn drop(&mut self) {
+        self.flush();
+    }
+}
+
+/// SQLite date modifier for the first day of a `days`-long window.
+fn since_modifier(days: u32) -> String {
+    format!("-{} days", days.saturating_sub(1))
+}
+
+fn migrate(conn: &Connection) -> rusqlite::Result<()> {
+    let version: i32 = conn.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+    if version >= SCHEMA_VERSION {
+        return Ok(());
+    }
+    if version < 1 {
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS key_counts (
+                 day TEXT NOT NULL,
+                 key TEXT NOT NULL,
+                 count INTEGER NOT NULL,
+                 PRIMARY KEY (day, key)
+             );
+             CREATE TABLE IF NOT EXISTS combo_counts (
+                 day TEXT NOT NULL,
+                 combo TEXT NOT NULL,
+                 kind TEXT NOT NULL,
+                 count INTEGER NOT NULL,
+                 PRIMARY KEY (day, combo)
+             );",
+        )?;
+    }
+    if version < 2 {
+        // Per-app attribution: the app joins the primary key, so the tables
+        // are rebuilt and older counts land under the unknown app.
+        conn.execute_batch(
+            "BEGIN;
+             CREATE TABLE key_counts_v2 (
+                 day TEXT NOT NULL,
+                 app TEXT NOT NULL DEFAULT '',
+                 key TEXT NOT NULL,
+                 count INTEGER NOT NULL,
+                 PRIMARY KEY (day, app, key)
+             );
+             INSERT INTO key_counts_v2 (day, key, count) SELECT day, key, count FROM key_counts;
+             DROP TABLE key_counts;
+             ALTER TABLE key_counts_v2 RENAME TO key_counts;
+             CREATE TABLE combo_counts_v2 (
+                 day TEXT NOT NULL,
+                 app TEXT NOT NULL DEFAULT '',
+                 combo TEXT NOT NULL,
+                 kind TEXT NOT NULL,
+                 count INTEGER NOT NULL,
+                 PRIMARY KEY (day, app, combo)
+             );
+             INSERT INTO combo_counts_v2 (day, combo, kind, count)
+                 SELECT day, combo, kind, count FROM combo_counts;
+             DROP TABLE combo_counts;
+             ALTER TABLE combo_counts_v2 RENAME TO combo_counts;
+             COMMIT;",
+        )?;
+    }
+    conn.pragma_update(None, "user_version", SCHEMA_VERSION)
+}
+
+/// Layout-independent key name: the evdev code without its `KEY_` prefix
+/// (`A`, `LEFTCTRL`), mouse buttons keep theirs (`BTN_LEFT`).
+pub fn key_name(key: Key) -> String {
+    let name = format!("{:?}", key);
+    match name.strip_prefix("KEY_") {
+        Some(stripped) => stripped.to_string(),
+        None => name,
+    }
+}
+
+fn key_category(key: Key) -> &'static str {
+    if is_modifier(key) {
+        return "modifier";
+    }
+    let name = key_name(key);
+    let digit = name.strip_prefix("KP").unwrap_or(&name);
+    if name.starts_with("BTN_") {
+        "mouse"
+    } else if name.len() == 1 && name.chars().all(|c| c.is_ascii_alphabetic()) {
+        "letter"
+    } else if digit.len() == 1 && digit.chars().all(|c| c.is_ascii_digit()) {
+        "number"
+    } else if name.len() > 1 && name.starts_with('F') && name[1..].parse::<u8>().is_ok() {
+        "function"
+    } else {
+        "other"
+    }
+}
+
+/// `~/.local/share/keyway-visualizer/stats.sqlite3`
+pub fn default_stats_path() -> PathBuf {
+    dirs::data_dir()
+        .unwrap_or_else(|| PathBuf::from("."))
+        .join("keyway-visualizer")
+        .join("stats.sqlite3")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::combo::ComboKind;
+
+    #[test]
+    fn counts_accumulate_per_day() {
+        let dir = std::env::temp_dir().join(format!("keyway-stats-{}", std::process::id()));
+        let path = dir.join("stats.sqlite3");
+        let _ = fs::remove_file(&path);
+
+        let mut store = StatsStore::open(&path).unwrap();
+        let save = ComboEvent {
+            text: "Ctrl+S".to_string(),
+            kind: ComboKind::Shortcut,
+        };
+        store.record_key(Key::KEY_S);
+        store.record_key(Key::KEY_S);
+        store.record_combo(&save);
+        store.flush();
+        store.set_app(Some("code"));
+        store.record_key(Key::KEY_S);
+        store.record_combo(&save);
+        store.flush();
+
+        let key_count: i64 = store
+            .conn
+            .query_row("SELECT SUM(count) FROM key_counts WHERE key = 'S'", [], |row| {
+                row.get(0)
+            })
+            .unwrap();
+        let combo_rows: i64 = store
+            .conn
+            .query_row("SELECT COUNT(*) FROM combo_counts", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(key_count, 3);
+        assert_eq!(combo_rows, 2);
+        assert_eq!(store.key_totals(Some(1), None).unwrap().get("S"), Some(&3));
+        assert_eq!(store.key_totals(None, Some("Code")).unwrap().get("S"), Some(&1));
+        assert_eq!(store.top_apps(None, 5).unwrap(), vec![("code".to_string(), 1)]);
+
+        let weekly = store.period_totals("%Y-W%W", None, None).unwrap();
+        assert_eq!(weekly.len(), 4);
+        assert!(weekly.iter().any(|row| row.app == "code" && row.kind == "combo"));
+
+        drop(store);
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn key_categories() {
+        assert_eq!(key_category(Key::KEY_Q), "letter");
+        assert_eq!(key_category(Key::KEY_7), "number");
+        assert_eq!(key_category(Key::KEY_KP7), "number");
+        assert_eq!(key_category(Key::KEY_LEFTSHIFT), "modifier");
+        assert_eq!(key_category(Key::KEY_F11), "function");
+        assert_eq!(key_category(Key::BTN_LEFT), "mouse");
+        assert_eq!(key_category(Key::KEY_SPACE), "other");
+    }
+}
@@ -0,0 +1,209 @@
+use crate::session_log::csv_field;
+use crate::settings::{default_config_path, Settings};
+use crate::stats::{self, StatsStore};
+use anyhow::{bail, Result};
+use clap::{Parser, Subcommand, ValueEnum};
+use serde_json::json;
+use std::path::PathBuf;
+
+/// Reports from the statistics database.
+#[derive(Parser, Debug, Clone)]
+pub struct StatsArgs {
+    #[command(subcommand)]
+    pub action: StatsAction,
+}
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum StatsAction {
+    /// Most used combos and keys
+    Top {
+        /// Time window: `today`, `7d`, `4w` or `all`
+        #[arg(long, default_value = "all")]
+        since: String,
+        /// Rows per table
+        #[arg(long, default_value_t = 10)]
+        limit: usize,
+        /// Only count keys and combos typed in this app (window class)
+        #[arg(long)]
+        app: Option<String>,
+        /// Print JSON instead of tables
+        #[arg(long)]
+        json: bool,
+    },
+    /// Per-day or per-week totals for charting in other tools
+    Export {
+        #[arg(long, value_enum, default_value = "csv")]
+        format: ExportFormat,
+        #[arg(long, value_enum, default_value = "day")]
+        period: Period,
+        /// Time window: `today`, `7d`, `4w` or `all`
+        #[arg(long, default_value = "all")]
+        since: String,
+        /// Only export this app (window class)
+        #[arg(long)]
+        app: Option<String>,
+    },
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy)]
+pub enum ExportFormat {
+    Csv,
+    Json,
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy)]
+pub enum Period {
+    Day,
+    Week,
+}
+
+/// `config` and `stats_path` come from the top-level options, so the
+/// command reads the same database the overlay writes.
+pub fn run(args: &StatsArgs, config: Option<PathBuf>, stats_path: Option<PathBuf>) -> Result<()> {
+    let path = stats_path
+        .or_else(|| {
+            let config = config.unwrap_or_else(default_config_path);
+            Settings::read_from(&config).ok()?.stats_path
+        })
+        .unwrap_or_else(stats::default_stats_path);
+    let mut store = StatsStore::open_existing(&path)?;
+
+    match &args.action {
+        StatsAction::Top {
+            since,
+            limit,
+            app,
+            json,
+        } => {
+            let days = parse_since(since)?;
+            let app = app.as_deref();
+            let combos = store.top_combos(days, app, *limit)?;
+            let mut keys: Vec<(String, u64)> =
+                store.key_totals(days, app)?.into_iter().collect();
+            keys.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+            keys.truncate(*limit);
+            // Without a filter, list the apps so one can be picked.
+            let apps = match app {
+                Some(_) => Vec::new(),
+                None => store.top_apps(days, *limit)?,
+            };
+
+            if *json {
+                let value = json!({
+                    "since_days": days,
+                    "app": app,
+                    "apps": apps
+                        .iter()
+                        .map(|(app, count)| json!({"app": app, "combos": count}))
+                        .collect::<Vec<_>>(),
+                    "combos": combos
+                        .iter()
+                        .map(|(text, kind, count)| json!({"text": text, "kind": kind, "count": count}))
+                        .collect::<Vec<_>>(),
+                    "keys": keys
+                        .iter()
+                        .map(|(key, count)| json!({"key": key, "count": count}))
+                        .collect::<Vec<_>>(),
+                });
+                println!("{}", value);
+            } else {
+                let scope = match app {
+                    Some(app) => format!("{}, {}", describe_since(days), app),
+                    None => describe_since(days),
+                };
+                println!("Top combos ({})", scope);
+                println!("{:>8}  {:<9} COMBO", "COUNT", "KIND");
+                for (text, kind, count) in &combos {
+                    println!("{:>8}  {:<9} {}", count, kind, text);
+                }
+                println!();
+                println!("Top keys ({})", scope);
+                println!("{:>8}  KEY", "COUNT");
+                for (key, count) in &keys {
+                    println!("{:>8}  {}", count, key);
+                }
+                if !apps.is_empty() {
+                    println!();
+                    println!("Top apps by combos ({})", scope);
+                    println!("{:>8}  APP", "COUNT");
+                    for (app, count) in &apps {
+                        println!("{:>8}  {}", count, app);
+                    }
+                }
+            }
+        }
+        StatsAction::Export {
+            format,
+            period,
+            since,
+            app,
+        } => {
+            let period_format = match period {
+                Period::Day => "%Y-%m-%d",
+                Period::Week => "%Y-W%W",
+            };
+            let rows = store.period_totals(period_format, parse_since(since)?, app.as_deref())?;
+            match format {
+                ExportFormat::Json => println!("{}", serde_json::to_string(&rows)?),
+                ExportFormat::Csv => {
+                    println!("period,app,type,name,count");
+                    for row in &rows {
+                        println!(
+                            "{},{},{},{},{}",
+                            row.period,
+                            csv_field(&row.app),
+                            row.kind,
+                            csv_field(&row.name),
+                            row.count
+                        );
+                    }
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Days covered by a `--since` value; `None` is all time.
+fn parse_since(value: &str) -> Result<Option<u32>> {
+    let value = value.trim().to_ascii_lowercase();
+    match value.as_str() {
+        "all" => return Ok(None),
+        "today" => return Ok(Some(1)),
+        _ => {}
+    }
+
+    let (number, unit) = match value.char_indices().last() {
+        Some((index, 'd')) => (&value[..index], 1),
+        Some((index, 'w')) => (&value[..index], 7),
+        _ => (value.as_str(), 1),
+    };
+    match number.parse::<u32>() {
+        Ok(n) if n > 0 => Ok(Some(n.saturating_mul(unit))),
+        _ => bail!("Invalid --since {:?} (use today, 7d, 4w or all)", value),
+    }
+}
+
+fn describe_since(days: Option<u32>) -> String {
+    match days {
+        None => "all time".to_string(),
+        Some(1) => "today".to_string(),
+        Some(days) => format!("last {} days", days),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn since_values() {
+        assert_eq!(parse_since("all").unwrap(), None);
+        assert_eq!(parse_since("today").unwrap(), Some(1));
+        assert_eq!(parse_since("7d").unwrap(), Some(7));
+        assert_eq!(parse_since("2w").unwrap(), Some(14));
+        assert_eq!(parse_since("30").unwrap(), Some(30));
+        assert!(parse_since("0d").is_err());
+        assert!(parse_since("soon").is_err());
+    }
+}
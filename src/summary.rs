@@ -0,0 +1,116 @@
+use crate::combo::{unix_millis, ComboEvent, ComboKind};
+use anyhow::{Context, Result};
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+const TOP_SHORTCUTS: usize = 10;
+
+/// Running totals for the summary written when the overlay quits.
+pub struct SessionSummary {
+    started: Instant,
+    keys: u64,
+    clicks: u64,
+    shortcuts: HashMap<String, u64>,
+}
+
+impl Default for SessionSummary {
+    fn default() -> Self {
+        Self {
+            started: Instant::now(),
+            keys: 0,
+            clicks: 0,
+            shortcuts: HashMap::new(),
+        }
+    }
+}
+
+impl SessionSummary {
+    pub fn record_key(&mut self) {
+        self.keys += 1;
+    }
+
+    pub fn record_click(&mut self) {
+        self.clicks += 1;
+    }
+
+    pub fn record_combo(&mut self, combo: &ComboEvent) {
+        if combo.kind == ComboKind::Shortcut {
+            *self.shortcuts.entry(combo.text.clone()).or_default() += 1;
+        }
+    }
+
+    /// Markdown, so it can be pasted into show notes as is.
+    pub fn render(&self) -> String {
+        render(self.started.elapsed(), self.keys, self.clicks, &self.shortcuts)
+    }
+
+    /// Writes `summary-<unix time>.md` into `dir` and returns its path.
+    pub fn write_to_dir(&self, dir: &Path) -> Result<PathBuf> {
+        fs::create_dir_all(dir).with_context(|| format!("Failed to create {:?}", dir))?;
+        let path = dir.join(format!("summary-{}.md", unix_millis() / 1000));
+        fs::write(&path, self.render()).with_context(|| format!("Failed to write {:?}", path))?;
+        Ok(path)
+    }
+}
+
+fn render(
+    duration: Duration,
+    keys: u64,
+    clicks: u64,
+    shortcuts: &HashMap<String, u64>,
+) -> String {
+    let secs = duration.as_secs();
+    let mut out = String::new();
+    let _ = writeln!(out, "# Keyway session summary\n");
+    let _ = writeln!(
+        out,
+        "- Duration: {}h {:02}m {:02}s",
+        secs / 3600,
+        secs / 60 % 60,
+        secs % 60
+    );
+    let _ = writeln!(out, "- Keys: {}", keys);
+    let _ = writeln!(out, "- Mouse clicks: {}", clicks);
+
+    let top = top_shortcuts(shortcuts, TOP_SHORTCUTS);
+    if !top.is_empty() {
+        let _ = writeln!(out, "\n## Top shortcuts\n");
+        for (index, (text, count)) in top.iter().enumerate() {
+            let _ = writeln!(out, "{}. `{}` ({})", index + 1, text, count);
+        }
+    }
+    out
+}
+
+/// The `limit` most used shortcuts, ties in alphabetical order.
+pub fn top_shortcuts(shortcuts: &HashMap<String, u64>, limit: usize) -> Vec<(String, u64)> {
+    let mut top: Vec<(&String, &u64)> = shortcuts.iter().collect();
+    top.sort_by(|a, b| b.1.cmp(a.1).then_with(|| a.0.cmp(b.0)));
+    top.into_iter()
+        .take(limit)
+        .map(|(text, count)| (text.clone(), *count))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn summary_lists_top_shortcuts_by_count() {
+        let shortcuts = HashMap::from([
+            ("Ctrl+S".to_string(), 3),
+            ("Ctrl+C".to_string(), 7),
+            ("Alt+Tab".to_string(), 3),
+        ]);
+        let text = render(Duration::from_secs(3725), 420, 12, &shortcuts);
+
+        assert!(text.contains("- Duration: 1h 02m 05s"));
+        assert!(text.contains("- Keys: 420"));
+        assert!(text.contains("- Mouse clicks: 12"));
+        assert!(text.contains("1. `Ctrl+C` (7)\n2. `Alt+Tab` (3)\n3. `Ctrl+S` (3)"));
+    }
+}
@@ -1,13 +1,22 @@
+use crate::i18n::tr;
 use async_channel::Sender;
-use ksni::{menu::StandardItem, Icon, MenuItem, Tray, TrayService};
+use ksni::menu::{CheckmarkItem, StandardItem, SubMenu};
+use ksni::{Icon, MenuItem, Tray, TrayService};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex};
 use tracing::{error, info};
 
 #[derive(Debug, Clone)]
 pub enum TrayAction {
     TogglePause,
+    TogglePresenter,
     OpenSettings,
+    OpenHeatmap,
+    OpenRolloverTest,
+    ClearStats,
     ToggleDrag,
+    /// Mute or unmute the input devices with this name
+    ToggleDevice(String),
     Quit,
 }
 
@@ -15,6 +24,10 @@ pub enum TrayAction {
 pub struct TrayState {
     pub paused: bool,
     pub drag_enabled: bool,
+    pub presenter: bool,
+    pub filter_suppressed: bool,
+    /// Devices being listened to, and whether each is muted
+    pub devices: Vec<(String, bool)>,
 }
 
 struct VisualizerTray {
@@ -36,21 +49,34 @@ impl Tray for VisualizerTray {
     }
 
     fn icon_pixmap(&self) -> Vec<Icon> {
-        generate_icon_pixmap()
+        let suppressed = self
+            .state
+            .lock()
+            .map(|s| s.filter_suppressed)
+            .unwrap_or(false);
+        generate_icon_pixmap(suppressed)
     }
 
     fn tool_tip(&self) -> ksni::ToolTip {
         let status = self
             .state
             .lock()
-            .map(|s| if s.paused { "Paused" } else { "Running" })
-            .unwrap_or("Running");
+            .map(|s| {
+                if s.paused {
+                    tr("Paused")
+                } else if s.filter_suppressed {
+                    tr("Hidden for this app")
+                } else {
+                    tr("Running")
+                }
+            })
+            .unwrap_or_else(|_| tr("Running"));
 
         ksni::ToolTip {
             icon_name: String::new(),
             icon_pixmap: Vec::new(),
             title: "Keyway Visualizer".to_string(),
-            description: format!("Status: {}", status),
+            description: format!("{} {}", tr("Status:"), status),
         }
     }
 
@@ -58,16 +84,24 @@ impl Tray for VisualizerTray {
         let pause_label = self
             .state
             .lock()
-            .map(|s| if s.paused { "Resume" } else { "Pause" })
-            .unwrap_or("Pause");
+            .map(|s| tr(if s.paused { "Resume" } else { "Pause" }))
+            .unwrap_or_else(|_| tr("Pause"));
 
         let drag_label = self
             .state
             .lock()
-            .map(|s| if s.drag_enabled { "Disable Drag" } else { "Enable Drag" })
-            .unwrap_or("Enable Drag");
+            .map(|s| tr(if s.drag_enabled { "Disable Drag" } else { "Enable Drag" }))
+            .unwrap_or_else(|_| tr("Enable Drag"));
 
-        vec![
+        let presenter_label = self
+            .state
+            .lock()
+            .map(|s| tr(if s.presenter { "Exit Presenter Mode" } else { "Presenter Mode" }))
+            .unwrap_or_else(|_| tr("Presenter Mode"));
+
+        let devices = self.state.lock().map(|s| s.devices.clone()).unwrap_or_default();
+
+        let mut menu = vec![
             MenuItem::Standard(StandardItem {
                 label: pause_label.to_string(),
                 activate: Box::new(|tray: &mut Self| {
@@ -86,9 +120,27 @@ impl Tray for VisualizerTray {
                 }),
                 ..Default::default()
             }),
+            MenuItem::Standard(StandardItem {
+                label: presenter_label.to_string(),
+                activate: Box::new(|tray: &mut Self| {
+                    if let Err(e) = tray.action_sender.send_blocking(TrayAction::TogglePresenter) {
+                        error!("Failed to send tray action: {}", e);
+                    }
+                }),
+                ..Default::default()
+            }),
+        ];
+        if !devices.is_empty() {
+            menu.push(MenuItem::SubMenu(SubMenu {
+                label: tr("Devices").to_string(),
+                submenu: devices.into_iter().map(device_item).collect(),
+                ..Default::default()
+            }));
+        }
+        menu.extend([
             MenuItem::Separator,
             MenuItem::Standard(StandardItem {
-                label: "Settings".to_string(),
+                label: tr("Settings").to_string(),
                 activate: Box::new(|tray: &mut Self| {
                     if let Err(e) = tray.action_sender.send_blocking(TrayAction::OpenSettings) {
                         error!("Failed to send tray action: {}", e);
@@ -96,9 +148,37 @@ impl Tray for VisualizerTray {
                 }),
                 ..Default::default()
             }),
+            MenuItem::Standard(StandardItem {
+                label: tr("Heatmap").to_string(),
+                activate: Box::new(|tray: &mut Self| {
+                    if let Err(e) = tray.action_sender.send_blocking(TrayAction::OpenHeatmap) {
+                        error!("Failed to send tray action: {}", e);
+                    }
+                }),
+                ..Default::default()
+            }),
+            MenuItem::Standard(StandardItem {
+                label: tr("Rollover Test").to_string(),
+                activate: Box::new(|tray: &mut Self| {
+                    let action = TrayAction::OpenRolloverTest;
+                    if let Err(e) = tray.action_sender.send_blocking(action) {
+                        error!("Failed to send tray action: {}", e);
+                    }
+                }),
+                ..Default::default()
+            }),
+            MenuItem::Standard(StandardItem {
+                label: tr("Clear Statistics").to_string(),
+                activate: Box::new(|tray: &mut Self| {
+                    if let Err(e) = tray.action_sender.send_blocking(TrayAction::ClearStats) {
+                        error!("Failed to send tray action: {}", e);
+                    }
+                }),
+                ..Default::default()
+            }),
             MenuItem::Separator,
             MenuItem::Standard(StandardItem {
-                label: "Quit".to_string(),
+                label: tr("Quit").to_string(),
                 activate: Box::new(|tray: &mut Self| {
                     if let Err(e) = tray.action_sender.send_blocking(TrayAction::Quit) {
                         error!("Failed to send tray action: {}", e);
@@ -106,18 +186,41 @@ impl Tray for VisualizerTray {
                 }),
                 ..Default::default()
             }),
-        ]
+        ]);
+        menu
     }
 }
 
-fn generate_icon_pixmap() -> Vec<Icon> {
+/// A device in the Devices submenu, checked while its input is shown.
+fn device_item((name, muted): (String, bool)) -> MenuItem<VisualizerTray> {
+    MenuItem::Checkmark(CheckmarkItem {
+        // A single underscore would mark an access key.
+        label: name.replace('_', "__"),
+        checked: !muted,
+        activate: Box::new(move |tray: &mut VisualizerTray| {
+            let action = TrayAction::ToggleDevice(name.clone());
+            if let Err(e) = tray.action_sender.send_blocking(action) {
+                error!("Failed to send tray action: {}", e);
+            }
+        }),
+        ..Default::default()
+    })
+}
+
+/// Grey keyboard, tinted amber while the app filter hides the overlay.
+fn generate_icon_pixmap(suppressed: bool) -> Vec<Icon> {
     let size = 22;
     let mut data = Vec::with_capacity(size * size * 4);
+    let color = if suppressed {
+        (230, 240, 170, 40)
+    } else {
+        (220, 128, 128, 128)
+    };
 
     for y in 0..size {
         for x in 0..size {
             let (a, r, g, b) = if is_keyboard_pixel(x, y, size) {
-                (220, 128, 128, 128)
+                color
             } else {
                 (0, 0, 0, 0)
             };
@@ -159,25 +262,55 @@ fn is_keyboard_pixel(x: usize, y: usize, size: usize) -> bool {
     on_body_outline || on_key
 }
 
+/// Setters only record the new state; `flush` sends it to the tray host in
+/// a single update, so several changes in one pump run do not make the menu
+/// flicker.
 #[derive(Clone)]
 pub struct TrayHandle {
     service_handle: ksni::Handle<VisualizerTray>,
     state: Arc<Mutex<TrayState>>,
+    dirty: Arc<AtomicBool>,
 }
 
 impl TrayHandle {
     pub fn set_paused(&self, paused: bool) {
-        if let Ok(mut state) = self.state.lock() {
-            state.paused = paused;
-        }
-        self.service_handle.update(|_| {});
+        self.modify(|state| std::mem::replace(&mut state.paused, paused) != paused);
     }
 
     pub fn set_drag_enabled(&self, enabled: bool) {
+        self.modify(|state| std::mem::replace(&mut state.drag_enabled, enabled) != enabled);
+    }
+
+    pub fn set_presenter(&self, presenter: bool) {
+        self.modify(|state| std::mem::replace(&mut state.presenter, presenter) != presenter);
+    }
+
+    pub fn set_devices(&self, devices: Vec<(String, bool)>) {
+        self.modify(|state| {
+            let changed = state.devices != devices;
+            state.devices = devices;
+            changed
+        });
+    }
+
+    pub fn set_filter_suppressed(&self, suppressed: bool) {
+        self.modify(|state| std::mem::replace(&mut state.filter_suppressed, suppressed) != suppressed);
+    }
+
+    /// Pushes the changes made since the last flush, if any.
+    pub fn flush(&self) {
+        if self.dirty.swap(false, Ordering::SeqCst) {
+            self.service_handle.update(|_| {});
+        }
+    }
+
+    /// `change` returns whether it changed anything.
+    fn modify(&self, change: impl FnOnce(&mut TrayState) -> bool) {
         if let Ok(mut state) = self.state.lock() {
-            state.drag_enabled = enabled;
+            if change(&mut state) {
+                self.dirty.store(true, Ordering::SeqCst);
+            }
         }
-        self.service_handle.update(|_| {});
     }
 }
 
@@ -194,6 +327,7 @@ pub fn start_tray() -> anyhow::Result<(async_channel::Receiver<TrayAction>, Tray
     let handle = TrayHandle {
         service_handle: service.handle(),
         state,
+        dirty: Arc::new(AtomicBool::new(false)),
     };
 
     service.spawn();
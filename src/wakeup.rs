@@ -0,0 +1,103 @@
+use async_channel::{Receiver, Sender};
+use gtk4::glib::{self, Priority, SourceId};
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+/// Runs the event pump on the GTK main loop when something needs it, instead
+/// of on a fixed timer. Wakes that arrive before the pump gets to run are
+/// folded into a single run.
+#[derive(Clone, Default)]
+pub struct PumpWaker {
+    inner: Rc<WakerInner>,
+}
+
+#[derive(Default)]
+struct WakerInner {
+    scheduled: Cell<bool>,
+    pump: RefCell<Option<Box<dyn Fn()>>>,
+    timer: RefCell<Option<(Instant, SourceId)>>,
+}
+
+impl PumpWaker {
+    pub fn set_pump<F: Fn() + 'static>(&self, pump: F) {
+        *self.inner.pump.borrow_mut() = Some(Box::new(pump));
+    }
+
+    pub fn wake(&self) {
+        if self.inner.scheduled.replace(true) {
+            return;
+        }
+        let inner = Rc::clone(&self.inner);
+        glib::idle_add_local_full(Priority::DEFAULT, move || {
+            inner.scheduled.set(false);
+            if let Some(pump) = inner.pump.borrow().as_ref() {
+                pump();
+            }
+            glib::ControlFlow::Break
+        });
+    }
+
+    /// Wakes the pump after `delay` unless an earlier timed wake is pending.
+    pub fn wake_after(&self, delay: Duration) {
+        let deadline = Instant::now() + delay;
+        let mut timer = self.inner.timer.borrow_mut();
+        if timer.as_ref().is_some_and(|(at, _)| *at <= deadline) {
+            return;
+        }
+        if let Some((_, source)) = timer.take() {
+            source.remove();
+        }
+        let waker = self.clone();
+        let source = glib::timeout_add_local_once(delay, move || {
+            waker.inner.timer.borrow_mut().take();
+            waker.wake();
+        });
+        *timer = Some((deadline, source));
+    }
+}
+
+/// Items from a worker thread, read on the main thread by the pump. A local
+/// task waits for the next item and wakes the pump; it holds at most that one
+/// item, so a full channel still pushes back on the worker. Dropping the
+/// inbox closes the channel, which is how the worker learns it should stop.
+pub struct Inbox<T> {
+    rx: Receiver<T>,
+    next: Rc<RefCell<Option<T>>>,
+    taken: Sender<()>,
+}
+
+impl<T: 'static> Inbox<T> {
+    pub fn new(rx: Receiver<T>, waker: &PumpWaker) -> Self {
+        let next: Rc<RefCell<Option<T>>> = Rc::default();
+        let (taken, taken_rx) = async_channel::bounded(1);
+        let task_rx = rx.clone();
+        let task_next = Rc::clone(&next);
+        let waker = waker.clone();
+        glib::MainContext::default().spawn_local(async move {
+            while let Ok(item) = task_rx.recv().await {
+                *task_next.borrow_mut() = Some(item);
+                waker.wake();
+                if taken_rx.recv().await.is_err() {
+                    break;
+                }
+            }
+        });
+        Self { rx, next, taken }
+    }
+
+    pub fn pop(&self) -> Option<T> {
+        if let Some(item) = self.next.borrow_mut().take() {
+            let _ = self.taken.try_send(());
+            return Some(item);
+        }
+        self.rx.try_recv().ok()
+    }
+}
+
+impl<T> Drop for Inbox<T> {
+    fn drop(&mut self) {
+        self.rx.close();
+        self.taken.close();
+    }
+}
@@ -0,0 +1,220 @@
+use gtk4::glib::translate::ToGlibPtr;
+use gtk4::prelude::*;
+use gtk4::{cairo, gdk, ApplicationWindow};
+use std::cell::Cell;
+use std::os::raw::c_ulong;
+use std::rc::Rc;
+use tracing::{info, warn};
+use x11rb::connection::Connection;
+use x11rb::protocol::xproto::{
+    ChangeWindowAttributesAux, ConfigureWindowAux, ConnectionExt as _, StackMode,
+};
+use x11rb::rust_connection::RustConnection;
+
+extern "C" {
+    // From GTK's X11 backend; gtk4-rs has no binding for it.
+    fn gdk_x11_surface_get_xid(surface: *mut gdk::ffi::GdkSurface) -> c_ulong;
+}
+
+/// Which monitor edges a window sits against and how far from each, as a
+/// layer-shell surface would be anchored. A window anchored to both or
+/// neither of two opposite edges is centered between them.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Anchor {
+    pub top: bool,
+    pub bottom: bool,
+    pub left: bool,
+    pub right: bool,
+    /// Top, bottom, left and right margins in logical pixels
+    pub margins: [i32; 4],
+}
+
+impl Anchor {
+    /// Top-left corner for a `size` window on a monitor at `monitor`
+    /// (x, y, width, height).
+    pub fn place(&self, monitor: (i32, i32, i32, i32), size: (i32, i32)) -> (i32, i32) {
+        let [top, bottom, left, right] = self.margins;
+        let (mx, my, mw, mh) = monitor;
+        let along = |start: bool, end: bool, origin: i32, room: i32, size: i32, before, after| {
+            match (start, end) {
+                (true, false) => origin + before,
+                (false, true) => origin + room - size - after,
+                _ => origin + (room - size) / 2,
+            }
+        };
+        (
+            along(self.left, self.right, mx, mw, size.0, left, right),
+            along(self.top, self.bottom, my, mh, size.1, top, bottom),
+        )
+    }
+}
+
+/// An undecorated window floated above everything on an X11 display, where
+/// layer shell is not available: override-redirect, so the window manager
+/// neither frames nor moves it, and placed by its `Anchor` whenever its size
+/// changes.
+pub struct FloatingWindow {
+    window: ApplicationWindow,
+    anchor: Rc<Cell<Anchor>>,
+    click_through: Rc<Cell<bool>>,
+    conn: Rc<RustConnection>,
+}
+
+impl FloatingWindow {
+    /// `None` unless the display is X11. Call before the window is realized.
+    pub fn new(window: &ApplicationWindow) -> Option<Self> {
+        let display = gtk4::prelude::WidgetExt::display(window);
+        if display.type_().name() != "GdkX11Display" {
+            return None;
+        }
+        let conn = match x11rb::connect(None) {
+            Ok((conn, _)) => Rc::new(conn),
+            Err(e) => {
+                warn!("Failed to connect to the X server for overlay placement: {}", e);
+                return None;
+            }
+        };
+        let anchor: Rc<Cell<Anchor>> = Rc::default();
+        let click_through = Rc::new(Cell::new(true));
+
+        let realize_conn = Rc::clone(&conn);
+        let realize_anchor = Rc::clone(&anchor);
+        let realize_click_through = Rc::clone(&click_through);
+        window.connect_realize(move |window| {
+            let Some(surface) = window.surface() else {
+                return;
+            };
+            let Some(xid) = surface_xid(&surface) else {
+                return;
+            };
+            // Has to reach the server before GTK maps the window, over
+            // GTK's own connection; the round trip makes sure it did.
+            let attributes = ChangeWindowAttributesAux::new().override_redirect(1);
+            let set = realize_conn
+                .change_window_attributes(xid, &attributes)
+                .and_then(|_| realize_conn.get_input_focus())
+                .map(|cookie| cookie.reply());
+            if let Err(e) = set {
+                warn!("Failed to float the overlay above other windows: {}", e);
+                return;
+            }
+            info!("Overlay floats as an override-redirect X11 window");
+            set_input_region(&surface, realize_click_through.get());
+
+            let conn = Rc::clone(&realize_conn);
+            let anchor = Rc::clone(&realize_anchor);
+            surface.connect_layout(move |surface, width, height| {
+                place(&conn, surface, anchor.get(), (width, height));
+            });
+        });
+
+        Some(Self {
+            window: window.clone(),
+            anchor,
+            click_through,
+            conn,
+        })
+    }
+
+    pub fn set_anchor(&self, anchor: Anchor) {
+        self.anchor.set(anchor);
+        if let Some(surface) = self.window.surface() {
+            place(&self.conn, &surface, anchor, (surface.width(), surface.height()));
+        }
+    }
+
+    /// Lets clicks through to the windows below, as a layer-shell overlay
+    /// without keyboard focus does; off while the overlay is being dragged.
+    pub fn set_click_through(&self, enabled: bool) {
+        self.click_through.set(enabled);
+        if let Some(surface) = self.window.surface() {
+            set_input_region(&surface, enabled);
+        }
+    }
+}
+
+fn set_input_region(surface: &gdk::Surface, click_through: bool) {
+    let region = if click_through {
+        cairo::Region::create()
+    } else {
+        let everything = cairo::RectangleInt::new(0, 0, i32::MAX / 2, i32::MAX / 2);
+        cairo::Region::create_rectangle(&everything)
+    };
+    surface.set_input_region(&region);
+}
+
+fn surface_xid(surface: &gdk::Surface) -> Option<u32> {
+    if surface.type_().name() != "GdkX11Surface" {
+        return None;
+    }
+    // SAFETY: the surface is a live GdkX11Surface, checked above.
+    let xid = unsafe { gdk_x11_surface_get_xid(surface.to_glib_none().0) };
+    u32::try_from(xid).ok()
+}
+
+/// Moves the window to its anchored spot on the monitor it is on (or the
+/// first one) and keeps it on top.
+fn place(conn: &RustConnection, surface: &gdk::Surface, anchor: Anchor, size: (i32, i32)) {
+    let Some(xid) = surface_xid(surface) else {
+        return;
+    };
+    let display = surface.display();
+    let monitor = display.monitor_at_surface(surface).or_else(|| {
+        display
+            .monitors()
+            .item(0)
+            .and_downcast::<gdk::Monitor>()
+    });
+    let Some(monitor) = monitor else {
+        return;
+    };
+    let geometry = monitor.geometry();
+    let scale = surface.scale_factor();
+    let (x, y) = anchor.place(
+        (geometry.x(), geometry.y(), geometry.width(), geometry.height()),
+        size,
+    );
+    let position = ConfigureWindowAux::new()
+        .x(x * scale)
+        .y(y * scale)
+        .stack_mode(StackMode::ABOVE);
+    let result = conn.configure_window(xid, &position).map(|_| ()).and_then(|()| conn.flush());
+    if let Err(e) = result {
+        warn!("Failed to place the overlay: {}", e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn anchors_place_like_layer_shell() {
+        let monitor = (1920, 0, 1920, 1080);
+        let size = (300, 60);
+        let corner = Anchor {
+            bottom: true,
+            right: true,
+            margins: [24; 4],
+            ..Anchor::default()
+        };
+        assert_eq!(corner.place(monitor, size), (1920 + 1920 - 300 - 24, 1080 - 60 - 24));
+
+        let custom = Anchor {
+            top: true,
+            left: true,
+            margins: [100, 0, 40, 0],
+            ..Anchor::default()
+        };
+        assert_eq!(custom.place(monitor, size), (1960, 100));
+
+        let spanning = Anchor {
+            top: true,
+            left: true,
+            right: true,
+            margins: [8; 4],
+            ..Anchor::default()
+        };
+        assert_eq!(spanning.place(monitor, size), (1920 + 810, 8));
+    }
+}
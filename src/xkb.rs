@@ -1,22 +1,67 @@
+use crate::combo::unix_millis;
 use evdev::Key;
-use xkbcommon::xkb;
+use serde_json::{json, Value};
+use std::borrow::Cow;
+use std::env;
+use std::ffi::OsString;
+use xkbcommon::xkb::{self, compose};
 
 const EVDEV_OFFSET: u32 = 8;
 
+/// Every ASCII character, so single-character labels can be handed out as
+/// `&'static str` slices instead of allocating per key press.
+static ASCII: [u8; 128] = {
+    let mut table = [0u8; 128];
+    let mut i = 0;
+    while i < 128 {
+        table[i] = i as u8;
+        i += 1;
+    }
+    table
+};
+
 pub struct XkbState {
     _context: xkb::Context,
-    _keymap: xkb::Keymap,
+    keymap: xkb::Keymap,
     state: xkb::State,
+    /// Dead-key and Compose sequences of the user's locale, when it has any
+    compose: Option<compose::State>,
+    /// Keymaps seldom give Scroll Lock a lock modifier, so it is toggled
+    /// here on each press; the second field ignores autorepeat
+    scroll_lock: (bool, bool),
+}
+
+/// Where a key press leaves a dead-key or Compose sequence.
+#[derive(Debug, PartialEq)]
+pub enum Compose {
+    /// Not part of a sequence; the key shows as usual
+    None,
+    /// Part of an unfinished or cancelled sequence; nothing to show
+    Pending,
+    /// The sequence produced this text
+    Done(String),
+}
+
+impl Default for XkbState {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl XkbState {
+    /// The keymap comes from the `XKB_DEFAULT_*` environment variables, so
+    /// `XKB_DEFAULT_LAYOUT=us,de` gives labels for both layouts.
     pub fn new() -> Self {
+        Self::with_layouts("")
+    }
+
+    fn with_layouts(layouts: &str) -> Self {
         let context = xkb::Context::new(xkb::CONTEXT_NO_FLAGS);
         let keymap = xkb::Keymap::new_from_names(
             &context,
             "",
             "",
-            "",
+            layouts,
             "",
             None,
             xkb::KEYMAP_COMPILE_NO_FLAGS,
@@ -24,34 +69,199 @@ impl XkbState {
         .expect("Failed to create XKB keymap (is xkbcommon installed?)");
 
         let state = xkb::State::new(&keymap);
+        let compose =
+            compose::Table::new_from_locale(&context, &locale(), compose::COMPILE_NO_FLAGS)
+                .ok()
+                .map(|table| compose::State::new(&table, compose::STATE_NO_FLAGS));
 
         Self {
             _context: context,
-            _keymap: keymap,
+            keymap,
             state,
+            compose,
+            scroll_lock: (false, false),
+        }
+    }
+
+    /// Feeds a key press to the Compose state.
+    pub fn compose(&mut self, key: Key) -> Compose {
+        let Some(compose) = &mut self.compose else {
+            return Compose::None;
+        };
+        let keysym = self.state.key_get_one_sym(key_to_keycode(key));
+        if compose.feed(keysym) == compose::FeedResult::Ignored {
+            return Compose::None;
+        }
+        match compose.status() {
+            compose::Status::Nothing => Compose::None,
+            compose::Status::Composing => Compose::Pending,
+            compose::Status::Cancelled => {
+                compose.reset();
+                Compose::Pending
+            }
+            compose::Status::Composed => {
+                let text = compose.utf8().or_else(|| {
+                    let c = char::from_u32(xkb::keysym_to_utf32(compose.keysym()?))?;
+                    Some(c.to_string())
+                });
+                compose.reset();
+                text.map_or(Compose::Pending, Compose::Done)
+            }
         }
     }
 
-    pub fn update_key(&mut self, key: Key, pressed: bool) {
+    /// True when the key switched the active layout (a group toggle from
+    /// `XKB_DEFAULT_OPTIONS`).
+    pub fn update_key(&mut self, key: Key, pressed: bool) -> bool {
+        if key == Key::KEY_SCROLLLOCK {
+            let (on, down) = self.scroll_lock;
+            self.scroll_lock = (on ^ (pressed && !down), pressed);
+        }
         let keycode = key_to_keycode(key);
         let direction = if pressed {
             xkb::KeyDirection::Down
         } else {
             xkb::KeyDirection::Up
         };
-        self.state.update_key(keycode, direction);
+        self.state.update_key(keycode, direction) & xkb::STATE_LAYOUT_EFFECTIVE != 0
     }
 
-    pub fn key_get_utf8(&self, key: Key) -> Option<String> {
-        let keycode = key_to_keycode(key);
-        let utf8 = self.state.key_get_utf8(keycode);
-        if utf8.is_empty() {
-            None
-        } else {
-            Some(utf8)
+    /// Name of the active layout, such as "English (US)".
+    pub fn layout_name(&self) -> &str {
+        let index = self.state.serialize_layout(xkb::STATE_LAYOUT_EFFECTIVE);
+        self.keymap.layout_get_name(index)
+    }
+
+    /// Makes `name` the active layout so labels follow it. False when the
+    /// keymap does not have that layout.
+    pub fn select_layout(&mut self, name: &str) -> bool {
+        let index = self.keymap.layout_get_index(name);
+        if index == xkb::LAYOUT_INVALID {
+            return false;
+        }
+        let depressed = self.state.serialize_mods(xkb::STATE_MODS_DEPRESSED);
+        let latched = self.state.serialize_mods(xkb::STATE_MODS_LATCHED);
+        let locked = self.state.serialize_mods(xkb::STATE_MODS_LOCKED);
+        self.state.update_mask(depressed, latched, locked, 0, 0, index);
+        true
+    }
+
+    /// The character the key's symbol stands for. Read from the keysym
+    /// rather than the state's text, which Ctrl turns into control
+    /// characters.
+    pub fn key_char(&self, key: Key) -> Option<char> {
+        let keysym = self.state.key_get_one_sym(key_to_keycode(key));
+        char::from_u32(xkb::keysym_to_utf32(keysym)).filter(|c| !c.is_control())
+    }
+
+    /// Lock modifiers as xkb tracks them from the keys seen so far. A lock
+    /// already on when Keyway started reads as off until it is toggled.
+    pub fn locks(&self) -> LockState {
+        LockState {
+            caps: self
+                .state
+                .mod_name_is_active(xkb::MOD_NAME_CAPS, xkb::STATE_MODS_LOCKED),
+            num: self
+                .state
+                .mod_name_is_active(xkb::MOD_NAME_NUM, xkb::STATE_MODS_LOCKED),
+            scroll: self.scroll_lock.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct LockState {
+    pub caps: bool,
+    pub num: bool,
+    /// Not part of the lock indicator, which only shows the two that
+    /// change what keys type
+    pub scroll: bool,
+}
+
+impl LockState {
+    /// Text of the lock bubble, or `None` when no lock is on.
+    pub fn label(&self) -> Option<&'static str> {
+        match (self.caps, self.num) {
+            (true, true) => Some("Caps Lock · Num Lock"),
+            (true, false) => Some("Caps Lock"),
+            (false, true) => Some("Num Lock"),
+            (false, false) => None,
         }
     }
 
+    /// `{"type":"lock","caps":..,"num":..,"scroll":..,"timestamp":..}`, stamped now.
+    pub fn to_json(self) -> Value {
+        json!({
+            "type": "lock",
+            "caps": self.caps,
+            "num": self.num,
+            "scroll": self.scroll,
+            "timestamp": unix_millis(),
+        })
+    }
+}
+
+/// Language names as xkb spells them, for layouts without a country code in
+/// their name.
+const LANGUAGE_CODES: &[(&str, &str)] = &[
+    ("Arabic", "ARA"),
+    ("Belarusian", "BY"),
+    ("Bulgarian", "BG"),
+    ("Chinese", "CN"),
+    ("Croatian", "HR"),
+    ("Czech", "CZ"),
+    ("Danish", "DK"),
+    ("Dutch", "NL"),
+    ("English", "EN"),
+    ("Estonian", "EE"),
+    ("Finnish", "FI"),
+    ("French", "FR"),
+    ("German", "DE"),
+    ("Greek", "GR"),
+    ("Hebrew", "IL"),
+    ("Hungarian", "HU"),
+    ("Italian", "IT"),
+    ("Japanese", "JP"),
+    ("Korean", "KR"),
+    ("Latvian", "LV"),
+    ("Lithuanian", "LT"),
+    ("Norwegian", "NO"),
+    ("Polish", "PL"),
+    ("Portuguese", "PT"),
+    ("Romanian", "RO"),
+    ("Russian", "RU"),
+    ("Serbian", "RS"),
+    ("Slovak", "SK"),
+    ("Slovenian", "SI"),
+    ("Spanish", "ES"),
+    ("Swedish", "SE"),
+    ("Turkish", "TR"),
+    ("Ukrainian", "UA"),
+];
+
+/// Short form of an xkb layout name: "English (US)" is "US", "German" is
+/// "DE", and unknown names keep their first two letters.
+pub fn layout_abbreviation(name: &str) -> String {
+    if let Some((_, rest)) = name.split_once('(') {
+        let code = rest.trim_end_matches(')');
+        if (2..=3).contains(&code.len()) && code.chars().all(|c| c.is_ascii_uppercase()) {
+            return code.to_string();
+        }
+    }
+    let language = name.split_whitespace().next().unwrap_or(name);
+    if let Some((_, code)) = LANGUAGE_CODES.iter().find(|(known, _)| *known == language) {
+        return code.to_string();
+    }
+    language.chars().take(2).flat_map(char::to_uppercase).collect()
+}
+
+/// The locale picking the Compose table, as libX11 would.
+fn locale() -> OsString {
+    ["LC_ALL", "LC_CTYPE", "LANG"]
+        .into_iter()
+        .filter_map(env::var_os)
+        .find(|value| !value.is_empty())
+        .unwrap_or_else(|| "C".into())
 }
 
 fn key_to_keycode(key: Key) -> xkb::Keycode {
@@ -73,24 +283,29 @@ pub fn is_modifier(key: Key) -> bool {
     )
 }
 
-pub fn key_label(key: Key, state: &XkbState) -> String {
+/// Borrowed for special keys and ASCII characters, which covers nearly
+/// every key press.
+pub fn key_label(key: Key, state: &XkbState) -> Cow<'static, str> {
     if let Some(label) = special_key_label(key) {
-        return label.to_string();
+        return Cow::Borrowed(label);
     }
 
-    if let Some(utf8) = state.key_get_utf8(key) {
-        if utf8 == " " {
-            return "Space".to_string();
-        }
-
-        if utf8.len() == 1 {
-            return utf8.to_uppercase();
-        }
-
-        return utf8;
+    match state.key_char(key) {
+        Some(' ') => Cow::Borrowed("Space"),
+        Some(c) => char_label(c),
+        None => Cow::Owned(fallback_label(key)),
     }
+}
 
-    fallback_label(key)
+/// ASCII letters are shown in capitals like the keycaps; other characters
+/// as typed.
+fn char_label(c: char) -> Cow<'static, str> {
+    if !c.is_ascii() {
+        return Cow::Owned(c.to_string());
+    }
+    let index = c.to_ascii_uppercase() as usize;
+    // A single ASCII byte is always valid UTF-8.
+    Cow::Borrowed(std::str::from_utf8(&ASCII[index..=index]).unwrap_or_default())
 }
 
 fn special_key_label(key: Key) -> Option<&'static str> {
@@ -130,3 +345,68 @@ fn fallback_label(key: Key) -> String {
     }
     name
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ascii_labels_are_borrowed_capitals() {
+        assert!(matches!(char_label('a'), Cow::Borrowed("A")));
+        assert!(matches!(char_label('/'), Cow::Borrowed("/")));
+        assert_eq!(char_label('é'), "é");
+    }
+
+    #[test]
+    fn caps_lock_toggles_on_each_press() {
+        let mut state = XkbState::new();
+        let tap = |state: &mut XkbState| {
+            state.update_key(Key::KEY_CAPSLOCK, true);
+            state.update_key(Key::KEY_CAPSLOCK, false);
+        };
+
+        tap(&mut state);
+        assert_eq!(state.locks().label(), Some("Caps Lock"));
+        assert_eq!(state.key_char(Key::KEY_A), Some('A'));
+        tap(&mut state);
+        assert_eq!(state.locks(), LockState::default());
+    }
+
+    #[test]
+    fn layout_abbreviations() {
+        assert_eq!(layout_abbreviation("English (US)"), "US");
+        assert_eq!(layout_abbreviation("German"), "DE");
+        assert_eq!(layout_abbreviation("Russian (phonetic)"), "RU");
+        assert_eq!(layout_abbreviation("Icelandic"), "IC");
+    }
+
+    #[test]
+    fn selected_layout_changes_labels() {
+        let mut state = XkbState::with_layouts("us,de");
+        assert_eq!(state.layout_name(), "English (US)");
+        assert_eq!(state.key_char(Key::KEY_Y), Some('y'));
+
+        assert!(state.select_layout("German"));
+        assert_eq!(state.layout_name(), "German");
+        assert_eq!(state.key_char(Key::KEY_Y), Some('z'));
+        assert!(!state.select_layout("Klingon"));
+    }
+
+    #[test]
+    fn dead_keys_compose_into_one_character() {
+        let mut state = XkbState::with_layouts("de");
+        let table = compose::Table::new_from_buffer(
+            &state._context,
+            "<dead_acute> <e> : \"é\"\n",
+            "C",
+            compose::FORMAT_TEXT_V1,
+            compose::COMPILE_NO_FLAGS,
+        )
+        .unwrap();
+        state.compose = Some(compose::State::new(&table, compose::STATE_NO_FLAGS));
+
+        assert_eq!(state.compose(Key::KEY_EQUAL), Compose::Pending);
+        assert_eq!(state.compose(Key::KEY_E), Compose::Done("é".to_string()));
+        assert_eq!(state.compose(Key::KEY_E), Compose::None);
+    }
+}